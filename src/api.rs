@@ -1,32 +1,704 @@
-use anyhow::{Result, Context};
+use anyhow::Result;
 use log::{info, error, warn};
+use rand::Rng;
 use reqwest::blocking::multipart::{Form, Part};
 use serde::{Serialize, Deserialize};
 use std::path::Path;
 use std::fs::File;
 use std::io::Read;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::config::Config;
-use crate::text_processor::TranscriptionProcessor;
+use crate::audio::Recording;
+use crate::config::{ApiConfig, Config, ResponseFormat, TranscriptionBackend};
+use crate::text_processor::{ProcessingNotice, TranscriptionProcessor, UserDictionary};
+
+/// Upper bound for the biasing prompt; Whisper only reads the last 224 tokens of it
+const MAX_PROMPT_CHARS: usize = 400;
+
+/// How many times a transcription request is attempted before giving up
+const MAX_RETRIES: usize = 3;
 
 /// OpenAI API client
 pub struct TranscriptionAPI {
     config: Config,
     client: reqwest::blocking::Client,
+    /// Client for the cancellable `*_async` methods; dropping their futures aborts the request
+    #[cfg(feature = "async")]
+    async_client: reqwest::Client,
+    processor: SharedProcessor,
+}
+
+/// Text processor kept across the transcriptions of one `TranscriptionAPI`, created on first use
+///
+/// Sharing it keeps the learned word frequencies in memory and stops concurrent transcriptions
+/// from overwriting each other's dictionary saves; the dictionary file is read again only
+/// when it changes.
+type SharedProcessor = Arc<Mutex<Option<TranscriptionProcessor>>>;
+
+/// Why a transcription failed, so callers can show specific guidance
+#[derive(Debug, thiserror::Error)]
+pub enum TranscriptionError {
+    /// The OpenAI backend is selected but no API key was found
+    #[error("API key not configured")]
+    NoApiKey,
+    /// The request could not be sent or timed out
+    #[error("Failed to send API request: {0}")]
+    Network(#[source] reqwest::Error),
+    /// The API kept answering with rate-limit errors
+    #[error("Rate limited by the API: {0}")]
+    RateLimited(String),
+    /// The API rejected the API key (401 or 403); retrying would get the same answer
+    #[error("API key rejected ({status}): {message}")]
+    Unauthorized { status: u16, message: String },
+    /// The API answered with an error status
+    #[error("API error {status}: {message}")]
+    Server { status: u16, message: String },
+    /// The response body could not be read
+    #[error("Failed to parse API response: {0}")]
+    Parse(#[source] reqwest::Error),
+    /// The response was read but holds no transcript; retrying would get the same answer
+    #[error("Unexpected API response: {0}")]
+    Schema(String),
+    /// Reading the audio file or running the local backend failed
+    #[error("{context}: {source}")]
+    Io { context: String, #[source] source: std::io::Error },
+    /// The local whisper command exited with an error
+    #[error("whisper exited with {status}: {stderr}")]
+    Whisper { status: std::process::ExitStatus, stderr: String },
+    /// The connectivity check before the upload (`api.offline_check`) found no network
+    #[error("No network connection to {host}: {reason}")]
+    Offline { host: String, reason: String },
+    /// The request could not be built from the config, e.g. an invalid `api.upload_mime`
+    #[error("Invalid API request: {0}")]
+    InvalidRequest(String),
+}
+
+impl TranscriptionError {
+    fn io(context: impl Into<String>) -> impl FnOnce(std::io::Error) -> Self {
+        let context = context.into();
+        move |source| Self::Io { context, source }
+    }
+}
+
+/// A transcript after text processing
+pub struct ProcessedTranscript {
+    pub text: String,
+    /// Things the user should know about the processing, such as a chunked or truncated format
+    pub notices: Vec<String>,
+    /// Language the API detected in the recording (`verbose_json` only)
+    pub language: Option<String>,
+    /// Backend of the chain that produced the transcript
+    pub backend: TranscriptionBackend,
 }
 
-/// Response from the transcription API
+/// `verbose_json` response from the transcription API
 #[derive(Debug, Serialize, Deserialize)]
-pub struct TranscriptionResponse {
+pub struct VerboseTranscriptionResponse {
+    pub text: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub segments: Vec<TranscriptionSegment>,
+}
+
+/// One segment of a `verbose_json` response; only the fields used for the quality check and markers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionSegment {
+    #[serde(default)]
+    pub start: f64,
+    #[serde(default)]
+    pub end: f64,
+    #[serde(default)]
+    pub text: String,
+    pub avg_logprob: f64,
+    pub no_speech_prob: f64,
+}
+
+/// Overall confidence of a transcript, from its `verbose_json` segments
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TranscriptQuality {
+    /// Mean `avg_logprob` of the segments, weighted by their duration
+    pub avg_logprob: f64,
+    /// Mean `no_speech_prob` of the segments, weighted by their duration
+    pub no_speech_prob: f64,
+}
+
+impl TranscriptQuality {
+    /// Combine the segment values; None without segments
+    pub fn from_segments(segments: &[TranscriptionSegment]) -> Option<Self> {
+        if segments.is_empty() {
+            return None;
+        }
+        // 長さが取れない区間（start/end が無い等）は同じ重みで扱う
+        let weights: Vec<f64> = segments.iter().map(|segment| segment.end - segment.start).collect();
+        let weights = if weights.iter().all(|weight| *weight > 0.0) { weights } else { vec![1.0; segments.len()] };
+        let total: f64 = weights.iter().sum();
+        let mean = |value: fn(&TranscriptionSegment) -> f64| {
+            segments.iter().zip(&weights).map(|(segment, weight)| value(segment) * weight).sum::<f64>() / total
+        };
+        Some(Self {
+            avg_logprob: mean(|segment| segment.avg_logprob),
+            no_speech_prob: mean(|segment| segment.no_speech_prob),
+        })
+    }
+    
+    /// Why the transcript is likely unreliable, if it is
+    pub fn issue(&self, min_avg_logprob: f64, max_no_speech_prob: f64) -> Option<QualityIssue> {
+        if self.no_speech_prob > max_no_speech_prob {
+            Some(QualityIssue::NoSpeech(self.no_speech_prob))
+        } else if self.avg_logprob < min_avg_logprob {
+            Some(QualityIssue::LowConfidence(self.avg_logprob))
+        } else {
+            None
+        }
+    }
+}
+
+/// A reason to record again, from `TranscriptQuality::issue`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QualityIssue {
+    /// The audio is probably not speech, with this `no_speech_prob`
+    NoSpeech(f64),
+    /// The transcript has this low mean `avg_logprob`
+    LowConfidence(f64),
+}
+
+impl QualityIssue {
+    /// Notice for the user in the UI language
+    pub fn notice(self) -> String {
+        match self {
+            QualityIssue::NoSpeech(prob) => crate::i18n::text().transcript_no_speech(prob),
+            QualityIssue::LowConfidence(logprob) => crate::i18n::text().transcript_low_confidence(logprob),
+        }
+    }
+}
+
+/// A transcript before text processing, with its confidence when the API reported it
+#[derive(Debug)]
+pub struct RawTranscript {
     pub text: String,
+    pub quality: Option<TranscriptQuality>,
+    /// Language the API detected, e.g. "japanese"
+    pub language: Option<String>,
+    /// Timed segments of `verbose_json` responses, used to place markers
+    pub segments: Vec<TranscriptionSegment>,
+    /// Backend of the chain that produced the transcript
+    pub backend: TranscriptionBackend,
+}
+
+impl From<VerboseTranscriptionResponse> for RawTranscript {
+    fn from(response: VerboseTranscriptionResponse) -> Self {
+        let quality = TranscriptQuality::from_segments(&response.segments);
+        Self { text: response.text, quality, language: response.language, segments: response.segments, backend: TranscriptionBackend::Openai }
+    }
+}
+
+impl RawTranscript {
+    fn text(text: String) -> Self {
+        Self { text, quality: None, language: None, segments: Vec::new(), backend: TranscriptionBackend::Openai }
+    }
+}
+
+/// Separator put in the transcript where a marker was dropped (`shortcuts.drop_marker`)
+pub const MARKER_SEPARATOR: &str = "--- marker ---";
+
+/// Shortest span of a timestamped paragraph (`ui.inline_timestamps`); segments are merged
+/// until a paragraph covers this long, so GPT formats a few paragraphs rather than every sentence
+const TIMESTAMP_PARAGRAPH_SECS: f64 = 30.0;
+
+/// `seconds` into the recording as "[mm:ss]", or "[h:mm:ss]" from an hour on
+fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("[{}:{:02}:{:02}]", hours, minutes, seconds)
+    } else {
+        format!("[{:02}:{:02}]", minutes, seconds)
+    }
+}
+
+/// Group `segments` into paragraphs of at least `TIMESTAMP_PARAGRAPH_SECS`, with the start of each
+fn timestamped_paragraphs(segments: &[TranscriptionSegment]) -> Vec<(f64, String)> {
+    let mut paragraphs: Vec<(f64, String)> = Vec::new();
+    let mut current: Option<(f64, String)> = None;
+    for segment in segments {
+        let (start, text) = current.get_or_insert_with(|| (segment.start, String::new()));
+        text.push_str(&segment.text);
+        if segment.end - *start >= TIMESTAMP_PARAGRAPH_SECS {
+            paragraphs.extend(current.take());
+        }
+    }
+    paragraphs.extend(current);
+    paragraphs.into_iter()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .map(|(start, text)| (start, text.trim().to_string()))
+        .collect()
+}
+
+/// Where `shortcuts.drop_marker` was pressed during a recording
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Markers {
+    /// Seconds after the recording started
+    pub times: Vec<f64>,
+    /// Seconds of pre-roll prepended to the audio, which puts each marker that much later in it
+    pub preroll_secs: f64,
+}
+
+/// Split `segments` at the segment boundaries nearest to `markers` (seconds into the recording)
+///
+/// The boundaries are the start of each segment and the end of the last one. Markers that
+/// land on the same boundary count once, and sections without text are left out.
+fn marker_sections<'a>(segments: &'a [TranscriptionSegment], markers: &[f64]) -> Vec<&'a [TranscriptionSegment]> {
+    let mut boundaries: Vec<f64> = segments.iter().map(|segment| segment.start).collect();
+    boundaries.push(segments.last().map_or(0.0, |segment| segment.end));
+    let mut cuts: Vec<usize> = markers.iter()
+        .filter_map(|marker| {
+            boundaries.iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| (*a - marker).abs().total_cmp(&(*b - marker).abs()))
+                .map(|(index, _)| index)
+        })
+        .collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+    
+    let mut sections = Vec::new();
+    let mut start = 0;
+    for cut in cuts.into_iter().chain(std::iter::once(segments.len())) {
+        let section = &segments[start..cut];
+        if section.iter().any(|segment| !segment.text.trim().is_empty()) {
+            sections.push(section);
+        }
+        start = cut;
+    }
+    sections
+}
+
+/// Process each section of `raw` between markers on its own and join them with `MARKER_SEPARATOR`
+///
+/// With `ui.inline_timestamps` each section is further split into timestamped paragraphs,
+/// which are processed on their own too. Formatting the pieces separately keeps GPT from
+/// moving or dropping the separators and times.
+fn process_sections(
+    config: Config,
+    shared: &SharedProcessor,
+    raw: RawTranscript,
+    markers: &Markers,
+    verbatim: bool,
+    mut notices: Vec<String>,
+) -> Result<ProcessedTranscript> {
+    // 段落ごとの (開始時刻, テキスト) をマーカーの区切りごとにまとめる
+    let sections: Vec<Vec<(Option<f64>, String)>> = if raw.segments.iter().all(|segment| segment.text.trim().is_empty()) {
+        // 区間の時刻が無いと位置を決められない
+        notices.push(crate::i18n::text().segments_need_verbose_json.to_string());
+        vec![vec![(None, raw.text)]]
+    } else {
+        let times: Vec<f64> = markers.times.iter().map(|time| time + markers.preroll_secs).collect();
+        marker_sections(&raw.segments, &times)
+            .into_iter()
+            .map(|section| {
+                if config.ui.inline_timestamps {
+                    // マーカーと同じく、録音を始めた時点からの時刻にする
+                    timestamped_paragraphs(section)
+                        .into_iter()
+                        .map(|(start, text)| (Some(start - markers.preroll_secs), text))
+                        .collect()
+                } else {
+                    let text: String = section.iter().map(|segment| segment.text.as_str()).collect();
+                    vec![(None, text.trim().to_string())]
+                }
+            })
+            .collect()
+    };
+    
+    let mut processed_sections = Vec::with_capacity(sections.len());
+    for section in sections {
+        let mut paragraphs = Vec::with_capacity(section.len());
+        for (start, text) in section {
+            let text = if verbatim {
+                text
+            } else {
+                let (text, processor_notices) = TranscriptionProcessor::process_shared(shared, &config, &text)?;
+                notices.extend(processor_notices.into_iter().map(ProcessingNotice::message));
+                text
+            };
+            paragraphs.push(match start {
+                Some(start) => format!("{} {}", format_timestamp(start), text),
+                None => text,
+            });
+        }
+        processed_sections.push(paragraphs.join("\n\n"));
+    }
+    let separator = format!("\n\n{}\n\n", MARKER_SEPARATOR);
+    Ok(ProcessedTranscript { text: processed_sections.join(&separator), notices, language: raw.language, backend: raw.backend })
+}
+
+/// Fields holding the transcript in JSON responses, in order of preference; compatible
+/// servers don't all use OpenAI's `text`
+const TRANSCRIPT_FIELDS: [&str; 3] = ["text", "transcript", "result"];
+
+/// Longest part of an unexpected response body quoted in the error
+const RESPONSE_EXCERPT_CHARS: usize = 200;
+
+/// Read the transcript out of a successful response body
+///
+/// JSON is looked through field by field rather than deserialized into a fixed shape, and a
+/// body without any known field is a `Schema` error.
+fn parse_transcript_body(body: &str, format: ResponseFormat) -> Result<RawTranscript, TranscriptionError> {
+    if format == ResponseFormat::Text {
+        return Ok(RawTranscript::text(body.trim().to_string()));
+    }
+    
+    let excerpt = || body.chars().take(RESPONSE_EXCERPT_CHARS).collect::<String>();
+    let value: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| TranscriptionError::Schema(format!("not JSON ({}): {}", e, excerpt())))?;
+    let text = TRANSCRIPT_FIELDS.iter()
+        .find_map(|field| value.get(field)?.as_str())
+        .ok_or_else(|| TranscriptionError::Schema(format!(
+            "no transcript field ({}) in the response: {}",
+            TRANSCRIPT_FIELDS.join(", "), excerpt()
+        )))?
+        .to_string();
+    
+    if format == ResponseFormat::VerboseJson {
+        // セグメントが読めない場合は信頼度の確認だけを諦める
+        let segments = value.get("segments")
+            .and_then(|segments| serde_json::from_value(segments.clone()).ok())
+            .unwrap_or_default();
+        let language = value.get("language")
+            .and_then(|language| language.as_str())
+            .filter(|language| !language.is_empty())
+            .map(str::to_string);
+        Ok(VerboseTranscriptionResponse { text, language, segments }.into())
+    } else {
+        Ok(RawTranscript::text(text))
+    }
+}
+
+/// Guess the MIME type of an audio file from its extension
+fn mime_type_for(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase()).as_deref() {
+        Some("mp3") | Some("mpga") | Some("mpeg") => "audio/mpeg",
+        Some("m4a") | Some("mp4") => "audio/mp4",
+        Some("ogg") | Some("oga") => "audio/ogg",
+        Some("opus") => "audio/opus",
+        Some("webm") => "audio/webm",
+        Some("flac") => "audio/flac",
+        _ => "audio/wav",
+    }
+}
+
+/// File name sent with the upload: the recording's own, or "audio" with its extension
+fn upload_file_name(path: &Path) -> String {
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.to_string(),
+        None => {
+            let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("wav");
+            format!("audio.{}", ext)
+        }
+    }
+}
+
+/// Read the audio to upload, along with the path its file name and MIME type are taken from
+fn read_recording(recording: &Recording) -> Result<(&Path, Vec<u8>), TranscriptionError> {
+    // in-memory recordings are uploaded as they are
+    match recording {
+        Recording::File(audio_path) => {
+            let mut file = File::open(audio_path)
+                .map_err(TranscriptionError::io("Failed to open audio file"))?;
+            let mut buffer = Vec::new();
+            file.read_to_end(&mut buffer)
+                .map_err(TranscriptionError::io("Failed to read audio file"))?;
+            Ok((Path::new(audio_path), buffer))
+        },
+        Recording::Memory { path, data } => Ok((Path::new(path), data.to_vec())),
+    }
+}
+
+/// Turn an error response into a TranscriptionError, and tell whether it is worth retrying
+fn classify_error_response(status: reqwest::StatusCode, error_text: &str) -> (TranscriptionError, bool) {
+    let rate_limited = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || error_text.contains("rate limit");
+    let unauthorized = matches!(status, reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN);
+    let error = if unauthorized {
+        TranscriptionError::Unauthorized { status: status.as_u16(), message: error_text.to_string() }
+    } else if rate_limited {
+        TranscriptionError::RateLimited(error_text.to_string())
+    } else {
+        TranscriptionError::Server { status: status.as_u16(), message: error_text.to_string() }
+    };
+    
+    // 5xxエラーや一時的なエラーのみリトライ
+    let retryable = status.is_server_error() || rate_limited || error_text.contains("timeout");
+    (error, retryable)
+}
+
+/// Headers added to every API request: `OpenAI-Organization` / `OpenAI-Project` for the
+/// configured organization and project, then `api.extra_headers`
+///
+/// Unset or empty values are left out, as are names and values that aren't valid header text.
+pub(crate) fn request_headers(config: &ApiConfig) -> reqwest::header::HeaderMap {
+    let mut headers = reqwest::header::HeaderMap::new();
+    let values = [("OpenAI-Organization", &config.organization), ("OpenAI-Project", &config.project)];
+    for (name, value) in values {
+        let Some(value) = value.as_deref().map(str::trim).filter(|value| !value.is_empty()) else {
+            continue;
+        };
+        match reqwest::header::HeaderValue::from_str(value) {
+            Ok(header) => {
+                headers.insert(name, header);
+            },
+            Err(_) => warn!("Ignoring invalid {} header value", name),
+        }
+    }
+    // 読み込み時に検証済みだが、設定を直接組み立てた場合に備えて再確認する
+    for (name, value) in &config.extra_headers {
+        match (reqwest::header::HeaderName::from_bytes(name.as_bytes()), reqwest::header::HeaderValue::from_str(value)) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            },
+            _ => warn!("Ignoring invalid header in api.extra_headers: {:?}", name),
+        }
+    }
+    headers
+}
+
+/// Root certificate from `api.ca_cert_path`, read as PEM or else DER
+///
+/// A missing or unreadable file is logged and skipped, leaving the system roots in place.
+fn custom_root_certificate(config: &ApiConfig) -> Option<reqwest::Certificate> {
+    let path = config.ca_cert_path.as_ref()?;
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Could not read api.ca_cert_path {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    match reqwest::Certificate::from_pem(&bytes).or_else(|_| reqwest::Certificate::from_der(&bytes)) {
+        Ok(cert) => {
+            info!("Trusting the root certificate from {}", path.display());
+            Some(cert)
+        },
+        Err(e) => {
+            warn!("api.ca_cert_path {} is not a PEM or DER certificate: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Blocking client builder with the TLS settings of `api.ca_cert_path` and
+/// `api.danger_accept_invalid_certs`
+pub(crate) fn blocking_client_builder(config: &ApiConfig) -> reqwest::blocking::ClientBuilder {
+    let mut builder = reqwest::blocking::Client::builder();
+    if let Some(cert) = custom_root_certificate(config) {
+        builder = builder.add_root_certificate(cert);
+    }
+    if config.danger_accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    builder
+}
+
+/// Async counterpart of `blocking_client_builder`
+#[cfg(feature = "async")]
+fn async_client_builder(config: &ApiConfig) -> reqwest::ClientBuilder {
+    let mut builder = reqwest::Client::builder();
+    if let Some(cert) = custom_root_certificate(config) {
+        builder = builder.add_root_certificate(cert);
+    }
+    if config.danger_accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    builder
+}
+
+/// Longest `Retry-After` wait that is honored; longer requests are cut to this
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Wait before the next attempt
+///
+/// Uses the server's `Retry-After` when given, otherwise a random wait within the exponential
+/// backoff window (`2^n` seconds, at most 30) so instances sharing a rate limit don't retry
+/// in lockstep.
+fn retry_delay(retry_count: usize, retry_after: Option<Duration>) -> Duration {
+    if let Some(retry_after) = retry_after {
+        return retry_after.min(MAX_RETRY_AFTER);
+    }
+    let window = Duration::from_secs(std::cmp::min(2u64.pow(retry_count as u32), 30));
+    let window_ms = window.as_millis() as u64;
+    Duration::from_millis(rand::thread_rng().gen_range(0..=window_ms))
+}
+
+/// How a failed attempt of `with_retries` ends
+pub(crate) enum AttemptError<E> {
+    /// Worth another attempt, after the server's `Retry-After` wait if it sent one
+    Retry(E, Option<Duration>),
+    /// Another attempt would get the same answer
+    Fatal(E),
+}
+
+/// Run `attempt` up to `max_attempts` times (at least once) until it succeeds or fails for good
+///
+/// `attempt` gets the number of earlier attempts. Between attempts it waits `retry_delay`;
+/// when every attempt fails, the error of the last one is returned.
+pub(crate) fn with_retries<T, E: std::fmt::Display>(
+    what: &str,
+    max_attempts: usize,
+    mut attempt: impl FnMut(usize) -> Result<T, AttemptError<E>>,
+) -> Result<T, E> {
+    let mut retry_count = 0;
+    loop {
+        match attempt(retry_count) {
+            Ok(value) => return Ok(value),
+            Err(AttemptError::Fatal(e)) => return Err(e),
+            Err(AttemptError::Retry(e, _)) if retry_count + 1 >= max_attempts => return Err(e),
+            Err(AttemptError::Retry(e, requested_wait)) => {
+                let wait_time = retry_delay(retry_count, requested_wait);
+                warn!("{} failed ({:#}), retrying in {:.1} seconds...", what, e, wait_time.as_secs_f32());
+                std::thread::sleep(wait_time);
+                retry_count += 1;
+            },
+        }
+    }
+}
+
+/// Async counterpart of `with_retries`
+#[cfg(feature = "async")]
+pub(crate) async fn with_retries_async<T, E, F, Fut>(what: &str, max_attempts: usize, mut attempt: F) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: FnMut(usize) -> Fut,
+    Fut: std::future::Future<Output = Result<T, AttemptError<E>>>,
+{
+    let mut retry_count = 0;
+    loop {
+        match attempt(retry_count).await {
+            Ok(value) => return Ok(value),
+            Err(AttemptError::Fatal(e)) => return Err(e),
+            Err(AttemptError::Retry(e, _)) if retry_count + 1 >= max_attempts => return Err(e),
+            Err(AttemptError::Retry(e, requested_wait)) => {
+                let wait_time = retry_delay(retry_count, requested_wait);
+                warn!("{} failed ({:#}), retrying in {:.1} seconds...", what, e, wait_time.as_secs_f32());
+                tokio::time::sleep(wait_time).await;
+                retry_count += 1;
+            },
+        }
+    }
+}
+
+/// A transcription request that couldn't be sent; timeouts and connection errors are retried
+fn send_error_attempt(e: reqwest::Error) -> AttemptError<TranscriptionError> {
+    error!("Failed to send API request: {}", e);
+    if e.is_timeout() || e.is_connect() {
+        AttemptError::Retry(TranscriptionError::Network(e), None)
+    } else {
+        // その他のエラーはすぐに失敗
+        AttemptError::Fatal(TranscriptionError::Network(e))
+    }
+}
+
+/// An error response to a transcription request, retried when `classify_error_response` says so
+fn error_response_attempt(status: reqwest::StatusCode, error_text: &str, requested_wait: Option<Duration>) -> AttemptError<TranscriptionError> {
+    error!("API error {}: {}", status, error_text);
+    match classify_error_response(status, error_text) {
+        (error, true) => AttemptError::Retry(error, requested_wait),
+        (error, false) => AttemptError::Fatal(error),
+    }
+}
+
+/// How long the connectivity check (`api.offline_check`) waits for the DNS lookup and for the API host to answer, each
+const OFFLINE_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Check that the host of `url` resolves and can be reached before uploading to it
+///
+/// Only a clear lack of network is an error: a failed DNS lookup, or a connection that times
+/// out or finds no route. A refused connection means the server is reachable but down, which
+/// the request itself reports. The check is skipped for URLs it can't read and when a proxy
+/// is set in the environment, since the host may then only be reachable through it.
+fn check_connectivity(url: &str) -> Result<(), TranscriptionError> {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return Ok(());
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Ok(());
+    };
+    let proxied = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+        .iter()
+        .any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()));
+    if proxied {
+        return Ok(());
+    }
+    probe_host(host, port)
+}
+
+/// Resolve `host` and try a TCP connection to `port` on its addresses (see `check_connectivity`)
+fn probe_host(host: &str, port: u16) -> Result<(), TranscriptionError> {
+    use std::net::ToSocketAddrs;
+    
+    let target = (host.to_string(), port);
+    let lookup = resolve_within(move || target.to_socket_addrs().map(Iterator::collect), OFFLINE_CHECK_TIMEOUT);
+    probe_addrs(host, lookup)
+}
+
+/// Run the address lookup `lookup` on its own thread and give up on it after `timeout`
+///
+/// The system resolver can't be cancelled, so a lookup that hangs is left to finish in the background.
+fn resolve_within<F>(lookup: F, timeout: Duration) -> Result<Vec<std::net::SocketAddr>, String>
+where
+    F: FnOnce() -> std::io::Result<Vec<std::net::SocketAddr>> + Send + 'static,
+{
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(lookup());
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result.map_err(|e| format!("DNS lookup failed ({})", e)),
+        Err(_) => Err(format!("DNS lookup timed out after {}s", timeout.as_secs_f32())),
+    }
+}
+
+/// Try a TCP connection to the addresses `lookup` resolved `host` to (see `check_connectivity`)
+fn probe_addrs(host: &str, lookup: Result<Vec<std::net::SocketAddr>, String>) -> Result<(), TranscriptionError> {
+    use std::net::TcpStream;
+    
+    let offline = |reason: String| TranscriptionError::Offline { host: host.to_string(), reason };
+    let addrs = lookup.map_err(offline)?;
+    let mut last_error = None;
+    for addr in &addrs {
+        match TcpStream::connect_timeout(addr, OFFLINE_CHECK_TIMEOUT) {
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => return Ok(()),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) => Err(offline(format!("connection failed ({})", e))),
+        None => Err(offline("the host has no addresses".to_string())),
+    }
+}
+
+/// Parse a `Retry-After` header, given either in seconds or as an HTTP date
+pub(crate) fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    // 過去の日時なら待たずにリトライ
+    Some((date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO))
 }
 
 impl TranscriptionAPI {
     /// Create a new API client
     pub fn new(config: Config) -> Self {
+        if config.api.danger_accept_invalid_certs {
+            warn!("api.danger_accept_invalid_certs is enabled: TLS certificates of the API server are NOT verified");
+        }
         // タイムアウト設定を長めに取ったクライアント設定
-        let client = reqwest::blocking::Client::builder()
+        let client = blocking_client_builder(&config.api)
             .timeout(Duration::from_secs(120)) // 2分のタイムアウト
             .connect_timeout(Duration::from_secs(30)) // 接続タイムアウト30秒
             .build()
@@ -34,135 +706,491 @@ impl TranscriptionAPI {
                 warn!("Failed to build custom client, using default");
                 reqwest::blocking::Client::new()
             });
+        #[cfg(feature = "async")]
+        let async_client = async_client_builder(&config.api)
+            .timeout(Duration::from_secs(120))
+            .connect_timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_else(|_| {
+                warn!("Failed to build custom async client, using default");
+                reqwest::Client::new()
+            });
             
         Self {
             config,
             client,
+            #[cfg(feature = "async")]
+            async_client,
+            processor: SharedProcessor::default(),
         }
     }
     
-    /// Transcribe an audio file
-    pub fn transcribe(&self, audio_path: &str) -> Result<String> {
-        info!("Transcribing audio file: {}", audio_path);
+    /// Transcribe an audio file, along with the segment confidence of `verbose_json` responses
+    ///
+    /// `language_override` replaces the configured `transcription.language` for this call only.
+    /// With a chain of backends, a failure is logged and the next backend tried; the error of
+    /// the last one is returned.
+    pub fn transcribe(&self, recording: &Recording, language_override: Option<&str>) -> Result<RawTranscript, TranscriptionError> {
+        info!("Transcribing audio file: {}", recording);
         
+        let (language, prompt) = self.request_options(language_override);
+        let (last, fallbacks) = self.backend_order();
+        for &backend in fallbacks {
+            match self.transcribe_with_backend(backend, recording, language, prompt.as_deref()) {
+                Ok(raw) => return Ok(raw),
+                Err(e) => warn!("The {:?} backend failed, trying the next one: {}", backend, e),
+            }
+        }
+        self.transcribe_with_backend(last, recording, language, prompt.as_deref())
+    }
+    
+    /// The last backend of `transcription.backend` and the ones tried before it
+    fn backend_order(&self) -> (TranscriptionBackend, &[TranscriptionBackend]) {
+        let (last, fallbacks) = self.config.transcription.backend.backends()
+            .split_last()
+            .expect("backend chains are never empty");
+        (*last, fallbacks)
+    }
+    
+    /// Transcribe with one backend of the chain
+    fn transcribe_with_backend(
+        &self,
+        backend: TranscriptionBackend,
+        recording: &Recording,
+        language: Option<&str>,
+        prompt: Option<&str>,
+    ) -> Result<RawTranscript, TranscriptionError> {
+        match backend {
+            TranscriptionBackend::Openai => self.transcribe_openai(recording, language, prompt),
+            TranscriptionBackend::WhisperPython => {
+                // whisper コマンドはファイルしか読めない
+                let audio_path = recording.path().ok_or_else(|| TranscriptionError::Io {
+                    context: "The whisper_python backend needs a recording file".to_string(),
+                    source: std::io::Error::new(std::io::ErrorKind::Unsupported, "in-memory recording"),
+                })?;
+                self.transcribe_whisper_python(audio_path, language, prompt)
+                    .map(|text| RawTranscript { backend, ..RawTranscript::text(text) })
+            },
+        }
+    }
+    
+    /// The language and biasing prompt to send with a request
+    fn request_options<'a>(&'a self, language_override: Option<&'a str>) -> (Option<&'a str>, Option<String>) {
+        let language = language_override.or(self.config.transcription.language.as_deref());
+        if let Some(language) = language {
+            info!("Transcription language: {}", language);
+        }
+        
+        let prompt = self.biasing_prompt();
+        if let Some(prompt) = &prompt {
+            info!("Transcription prompt: {}", prompt);
+        }
+        (language, prompt)
+    }
+    
+    /// Text fields of the multipart upload, besides the audio file itself
+    ///
+    /// `metadata` is the sample rate and channel count sent with `api.send_audio_metadata`.
+    fn form_fields(&self, language: Option<&str>, prompt: Option<&str>, metadata: Option<(u32, u16)>) -> Vec<(&'static str, String)> {
+        let response_format = self.config.transcription.response_format;
+        // 区間ごとの信頼度 (verbose_json) は whisper-1 だけが返す
+        let model = match response_format {
+            ResponseFormat::VerboseJson => "whisper-1",
+            _ => "gpt-4o-mini-transcribe",
+        };
+        let mut fields = vec![("model", model.to_string())];
+        if let Some(language) = language {
+            fields.push(("language", language.to_string()));
+        }
+        if let Some(prompt) = prompt {
+            fields.push(("prompt", prompt.to_string()));
+        }
+        match response_format {
+            ResponseFormat::Json => {},
+            ResponseFormat::Text => fields.push(("response_format", "text".to_string())),
+            ResponseFormat::VerboseJson => fields.push(("response_format", "verbose_json".to_string())),
+        }
+        if let Some((sample_rate, channels)) = metadata {
+            fields.push(("sample_rate", sample_rate.to_string()));
+            fields.push(("channels", channels.to_string()));
+        }
+        fields
+    }
+    
+    /// Sample rate and channel count to send with the upload when `api.send_audio_metadata` is set
+    ///
+    /// Only WAV headers are read; other formats are sent without them.
+    fn audio_metadata(&self, audio: &[u8]) -> Option<(u32, u16)> {
+        if !self.config.api.send_audio_metadata {
+            return None;
+        }
+        match hound::WavReader::new(std::io::Cursor::new(audio)) {
+            Ok(reader) => Some((reader.spec().sample_rate, reader.spec().channels)),
+            Err(e) => {
+                info!("Not sending audio metadata, the upload isn't a readable WAV: {}", e);
+                None
+            },
+        }
+    }
+    
+    /// MIME type of the uploaded audio: `api.upload_mime`, or guessed from the file extension
+    fn upload_mime(&self, path: &Path) -> String {
+        match self.config.api.upload_mime.as_deref().map(str::trim) {
+            Some(mime) if !mime.is_empty() => mime.to_string(),
+            _ => mime_type_for(path).to_string(),
+        }
+    }
+    
+    /// Endpoint of the transcription API
+    fn transcriptions_url(&self) -> String {
+        crate::config::endpoint_url(&self.config.api_base_url, &self.config.api.transcription_path)
+    }
+    
+    /// Fail fast with `TranscriptionError::Offline` instead of retrying when there's clearly
+    /// no network, if `api.offline_check` is set
+    fn check_online(&self) -> Result<(), TranscriptionError> {
+        if !self.config.api.offline_check {
+            return Ok(());
+        }
+        check_connectivity(&self.transcriptions_url())
+    }
+    
+    /// Async counterpart of `check_online`; the check runs on tokio's blocking pool
+    #[cfg(feature = "async")]
+    async fn check_online_async(&self) -> Result<(), TranscriptionError> {
+        if !self.config.api.offline_check {
+            return Ok(());
+        }
+        let url = self.transcriptions_url();
+        // チェック自体が失敗した場合はリクエストに任せる
+        tokio::task::spawn_blocking(move || check_connectivity(&url)).await.unwrap_or(Ok(()))
+    }
+    
+    /// Build the vocabulary prompt from `recording.biasing_terms` and the dictionary's original words
+    fn biasing_prompt(&self) -> Option<String> {
+        let dictionary = UserDictionary::load(&self.config.temp_dir.join("user_dictionary.json"));
+        
+        let mut terms: Vec<&str> = Vec::new();
+        let candidates = self.config.recording.biasing_terms.iter()
+            .map(|term| term.trim())
+            .chain(dictionary.originals());
+        let mut length = 0;
+        for term in candidates {
+            if term.is_empty() || terms.contains(&term) {
+                continue;
+            }
+            let added = term.chars().count() + if terms.is_empty() { 0 } else { 2 };
+            if length + added > MAX_PROMPT_CHARS {
+                warn!("Too many biasing terms, the rest are not sent with the prompt");
+                break;
+            }
+            length += added;
+            terms.push(term);
+        }
+        
+        if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join(", "))
+        }
+    }
+    
+    /// Transcribe an audio file with the locally installed Python `whisper` command
+    fn transcribe_whisper_python(&self, audio_path: &str, language: Option<&str>, prompt: Option<&str>) -> Result<String, TranscriptionError> {
+        let whisper = &self.config.transcription.whisper_python;
+        let output_dir = self.config.temp_dir.join("whisper_output");
+        std::fs::create_dir_all(&output_dir)
+            .map_err(TranscriptionError::io("Failed to create whisper output directory"))?;
+        
+        info!("Running {} (model: {})", whisper.command, whisper.model);
+        let mut command = Command::new(&whisper.command);
+        command
+            .arg(audio_path)
+            .arg("--model")
+            .arg(&whisper.model)
+            .arg("--output_format")
+            .arg("txt")
+            .arg("--output_dir")
+            .arg(&output_dir);
+        if let Some(language) = language {
+            command.arg("--language").arg(language);
+        }
+        if let Some(prompt) = prompt {
+            command.arg("--initial_prompt").arg(prompt);
+        }
+        let output = command
+            .args(&whisper.extra_args)
+            .output()
+            .map_err(TranscriptionError::io(format!("Failed to run {} (is openai-whisper installed?)", whisper.command)))?;
+        
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("whisper exited with {}: {}", output.status, stderr);
+            return Err(TranscriptionError::Whisper { status: output.status, stderr: stderr.trim().to_string() });
+        }
+        
+        // whisper writes <stem>.txt into the output directory
+        let stem = Path::new(audio_path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("audio");
+        let text_path = output_dir.join(format!("{}.txt", stem));
+        let text = std::fs::read_to_string(&text_path)
+            .map_err(TranscriptionError::io(format!("Failed to read whisper output: {}", text_path.display())))?;
+        
+        if let Err(e) = std::fs::remove_file(&text_path) {
+            warn!("Failed to remove whisper output {}: {}", text_path.display(), e);
+        }
+        
+        info!("Transcription successful (whisper_python)");
+        Ok(text.trim().to_string())
+    }
+    
+    /// Transcribe an audio file with the OpenAI API
+    fn transcribe_openai(&self, recording: &Recording, language: Option<&str>, prompt: Option<&str>) -> Result<RawTranscript, TranscriptionError> {
         // Check if API key is set
         if self.config.api_key.is_empty() {
-            return Err(anyhow::anyhow!("API key not configured"));
+            return Err(TranscriptionError::NoApiKey);
         }
         
-        // Read the audio file
-        let path = Path::new(audio_path);
-        let mut file = File::open(path)
-            .context("Failed to open audio file")?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)
-            .context("Failed to read audio file")?;
+        let (path, buffer) = read_recording(recording)?;
+        // オフラインならリトライを待たずに失敗させる
+        self.check_online()?;
             
-        // Determine filename for the API
-        let filename = path.file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or("audio.wav");
+        // Determine filename and content type for the API
+        let filename = upload_file_name(path);
+        let mime = self.upload_mime(path);
+        let metadata = self.audio_metadata(&buffer);
             
-        // APIリクエストをリトライループで囲む
-        let max_retries = 3;
-        let mut retry_count = 0;
-        let mut last_error = None;
+        let response_format = self.config.transcription.response_format;
         
-        while retry_count < max_retries {
+        // APIリクエストをリトライループで囲む
+        with_retries("Transcription request", MAX_RETRIES, |retry_count| {
             // Create form part with audio file
-            let part = match Part::bytes(buffer.clone())
-                .file_name(filename.to_string())
-                .mime_str("audio/wav") {
-                Ok(p) => p,
-                Err(e) => {
+            let part = Part::bytes(buffer.clone())
+                .file_name(filename.clone())
+                .mime_str(&mime)
+                .map_err(|e| {
                     error!("Failed to create multipart form: {}", e);
-                    return Err(anyhow::anyhow!("Failed to create multipart form: {}", e));
-                }
-            };
+                    AttemptError::Fatal(TranscriptionError::InvalidRequest(format!("upload MIME type {:?}: {}", mime, e)))
+                })?;
                 
             // Create multipart form
-            let form = Form::new()
-                .part("file", part)
-                .text("model", "gpt-4o-mini-transcribe");
+            let mut form = Form::new().part("file", part);
+            for (name, value) in self.form_fields(language, prompt, metadata) {
+                form = form.text(name, value);
+            }
                 
-            info!("Sending API request (attempt {}/{})", retry_count + 1, max_retries);
+            info!("Sending API request (attempt {}/{})", retry_count + 1, MAX_RETRIES);
             
             // Send request to OpenAI API
-            let response_result = self.client.post("https://api.openai.com/v1/audio/transcriptions")
+            let response = self.client.post(self.transcriptions_url())
                 .header("Authorization", format!("Bearer {}", self.config.api_key))
+                .headers(request_headers(&self.config.api))
                 .multipart(form)
-                .send();
+                .send()
+                .map_err(send_error_attempt)?;
                 
-            match response_result {
-                Ok(response) => {
-                    // Check if request was successful
-                    if response.status().is_success() {
-                        // Parse response
-                        match response.json::<TranscriptionResponse>() {
-                            Ok(transcription) => {
-                                info!("Transcription successful");
-                                return Ok(transcription.text);
-                            },
-                            Err(e) => {
-                                error!("Failed to parse API response: {}", e);
-                                last_error = Some(anyhow::anyhow!("Failed to parse API response: {}", e));
-                            }
-                        }
-                    } else {
-                        let status = response.status();
-                        let error_text = response.text()
-                            .unwrap_or_else(|_| "Failed to read error response".to_string());
-                            
-                        error!("API error {}: {}", status, error_text);
-                        
-                        // 5xxエラーや一時的なエラーのみリトライ
-                        if status.is_server_error() || 
-                           error_text.contains("rate limit") || 
-                           error_text.contains("timeout") {
-                            warn!("Retryable error detected, will retry");
-                            last_error = Some(anyhow::anyhow!("API error {}: {}", status, error_text));
-                        } else {
-                            // それ以外のエラーはすぐに失敗
-                            return Err(anyhow::anyhow!("API error {}: {}", status, error_text));
-                        }
-                    }
-                },
-                Err(e) => {
-                    error!("Failed to send API request: {}", e);
-                    last_error = Some(anyhow::anyhow!("Failed to send API request: {}", e));
-                    
-                    // タイムアウトやネットワークエラーはリトライ
-                    if e.is_timeout() || e.is_connect() {
-                        warn!("Network error detected, will retry");
-                    } else {
-                        // その他のエラーはすぐに失敗
-                        return Err(anyhow::anyhow!("Failed to send API request: {}", e));
-                    }
-                }
+            let status = response.status();
+            if status.is_success() {
+                // Parse response（読み取りの失敗はリトライし、形式の違いはすぐに失敗）
+                let body = response.text().map_err(|e| {
+                    error!("Failed to read API response: {}", e);
+                    AttemptError::Retry(TranscriptionError::Parse(e), None)
+                })?;
+                let transcript = parse_transcript_body(&body, response_format).map_err(AttemptError::Fatal)?;
+                info!("Transcription successful");
+                return Ok(transcript);
             }
-            
-            // リトライの前に待機（指数バックオフ）
-            let wait_time = std::cmp::min(2u64.pow(retry_count as u32), 30);
-            warn!("Retrying in {} seconds...", wait_time);
-            std::thread::sleep(Duration::from_secs(wait_time));
-            
-            retry_count += 1;
+            let requested_wait = retry_after(response.headers());
+            let error_text = response.text()
+                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            Err(error_response_attempt(status, &error_text, requested_wait))
+        })
+    }
+    
+    /// Async counterpart of `transcribe`
+    ///
+    /// The OpenAI request is cancelled as soon as the returned future is dropped. The
+    /// whisper_python backend still runs the command synchronously, so it blocks the runtime
+    /// it is polled on.
+    #[cfg(feature = "async")]
+    pub async fn transcribe_async(&self, recording: &Recording, language_override: Option<&str>) -> Result<RawTranscript, TranscriptionError> {
+        info!("Transcribing audio file: {}", recording);
+        
+        let (language, prompt) = self.request_options(language_override);
+        let (last, fallbacks) = self.backend_order();
+        for &backend in fallbacks {
+            match self.transcribe_with_backend_async(backend, recording, language, prompt.as_deref()).await {
+                Ok(raw) => return Ok(raw),
+                Err(e) => warn!("The {:?} backend failed, trying the next one: {}", backend, e),
+            }
+        }
+        self.transcribe_with_backend_async(last, recording, language, prompt.as_deref()).await
+    }
+    
+    /// Async counterpart of `transcribe_with_backend`
+    #[cfg(feature = "async")]
+    async fn transcribe_with_backend_async(
+        &self,
+        backend: TranscriptionBackend,
+        recording: &Recording,
+        language: Option<&str>,
+        prompt: Option<&str>,
+    ) -> Result<RawTranscript, TranscriptionError> {
+        match backend {
+            TranscriptionBackend::Openai => self.transcribe_openai_async(recording, language, prompt).await,
+            TranscriptionBackend::WhisperPython => self.transcribe_with_backend(backend, recording, language, prompt),
         }
+    }
+    
+    /// Async counterpart of `transcribe_openai`, with the same retries
+    #[cfg(feature = "async")]
+    async fn transcribe_openai_async(&self, recording: &Recording, language: Option<&str>, prompt: Option<&str>) -> Result<RawTranscript, TranscriptionError> {
+        if self.config.api_key.is_empty() {
+            return Err(TranscriptionError::NoApiKey);
+        }
+        
+        let (path, buffer) = read_recording(recording)?;
+        self.check_online_async().await?;
+        let filename = upload_file_name(path);
+        let mime = self.upload_mime(path);
+        let metadata = self.audio_metadata(&buffer);
+        let response_format = self.config.transcription.response_format;
         
-        // 全てのリトライが失敗
-        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("API request failed after {} retries", max_retries)))
+        let (buffer, filename, mime) = (&buffer, &filename, &mime);
+        with_retries_async("Transcription request", MAX_RETRIES, |retry_count| async move {
+            let part = reqwest::multipart::Part::bytes(buffer.clone())
+                .file_name(filename.clone())
+                .mime_str(mime)
+                .map_err(|e| AttemptError::Fatal(TranscriptionError::InvalidRequest(format!("upload MIME type {:?}: {}", mime, e))))?;
+            let mut form = reqwest::multipart::Form::new().part("file", part);
+            for (name, value) in self.form_fields(language, prompt, metadata) {
+                form = form.text(name, value);
+            }
+            
+            info!("Sending API request (attempt {}/{})", retry_count + 1, MAX_RETRIES);
+            let response = self.async_client.post(self.transcriptions_url())
+                .header("Authorization", format!("Bearer {}", self.config.api_key))
+                .headers(request_headers(&self.config.api))
+                .multipart(form)
+                .send()
+                .await
+                .map_err(send_error_attempt)?;
+            
+            let status = response.status();
+            if status.is_success() {
+                let body = response.text().await.map_err(|e| {
+                    error!("Failed to read API response: {}", e);
+                    AttemptError::Retry(TranscriptionError::Parse(e), None)
+                })?;
+                let transcript = parse_transcript_body(&body, response_format).map_err(AttemptError::Fatal)?;
+                info!("Transcription successful");
+                return Ok(transcript);
+            }
+            let requested_wait = retry_after(response.headers());
+            let error_text = response.text().await
+                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            Err(error_response_attempt(status, &error_text, requested_wait))
+        }).await
     }
     
     /// Transcribe an audio file with text processing
-    pub fn transcribe_with_processing(&self, audio_path: &str) -> Result<String> {
+    pub fn transcribe_with_processing(&self, recording: &Recording, language_override: Option<&str>) -> Result<ProcessedTranscript> {
         // 通常の文字起こし実行
-        let raw_text = self.transcribe(audio_path)?;
+        let raw = self.transcribe(recording, language_override)?;
         
         // テキスト処理を適用
-        let mut processor = TranscriptionProcessor::new(self.config.clone());
-        let processed_text = processor.process_transcription(&raw_text)?;
+        let (processed_text, processor_notices) = TranscriptionProcessor::process_shared(&self.processor, &self.config, &raw.text)?;
         
-        Ok(processed_text)
+        let mut notices = self.quality_notice(&raw).into_iter().collect::<Vec<_>>();
+        notices.extend(processor_notices.into_iter().map(ProcessingNotice::message));
+        Ok(ProcessedTranscript { text: processed_text, notices, language: raw.language, backend: raw.backend })
+    }
+    
+    /// Transcribe without the dictionary, filler removal or GPT formatting
+    #[cfg(not(feature = "async"))]
+    pub fn transcribe_verbatim(&self, recording: &Recording, language_override: Option<&str>) -> Result<ProcessedTranscript> {
+        let raw = self.transcribe(recording, language_override)?;
+        let notices = self.quality_notice(&raw).into_iter().collect();
+        Ok(ProcessedTranscript { text: raw.text, notices, language: raw.language, backend: raw.backend })
+    }
+    
+    /// Whether a transcription with `markers` has to go through `transcribe_with_markers`
+    /// to place them or the `ui.inline_timestamps` times
+    pub fn needs_segments(&self, markers: &Markers) -> bool {
+        !markers.times.is_empty() || self.config.ui.inline_timestamps
+    }
+    
+    /// Transcribe like `transcribe_with_processing` (or `transcribe_verbatim`), with a
+    /// `MARKER_SEPARATOR` at each of `markers` and timestamped paragraphs with
+    /// `ui.inline_timestamps`
+    ///
+    /// Placing the markers and times needs the segment times of `verbose_json` responses; with
+    /// other response formats they are left out and a notice says so.
+    #[cfg(not(feature = "async"))]
+    pub fn transcribe_with_markers(
+        &self,
+        recording: &Recording,
+        language_override: Option<&str>,
+        markers: &Markers,
+        verbatim: bool,
+    ) -> Result<ProcessedTranscript> {
+        let raw = self.transcribe(recording, language_override)?;
+        let notices = self.quality_notice(&raw).into_iter().collect();
+        process_sections(self.config.clone(), &self.processor, raw, markers, verbatim, notices)
+    }
+    
+    /// Async counterpart of `transcribe_with_markers`
+    #[cfg(feature = "async")]
+    pub async fn transcribe_with_markers_async(
+        &self,
+        recording: &Recording,
+        language_override: Option<&str>,
+        markers: &Markers,
+        verbatim: bool,
+    ) -> Result<ProcessedTranscript> {
+        let raw = self.transcribe_async(recording, language_override).await?;
+        let notices = self.quality_notice(&raw).into_iter().collect();
+        let config = self.config.clone();
+        let processor = self.processor.clone();
+        let markers = markers.clone();
+        tokio::task::spawn_blocking(move || process_sections(config, &processor, raw, &markers, verbatim, notices)).await?
+    }
+    
+    /// Async counterpart of `transcribe_verbatim`
+    #[cfg(feature = "async")]
+    pub async fn transcribe_verbatim_async(&self, recording: &Recording, language_override: Option<&str>) -> Result<ProcessedTranscript> {
+        let raw = self.transcribe_async(recording, language_override).await?;
+        let notices = self.quality_notice(&raw).into_iter().collect();
+        Ok(ProcessedTranscript { text: raw.text, notices, language: raw.language, backend: raw.backend })
+    }
+    
+    /// Warning about a low-confidence transcript, per `transcription.min_avg_logprob`
+    /// and `transcription.max_no_speech_prob`
+    fn quality_notice(&self, transcript: &RawTranscript) -> Option<String> {
+        let quality = transcript.quality?;
+        let transcription = &self.config.transcription;
+        info!("Transcript quality: avg_logprob {:.2}, no_speech_prob {:.2}", quality.avg_logprob, quality.no_speech_prob);
+        quality.issue(transcription.min_avg_logprob, transcription.max_no_speech_prob).map(QualityIssue::notice)
+    }
+    
+    /// Async counterpart of `transcribe_with_processing`
+    ///
+    /// Text processing uses the blocking client, so it runs on tokio's blocking pool.
+    #[cfg(feature = "async")]
+    pub async fn transcribe_with_processing_async(&self, recording: &Recording, language_override: Option<&str>) -> Result<ProcessedTranscript> {
+        let raw = self.transcribe_async(recording, language_override).await?;
+        let mut notices = self.quality_notice(&raw).into_iter().collect::<Vec<_>>();
+        
+        let config = self.config.clone();
+        let processor = self.processor.clone();
+        tokio::task::spawn_blocking(move || {
+            let (processed_text, processor_notices) = TranscriptionProcessor::process_shared(&processor, &config, &raw.text)?;
+            notices.extend(processor_notices.into_iter().map(ProcessingNotice::message));
+            Ok(ProcessedTranscript { text: processed_text, notices, language: raw.language, backend: raw.backend })
+        })
+        .await?
     }
     
     /// Implement mock transcription for testing without API key
@@ -176,9 +1204,567 @@ impl TranscriptionAPI {
     pub fn mock_transcribe_with_processing(&self, _audio_path: &str) -> Result<String> {
         let raw_text = "えーと、今日はですね、あのー音声認識の精度についてまぁ話をしたいとおもいます。えっと、最近の技術では、えー、かなり高い精度で認識ができるようになってきてますよね。";
         
-        let mut processor = TranscriptionProcessor::new(self.config.clone());
-        let processed_text = processor.process_transcription(raw_text)?;
+        TranscriptionProcessor::process_shared(&self.processor, &self.config, raw_text).map(|(text, _)| text)
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::analyze_wav;
+    use crate::config::default_config;
+    use crate::text_processor::UserDictionary;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+
+    const MOCK_TRANSCRIPT: &str = "えーと、今日は「ラスト」について話します";
+
+    #[test]
+    fn upload_type_follows_extension_unless_overridden() {
+        assert_eq!(mime_type_for(Path::new("/tmp/rec.OGG")), "audio/ogg");
+        assert_eq!(mime_type_for(Path::new("/tmp/rec.opus")), "audio/opus");
+        assert_eq!(mime_type_for(Path::new("/tmp/rec")), "audio/wav");
+        assert_eq!(upload_file_name(Path::new("/tmp/rec.mp3")), "rec.mp3");
+        assert_eq!(upload_file_name(Path::new("")), "audio.wav");
+
+        let mut config = default_config();
+        let api = TranscriptionAPI::new(config.clone());
+        assert_eq!(api.upload_mime(Path::new("/tmp/rec.flac")), "audio/flac");
+        config.api.upload_mime = Some("audio/x-wav".to_string());
+        let api = TranscriptionAPI::new(config);
+        assert_eq!(api.upload_mime(Path::new("/tmp/rec.wav")), "audio/x-wav");
+    }
+
+    #[test]
+    fn audio_metadata_is_read_from_the_wav_header_when_enabled() {
+        let spec = hound::WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut wav = std::io::Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        writer.write_sample(0i16).unwrap();
+        writer.write_sample(0i16).unwrap();
+        writer.finalize().unwrap();
+        let wav = wav.into_inner();
+
+        let mut config = default_config();
+        assert_eq!(TranscriptionAPI::new(config.clone()).audio_metadata(&wav), None);
+        config.api.send_audio_metadata = true;
+        let api = TranscriptionAPI::new(config);
+        assert_eq!(api.audio_metadata(&wav), Some((44100, 2)));
+        assert_eq!(api.audio_metadata(b"OggS not a wav"), None);
+        let fields = api.form_fields(None, None, Some((44100, 2)));
+        assert!(fields.contains(&("sample_rate", "44100".to_string())));
+        assert!(fields.contains(&("channels", "2".to_string())));
+    }
+
+    /// Minimal OpenAI stand-in: answers transcription and chat requests and records their bodies
+    fn start_mock_server(requests: Arc<Mutex<Vec<(String, String)>>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default().to_string();
+
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap_or(0);
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8_lossy(&body).to_string();
+
+                let response_body = if path.ends_with("/audio/transcriptions") && body.contains("verbose_json") {
+                    // 後半の区間は信頼度が低い
+                    serde_json::json!({
+                        "text": MOCK_TRANSCRIPT,
+                        "language": "japanese",
+                        "segments": [
+                            { "start": 0.0, "end": 1.0, "avg_logprob": -0.2, "no_speech_prob": 0.01 },
+                            { "start": 1.0, "end": 4.0, "avg_logprob": -1.8, "no_speech_prob": 0.1 }
+                        ]
+                    }).to_string()
+                } else if path.ends_with("/audio/transcriptions") && body.contains("name=\"response_format\"") {
+                    // response_format=text ではプレーンテキストを返す
+                    format!("{}\n", MOCK_TRANSCRIPT)
+                } else if path.ends_with("/audio/transcriptions") {
+                    serde_json::json!({ "text": MOCK_TRANSCRIPT }).to_string()
+                } else {
+                    // 整形役: 辞書の置換を適用したテキストを返す
+                    serde_json::json!({
+                        "choices": [{ "message": { "content": "今日は「Rust」について話します。" } }]
+                    }).to_string()
+                };
+                requests.lock().unwrap().push((path, body));
+
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response_body.len(),
+                    response_body
+                );
+            }
+        });
+
+        base_url
+    }
+
+    /// Fresh temp_dir holding a one-second 440 Hz recording and a user dictionary
+    fn setup_recording(name: &str) -> (PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("wispr_pipeline_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let wav_path = dir.join("recording_test.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&wav_path, spec).unwrap();
+        for i in 0..16000 {
+            let t = i as f32 / 16000.0;
+            let sample = (t * 440.0 * 2.0 * std::f32::consts::PI).sin() * 0.3;
+            writer.write_sample((sample * i16::MAX as f32) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut dictionary = UserDictionary::new();
+        dictionary.add_word("ラスト".to_string(), "Rust".to_string());
+        dictionary.save(&dir.join("user_dictionary.json")).unwrap();
+
+        (dir, wav_path.to_string_lossy().to_string())
+    }
+
+    fn test_config(dir: &Path, base_url: String) -> Config {
+        let mut config = default_config();
+        config.api_key = "test-key".to_string();
+        config.api_base_url = base_url;
+        config.temp_dir = dir.to_path_buf();
+        // 接続確認の空の接続でモックサーバーを止めない
+        config.api.offline_check = false;
+        config
+    }
+
+    #[test]
+    fn pipeline_applies_dictionary_locally() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = start_mock_server(requests.clone());
+        let (dir, wav_path) = setup_recording("local");
+
+        let analysis = analyze_wav(&wav_path).unwrap();
+        assert!(!analysis.is_silent());
+        assert!(!analysis.is_clipped());
+
+        let mut config = test_config(&dir, base_url);
+        config.text_processing.gpt_formatting = false;
+        let api = TranscriptionAPI::new(config);
+        let text = api.transcribe_with_processing(&Recording::File(wav_path), Some("ja")).unwrap().text;
+
+        assert!(!text.is_empty());
+        assert!(text.contains("Rust"), "dictionary was not applied: {}", text);
+        assert!(!text.contains("えーと"), "filler was not stripped: {}", text);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].0.ends_with("/audio/transcriptions"));
+        assert!(requests[0].1.contains("recording_test.wav"));
+        // 辞書の置換元がプロンプトとして送られていること
+        assert!(requests[0].1.contains("name=\"prompt\""));
+        assert!(requests[0].1.contains("ラスト"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn frequencies_persist_across_transcriptions_and_dictionary_edits_are_picked_up() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = start_mock_server(requests);
+        let (dir, wav_path) = setup_recording("session");
+        let dictionary_path = dir.join("user_dictionary.json");
+        let frequency = || {
+            let dictionary: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&dictionary_path).unwrap()).unwrap();
+            dictionary["frequent_terms"][MOCK_TRANSCRIPT].as_u64()
+        };
+
+        let mut config = test_config(&dir, base_url);
+        config.text_processing.gpt_formatting = false;
+        let api = TranscriptionAPI::new(config);
+        let recording = Recording::File(wav_path);
+        api.transcribe_with_processing(&recording, None).unwrap();
+        api.transcribe_with_processing(&recording, None).unwrap();
+        assert_eq!(frequency(), Some(2));
+
+        // 辞書パネルなど別の場所で保存された単語も次の文字起こしで使われる
+        let mut dictionary = UserDictionary::load(&dictionary_path);
+        dictionary.add_word("今日".to_string(), "本日".to_string());
+        dictionary.save(&dictionary_path).unwrap();
+        let text = api.transcribe_with_processing(&recording, None).unwrap().text;
+        assert!(text.contains("本日") && text.contains("Rust"), "dictionary edit was not picked up: {}", text);
+        assert_eq!(frequency(), Some(3));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn pipeline_sends_dictionary_to_formatter() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = start_mock_server(requests.clone());
+        let (dir, wav_path) = setup_recording("gpt");
+
+        let api = TranscriptionAPI::new(test_config(&dir, base_url));
+        let text = api.transcribe_with_processing(&Recording::File(wav_path), None).unwrap().text;
+
+        assert_eq!(text, "今日は「Rust」について話します。");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].0.ends_with("/chat/completions"));
+        // 整形プロンプトに文字起こし結果と辞書の置換ルールが含まれていること
+        let chat_body = &requests[1].1;
+        assert!(chat_body.contains("「ラスト」について"));
+        assert!(chat_body.contains("Rust"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn transcript_is_read_from_common_field_names() {
+        let json = |body: &str| parse_transcript_body(body, crate::config::ResponseFormat::Json);
+        assert_eq!(json(r#"{"text": "こんにちは"}"#).unwrap().text, "こんにちは");
+        assert_eq!(json(r#"{"transcript": "hello"}"#).unwrap().text, "hello");
+        assert_eq!(json(r#"{"result": "hello", "language": "en"}"#).unwrap().text, "hello");
+        assert!(matches!(json(r#"{"output": "hello"}"#), Err(TranscriptionError::Schema(_))));
+        assert!(matches!(json("<html>"), Err(TranscriptionError::Schema(_))));
+        
+        let verbose = parse_transcript_body(r#"{"transcript": "hi", "segments": "?"}"#, crate::config::ResponseFormat::VerboseJson).unwrap();
+        assert_eq!(verbose.text, "hi");
+        assert!(verbose.quality.is_none());
+    }
+
+    #[test]
+    fn text_response_format_reads_plain_body() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = start_mock_server(requests.clone());
+        let (dir, wav_path) = setup_recording("text");
+
+        let mut config = test_config(&dir, base_url);
+        config.transcription.response_format = crate::config::ResponseFormat::Text;
+        let api = TranscriptionAPI::new(config);
+
+        assert_eq!(api.transcribe(&Recording::File(wav_path), None).unwrap().text, MOCK_TRANSCRIPT);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn verbose_json_flags_low_confidence_transcripts() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = start_mock_server(requests.clone());
+        let (dir, wav_path) = setup_recording("verbose");
+
+        let mut config = test_config(&dir, base_url);
+        config.transcription.response_format = crate::config::ResponseFormat::VerboseJson;
+        config.text_processing.gpt_formatting = false;
+        let api = TranscriptionAPI::new(config);
+
+        let raw = api.transcribe(&Recording::File(wav_path.clone()), None).unwrap();
+        assert_eq!(raw.text, MOCK_TRANSCRIPT);
+        let quality = raw.quality.unwrap();
+        // 長さで重み付け: (-0.2 * 1 + -1.8 * 3) / 4
+        assert!((quality.avg_logprob - -1.4).abs() < 1e-9);
+        assert!(matches!(quality.issue(-1.0, 0.6), Some(QualityIssue::LowConfidence(_))));
+        assert!(quality.issue(-1.5, 0.6).is_none());
+
+        let processed = api.transcribe_with_processing(&Recording::File(wav_path), None).unwrap();
+        assert!(processed.notices.iter().any(|notice| notice.contains("信頼度")));
+        assert_eq!(processed.language.as_deref(), Some("japanese"));
+        assert!(requests.lock().unwrap()[0].1.contains("whisper-1"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn segment(start: f64, end: f64, text: &str) -> TranscriptionSegment {
+        TranscriptionSegment {
+            start,
+            end,
+            text: text.to_string(),
+            avg_logprob: 0.0,
+            no_speech_prob: 0.0,
+        }
+    }
+
+    #[test]
+    fn markers_split_at_the_nearest_segment_boundary() {
+        let segments = [segment(0.0, 2.0, "一つ目。"), segment(2.0, 5.0, "二つ目。"), segment(5.0, 9.0, "三つ目。")];
+        let split_at_markers = |segments: &[TranscriptionSegment], markers: &[f64]| -> Vec<String> {
+            marker_sections(segments, markers)
+                .into_iter()
+                .map(|section| section.iter().map(|segment| segment.text.as_str()).collect())
+                .collect()
+        };
+        assert_eq!(split_at_markers(&segments, &[]), ["一つ目。二つ目。三つ目。"]);
+        assert_eq!(split_at_markers(&segments, &[4.0, 4.5]), ["一つ目。二つ目。", "三つ目。"]);
+        assert_eq!(split_at_markers(&segments, &[1.2, 8.0]), ["一つ目。", "二つ目。三つ目。"]);
+        // 先頭・末尾のマーカーは空の区間になるので無視される
+        assert_eq!(split_at_markers(&segments, &[0.1, 30.0]), ["一つ目。二つ目。三つ目。"]);
+        
+        let markers = |times: &[f64], preroll_secs: f64| Markers { times: times.to_vec(), preroll_secs };
+        let raw = RawTranscript { text: "全文".to_string(), quality: None, language: None, segments: segments.to_vec(), backend: TranscriptionBackend::Openai };
+        let processed = process_sections(default_config(), &SharedProcessor::default(), raw, &markers(&[2.9], 0.0), true, Vec::new()).unwrap();
+        assert_eq!(processed.text, "一つ目。\n\n--- marker ---\n\n二つ目。三つ目。");
+        
+        // 直前の入力を付け足した分だけ録音内の位置は後ろになる
+        let raw = RawTranscript { text: "全文".to_string(), quality: None, language: None, segments: segments.into(), backend: TranscriptionBackend::Openai };
+        let processed = process_sections(default_config(), &SharedProcessor::default(), raw, &markers(&[0.9], 2.0), true, Vec::new()).unwrap();
+        assert_eq!(processed.text, "一つ目。\n\n--- marker ---\n\n二つ目。三つ目。");
+        
+        let processed = process_sections(default_config(), &SharedProcessor::default(), RawTranscript::text("全文".to_string()), &markers(&[3.9], 0.0), true, Vec::new()).unwrap();
+        assert_eq!(processed.text, "全文");
+        assert_eq!(processed.notices.len(), 1);
+    }
+    
+    #[test]
+    fn inline_timestamps_start_each_paragraph() {
+        assert_eq!(format_timestamp(5.7), "[00:05]");
+        assert_eq!(format_timestamp(3725.0), "[1:02:05]");
+        
+        // 30秒に達するまで区間をまとめて1段落にする
+        let segments = || vec![
+            segment(5.0, 20.0, " 議題は二つです。"),
+            segment(20.0, 40.0, " まず予算について。"),
+            segment(40.0, 50.0, " 次に日程です。"),
+            segment(50.0, 65.0, " 以上です。"),
+        ];
+        let mut config = default_config();
+        config.ui.inline_timestamps = true;
+        let raw = RawTranscript { text: "全文".to_string(), quality: None, language: None, segments: segments(), backend: TranscriptionBackend::Openai };
+        let processed = process_sections(config.clone(), &SharedProcessor::default(), raw, &Markers::default(), true, Vec::new()).unwrap();
+        assert_eq!(processed.text, "[00:05] 議題は二つです。 まず予算について。\n\n[00:40] 次に日程です。 以上です。");
         
-        Ok(processed_text)
+        let raw = RawTranscript { text: "全文".to_string(), quality: None, language: None, segments: segments(), backend: TranscriptionBackend::Openai };
+        let markers = Markers { times: vec![40.0], preroll_secs: 0.0 };
+        let processed = process_sections(config.clone(), &SharedProcessor::default(), raw, &markers, true, Vec::new()).unwrap();
+        assert_eq!(
+            processed.text,
+            "[00:05] 議題は二つです。 まず予算について。\n\n--- marker ---\n\n[00:40] 次に日程です。 以上です。"
+        );
+        
+        // 直前の入力の分を引いて、録音を始めた時点からの時刻にする
+        let raw = RawTranscript { text: "全文".to_string(), quality: None, language: None, segments: segments(), backend: TranscriptionBackend::Openai };
+        let markers = Markers { times: vec![37.0], preroll_secs: 3.0 };
+        let processed = process_sections(config, &SharedProcessor::default(), raw, &markers, true, Vec::new()).unwrap();
+        assert_eq!(
+            processed.text,
+            "[00:02] 議題は二つです。 まず予算について。\n\n--- marker ---\n\n[00:37] 次に日程です。 以上です。"
+        );
+    }
+    
+    #[test]
+    fn failed_backend_falls_back_to_the_next_one() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = start_mock_server(requests.clone());
+        let (dir, wav_path) = setup_recording("fallback");
+
+        let mut config = test_config(&dir, base_url);
+        config.transcription.backend = crate::config::BackendChain::Chain(vec![
+            TranscriptionBackend::WhisperPython,
+            TranscriptionBackend::Openai,
+        ]);
+        config.transcription.whisper_python.command = "wispr-missing-whisper".to_string();
+        let api = TranscriptionAPI::new(config);
+        let raw = api.transcribe(&Recording::File(wav_path), None).unwrap();
+        assert_eq!(raw.text, MOCK_TRANSCRIPT);
+        assert_eq!(raw.backend, TranscriptionBackend::Openai);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn in_memory_recording_is_uploaded_without_a_file() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = start_mock_server(requests.clone());
+        let (dir, wav_path) = setup_recording("memory");
+        let data = std::fs::read(&wav_path).unwrap();
+        std::fs::remove_file(&wav_path).unwrap();
+
+        let api = TranscriptionAPI::new(test_config(&dir, base_url));
+        let recording = Recording::Memory { path: wav_path, data: Arc::new(data) };
+        assert_eq!(api.transcribe(&recording, None).unwrap().text, MOCK_TRANSCRIPT);
+        assert!(requests.lock().unwrap()[0].1.contains("recording_test.wav"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_pipeline_matches_blocking_pipeline() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = start_mock_server(requests.clone());
+        let (dir, wav_path) = setup_recording("async");
+
+        let api = TranscriptionAPI::new(test_config(&dir, base_url));
+        let recording = Recording::File(wav_path);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let processed = runtime.block_on(api.transcribe_with_processing_async(&recording, None)).unwrap();
+        assert_eq!(processed.text, api.transcribe_with_processing(&recording, None).unwrap().text);
+        assert!(requests.lock().unwrap()[0].0.ends_with("/audio/transcriptions"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn retry_delay_is_jittered_within_the_backoff_window() {
+        for retry_count in 0..8 {
+            let window = Duration::from_secs(std::cmp::min(2u64.pow(retry_count as u32), 30));
+            assert!(retry_delay(retry_count, None) <= window);
+        }
+        assert_eq!(retry_delay(0, Some(Duration::from_secs(7))), Duration::from_secs(7));
+        assert_eq!(retry_delay(0, Some(Duration::from_secs(3600))), MAX_RETRY_AFTER);
+    }
+
+    #[test]
+    fn with_retries_stops_at_success_fatal_errors_or_the_attempt_limit() {
+        let mut calls = 0;
+        let result: Result<usize, String> = with_retries("test", 3, |retry_count| {
+            calls += 1;
+            match retry_count {
+                0 => Err(AttemptError::Retry("503".to_string(), Some(Duration::ZERO))),
+                n => Ok(n),
+            }
+        });
+        assert_eq!((result, calls), (Ok(1), 2));
+
+        let mut calls = 0;
+        let result: Result<(), String> = with_retries("test", 3, |_| {
+            calls += 1;
+            Err(AttemptError::Fatal("401".to_string()))
+        });
+        assert_eq!((result, calls), (Err("401".to_string()), 1));
+
+        let mut calls = 0;
+        let result: Result<(), String> = with_retries("test", 3, |retry_count| {
+            calls += 1;
+            Err(AttemptError::Retry(format!("503 #{}", retry_count), Some(Duration::ZERO)))
+        });
+        assert_eq!((result, calls), (Err("503 #2".to_string()), 3));
+    }
+
+    #[test]
+    fn retry_after_header_accepts_seconds_and_dates() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(reqwest::header::RETRY_AFTER, "12".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(12)));
+        headers.insert(reqwest::header::RETRY_AFTER, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+        headers.insert(reqwest::header::RETRY_AFTER, "soon".parse().unwrap());
+        assert_eq!(retry_after(&headers), None);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn only_a_clear_lack_of_network_counts_as_offline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(probe_host("127.0.0.1", listener.local_addr().unwrap().port()).is_ok());
+        // 接続を拒否されるのはサーバーが落ちているだけなのでリクエストに任せる
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        assert!(probe_addrs("localhost", Ok(vec![addr])).is_ok());
+        assert!(matches!(probe_addrs("wispr.invalid", Err("no such host".to_string())), Err(TranscriptionError::Offline { .. })));
+        assert!(matches!(probe_addrs("wispr.invalid", Ok(Vec::new())), Err(TranscriptionError::Offline { .. })));
+        assert!(check_connectivity("not a url").is_ok());
+    }
+
+    #[test]
+    fn a_hanging_dns_lookup_gives_up_after_the_timeout() {
+        let hanging = || {
+            std::thread::sleep(Duration::from_secs(5));
+            Ok(Vec::new())
+        };
+        let started = std::time::Instant::now();
+        assert!(resolve_within(hanging, Duration::from_millis(50)).unwrap_err().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        
+        let failing = || Err(std::io::Error::other("no such host"));
+        assert!(resolve_within(failing, Duration::from_secs(2)).unwrap_err().contains("no such host"));
+    }
+
+    #[test]
+    fn organization_headers_are_sent_only_when_set() {
+        let mut config = default_config().api;
+        assert!(request_headers(&config).is_empty());
+
+        config.organization = Some("org-123".to_string());
+        config.project = Some(" ".to_string());
+        let headers = request_headers(&config);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers["OpenAI-Organization"], "org-123");
+
+        config.project = Some("proj_456".to_string());
+        assert_eq!(request_headers(&config)["OpenAI-Project"], "proj_456");
+
+        config.extra_headers.insert("X-Gateway-Token".to_string(), "token".to_string());
+        assert_eq!(request_headers(&config)["X-Gateway-Token"], "token");
+    }
+
+    #[test]
+    fn unusable_ca_cert_is_skipped() {
+        let dir = std::env::temp_dir().join(format!("wispr_ca_cert_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = default_config().api;
+        config.ca_cert_path = Some(dir.join("missing.pem"));
+        assert!(custom_root_certificate(&config).is_none());
+
+        let garbage = dir.join("garbage.pem");
+        std::fs::write(&garbage, "not a certificate").unwrap();
+        config.ca_cert_path = Some(garbage);
+        assert!(custom_root_certificate(&config).is_none());
+        // 証明書が使えなくてもクライアントは作れる
+        assert!(blocking_client_builder(&config).build().is_ok());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_api_key_is_reported_as_no_api_key() {
+        let (dir, wav_path) = setup_recording("nokey");
+        let mut config = test_config(&dir, "http://127.0.0.1:9/v1".to_string());
+        config.api_key.clear();
+        let api = TranscriptionAPI::new(config);
+
+        let error = api.transcribe(&Recording::File(wav_path), None).unwrap_err();
+        assert!(matches!(error, TranscriptionError::NoApiKey), "unexpected error: {}", error);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejected_keys_are_not_retried() {
+        for status in [reqwest::StatusCode::UNAUTHORIZED, reqwest::StatusCode::FORBIDDEN] {
+            let (error, retryable) = classify_error_response(status, "Incorrect API key provided");
+            assert!(matches!(error, TranscriptionError::Unauthorized { .. }), "unexpected error: {}", error);
+            assert!(!retryable);
+        }
+        let (error, retryable) = classify_error_response(reqwest::StatusCode::BAD_GATEWAY, "bad gateway");
+        assert!(matches!(error, TranscriptionError::Server { status: 502, .. }));
+        assert!(retryable);
+    }
+}
@@ -1,24 +1,522 @@
 use anyhow::{Result, Context};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::SizedSample;
+use cpal::{FromSample, SizedSample};
 use log::{info, error, warn};
+use std::collections::VecDeque;
 use std::fs::File;
-use std::io::BufWriter;
-use std::sync::{Arc, Mutex};
+use std::io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::sync::atomic::{AtomicBool, Ordering, AtomicU64};
 use std::time::{Duration, Instant};
 use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 
-use crate::config::Config;
+use crate::config::{Config, RecordingConfig, UiConfig, SampleFormatPreference, TranscriptionBackend, DEFAULT_FILENAME_TEMPLATE};
+
+/// Read, measure and modify 16-bit WAV audio held in memory
+pub mod wav_utils;
+
+/// Prefix of device names that refer to a PulseAudio/PipeWire monitor source
+pub const LOOPBACK_PREFIX: &str = "Monitor: ";
+
+/// ALSA device that routes through the sound server, used to open monitor sources
+const LOOPBACK_DEVICE: &str = "pulse";
+
+/// The cpal host named by `audio.host`, or the system default host
+///
+/// "default" (or an empty name) selects `cpal::default_host()`. Other names are matched
+/// case-insensitively against the hosts compiled into cpal; an unknown or unavailable host
+/// logs a warning and falls back to the default.
+pub fn audio_host(name: &str) -> cpal::Host {
+    let name = name.trim();
+    if name.is_empty() || name.eq_ignore_ascii_case("default") {
+        return cpal::default_host();
+    }
+    let Some(id) = host_id(name) else {
+        let available: Vec<&str> = cpal::available_hosts().iter().map(|id| id.name()).collect();
+        warn!("Audio host {:?} is not available (available: {}); using the default host", name, available.join(", "));
+        return cpal::default_host();
+    };
+    match cpal::host_from_id(id) {
+        Ok(host) => host,
+        Err(e) => {
+            warn!("Failed to open audio host {}: {}; using the default host", id.name(), e);
+            cpal::default_host()
+        }
+    }
+}
+
+/// What an input device supports, for the device tooltip
+pub struct DeviceCapabilities {
+    /// Sample rate, channels and format of `default_input_config`
+    pub default: Option<(u32, u16, cpal::SampleFormat)>,
+    /// Min and max sample rate, channels and format of each `supported_input_configs` range
+    pub ranges: Vec<(u32, u32, u16, cpal::SampleFormat)>,
+}
+
+impl DeviceCapabilities {
+    /// Query the input device `name` of the `audio.host` host (`None` for its default device)
+    ///
+    /// Monitor sources are opened through the `pulse` device, so that device is described.
+    pub fn query(host_name: &str, name: Option<&str>) -> Result<Self> {
+        let host = audio_host(host_name);
+        let name = name.map(|name| if name.starts_with(LOOPBACK_PREFIX) { LOOPBACK_DEVICE } else { name });
+        let device = match name {
+            Some(name) => host.input_devices()?
+                .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+                .with_context(|| format!("Device {} not found", name))?,
+            None => host.default_input_device().context("No input device found")?,
+        };
+        let default = device.default_input_config().ok()
+            .map(|config| (config.sample_rate().0, config.channels(), config.sample_format()));
+        let ranges = match device.supported_input_configs() {
+            Ok(configs) => configs
+                .map(|range| (range.min_sample_rate().0, range.max_sample_rate().0, range.channels(), range.sample_format()))
+                .collect(),
+            Err(e) => {
+                warn!("Failed to list supported input configs: {}", e);
+                Vec::new()
+            }
+        };
+        Ok(Self { default, ranges })
+    }
+    
+    /// One line for the default config and one per sample format, e.g. "f32: 1, 2 ch, 8000-192000 Hz"
+    ///
+    /// `default_label` and `supported_label` head the two parts.
+    pub fn summary(&self, default_label: &str, supported_label: &str) -> String {
+        let mut lines = Vec::new();
+        if let Some((rate, channels, format)) = self.default {
+            lines.push(format!("{}: {} Hz, {} ch, {}", default_label, rate, channels, format));
+        }
+        // 形式ごとにチャンネル数とサンプルレートの範囲をまとめる
+        let mut formats: Vec<(String, u32, u32, Vec<u16>)> = Vec::new();
+        for &(min_rate, max_rate, channels, format) in &self.ranges {
+            let format = format.to_string();
+            match formats.iter_mut().find(|(name, ..)| *name == format) {
+                Some((_, min, max, all_channels)) => {
+                    *min = (*min).min(min_rate);
+                    *max = (*max).max(max_rate);
+                    if !all_channels.contains(&channels) {
+                        all_channels.push(channels);
+                    }
+                },
+                None => formats.push((format, min_rate, max_rate, vec![channels])),
+            }
+        }
+        if !formats.is_empty() {
+            lines.push(format!("{}:", supported_label));
+        }
+        for (format, min_rate, max_rate, mut channels) in formats {
+            channels.sort_unstable();
+            let channels: Vec<String> = channels.iter().map(u16::to_string).collect();
+            let rates = if min_rate == max_rate {
+                format!("{} Hz", min_rate)
+            } else {
+                format!("{}-{} Hz", min_rate, max_rate)
+            };
+            lines.push(format!("  {}: {} ch, {}", format, channels.join(", "), rates));
+        }
+        lines.join("\n")
+    }
+}
+
+/// The compiled-in cpal host called `name`, ignoring case
+fn host_id(name: &str) -> Option<cpal::HostId> {
+    cpal::available_hosts().into_iter().find(|id| id.name().eq_ignore_ascii_case(name))
+}
+
+/// List the monitor (loopback) sources exposed by the sound server
+///
+/// cpal only sees ALSA devices, so monitor sources are listed with `pactl` and opened
+/// through the `pulse` ALSA device. The list is empty when `pactl` is not available or
+/// the sound server exposes no monitor sources.
+pub fn loopback_sources() -> Vec<String> {
+    match std::process::Command::new("pactl").args(["list", "short", "sources"]).output() {
+        Ok(output) if output.status.success() => {
+            parse_monitor_sources(&String::from_utf8_lossy(&output.stdout))
+        }
+        Ok(output) => {
+            warn!("pactl exited with status {}", output.status);
+            Vec::new()
+        }
+        Err(e) => {
+            info!("pactl not available, monitor sources will not be listed: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// List the output sinks of the sound server, which `play_wav` can play back on
+///
+/// The list is empty when `pactl` is not available.
+pub fn output_sinks() -> Vec<String> {
+    match std::process::Command::new("pactl").args(["list", "short", "sinks"]).output() {
+        Ok(output) if output.status.success() => {
+            parse_sink_names(&String::from_utf8_lossy(&output.stdout))
+        }
+        Ok(output) => {
+            warn!("pactl exited with status {}", output.status);
+            Vec::new()
+        }
+        Err(e) => {
+            info!("pactl not available, output devices will not be listed: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Pick the sink names out of `pactl list short sinks` output
+fn parse_sink_names(output: &str) -> Vec<String> {
+    output.lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Pick the monitor source names out of `pactl list short sources` output
+fn parse_monitor_sources(output: &str) -> Vec<String> {
+    output.lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .filter(|name| name.ends_with(".monitor"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// List the indexes of this process's recording streams on the sound server
+///
+/// The list is empty when `pactl` is not available.
+fn own_source_outputs() -> Vec<u32> {
+    match std::process::Command::new("pactl").args(["--format=json", "list", "source-outputs"]).output() {
+        Ok(output) if output.status.success() => {
+            parse_source_outputs(&String::from_utf8_lossy(&output.stdout), std::process::id())
+        }
+        Ok(output) => {
+            warn!("pactl exited with status {}", output.status);
+            Vec::new()
+        }
+        Err(e) => {
+            warn!("pactl not available, recording streams can't be moved: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Pick the indexes of the streams of process `pid` out of `pactl --format=json list source-outputs` output
+fn parse_source_outputs(output: &str, pid: u32) -> Vec<u32> {
+    let outputs: Vec<serde_json::Value> = serde_json::from_str(output).unwrap_or_default();
+    let pid = pid.to_string();
+    outputs.iter()
+        .filter(|output| output["properties"]["application.process.id"].as_str() == Some(pid.as_str()))
+        .filter_map(|output| output["index"].as_u64())
+        .filter_map(|index| u32::try_from(index).ok())
+        .collect()
+}
+
+/// Move the recording streams of this process that aren't in `existing` to `source`
+///
+/// The `pulse` ALSA plugin records from the default source, so a stream opened for a monitor
+/// source is moved there once the sound server knows it. Neither the server's default source
+/// nor the environment of this process is changed.
+fn move_new_source_outputs(existing: &[u32], source: &str) {
+    let moved: Vec<u32> = own_source_outputs().into_iter().filter(|index| !existing.contains(index)).collect();
+    if moved.is_empty() {
+        warn!("Recording stream not found on the sound server, recording from the default source instead of {}", source);
+    }
+    for index in moved {
+        match std::process::Command::new("pactl").args(["move-source-output", &index.to_string(), source]).status() {
+            Ok(status) if status.success() => info!("Moved recording stream {} to {}", index, source),
+            Ok(status) => warn!("pactl move-source-output exited with status {}", status),
+            Err(e) => warn!("Failed to move recording stream {} to {}: {}", index, source, e),
+        }
+    }
+}
+
+/// Counter for the `{counter}` filename placeholder, shared by every recording in this session
+static RECORDING_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+/// Build a recording file name from `recording.filename_template`
+///
+/// Supported placeholders are `{date}` (YYYYMMDD), `{time}` (HHMMSS), `{timestamp}`
+/// (`{date}_{time}`) and `{counter}`, which is bumped until no file in `dir` has that name.
+/// A `.wav` extension is appended when the template doesn't already end in one.
+pub fn recording_file_name(template: &str, dir: &std::path::Path) -> Result<String> {
+    let now = chrono::Local::now();
+    let render = |counter: u64| {
+        let mut name = template
+            .replace("{date}", &now.format("%Y%m%d").to_string())
+            .replace("{time}", &now.format("%H%M%S").to_string())
+            .replace("{timestamp}", &now.format("%Y%m%d_%H%M%S").to_string())
+            .replace("{counter}", &counter.to_string());
+        if !name.to_lowercase().ends_with(".wav") {
+            name.push_str(".wav");
+        }
+        name
+    };
+    
+    let mut name = render(RECORDING_COUNTER.load(Ordering::SeqCst));
+    if template.contains("{counter}") {
+        loop {
+            let counter = RECORDING_COUNTER.fetch_add(1, Ordering::SeqCst);
+            name = render(counter);
+            if !dir.join(&name).exists() {
+                break;
+            }
+        }
+    }
+    
+    validate_file_name(&name)?;
+    Ok(name)
+}
+
+/// Reject file names that would escape the output directory or that filesystems can't store
+fn validate_file_name(name: &str) -> Result<()> {
+    let stem = &name[..name.len() - ".wav".len()];
+    if stem.trim().is_empty() {
+        anyhow::bail!("file name is empty");
+    }
+    if name.len() > 255 {
+        anyhow::bail!("file name is longer than 255 bytes");
+    }
+    if name.starts_with('.') {
+        anyhow::bail!("file name must not start with '.'");
+    }
+    if let Some(c) = name.chars().find(|c| matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control()) {
+        anyhow::bail!("file name contains the character {:?}", c);
+    }
+    if let Some(placeholder) = regex::Regex::new(r"\{[^}]*\}").ok().and_then(|re| re.find(name)) {
+        anyhow::bail!("unknown placeholder {}", placeholder.as_str());
+    }
+    Ok(())
+}
+
+/// Largest in-memory recording; longer recordings continue in their file
+const MAX_IN_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// A finished recording
+#[derive(Clone)]
+pub enum Recording {
+    /// WAV file on disk
+    File(String),
+    /// WAV data that was never written to disk (`recording.in_memory`); `path` is
+    /// where it would have been recorded
+    Memory { path: String, data: Arc<Vec<u8>> },
+}
+
+impl Recording {
+    /// Path of the recording file, None for in-memory recordings
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::File(path) => Some(path),
+            Self::Memory { .. } => None,
+        }
+    }
+    
+    /// Write an in-memory recording to its path and return the path of the file
+    pub fn persist(self) -> Result<String> {
+        match self {
+            Self::File(path) => Ok(path),
+            Self::Memory { path, data } => {
+                std::fs::write(&path, data.as_slice())
+                    .with_context(|| format!("Failed to write recording to {}", path))?;
+                Ok(path)
+            }
+        }
+    }
+    
+    /// Delete the recording file; in-memory recordings just need to be dropped
+    pub fn discard(self) {
+        if let Self::File(path) = self {
+            match std::fs::remove_file(&path) {
+                Ok(_) => info!("Recording discarded: {}", path),
+                Err(e) => warn!("Failed to remove recording {}: {}", path, e),
+            }
+        }
+    }
+    
+    fn len(&self) -> Result<u64> {
+        match self {
+            Self::File(path) => Ok(std::fs::metadata(path)?.len()),
+            Self::Memory { data, .. } => Ok(data.len() as u64),
+        }
+    }
+    
+    /// Check that the recording has a readable WAV header and at least one sample
+    ///
+    /// The error is shown to the user instead of uploading the file and getting an API error.
+    fn validate(&self) -> Result<(), InvalidRecording> {
+        let samples = match self {
+            Self::File(path) => hound::WavReader::open(path).map(|reader| reader.len()),
+            Self::Memory { data, .. } => hound::WavReader::new(Cursor::new(data.as_slice())).map(|reader| reader.len()),
+        };
+        match samples {
+            Ok(0) => Err(InvalidRecording::Empty),
+            Ok(_) => Ok(()),
+            Err(e) => Err(InvalidRecording::Corrupt(e.to_string())),
+        }
+    }
+}
+
+/// Why a finished recording can't be transcribed (see `Recording::validate`)
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidRecording {
+    /// The WAV header is fine but holds no samples
+    #[error("The recording is empty")]
+    Empty,
+    /// The WAV header couldn't be read
+    #[error("The recording file is corrupt: {0}")]
+    Corrupt(String),
+}
+
+/// Build an in-memory recording from audio piped to standard input
+///
+/// Input starting with a RIFF header is used as WAV. Anything else is taken as raw signed
+/// 16-bit little-endian PCM (what `parec` writes by default), which needs `sample_rate`;
+/// `channels` defaults to mono. `path` is where the recording would be persisted.
+pub fn recording_from_stdin_bytes(bytes: Vec<u8>, sample_rate: Option<u32>, channels: Option<u16>, path: &Path) -> Result<Recording> {
+    let data = if bytes.starts_with(b"RIFF") {
+        if sample_rate.is_some() || channels.is_some() {
+            info!("Input has a WAV header; ignoring --sample-rate and --channels");
+        }
+        bytes
+    } else {
+        let sample_rate = sample_rate.ok_or_else(|| anyhow::anyhow!(
+            "Standard input is not WAV; pass --sample-rate for raw PCM (s16le)"
+        ))?;
+        let channels = channels.unwrap_or(1);
+        if sample_rate == 0 || channels == 0 {
+            return Err(anyhow::anyhow!("--sample-rate and --channels must be at least 1"));
+        }
+        let spec = wav_utils::pcm_spec(sample_rate, channels);
+        let frame_bytes = 2 * channels as usize;
+        if !bytes.len().is_multiple_of(frame_bytes) {
+            warn!("Dropping {} trailing bytes that don't make up a whole frame", bytes.len() % frame_bytes);
+        }
+        let mut cursor = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut cursor, spec).context("Failed to create WAV header")?;
+        for frame in bytes.chunks_exact(frame_bytes) {
+            for sample in frame.chunks_exact(2) {
+                writer.write_sample(i16::from_le_bytes([sample[0], sample[1]]))?;
+            }
+        }
+        writer.finalize().context("Failed to finalize WAV data")?;
+        cursor.into_inner()
+    };
+    
+    let recording = Recording::Memory { path: path.to_string_lossy().into_owned(), data: Arc::new(data) };
+    recording.validate()?;
+    Ok(recording)
+}
+
+impl std::fmt::Display for Recording {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path),
+            Self::Memory { path, data } => write!(f, "{} (in memory, {} bytes)", path, data.len()),
+        }
+    }
+}
+
+enum SinkTarget {
+    Memory(Cursor<Vec<u8>>),
+    File(BufWriter<File>),
+}
+
+/// WAV destination shared between the stream callback and the recorder
+///
+/// An in-memory sink moves to `path` once it grows past `MAX_IN_MEMORY_BYTES`.
+#[derive(Clone)]
+struct RecordingSink {
+    target: Arc<Mutex<SinkTarget>>,
+    path: String,
+}
+
+impl RecordingSink {
+    fn file(path: &str) -> Result<Self> {
+        let file = File::create(path).context("Failed to create WAV file")?;
+        Ok(Self {
+            target: Arc::new(Mutex::new(SinkTarget::File(BufWriter::new(file)))),
+            path: path.to_string(),
+        })
+    }
+    
+    fn memory(path: &str) -> Self {
+        Self {
+            target: Arc::new(Mutex::new(SinkTarget::Memory(Cursor::new(Vec::new())))),
+            path: path.to_string(),
+        }
+    }
+    
+    fn lock(&self) -> io::Result<MutexGuard<'_, SinkTarget>> {
+        self.target.lock().map_err(|_| io::Error::other("recording sink lock poisoned"))
+    }
+    
+    /// Take the recording once the WAV writer has been finalized
+    fn finish(&self) -> Result<Recording> {
+        let mut target = self.lock()?;
+        match std::mem::replace(&mut *target, SinkTarget::Memory(Cursor::new(Vec::new()))) {
+            SinkTarget::Memory(cursor) => Ok(Recording::Memory {
+                path: self.path.clone(),
+                data: Arc::new(cursor.into_inner()),
+            }),
+            SinkTarget::File(mut file) => {
+                file.flush().context("Failed to flush WAV file")?;
+                Ok(Recording::File(self.path.clone()))
+            }
+        }
+    }
+}
+
+impl Write for RecordingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut target = self.lock()?;
+        if let SinkTarget::Memory(cursor) = &*target {
+            if cursor.get_ref().len() + buf.len() > MAX_IN_MEMORY_BYTES {
+                // 長い録音はメモリを使い切らないようファイルに切り替える
+                warn!("In-memory recording exceeded {} MiB, continuing in {}", MAX_IN_MEMORY_BYTES / (1024 * 1024), self.path);
+                let position = cursor.position();
+                let mut file = BufWriter::new(File::create(&self.path)?);
+                file.write_all(cursor.get_ref())?;
+                file.seek(SeekFrom::Start(position))?;
+                *target = SinkTarget::File(file);
+            }
+        }
+        match &mut *target {
+            SinkTarget::Memory(cursor) => cursor.write(buf),
+            SinkTarget::File(file) => file.write(buf),
+        }
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut *self.lock()? {
+            SinkTarget::Memory(_) => Ok(()),
+            SinkTarget::File(file) => file.flush(),
+        }
+    }
+}
+
+impl Seek for RecordingSink {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match &mut *self.lock()? {
+            SinkTarget::Memory(cursor) => cursor.seek(pos),
+            SinkTarget::File(file) => file.seek(pos),
+        }
+    }
+}
 
 /// Audio recorder that handles microphone capture
 pub struct AudioRecorder {
     config: Config,
     recording: Arc<AtomicBool>,
-    output_file: Option<String>,
+    sink: Option<RecordingSink>,
     start_time: Option<Instant>,
     stream: Option<StreamWrapper>,
     last_active: Arc<AtomicU64>, // 録音アクティビティの最終時刻
+    level_meter: Option<Arc<Mutex<f64>>>, // 録音中の入力レベル（0.0-1.0）の出力先
+    preroll: Option<Arc<Mutex<PrerollBuffer>>>, // 録音の先頭に付け足す直前の入力
+    preroll_secs: f64, // 今回の録音の先頭に付け足した直前の入力の秒数
+    armed: bool, // 音声を検出するまで書き込みを待つかどうか
+    activity_detected: Arc<AtomicBool>, // しきい値を超える音声を検出したか
     _marker: PhantomData<*const ()>, // Add a PhantomData to opt out of Send/Sync
 }
 
@@ -42,16 +540,55 @@ impl AudioRecorder {
     /// Create a new audio recorder
     pub fn new(config: Config) -> Self {
         Self {
-            config,
             recording: Arc::new(AtomicBool::new(false)),
-            output_file: None,
+            sink: None,
             start_time: None,
             stream: None,
             last_active: Arc::new(AtomicU64::new(0)),
+            level_meter: None,
+            preroll: None,
+            preroll_secs: 0.0,
+            armed: false,
+            activity_detected: Arc::new(AtomicBool::new(false)),
+            config,
             _marker: PhantomData,
         }
     }
     
+    /// Report the input level (0.0-1.0) of the recording stream to a shared meter
+    pub fn set_level_meter(&mut self, level: Arc<Mutex<f64>>) {
+        self.level_meter = Some(level);
+    }
+    
+    /// Prepend the input buffered in `preroll` to the recording
+    pub fn set_preroll(&mut self, preroll: Arc<Mutex<PrerollBuffer>>) {
+        self.preroll = Some(preroll);
+    }
+    
+    /// Seconds of pre-roll prepended to the current recording, which is 0.0 before it starts
+    pub fn preroll_secs(&self) -> f64 {
+        self.preroll_secs
+    }
+    
+    /// Wait for sound above `recording.silence_threshold` before writing anything
+    ///
+    /// Must be called before starting; `activity_detected` reports when the wait is over.
+    pub fn set_armed(&mut self, armed: bool) {
+        self.armed = armed;
+    }
+    
+    /// Flag that stops writing when cleared; the stream keeps the device open until `stop`
+    ///
+    /// Unlike the recorder itself it can be shared with other threads.
+    pub fn recording_flag(&self) -> Arc<AtomicBool> {
+        self.recording.clone()
+    }
+    
+    /// Whether sound above the threshold has been heard (always true when not armed)
+    pub fn activity_detected(&self) -> bool {
+        self.activity_detected.load(Ordering::SeqCst)
+    }
+    
     /// Start recording with a specific device
     pub fn start_with_device(&mut self, device_name: Option<String>) -> Result<()> {
         if self.recording.load(Ordering::SeqCst) {
@@ -59,13 +596,19 @@ impl AudioRecorder {
         }
         
         // Create output file path
-        let output_file = format!("{}/recording_{}.wav", 
-            self.config.temp_dir.display(),
-            chrono::Local::now().format("%Y%m%d_%H%M%S"));
+        let template = &self.config.recording.filename_template;
+        let file_name = match recording_file_name(template, &self.config.temp_dir) {
+            Ok(name) => name,
+            Err(e) => {
+                warn!("Invalid recording.filename_template {:?} ({}), using default", template, e);
+                recording_file_name(DEFAULT_FILENAME_TEMPLATE, &self.config.temp_dir)?
+            }
+        };
+        let output_file = format!("{}/{}", self.config.temp_dir.display(), file_name);
         
-        // Set output file and recording flag
-        self.output_file = Some(output_file.clone());
+        // Set recording flag
         self.recording.store(true, Ordering::SeqCst);
+        self.activity_detected.store(!self.armed, Ordering::SeqCst);
         self.start_time = Some(Instant::now());
         
         // 録音開始時の時刻を記録
@@ -77,7 +620,17 @@ impl AudioRecorder {
             Ordering::SeqCst
         );
         
-        info!("Starting audio recording to {}", output_file);
+        // ローカルの whisper はファイルを読むため、メモリ上の録音はAPI利用時のみ
+        let in_memory = self.config.recording.in_memory
+            && self.config.transcription.backend.only(TranscriptionBackend::Openai);
+        if self.config.recording.in_memory && !in_memory {
+            warn!("recording.in_memory only works with the openai backend, recording to a file");
+        }
+        if in_memory {
+            info!("Starting audio recording in memory");
+        } else {
+            info!("Starting audio recording to {}", output_file);
+        }
         
         // Ensure the output directory exists
         let dir = std::path::Path::new(&self.config.temp_dir);
@@ -88,7 +641,17 @@ impl AudioRecorder {
         }
         
         // Get host and determine input device
-        let host = cpal::default_host();
+        let host = audio_host(&self.config.audio.host);
+        // モニターソースは "pulse" デバイスから開き、開いたストリームをそのソースへ移す
+        let mut loopback = None;
+        let device_name = match device_name.as_deref().and_then(|name| name.strip_prefix(LOOPBACK_PREFIX)) {
+            Some(source) => {
+                info!("Recording from monitor source: {}", source);
+                loopback = Some((source.to_string(), own_source_outputs()));
+                Some(LOOPBACK_DEVICE.to_string())
+            }
+            None => device_name,
+        };
         let device = if let Some(name) = device_name {
             // Try to find the specified device
             let mut found_device = None;
@@ -124,8 +687,26 @@ impl AudioRecorder {
         info!("Using input device: {}", device.name()?);
         
         // Get default config
-        let default_config = device.default_input_config()
+        let mut default_config = device.default_input_config()
             .context("Failed to get default input config")?;
+        
+        // recording.preferred_format が指定されていれば、対応している場合にその形式を使う
+        if let Some(preferred) = preferred_sample_format(self.config.recording.preferred_format) {
+            let ranges: Vec<_> = match device.supported_input_configs() {
+                Ok(configs) => configs.collect(),
+                Err(e) => {
+                    warn!("Failed to list supported input configs: {}", e);
+                    Vec::new()
+                }
+            };
+            match config_with_format(&ranges, preferred, &default_config) {
+                Some(supported) => {
+                    info!("Using preferred sample format {:?}", preferred);
+                    default_config = supported;
+                },
+                None => warn!("Device does not support {:?} input, using its default {:?}", preferred, default_config.sample_format()),
+            }
+        }
             
         // Debug info
         info!("Default config: {:?}", default_config);
@@ -135,68 +716,122 @@ impl AudioRecorder {
         info!("Sample rate: {}", default_config.sample_rate().0);
         
         // Create stream config from default settings
+        let supported_buffer = *default_config.buffer_size();
         let mut config: cpal::StreamConfig = default_config.into();
+        validate_stream_config(&config)?;
         
-        // 追加: 設定ファイルのサンプルレートを適用
-        if self.config.recording.sample_rate > 0 {
-            info!("Overriding sample rate with user setting: {} Hz", self.config.recording.sample_rate);
-            config.sample_rate = cpal::SampleRate(self.config.recording.sample_rate);
+        // 追加: 設定ファイルのサンプルレートを適用（デバイスが対応する最も近いレートにする）
+        let requested_rate = self.config.recording.sample_rate;
+        if requested_rate > 0 {
+            let ranges: Vec<(u32, u32)> = match device.supported_input_configs() {
+                Ok(configs) => configs
+                    .filter(|supported| supported.channels() == config.channels && supported.sample_format() == sample_format)
+                    .map(|supported| (supported.min_sample_rate().0, supported.max_sample_rate().0))
+                    .collect(),
+                Err(e) => {
+                    warn!("Failed to list supported input configs: {}", e);
+                    Vec::new()
+                }
+            };
+            match closest_supported_rate(requested_rate, &ranges) {
+                Some(rate) if rate == requested_rate => {
+                    info!("Overriding sample rate with user setting: {} Hz", rate);
+                    config.sample_rate = cpal::SampleRate(rate);
+                },
+                Some(rate) => {
+                    warn!("Device does not support {} Hz, using the closest supported rate {} Hz", requested_rate, rate);
+                    config.sample_rate = cpal::SampleRate(rate);
+                },
+                None => {
+                    warn!("Device supports no rate close to {} Hz, using its default {} Hz", requested_rate, config.sample_rate.0);
+                },
+            }
         }
         
-        // 汎用的で堅牢なバッファリング設定
-        // システムとデバイスの特性を考慮して自動的に適切なバッファサイズを選択
-        info!("Using system-selected optimal buffer size for maximum compatibility");
-        config.buffer_size = cpal::BufferSize::Default;
+        // recording.buffer_frames が 0 の場合はシステムが選ぶバッファサイズを使う
+        config.buffer_size = buffer_size_for(self.config.recording.buffer_frames, &supported_buffer);
         
         // Open output file
-        let spec = hound::WavSpec {
-            channels: config.channels,
-            sample_rate: config.sample_rate.0,
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
-        };
+        let spec = wav_utils::pcm_spec(config.sample_rate.0, config.channels);
         
         info!("Creating WAV file with spec: {:?}", spec);
         
-        let output_file_arc = Arc::new(Mutex::new(
-            Some(hound::WavWriter::create(&output_file, spec)
-                .context("Failed to create WAV file")?)
-        ));
+        let sink = if in_memory {
+            RecordingSink::memory(&output_file)
+        } else {
+            RecordingSink::file(&output_file)?
+        };
+        self.sink = Some(sink.clone());
+        let mut writer = hound::WavWriter::new(sink, spec)
+            .context("Failed to create WAV file")?;
+        
+        // 直前の入力を先頭に書く（音声待ちの録音では、待機前の音は関係ないので付けない）
+        self.preroll_secs = 0.0;
+        if let Some(preroll) = self.preroll.as_ref().filter(|_| !self.armed) {
+            let samples = preroll.lock()
+                .map(|mut preroll| preroll.take(spec.sample_rate, spec.channels))
+                .unwrap_or_default();
+            let input_gain = self.config.recording.input_gain;
+            for sample in &samples {
+                let sample = (*sample as f32 * input_gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+                writer.write_sample(sample).context("Failed to write the pre-roll")?;
+            }
+            if !samples.is_empty() {
+                let frames = samples.len() / spec.channels.max(1) as usize;
+                self.preroll_secs = frames as f64 / f64::from(spec.sample_rate.max(1));
+                info!("Prepended {} ms of pre-roll", frames as u64 * 1000 / spec.sample_rate.max(1) as u64);
+            }
+        }
+        let output_file_arc = Arc::new(Mutex::new(Some(writer)));
         
         // Clone Atomic bool for capture thread
         let recording = self.recording.clone();
         let last_active = self.last_active.clone();
         
         // Create and start the stream
-        let err_fn = move |err| {
+        let err_fn = || move |err| {
             error!("Audio error: {}", err);
         };
         
         // Set up the input stream based on the device's sample format
-        let stream = match sample_format {
-            cpal::SampleFormat::I16 => self.setup_stream::<i16>(&device, &config, err_fn, output_file_arc.clone(), recording.clone()),
-            cpal::SampleFormat::F32 => self.setup_stream::<f32>(&device, &config, err_fn, output_file_arc.clone(), recording.clone()),
-            cpal::SampleFormat::U16 => return Err(anyhow::anyhow!("Unsupported sample format: U16")),
-            _ => return Err(anyhow::anyhow!("Unknown sample format")),
-        }?;
+        let build_stream = |config: &cpal::StreamConfig| match sample_format {
+            cpal::SampleFormat::I16 => self.setup_stream::<i16>(&device, config, err_fn(), output_file_arc.clone(), recording.clone()),
+            cpal::SampleFormat::F32 => self.setup_stream::<f32>(&device, config, err_fn(), output_file_arc.clone(), recording.clone()),
+            cpal::SampleFormat::U16 => self.setup_stream::<u16>(&device, config, err_fn(), output_file_arc.clone(), recording.clone()),
+            _ => Err(anyhow::anyhow!("Unknown sample format")),
+        };
+        let stream = match build_stream(&config) {
+            // 固定サイズを受け付けないデバイスではシステムの既定に戻す
+            Err(e) if config.buffer_size != cpal::BufferSize::Default => {
+                warn!("Device rejected a buffer of {:?} ({:#}), using the default buffer size", config.buffer_size, e);
+                config.buffer_size = cpal::BufferSize::Default;
+                build_stream(&config)?
+            },
+            result => result?,
+        };
+        info!("Buffer size: {:?} (device supports {:?})", config.buffer_size, supported_buffer);
         
         // Save stream and start it
         info!("Playing audio stream");
         stream.play().context("Failed to start audio stream")?;
         self.stream = Some(StreamWrapper::new(stream));
+        if let Some((source, existing)) = &loopback {
+            move_new_source_outputs(existing, source);
+        }
         
-        // Spawn a thread to stop recording after max duration
-        let max_duration = self.config.recording.max_duration_secs;
+        // Spawn a thread to stop a recording that has gone silent
+        // （最大録音時間で止めるのはウィンドウ側のタイマーだけにする）
         let recording_clone = self.recording.clone();
         let last_active_clone = self.last_active.clone();
         let disable_silence_detection = self.config.recording.disable_silence_detection;
+        let activity_clone = self.activity_detected.clone();
         
         std::thread::spawn(move || {
             // 一定間隔でチェックを行う（10秒ごと）
             let check_interval = Duration::from_secs(10);
             let mut elapsed = Duration::from_secs(0);
             
-            while recording_clone.load(Ordering::SeqCst) && elapsed.as_secs() < max_duration {
+            while recording_clone.load(Ordering::SeqCst) {
                 std::thread::sleep(check_interval);
                 elapsed += check_interval;
                 
@@ -210,6 +845,12 @@ impl AudioRecorder {
                         .as_secs();
                     let last_active_time = last_active_clone.load(Ordering::SeqCst);
                     
+                    // 音声待ちの間は無音で止めない
+                    if !activity_clone.load(Ordering::SeqCst) {
+                        last_active_clone.store(current_time, Ordering::SeqCst);
+                        continue;
+                    }
+                    
                     // 録音開始から少なくとも20秒は無音検出をスキップする（準備時間）
                     if elapsed.as_secs() < 20 {
                         // 20秒未満の場合は最終アクティブ時間を更新して無音検出をスキップ
@@ -224,12 +865,6 @@ impl AudioRecorder {
                     }
                 }
             }
-            
-            // 最大時間に達したら録音を停止
-            if recording_clone.load(Ordering::SeqCst) && elapsed.as_secs() >= max_duration {
-                warn!("Reached maximum recording duration of {} seconds", max_duration);
-                recording_clone.store(false, Ordering::SeqCst);
-            }
         });
         
         Ok(())
@@ -240,8 +875,8 @@ impl AudioRecorder {
         self.start_with_device(None)
     }
     
-    /// Stop recording and return the path to the recording
-    pub fn stop(&mut self) -> Result<Option<String>> {
+    /// Stop recording and return the recording
+    pub fn stop(&mut self) -> Result<Option<Recording>> {
         if !self.recording.load(Ordering::SeqCst) {
             return Ok(None);
         }
@@ -267,25 +902,40 @@ impl AudioRecorder {
             self.start_time = None;
         }
         
-        // Check if the output file exists and is valid
-        if let Some(path) = &self.output_file {
-            match std::fs::metadata(path) {
-                Ok(metadata) => {
-                    let file_size = metadata.len();
-                    info!("Recorded file size: {} bytes", file_size);
-                    if file_size < 100 {
-                        warn!("Warning: Audio file is very small ({} bytes), may not contain valid audio data", file_size);
-                    }
-                },
-                Err(e) => {
-                    error!("Failed to get file metadata: {}", e);
-                }
+        // The stream owned the WAV writer, so the recording is finalized by now
+        let Some(sink) = self.sink.take() else {
+            return Ok(None);
+        };
+        let mut recording = sink.finish()?;
+        
+        // Check that the recording is valid
+        match recording.len() {
+            Ok(size) => info!("Recorded {} bytes: {}", size, recording),
+            Err(e) => error!("Failed to get recording size: {}", e),
+        }
+        // 空や壊れたWAVはアップロードせずにエラーとして返す
+        if let Err(e) = recording.validate() {
+            warn!("Discarding invalid recording {}: {}", recording, e);
+            recording.discard();
+            return Err(e.into());
+        }
+        
+        // 前後の無音を削ってから返す
+        if self.config.recording.trim_silence {
+            let threshold = self.config.recording.silence_threshold;
+            let trimmed = match &recording {
+                Recording::File(path) => trim_silence(path, threshold).map(|_| None),
+                Recording::Memory { path, data } => trim_silence_in_memory(data, threshold)
+                    .map(|trimmed| trimmed.map(|data| Recording::Memory { path: path.clone(), data: Arc::new(data) })),
+            };
+            match trimmed {
+                Ok(Some(trimmed)) => recording = trimmed,
+                Ok(None) => {},
+                Err(e) => warn!("Failed to trim silence from {}: {}", recording, e),
             }
         }
         
-        // Return the output file path
-        let output_file = self.output_file.take();
-        Ok(output_file)
+        Ok(Some(recording))
     }
     
     /// Setup audio stream with correct sample type
@@ -293,10 +943,10 @@ impl AudioRecorder {
                      device: &cpal::Device,
                      config: &cpal::StreamConfig,
                      err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
-                     writer: Arc<Mutex<Option<hound::WavWriter<BufWriter<File>>>>>,
+                     writer: Arc<Mutex<Option<hound::WavWriter<RecordingSink>>>>,
                      recording: Arc<AtomicBool>) -> Result<cpal::Stream>
     where
-        T: cpal::Sample + hound::Sample + SizedSample,
+        T: cpal::Sample + SizedSample,
     {
         info!("Setting up audio stream with type {}", std::any::type_name::<T>());
         
@@ -304,6 +954,14 @@ impl AudioRecorder {
         let last_active = self.last_active.clone();
         // Capture the config value we need
         let disable_silence_detection = self.config.recording.disable_silence_detection;
+        let silence_threshold = self.config.recording.silence_threshold;
+        let input_gain = self.config.recording.input_gain;
+        let level_meter = self.level_meter.clone();
+        let level_scale = LevelScale::from_config(&self.config.ui);
+        let activity_detected = self.activity_detected.clone();
+        // コールバックをまたいで前回の書き出し時刻を保持する
+        let mut flush_schedule = FlushSchedule::new(Duration::from_millis(self.config.recording.flush_interval_ms));
+        let max_file_bytes = self.config.recording.max_file_bytes;
         
         let stream = match std::any::type_name::<T>() {
             "f32" => {
@@ -312,6 +970,20 @@ impl AudioRecorder {
                     config,
                     move |data: &[f32], _: &cpal::InputCallbackInfo| {
                         if recording.load(Ordering::SeqCst) {
+                            let rms = (data.iter().map(|&sample| sample * sample).sum::<f32>() / data.len().max(1) as f32).sqrt();
+                            if let Some(meter) = &level_meter {
+                                update_level_meter(meter, level_scale, rms);
+                            }
+                            
+                            // 待機中はしきい値を超える音声が来るまで書き込まない
+                            if !activity_detected.load(Ordering::SeqCst) {
+                                if rms <= silence_threshold {
+                                    return;
+                                }
+                                info!("Voice detected, recording started");
+                                activity_detected.store(true, Ordering::SeqCst);
+                            }
+                            
                             // 無音検出が有効な場合のみ音声アクティビティをチェック
                             if !disable_silence_detection {
                                 // RMSベースの音声レベル検出に変更（より正確）
@@ -321,8 +993,8 @@ impl AudioRecorder {
                                 let rms = rms.sqrt();
                                 
                                 // しきい値を引き下げて、より小さな音声でもアクティビティとして検出
-                                // 0.01より少ない0.003で検出（約70%減少）
-                                if rms > 0.003 {
+                                // デフォルトは0.01より少ない0.003（約70%減少）
+                                if rms > silence_threshold {
                                     last_active.store(
                                         std::time::SystemTime::now()
                                             .duration_since(std::time::UNIX_EPOCH)
@@ -345,9 +1017,16 @@ impl AudioRecorder {
                             // Write samples to WAV file
                             if let Ok(mut guard) = writer.lock() {
                                 if let Some(writer) = guard.as_mut() {
+                                    if exceeds_file_cap(writer.len(), data.len(), max_file_bytes) {
+                                        warn!("Recording reached recording.max_file_bytes ({} bytes), stopping", max_file_bytes);
+                                        recording.store(false, Ordering::SeqCst);
+                                        return;
+                                    }
+                                    
                                     // Process data in chunks for each channel
-                                    for chunk in data.chunks(channels) {
+                                    for chunk in data.chunks(channels.max(1)) {
                                         for &sample in chunk {
+                                            let sample = sample * input_gain;
                                             // Convert f32 [-1.0, 1.0] to i16 range with clipping protection
                                             let sample_clipped = if sample > 1.0 {
                                                 1.0
@@ -368,7 +1047,7 @@ impl AudioRecorder {
                                     }
                                     
                                     // Attempt to flush the writer periodically
-                                    if data.len() > 1000 {
+                                    if flush_schedule.due(Instant::now()) {
                                         if let Err(e) = writer.flush() {
                                             error!("Error flushing writer: {}", e);
                                             // エラーが発生しても継続する
@@ -391,35 +1070,28 @@ impl AudioRecorder {
                     None
                 )?
             },
-            "i16" => {
+            "i16" | "u16" => {
                 let channels = config.channels as usize;
-                device.build_input_stream(
-                    config,
-                    move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                        if recording.load(Ordering::SeqCst) {
-                            // 無音検出が有効な場合のみ音声アクティビティをチェック
-                            if !disable_silence_detection {
-                                // i16の場合のRMSベースの音声レベル検出
-                                let rms: f32 = data.iter()
-                                    .map(|&sample| {
-                                        let normalized = sample as f32 / 32767.0;
-                                        normalized * normalized
-                                    })
-                                    .sum::<f32>() / data.len() as f32;
-                                let rms = rms.sqrt();
-                                
-                                // しきい値を設定
-                                if rms > 0.003 {
-                                    last_active.store(
-                                        std::time::SystemTime::now()
-                                            .duration_since(std::time::UNIX_EPOCH)
-                                            .unwrap_or_default()
-                                            .as_secs(),
-                                        Ordering::SeqCst
-                                    );
-                                }
-                            } else {
-                                // 無音検出が無効の場合は常に最終アクティブ時間を更新
+                let mut write_i16 = move |data: &[i16]| {
+                    if recording.load(Ordering::SeqCst) {
+                        let rms = wav_utils::rms(data);
+                        if let Some(meter) = &level_meter {
+                            update_level_meter(meter, level_scale, rms);
+                        }
+                        
+                        // 待機中はしきい値を超える音声が来るまで書き込まない
+                        if !activity_detected.load(Ordering::SeqCst) {
+                            if rms <= silence_threshold {
+                                return;
+                            }
+                            info!("Voice detected, recording started");
+                            activity_detected.store(true, Ordering::SeqCst);
+                        }
+                        
+                        // 無音検出が有効な場合のみ音声アクティビティをチェック
+                        if !disable_silence_detection {
+                            // しきい値を設定
+                            if rms > silence_threshold {
                                 last_active.store(
                                     std::time::SystemTime::now()
                                         .duration_since(std::time::UNIX_EPOCH)
@@ -428,41 +1100,76 @@ impl AudioRecorder {
                                     Ordering::SeqCst
                                 );
                             }
-                            
-                            // Write samples to WAV file
-                            if let Ok(mut guard) = writer.lock() {
-                                if let Some(writer) = guard.as_mut() {
-                                    // Process data in chunks for each channel
-                                    for chunk in data.chunks(channels) {
-                                        for &sample in chunk {
-                                            if let Err(e) = writer.write_sample(sample) {
-                                                error!("Error writing sample: {}", e);
-                                            }
+                        } else {
+                            // 無音検出が無効の場合は常に最終アクティブ時間を更新
+                            last_active.store(
+                                std::time::SystemTime::now()
+                                    .duration_since(std::time::UNIX_EPOCH)
+                                    .unwrap_or_default()
+                                    .as_secs(),
+                                Ordering::SeqCst
+                            );
+                        }
+                        
+                        // Write samples to WAV file
+                        if let Ok(mut guard) = writer.lock() {
+                            if let Some(writer) = guard.as_mut() {
+                                if exceeds_file_cap(writer.len(), data.len(), max_file_bytes) {
+                                    warn!("Recording reached recording.max_file_bytes ({} bytes), stopping", max_file_bytes);
+                                    recording.store(false, Ordering::SeqCst);
+                                    return;
+                                }
+                                
+                                // Process data in chunks for each channel
+                                for chunk in data.chunks(channels.max(1)) {
+                                    for &sample in chunk {
+                                        let sample = (sample as f32 * input_gain)
+                                            .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+                                        if let Err(e) = writer.write_sample(sample) {
+                                            error!("Error writing sample: {}", e);
                                         }
                                     }
-                                    
-                                    // Attempt to flush the writer periodically
-                                    if data.len() > 1000 {
-                                        if let Err(e) = writer.flush() {
-                                            error!("Error flushing writer: {}", e);
-                                        }
+                                }
+                                
+                                // Attempt to flush the writer periodically
+                                if flush_schedule.due(Instant::now()) {
+                                    if let Err(e) = writer.flush() {
+                                        error!("Error flushing writer: {}", e);
                                     }
                                 }
                             }
-                        } else if let Ok(mut guard) = writer.lock() {
-                            // Finish and close the file when recording stops
-                            if let Some(writer) = guard.take() {
-                                info!("Finalizing WAV file from stream");
-                                if let Err(e) = writer.finalize() {
-                                    error!("Error finalizing WAV file: {}", e);
-                                }
-                                info!("WAV file finalized successfully");
+                        }
+                    } else if let Ok(mut guard) = writer.lock() {
+                        // Finish and close the file when recording stops
+                        if let Some(writer) = guard.take() {
+                            info!("Finalizing WAV file from stream");
+                            if let Err(e) = writer.finalize() {
+                                error!("Error finalizing WAV file: {}", e);
                             }
+                            info!("WAV file finalized successfully");
                         }
-                    },
-                    err_fn,
-                    None
-                )?
+                    }
+                };
+                
+                if std::any::type_name::<T>() == "u16" {
+                    device.build_input_stream(
+                        config,
+                        move |data: &[u16], _: &cpal::InputCallbackInfo| {
+                            // U16 は 32768 を無音とする符号なし形式なので i16 に変換して書き込む
+                            let converted: Vec<i16> = data.iter().map(|&sample| u16_to_i16(sample)).collect();
+                            write_i16(&converted);
+                        },
+                        err_fn,
+                        None
+                    )?
+                } else {
+                    device.build_input_stream(
+                        config,
+                        move |data: &[i16], _: &cpal::InputCallbackInfo| write_i16(data),
+                        err_fn,
+                        None
+                    )?
+                }
             },
             _ => return Err(anyhow::anyhow!("Unsupported sample format")),
         };
@@ -474,4 +1181,1082 @@ impl AudioRecorder {
     pub fn is_recording(&self) -> bool {
         self.recording.load(Ordering::SeqCst)
     }
-} 
\ No newline at end of file
+}
+
+/// Largest header hound writes for a 16-bit WAV (WAVE_FORMAT_EXTENSIBLE, more than 2 channels)
+const WAV_HEADER_BYTES: u64 = 68;
+
+/// Whether writing `pending` more 16-bit samples after `written` would grow the WAV file past
+/// `max_file_bytes` (`recording.max_file_bytes`); never with a cap of 0
+///
+/// Checked before each buffer is written, so the file stays within the cap.
+fn exceeds_file_cap(written: u32, pending: usize, max_file_bytes: u64) -> bool {
+    max_file_bytes > 0 && WAV_HEADER_BYTES + (written as u64 + pending as u64) * 2 > max_file_bytes
+}
+
+/// When the recording callbacks flush the WAV writer (`recording.flush_interval_ms`)
+///
+/// The first call starts the interval instead of flushing, since nothing has been
+/// written before it.
+#[derive(Debug)]
+struct FlushSchedule {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl FlushSchedule {
+    fn new(interval: Duration) -> Self {
+        Self { interval, last: None }
+    }
+    
+    /// Whether to flush now; a due flush restarts the interval
+    fn due(&mut self, now: Instant) -> bool {
+        let Some(last) = self.last else {
+            self.last = Some(now);
+            return self.interval.is_zero();
+        };
+        if now.saturating_duration_since(last) < self.interval {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+/// Convert an unsigned 16-bit sample (silence at 32768) to signed 16-bit
+fn u16_to_i16(sample: u16) -> i16 {
+    (sample as i32 - 32768) as i16
+}
+
+/// Most channels accepted from an input device; more is a broken or virtual device
+const MAX_CHANNELS: u16 = 32;
+
+/// Reject channel counts a WAV recording can't be made from
+fn validate_stream_config(config: &cpal::StreamConfig) -> Result<()> {
+    if !(1..=MAX_CHANNELS).contains(&config.channels) {
+        return Err(anyhow::anyhow!(
+            "The input device reports {} channels; only 1 to {} are supported",
+            config.channels, MAX_CHANNELS
+        ));
+    }
+    Ok(())
+}
+
+/// Pre-roll older than this when a recording starts is from an earlier moment and dropped
+const PREROLL_MAX_AGE: Duration = Duration::from_millis(500);
+
+/// The last few seconds of idle monitor input (`recording.preroll_secs`)
+///
+/// Holds at most `preroll_secs` of samples, so memory stays bounded however long the
+/// monitor runs. A recording takes the samples when it starts and converts them to its
+/// own format, since the monitor may run at another rate or channel count.
+#[derive(Debug, Default)]
+pub struct PrerollBuffer {
+    samples: VecDeque<i16>,
+    capacity: usize,
+    sample_rate: u32,
+    channels: u16,
+    updated: Option<Instant>,
+}
+
+impl PrerollBuffer {
+    /// Keep `secs` seconds of input in the given format, dropping samples of another format
+    pub fn configure(&mut self, secs: f32, sample_rate: u32, channels: u16) {
+        if (sample_rate, channels) != (self.sample_rate, self.channels) {
+            self.samples.clear();
+        }
+        let secs = if secs.is_finite() { secs.max(0.0) } else { 0.0 };
+        self.capacity = (secs * sample_rate as f32) as usize * channels as usize;
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        let excess = self.samples.len().saturating_sub(self.capacity);
+        self.samples.drain(..excess);
+    }
+    
+    /// Append input, dropping the oldest samples beyond the capacity
+    pub fn push<T>(&mut self, data: &[T])
+    where
+        T: cpal::Sample,
+        i16: FromSample<T>,
+    {
+        if self.capacity == 0 {
+            return;
+        }
+        // 容量を超える分は先頭から捨てる（フレームの途中で切れないよう チャンネル数単位で）
+        let data = &data[data.len().saturating_sub(self.capacity)..];
+        let overflow = (self.samples.len() + data.len()).saturating_sub(self.capacity);
+        let channels = self.channels.max(1) as usize;
+        self.samples.drain(..overflow.div_ceil(channels).saturating_mul(channels).min(self.samples.len()));
+        self.samples.extend(data.iter().map(|&sample| cpal::Sample::to_sample::<i16>(sample)));
+        self.updated = Some(Instant::now());
+    }
+    
+    /// Take the buffered input converted to `sample_rate` and `channels`
+    ///
+    /// Empty when nothing arrived in the last moment, e.g. while idle monitoring was off.
+    pub fn take(&mut self, sample_rate: u32, channels: u16) -> Vec<i16> {
+        let samples: Vec<i16> = self.samples.drain(..).collect();
+        if self.updated.is_none_or(|updated| updated.elapsed() > PREROLL_MAX_AGE) {
+            return Vec::new();
+        }
+        wav_utils::convert(&samples, self.sample_rate, self.channels, sample_rate, channels)
+    }
+}
+
+/// Lowest level shown by the dB meter; quieter input leaves the meter empty
+const METER_DB_FLOOR: f32 = -60.0;
+
+/// How input RMS is mapped to the 0.0-1.0 level meter (`ui.meter_scale`, `ui.meter_db`)
+#[derive(Debug, Clone, Copy)]
+pub struct LevelScale {
+    factor: f32,
+    db: bool,
+    smoothing: f32,
+}
+
+impl LevelScale {
+    pub fn from_config(ui: &UiConfig) -> Self {
+        let smoothing = if ui.meter_smoothing.is_finite() { ui.meter_smoothing.clamp(0.0, 0.99) } else { 0.0 };
+        Self { factor: ui.meter_scale, db: ui.meter_db, smoothing }
+    }
+    
+    /// Meter position for an RMS value normalized to 0.0-1.0
+    pub fn level(&self, rms: f32) -> f64 {
+        let level = if self.db {
+            // 0 dBFS で満杯、METER_DB_FLOOR 以下は空
+            let db = 20.0 * rms.max(f32::MIN_POSITIVE).log10();
+            (db - METER_DB_FLOOR) / -METER_DB_FLOOR
+        } else {
+            rms * self.factor
+        };
+        level.clamp(0.0, 1.0) as f64
+    }
+    
+    /// Move the meter from `previous` toward the level of `rms`
+    ///
+    /// An exponential moving average like a VU meter: each update keeps `smoothing` of the
+    /// previous position while falling, and half as much while rising so peaks still show.
+    pub fn smoothed_level(&self, previous: f64, rms: f32) -> f64 {
+        let target = self.level(rms);
+        let keep = if target > previous { self.smoothing / 2.0 } else { self.smoothing } as f64;
+        previous * keep + target * (1.0 - keep)
+    }
+}
+
+/// Scale an RMS value to the 0.0-1.0 meter range and store it, smoothed
+fn update_level_meter(meter: &Mutex<f64>, scale: LevelScale, rms: f32) {
+    if let Ok(mut level) = meter.lock() {
+        *level = scale.smoothed_level(*level, rms);
+    }
+}
+
+/// How long input must stay above the threshold before voice counts as started
+const VAD_ONSET_MS: f32 = 250.0;
+/// How long input must stay quiet before the detector resets
+const VAD_RELEASE_MS: f32 = 300.0;
+
+/// Voice-activity detector with hysteresis for hands-free recording
+///
+/// Voice is reported once the RMS has been above `threshold` for `VAD_ONSET_MS`.
+/// Only input below half the threshold counts as quiet, so brief dips in speech
+/// don't reset it, and a quiet gap of `VAD_RELEASE_MS` discards short noises
+/// and re-arms the detector after a trigger.
+#[derive(Debug, Clone)]
+pub struct VoiceActivityDetector {
+    threshold: f32,
+    voiced_ms: f32,
+    quiet_ms: f32,
+    triggered: bool,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            voiced_ms: 0.0,
+            quiet_ms: 0.0,
+            triggered: false,
+        }
+    }
+    
+    /// Feed the RMS of a chunk lasting `chunk_ms`; returns true when voice onset is detected
+    pub fn update(&mut self, rms: f32, chunk_ms: f32) -> bool {
+        if rms > self.threshold {
+            self.voiced_ms += chunk_ms;
+            self.quiet_ms = 0.0;
+        } else if rms < self.threshold * 0.5 {
+            self.quiet_ms += chunk_ms;
+            if self.quiet_ms >= VAD_RELEASE_MS {
+                self.voiced_ms = 0.0;
+                self.triggered = false;
+            }
+        }
+        
+        if !self.triggered && self.voiced_ms >= VAD_ONSET_MS {
+            self.triggered = true;
+            return true;
+        }
+        false
+    }
+}
+
+/// Audio file extensions accepted by the transcription API
+pub const SUPPORTED_AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "m4a", "mp4", "mpeg", "mpga", "ogg", "oga", "webm", "flac"];
+
+/// Whether the path has an audio extension the transcription API accepts
+pub fn has_supported_audio_extension(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| SUPPORTED_AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// cpal format for `recording.preferred_format`, or None for the device default
+fn preferred_sample_format(preference: SampleFormatPreference) -> Option<cpal::SampleFormat> {
+    match preference {
+        SampleFormatPreference::Auto => None,
+        SampleFormatPreference::I16 => Some(cpal::SampleFormat::I16),
+        SampleFormatPreference::F32 => Some(cpal::SampleFormat::F32),
+    }
+}
+
+/// Supported config in `format` with the default config's channels, keeping its sample rate when possible
+fn config_with_format(
+    ranges: &[cpal::SupportedStreamConfigRange],
+    format: cpal::SampleFormat,
+    default: &cpal::SupportedStreamConfig,
+) -> Option<cpal::SupportedStreamConfig> {
+    if default.sample_format() == format {
+        return Some(default.clone());
+    }
+    let candidates = || ranges.iter().filter(|range| range.sample_format() == format && range.channels() == default.channels());
+    let rate = default.sample_rate();
+    candidates()
+        .find(|range| range.min_sample_rate() <= rate && rate <= range.max_sample_rate())
+        .map(|range| range.with_sample_rate(rate))
+        .or_else(|| candidates().next().map(|range| range.with_max_sample_rate()))
+}
+
+/// Buffer size to request for `recording.buffer_frames`, given what the device reports
+///
+/// 0 leaves the choice to the system, and a size outside the device's range falls back to it
+/// with a warning.
+fn buffer_size_for(frames: u32, supported: &cpal::SupportedBufferSize) -> cpal::BufferSize {
+    if frames == 0 {
+        return cpal::BufferSize::Default;
+    }
+    match *supported {
+        cpal::SupportedBufferSize::Range { min, max } if !(min..=max).contains(&frames) => {
+            warn!("Device supports buffers of {} to {} frames, not {}; using the default buffer size", min, max, frames);
+            cpal::BufferSize::Default
+        },
+        _ => cpal::BufferSize::Fixed(frames),
+    }
+}
+
+/// Largest relative difference from the requested sample rate that is still used instead
+/// of the device default
+const MAX_SAMPLE_RATE_DEVIATION: f64 = 0.25;
+
+/// Supported sample rate closest to `requested`, given the device's (min, max) rate ranges
+///
+/// None when no range comes within `MAX_SAMPLE_RATE_DEVIATION` of the request.
+fn closest_supported_rate(requested: u32, ranges: &[(u32, u32)]) -> Option<u32> {
+    ranges.iter()
+        .map(|&(min, max)| requested.clamp(min, max.max(min)))
+        .min_by_key(|rate| rate.abs_diff(requested))
+        .filter(|rate| rate.abs_diff(requested) as f64 <= requested as f64 * MAX_SAMPLE_RATE_DEVIATION)
+}
+
+/// Peak/RMS statistics of a recorded WAV file
+#[derive(Debug, Clone, Copy)]
+pub struct AudioAnalysis {
+    /// Peak absolute sample value (0.0 - 1.0)
+    pub peak: f32,
+    /// RMS of all samples (0.0 - 1.0)
+    pub rms: f32,
+    /// Ratio of samples at (or very near) full scale
+    pub clipped_ratio: f32,
+}
+
+/// RMS below which a recording is considered essentially silent
+const SILENCE_RMS_THRESHOLD: f32 = 0.003;
+/// Absolute sample value treated as full scale
+const CLIPPING_LEVEL: f32 = 0.999;
+/// Ratio of full-scale samples above which a recording is considered clipped
+const CLIPPING_RATIO_THRESHOLD: f32 = 0.01;
+
+impl AudioAnalysis {
+    /// Whether the recording is essentially silent
+    pub fn is_silent(&self) -> bool {
+        self.rms < SILENCE_RMS_THRESHOLD
+    }
+    
+    /// Whether the recording is heavily clipped
+    pub fn is_clipped(&self) -> bool {
+        self.clipped_ratio > CLIPPING_RATIO_THRESHOLD
+    }
+    
+    /// Human readable warning if the recording looks unusable
+    pub fn warning(&self) -> Option<String> {
+        if self.is_silent() {
+            Some(crate::i18n::text().recording_silent(self.peak, self.rms))
+        } else if self.is_clipped() {
+            Some(crate::i18n::text().recording_clipped(self.clipped_ratio))
+        } else {
+            None
+        }
+    }
+}
+
+/// Scan a 16-bit WAV file and measure its peak and RMS levels
+pub fn analyze_wav(path: &str) -> Result<AudioAnalysis> {
+    let reader = hound::WavReader::open(path)
+        .context("Failed to open WAV file for analysis")?;
+    analyze_samples(reader)
+}
+
+/// Measure the peak and RMS levels of a recording, on disk or in memory
+pub fn analyze_recording(recording: &Recording) -> Result<AudioAnalysis> {
+    match recording {
+        Recording::File(path) => analyze_wav(path),
+        Recording::Memory { data, .. } => {
+            let reader = hound::WavReader::new(Cursor::new(data.as_slice()))
+                .context("Failed to read in-memory recording for analysis")?;
+            analyze_samples(reader)
+        }
+    }
+}
+
+/// Length of a recording, read from its WAV header
+pub fn recording_duration(recording: &Recording) -> Result<Duration> {
+    let (frames, sample_rate) = match recording {
+        Recording::File(path) => {
+            let reader = hound::WavReader::open(path).context("Failed to open WAV file")?;
+            (reader.duration(), reader.spec().sample_rate)
+        },
+        Recording::Memory { data, .. } => {
+            let reader = hound::WavReader::new(Cursor::new(data.as_slice()))
+                .context("Failed to read in-memory recording")?;
+            (reader.duration(), reader.spec().sample_rate)
+        }
+    };
+    Ok(Duration::from_secs_f64(frames as f64 / sample_rate.max(1) as f64))
+}
+
+fn analyze_samples<R: Read>(mut reader: hound::WavReader<R>) -> Result<AudioAnalysis> {
+    let mut peak = 0.0f32;
+    let mut sum_squares = 0.0f64;
+    let mut clipped = 0u64;
+    let mut count = 0u64;
+    
+    for sample in reader.samples::<i16>() {
+        let sample = sample.context("Failed to read WAV sample")? as f32 / 32767.0;
+        let abs = sample.abs();
+        peak = peak.max(abs);
+        sum_squares += (sample * sample) as f64;
+        if abs >= CLIPPING_LEVEL {
+            clipped += 1;
+        }
+        count += 1;
+    }
+    
+    let analysis = if count == 0 {
+        AudioAnalysis { peak: 0.0, rms: 0.0, clipped_ratio: 0.0 }
+    } else {
+        AudioAnalysis {
+            peak,
+            rms: (sum_squares / count as f64).sqrt() as f32,
+            clipped_ratio: clipped as f32 / count as f32,
+        }
+    };
+    
+    info!("Recording analysis: peak={:.4}, rms={:.4}, clipped={:.2}% ({} samples)",
+        analysis.peak, analysis.rms, analysis.clipped_ratio * 100.0, count);
+    
+    Ok(analysis)
+}
+
+/// Speech RMS the calibration aims for
+pub const CALIBRATION_TARGET_RMS: f32 = 0.1;
+/// Range of `input_gain` values the calibration recommends
+const MIN_INPUT_GAIN: f32 = 0.25;
+const MAX_INPUT_GAIN: f32 = 8.0;
+/// Loudest peak allowed after applying the recommended gain
+const CALIBRATION_PEAK_LIMIT: f32 = 0.9;
+
+/// Input level measured by a calibration recording
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    /// RMS of the windows that contained speech, before `input_gain`
+    pub speech_rms: f32,
+    pub peak: f32,
+    /// Gain that brings `speech_rms` to `CALIBRATION_TARGET_RMS` without clipping
+    pub recommended_gain: f32,
+}
+
+/// Record `secs` seconds from the default input device and measure the speech level
+///
+/// The recording is made with `input_gain` 1.0 so the result doesn't depend on the
+/// current setting. Returns None when no speech above `silence_threshold` was heard.
+pub fn run_calibration(config: &Config, secs: u64) -> Result<Option<Calibration>> {
+    let mut calibration_config = config.clone();
+    calibration_config.recording.filename_template = "calibration.wav".to_string();
+    calibration_config.recording.input_gain = 1.0;
+    calibration_config.recording.in_memory = false;
+    calibration_config.recording.disable_silence_detection = true;
+    calibration_config.recording.trim_silence = false;
+    
+    let mut recorder = AudioRecorder::new(calibration_config);
+    recorder.start()?;
+    info!("Calibration recording started ({} s)", secs);
+    std::thread::sleep(Duration::from_secs(secs));
+    let path = recorder.stop()?
+        .and_then(|recording| recording.path().map(str::to_string))
+        .ok_or_else(|| anyhow::anyhow!("Calibration recording produced no audio"))?;
+    
+    let result = calibrate_wav(&path, config.recording.silence_threshold);
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Measure the speech level of a 16-bit WAV file
+pub fn calibrate_wav(path: &str, silence_threshold: f32) -> Result<Option<Calibration>> {
+    let (spec, samples) = wav_utils::read_wav_file(Path::new(path))
+        .context("Failed to read WAV file for calibration")?;
+    
+    Ok(calibrate_samples(&samples, spec.channels as usize, spec.sample_rate, silence_threshold))
+}
+
+/// Windowed RMS over the voiced parts of `samples` and the gain that reaches the target
+fn calibrate_samples(samples: &[i16], channels: usize, sample_rate: u32, silence_threshold: f32) -> Option<Calibration> {
+    let window = (sample_rate as usize * wav_utils::TRIM_WINDOW_MS / 1000).max(1) * channels.max(1);
+    
+    // 話していない区間を除いてレベルを測る
+    let voiced: Vec<f32> = samples.chunks(window)
+        .map(wav_utils::rms)
+        .filter(|&rms| rms > silence_threshold)
+        .collect();
+    if voiced.is_empty() {
+        return None;
+    }
+    let speech_rms = (voiced.iter().map(|rms| rms * rms).sum::<f32>() / voiced.len() as f32).sqrt();
+    let peak = wav_utils::peak(samples);
+    
+    let mut gain = CALIBRATION_TARGET_RMS / speech_rms;
+    if peak > 0.0 {
+        gain = gain.min(CALIBRATION_PEAK_LIMIT / peak);
+    }
+    let recommended_gain = (gain.clamp(MIN_INPUT_GAIN, MAX_INPUT_GAIN) * 100.0).round() / 100.0;
+    
+    Some(Calibration { speech_rms, peak, recommended_gain })
+}
+
+/// Cut leading and trailing audio below `threshold` RMS from a WAV file in place
+pub fn trim_silence(path: &str, threshold: f32) -> Result<()> {
+    let (spec, samples) = wav_utils::read_wav_file(Path::new(path))
+        .context("Failed to read WAV file for trimming")?;
+    if let Some(trimmed) = trimmed_samples(spec, &samples, threshold) {
+        wav_utils::write_wav_file(Path::new(path), spec, trimmed)
+            .context("Failed to replace WAV file with trimmed version")?;
+    }
+    Ok(())
+}
+
+/// Cut leading and trailing silence from an in-memory WAV; None when there is nothing to cut
+fn trim_silence_in_memory(wav: &[u8], threshold: f32) -> Result<Option<Vec<u8>>> {
+    let (spec, samples) = wav_utils::read_samples(wav)
+        .context("Failed to read in-memory recording for trimming")?;
+    trimmed_samples(spec, &samples, threshold)
+        .map(|trimmed| wav_utils::write_samples(spec, trimmed))
+        .transpose()
+}
+
+/// The voiced part of a recording's samples; None when there is no silence to trim
+fn trimmed_samples(spec: hound::WavSpec, samples: &[i16], threshold: f32) -> Option<&[i16]> {
+    let samples_per_sec = spec.sample_rate as f64 * spec.channels as f64;
+    let original_secs = samples.len() as f64 / samples_per_sec;
+    
+    let Some(trimmed) = wav_utils::trim_silence(samples, spec, threshold) else {
+        info!("No audio above the silence threshold, leaving {:.2}s recording untrimmed", original_secs);
+        return None;
+    };
+    if trimmed.len() == samples.len() {
+        info!("Recording has no leading/trailing silence to trim ({:.2}s)", original_secs);
+        return None;
+    }
+    
+    info!("Trimmed silence: {:.2}s -> {:.2}s", original_secs, trimmed.len() as f64 / samples_per_sec);
+    Some(trimmed)
+}
+
+/// Play a WAV file with `paplay`, falling back to `aplay`
+///
+/// `sink` names a sound server sink; without one the default output is used. `aplay`
+/// reaches the sink through the `pulse` ALSA device, which reads `PULSE_SINK`.
+pub fn play_wav(path: &str, sink: Option<&str>) -> Result<()> {
+    for player in ["paplay", "aplay"] {
+        let mut command = std::process::Command::new(player);
+        if let Some(sink) = sink {
+            match player {
+                "paplay" => command.arg(format!("--device={}", sink)),
+                _ => command.args(["-D", "pulse"]).env("PULSE_SINK", sink),
+            };
+        }
+        match command.arg(path).status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => warn!("{} exited with {}", player, status),
+            Err(e) => warn!("Failed to run {}: {}", player, e),
+        }
+    }
+    Err(anyhow::anyhow!("No audio player could play {}", path))
+}
+
+/// Play a start/stop cue on `sink` like the microphone test, or on the default output without one
+///
+/// Returns `Ok(false)` without playing when `sink` isn't among the `available` sinks, so the
+/// cue of an unplugged headset is skipped instead of coming out of the speakers. An empty
+/// list means the sinks couldn't be listed and the cue is tried anyway. The players go
+/// through the sound server, so a duplex device that is being recorded from isn't opened twice.
+pub fn play_cue_wav(path: &str, sink: Option<&str>, available: &[String]) -> Result<bool> {
+    if let Some(sink) = sink {
+        if !available.is_empty() && !available.iter().any(|name| name == sink) {
+            return Ok(false);
+        }
+    }
+    play_wav(path, sink)?;
+    Ok(true)
+}
+
+/// Largest part of a WAV file searched for the `data` chunk when repairing its header
+const MAX_WAV_HEADER_BYTES: u64 = 64 * 1024;
+
+/// Sizes a WAV header should hold for the audio actually in a file
+#[derive(Debug, PartialEq)]
+struct WavHeaderFix {
+    riff_size: u32,
+    data_size_offset: u64,
+    data_size: u32,
+    /// The file is cut here when it ends in the middle of a frame
+    file_len: u64,
+}
+
+/// What to write into the header `head` of a `file_len` byte WAV file, or None when it is consistent
+///
+/// The writer only updates the sizes when it flushes or finalizes, so after a crash the RIFF
+/// size is short of the file length. The data chunk then takes the rest of the file, in whole frames.
+fn wav_header_fix(head: &[u8], file_len: u64) -> Result<Option<WavHeaderFix>> {
+    let u32_at = |pos: usize| head.get(pos..pos + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    if head.len() < 12 || &head[0..4] != b"RIFF" || &head[8..12] != b"WAVE" {
+        anyhow::bail!("Not a WAV file");
+    }
+    if u32_at(4).map(|size| size as u64 + 8) == Some(file_len) {
+        return Ok(None);
+    }
+    let mut block_align = 1;
+    let mut pos = 12;
+    loop {
+        let (Some(id), Some(size)) = (head.get(pos..pos + 4), u32_at(pos + 4)) else {
+            anyhow::bail!("No data chunk in the WAV header");
+        };
+        if id == b"fmt " {
+            if let Some(b) = head.get(pos + 20..pos + 22) {
+                block_align = u16::from_le_bytes([b[0], b[1]]).max(1) as u64;
+            }
+        } else if id == b"data" {
+            let data_start = (pos + 8) as u64;
+            let available = file_len.saturating_sub(data_start).min(u32::MAX as u64 - data_start);
+            let data_size = available - available % block_align;
+            if data_size == 0 {
+                anyhow::bail!("No audio was recorded");
+            }
+            return Ok(Some(WavHeaderFix {
+                riff_size: (data_start - 8 + data_size) as u32,
+                data_size_offset: pos as u64 + 4,
+                data_size: data_size as u32,
+                file_len: data_start + data_size,
+            }));
+        }
+        pos += 8 + size as usize + (size as usize & 1);
+    }
+}
+
+/// Rewrite the sizes in the header of a WAV file that was never finalized, e.g. after a crash
+///
+/// Returns whether the file needed it; a consistent header is left alone.
+pub fn repair_wav_header(path: &Path) -> Result<bool> {
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let file_len = file.metadata().context("Failed to read the file size")?.len();
+    let mut head = Vec::new();
+    (&mut file).take(MAX_WAV_HEADER_BYTES).read_to_end(&mut head).context("Failed to read the WAV header")?;
+    let Some(fix) = wav_header_fix(&head, file_len)? else {
+        return Ok(false);
+    };
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&fix.riff_size.to_le_bytes())?;
+    file.seek(SeekFrom::Start(fix.data_size_offset))?;
+    file.write_all(&fix.data_size.to_le_bytes())?;
+    if fix.file_len < file_len {
+        file.set_len(fix.file_len)?;
+    }
+    file.sync_all().context("Failed to write the WAV header")?;
+    Ok(true)
+}
+
+/// Files written to more recently than this may belong to a recording still going on in
+/// another instance, and are not repaired
+const RECOVERY_MIN_AGE: Duration = Duration::from_secs(60);
+
+/// Whether `name` could have been made from `recording.filename_template` `template`
+fn matches_filename_template(template: &str, name: &str) -> bool {
+    let mut pattern = regex::escape(template)
+        .replace(r"\{date\}", r"\d{8}")
+        .replace(r"\{time\}", r"\d{6}")
+        .replace(r"\{timestamp\}", r"\d{8}_\d{6}")
+        .replace(r"\{counter\}", r"\d+");
+    if !template.to_lowercase().ends_with(".wav") {
+        pattern.push_str(r"\.wav");
+    }
+    regex::RegexBuilder::new(&format!("^{}$", pattern))
+        .case_insensitive(true)
+        .build()
+        .is_ok_and(|pattern| pattern.is_match(name))
+}
+
+/// Repair the WAV recordings in `temp_dir` that were left unfinalized and return them
+///
+/// Only files directly in the directory whose names follow `template` (or the default
+/// template) are checked, so archived recordings and other WAV files are not. Files written to
+/// within `RECOVERY_MIN_AGE` are skipped, since another instance may still be recording them.
+/// Files that can't be repaired, such as ones with no audio, are logged and left as they are.
+pub fn recover_interrupted_recordings(temp_dir: &Path, template: &str) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(temp_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Failed to look for interrupted recordings in {}: {}", temp_dir.display(), e);
+            }
+            return Vec::new();
+        }
+    };
+    let mut recovered: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            matches_filename_template(template, &name) || matches_filename_template(DEFAULT_FILENAME_TEMPLATE, &name)
+        })
+        .filter(|path| {
+            let age = std::fs::metadata(path).and_then(|metadata| metadata.modified()).map(|modified| modified.elapsed());
+            match age {
+                Ok(Ok(age)) if age >= RECOVERY_MIN_AGE => true,
+                _ => {
+                    info!("Leaving {} alone, it was written to recently", path.display());
+                    false
+                }
+            }
+        })
+        .filter(|path| match repair_wav_header(path) {
+            Ok(repaired) => {
+                if repaired {
+                    info!("Recovered interrupted recording: {}", path.display());
+                }
+                repaired
+            },
+            Err(e) => {
+                warn!("Could not recover {}: {:#}", path.display(), e);
+                false
+            }
+        })
+        .collect();
+    recovered.sort();
+    recovered
+}
+
+/// Move a transcribed recording into the archive, or delete it when `save_recordings` is off
+///
+/// The archive defaults to `recordings/` under `temp_dir`. Returns the archived path.
+/// In-memory recordings are never written to disk.
+pub fn archive_recording(recording: &Recording, config: &RecordingConfig, temp_dir: &Path) -> Result<Option<PathBuf>> {
+    let path = match recording {
+        Recording::File(path) => path,
+        Recording::Memory { .. } => {
+            // メモリ上の録音はディスクに書き出さない
+            if config.save_recordings {
+                info!("recording.in_memory is set, so the recording is not saved");
+            }
+            return Ok(None);
+        }
+    };
+    if !config.save_recordings {
+        std::fs::remove_file(path).context("Failed to delete recording")?;
+        info!("Recording deleted after transcription: {}", path);
+        return Ok(None);
+    }
+    
+    let archive_dir = config.archive_dir.clone().unwrap_or_else(|| temp_dir.join("recordings"));
+    std::fs::create_dir_all(&archive_dir).context("Failed to create recording archive directory")?;
+    let file_name = Path::new(path).file_name().context("Recording path has no file name")?;
+    let archived = archive_dir.join(file_name);
+    
+    // 別のファイルシステムへは rename できないのでコピーしてから削除
+    if std::fs::rename(path, &archived).is_err() {
+        std::fs::copy(path, &archived).context("Failed to copy recording to the archive")?;
+        std::fs::remove_file(path).context("Failed to remove recording after archiving")?;
+    }
+    info!("Recording archived to {}", archived.display());
+    Ok(Some(archived))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cues_for_a_missing_sink_are_skipped() {
+        let sinks = vec!["alsa_output.speakers".to_string()];
+        // プレーヤーを起動する前に飛ばすので、存在しないファイルでもエラーにならない
+        assert!(!play_cue_wav("/nonexistent/cue.wav", Some("bluez_output.headset"), &sinks).unwrap());
+        assert!(play_cue_wav("/nonexistent/cue.wav", Some("alsa_output.speakers"), &sinks).is_err());
+        assert!(play_cue_wav("/nonexistent/cue.wav", Some("bluez_output.headset"), &[]).is_err());
+    }
+
+    #[test]
+    fn preferred_format_is_used_only_when_supported() {
+        use cpal::{SampleFormat, SampleRate, SupportedBufferSize, SupportedStreamConfigRange};
+        let range = |format, min, max| SupportedStreamConfigRange::new(2, SampleRate(min), SampleRate(max), SupportedBufferSize::Unknown, format);
+        let default = range(SampleFormat::F32, 44100, 48000).with_sample_rate(SampleRate(48000));
+        let ranges = [range(SampleFormat::F32, 44100, 48000), range(SampleFormat::I16, 8000, 96000)];
+        
+        let picked = config_with_format(&ranges, SampleFormat::I16, &default).unwrap();
+        assert_eq!(picked.sample_format(), SampleFormat::I16);
+        assert_eq!(picked.sample_rate(), SampleRate(48000));
+        
+        // 対応していない形式は既定に戻す
+        assert!(config_with_format(&ranges[..1], SampleFormat::I16, &default).is_none());
+        assert_eq!(preferred_sample_format(SampleFormatPreference::Auto), None);
+    }
+    
+    #[test]
+    fn sample_rate_falls_back_to_the_closest_supported_rate() {
+        let ranges = [(8000, 8000), (16000, 16000), (48000, 48000)];
+        assert_eq!(closest_supported_rate(48000, &ranges), Some(48000));
+        assert_eq!(closest_supported_rate(44100, &ranges), Some(48000));
+        assert_eq!(closest_supported_rate(22050, &[(44100, 96000)]), None);
+        assert_eq!(closest_supported_rate(32000, &[(8000, 96000)]), Some(32000));
+        assert_eq!(closest_supported_rate(44100, &[]), None);
+    }
+    
+    #[test]
+    fn buffer_frames_are_fixed_only_within_the_device_range() {
+        use cpal::{BufferSize, SupportedBufferSize};
+        let range = SupportedBufferSize::Range { min: 64, max: 4096 };
+        assert_eq!(buffer_size_for(0, &range), BufferSize::Default);
+        assert_eq!(buffer_size_for(256, &range), BufferSize::Fixed(256));
+        assert_eq!(buffer_size_for(16, &range), BufferSize::Default);
+        assert_eq!(buffer_size_for(8192, &range), BufferSize::Default);
+        // 範囲が分からない場合はそのまま要求し、拒否されたら既定に戻す
+        assert_eq!(buffer_size_for(256, &SupportedBufferSize::Unknown), BufferSize::Fixed(256));
+    }
+    
+    #[test]
+    fn calibration_recommends_gain_towards_target() {
+        // 1kHz・モノラル: 無音0.5秒 + 矩形波（振幅0.025）0.5秒
+        let mut samples = vec![0i16; 500];
+        samples.extend((0..500).map(|i| if i % 2 == 0 { 819 } else { -819 }));
+        
+        let calibration = calibrate_samples(&samples, 1, 1000, 0.003).unwrap();
+        // 無音区間は測定に含めない
+        assert!((calibration.speech_rms - 0.025).abs() < 0.001);
+        assert!((calibration.recommended_gain - 4.0).abs() < 0.05);
+        
+        assert!(calibrate_samples(&[0i16; 1000], 1, 1000, 0.003).is_none());
+    }
+
+    #[test]
+    fn memory_sink_keeps_the_recording_off_disk() {
+        let path = std::env::temp_dir().join(format!("wispr_memory_sink_{}.wav", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let sink = RecordingSink::memory(&path);
+        let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        
+        // 無音0.5秒 + 音声0.5秒 + 無音0.5秒
+        let mut writer = hound::WavWriter::new(sink.clone(), spec).unwrap();
+        for i in 0..1500 {
+            let sample = if (500..1000).contains(&i) && i % 2 == 0 { 8000 } else { 0 };
+            writer.write_sample(sample as i16).unwrap();
+        }
+        drop(writer);
+        
+        let recording = sink.finish().unwrap();
+        assert!(recording.path().is_none());
+        assert!(!Path::new(&path).exists());
+        assert!(!analyze_recording(&recording).unwrap().is_silent());
+        
+        let Recording::Memory { data, .. } = recording else { unreachable!() };
+        let trimmed = trim_silence_in_memory(&data, 0.003).unwrap().unwrap();
+        assert!(trimmed.len() < data.len());
+    }
+
+    #[test]
+    fn empty_or_truncated_wav_is_rejected() {
+        let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let wav = |samples: usize| {
+            let mut data = Cursor::new(Vec::new());
+            let mut writer = hound::WavWriter::new(&mut data, spec).unwrap();
+            for _ in 0..samples {
+                writer.write_sample(0i16).unwrap();
+            }
+            writer.finalize().unwrap();
+            data.into_inner()
+        };
+        let memory = |data: Vec<u8>| Recording::Memory { path: String::new(), data: Arc::new(data) };
+        
+        assert!(memory(wav(100)).validate().is_ok());
+        // ヘッダーだけでサンプルがない
+        assert!(matches!(memory(wav(0)).validate(), Err(InvalidRecording::Empty)));
+        // ヘッダーの途中で切れている
+        let mut truncated = wav(100);
+        truncated.truncate(20);
+        assert!(matches!(memory(truncated).validate(), Err(InvalidRecording::Corrupt(_))));
+        
+        let path = std::env::temp_dir().join(format!("wispr_truncated_{}.wav", std::process::id()));
+        std::fs::write(&path, &wav(100)[..30]).unwrap();
+        assert!(Recording::File(path.to_string_lossy().to_string()).validate().is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+    
+    #[test]
+    fn interrupted_recordings_get_a_header_for_their_audio() {
+        let dir = std::env::temp_dir().join(format!("wispr_recover_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let spec = hound::WavSpec { channels: 1, sample_rate: 16000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let samples: Vec<i16> = (0..1600).map(|i| (i % 200) as i16).collect();
+        let wav = wav_utils::write_samples(spec, &samples).unwrap();
+        
+        // 最初のフラッシュ前に落ちた録音: サイズが 0 のままで、最後のサンプルが途中まで
+        let mut interrupted = wav.clone();
+        interrupted[4..8].copy_from_slice(&0u32.to_le_bytes());
+        interrupted[40..44].copy_from_slice(&0u32.to_le_bytes());
+        interrupted.push(7);
+        let write_old = |name: &str, contents: &[u8]| {
+            std::fs::write(dir.join(name), contents).unwrap();
+            let modified = std::time::SystemTime::now() - RECOVERY_MIN_AGE * 2;
+            File::options().write(true).open(dir.join(name)).unwrap().set_modified(modified).unwrap();
+        };
+        write_old("memo_1.wav", &interrupted);
+        write_old("memo_5.wav", &wav);
+        write_old("memo_2.wav", &interrupted[..44]);
+        write_old("recording_20260101_120000.wav", &interrupted);
+        write_old("other.wav", &interrupted);
+        write_old("memo_3.txt", b"RIFF");
+        // 別のインスタンスが録音中かもしれない新しいファイルは触らない
+        std::fs::write(dir.join("memo_4.wav"), &interrupted).unwrap();
+        
+        assert_eq!(
+            recover_interrupted_recordings(&dir, "memo_{counter}"),
+            vec![dir.join("memo_1.wav"), dir.join("recording_20260101_120000.wav")]
+        );
+        assert_eq!(wav_utils::read_wav_file(&dir.join("memo_1.wav")).unwrap().1, samples);
+        assert!(recover_interrupted_recordings(&dir, "memo_{counter}").is_empty());
+        assert_eq!(std::fs::read(dir.join("memo_5.wav")).unwrap(), wav);
+        assert_eq!(std::fs::read(dir.join("other.wav")).unwrap(), interrupted);
+        assert_eq!(std::fs::read(dir.join("memo_4.wav")).unwrap(), interrupted);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn stdin_audio_is_read_as_wav_or_raw_pcm() {
+        let path = Path::new("/tmp/stdin.wav");
+        // raw s16le ステレオ 2 フレーム + 端数 1 バイト
+        let raw = vec![1, 0, 2, 0, 3, 0, 4, 0, 9];
+        let recording = recording_from_stdin_bytes(raw.clone(), Some(16000), Some(2), path).unwrap();
+        let Recording::Memory { data, .. } = &recording else { panic!("expected an in-memory recording") };
+        let mut reader = hound::WavReader::new(Cursor::new(data.as_slice())).unwrap();
+        assert_eq!(reader.spec().sample_rate, 16000);
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.samples::<i16>().map(|s| s.unwrap()).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        
+        // WAV はそのまま使い、raw はサンプルレートが必要
+        let wav = data.as_slice().to_vec();
+        assert!(recording_from_stdin_bytes(wav, None, None, path).is_ok());
+        assert!(recording_from_stdin_bytes(raw, None, None, path).is_err());
+        assert!(recording_from_stdin_bytes(Vec::new(), Some(16000), None, path).is_err());
+    }
+    
+    #[test]
+    fn sink_names_are_the_second_column() {
+        let output = "0\talsa_output.pci-0000_00_1f.3.hdmi-stereo\tPipeWire\ts32le 2ch 48000Hz\tSUSPENDED\n\
+                      1\tbluez_output.00_11_22_33_44_55.1\tPipeWire\ts16le 2ch 48000Hz\tRUNNING\n\n";
+        assert_eq!(
+            parse_sink_names(output),
+            vec!["alsa_output.pci-0000_00_1f.3.hdmi-stereo".to_string(), "bluez_output.00_11_22_33_44_55.1".to_string()]
+        );
+    }
+    
+    #[test]
+    fn only_monitor_sources_are_listed() {
+        let output = "0\talsa_input.pci-0000_00_1f.3.analog-stereo\tPipeWire\ts32le 2ch 48000Hz\tSUSPENDED\n\
+                      1\talsa_output.pci-0000_00_1f.3.analog-stereo.monitor\tPipeWire\ts32le 2ch 48000Hz\tRUNNING\n";
+        assert_eq!(
+            parse_monitor_sources(output),
+            vec!["alsa_output.pci-0000_00_1f.3.analog-stereo.monitor".to_string()]
+        );
+    }
+    
+    #[test]
+    fn only_streams_of_this_process_are_moved() {
+        let output = r#"[
+            {"index": 41, "properties": {"application.name": "Firefox", "application.process.id": "1200"}},
+            {"index": 57, "properties": {"application.name": "ALSA plug-in [wispr]", "application.process.id": "4321"}},
+            {"index": 58, "properties": {"application.process.id": "4321"}}
+        ]"#;
+        assert_eq!(parse_source_outputs(output, 4321), vec![57, 58]);
+        assert!(parse_source_outputs(output, 99).is_empty());
+        assert!(parse_source_outputs("Failed to connect", 4321).is_empty());
+    }
+
+    #[test]
+    fn vad_ignores_short_noises() {
+        let mut vad = VoiceActivityDetector::new(0.01);
+        // 50ms のクリック音が間隔を空けて続いても反応しない
+        for _ in 0..10 {
+            assert!(!vad.update(0.2, 50.0));
+            for _ in 0..4 {
+                assert!(!vad.update(0.0, 100.0));
+            }
+        }
+    }
+
+    #[test]
+    fn vad_triggers_once_on_sustained_voice() {
+        let mut vad = VoiceActivityDetector::new(0.01);
+        let triggers = (0..20)
+            // 閾値の半分〜閾値の間の短い落ち込みはリセットしない
+            .map(|i| vad.update(if i % 4 == 3 { 0.007 } else { 0.05 }, 20.0))
+            .filter(|&triggered| triggered)
+            .count();
+        assert_eq!(triggers, 1);
+
+        // 静かになってから再び話すと、もう一度検出する
+        assert!(!vad.update(0.0, 400.0));
+        assert!(!vad.update(0.05, 200.0));
+        assert!(vad.update(0.05, 100.0));
+    }
+
+    #[test]
+    fn u16_samples_are_centered_on_zero() {
+        assert_eq!(u16_to_i16(32768), 0);
+        assert_eq!(u16_to_i16(0), i16::MIN);
+        assert_eq!(u16_to_i16(u16::MAX), i16::MAX);
+    }
+
+    #[test]
+    fn capabilities_are_summarized_per_format() {
+        let capabilities = DeviceCapabilities {
+            default: Some((48000, 2, cpal::SampleFormat::F32)),
+            ranges: vec![
+                (8000, 48000, 2, cpal::SampleFormat::I16),
+                (8000, 96000, 1, cpal::SampleFormat::I16),
+                (44100, 44100, 2, cpal::SampleFormat::F32),
+            ],
+        };
+        assert_eq!(
+            capabilities.summary("Default", "Supported"),
+            "Default: 48000 Hz, 2 ch, f32\nSupported:\n  i16: 1, 2 ch, 8000-96000 Hz\n  f32: 2 ch, 44100 Hz"
+        );
+        let unknown = DeviceCapabilities { default: None, ranges: Vec::new() };
+        assert_eq!(unknown.summary("Default", "Supported"), "");
+    }
+
+    #[test]
+    fn audio_hosts_are_matched_ignoring_case() {
+        assert_eq!(host_id("ALSA"), Some(cpal::HostId::Alsa));
+        assert_eq!(host_id("alsa"), Some(cpal::HostId::Alsa));
+        assert_eq!(host_id("no-such-host"), None);
+        // 不明なホストは既定のホストになる
+        assert_eq!(audio_host("no-such-host").id(), cpal::default_host().id());
+    }
+
+    #[test]
+    fn file_cap_stops_before_the_buffer_that_would_exceed_it() {
+        assert!(!exceeds_file_cap(u32::MAX, 1024, 0));
+        assert!(!exceeds_file_cap(0, 16, 100));
+        assert!(!exceeds_file_cap(10, 6, 100));
+        assert!(exceeds_file_cap(10, 7, 100));
+        assert!(exceeds_file_cap(100, 0, 100));
+    }
+
+    #[test]
+    fn flushes_follow_the_interval_across_callbacks() {
+        let start = Instant::now();
+        let mut schedule = FlushSchedule::new(Duration::from_millis(1000));
+        assert!(!schedule.due(start));
+        assert!(!schedule.due(start + Duration::from_millis(999)));
+        assert!(schedule.due(start + Duration::from_millis(1000)));
+        assert!(!schedule.due(start + Duration::from_millis(1500)));
+        assert!(schedule.due(start + Duration::from_millis(2100)));
+
+        let mut every_buffer = FlushSchedule::new(Duration::ZERO);
+        assert!(every_buffer.due(start));
+        assert!(every_buffer.due(start));
+    }
+    
+    #[test]
+    fn level_scale_maps_rms_linearly_or_in_db() {
+        let linear = LevelScale { factor: 5.0, db: false, smoothing: 0.0 };
+        assert!((linear.level(0.1) - 0.5).abs() < 1e-6);
+        assert_eq!(linear.level(0.5), 1.0);
+        
+        let db = LevelScale { factor: 5.0, db: true, smoothing: 0.0 };
+        assert_eq!(db.level(1.0), 1.0);
+        // -20 dBFS は -60〜0 dB の 2/3
+        assert!((db.level(0.1) - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(db.level(0.0), 0.0);
+    }
+    
+    #[test]
+    fn smoothed_level_rises_faster_than_it_falls() {
+        let raw = LevelScale { factor: 1.0, db: false, smoothing: 0.0 };
+        assert_eq!(raw.smoothed_level(0.8, 0.2), 0.2f32 as f64);
+        
+        let smooth = LevelScale { factor: 1.0, db: false, smoothing: 0.5 };
+        // 上昇は前の値を 1/4 だけ残し、下降は 1/2 残す
+        assert!((smooth.smoothed_level(0.0, 1.0) - 0.75).abs() < 1e-6);
+        assert!((smooth.smoothed_level(1.0, 0.0) - 0.5).abs() < 1e-6);
+    }
+    
+    #[test]
+    fn unusual_channel_counts_are_rejected() {
+        let config = |channels| cpal::StreamConfig {
+            channels,
+            sample_rate: cpal::SampleRate(16000),
+            buffer_size: cpal::BufferSize::Default,
+        };
+        assert!(validate_stream_config(&config(0)).is_err());
+        assert!(validate_stream_config(&config(33)).is_err());
+        assert!(validate_stream_config(&config(1)).is_ok());
+        assert!(validate_stream_config(&config(32)).is_ok());
+    }
+    
+    #[test]
+    fn preroll_keeps_only_the_latest_samples() {
+        let mut preroll = PrerollBuffer::default();
+        preroll.configure(1.0, 4, 2);
+        preroll.push(&[1i16, 1, 2, 2, 3, 3]);
+        preroll.push(&[4i16, 4, 5, 5, 6, 6]);
+        assert_eq!(preroll.take(4, 2), vec![3, 3, 4, 4, 5, 5, 6, 6]);
+        // 取り出した後は空
+        assert!(preroll.take(4, 2).is_empty());
+        
+        let mut disabled = PrerollBuffer::default();
+        disabled.configure(0.0, 4, 2);
+        disabled.push(&[1i16, 1]);
+        assert!(disabled.take(4, 2).is_empty());
+    }
+}
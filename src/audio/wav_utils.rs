@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+/// Length of the windows used to find speech when trimming silence
+pub const TRIM_WINDOW_MS: usize = 10;
+/// Audio kept before the first and after the last loud window so words aren't clipped
+pub const TRIM_MARGIN_MS: usize = 250;
+
+/// Format of 16-bit PCM audio
+pub fn pcm_spec(sample_rate: u32, channels: u16) -> hound::WavSpec {
+    hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    }
+}
+
+/// Format and samples of a WAV read from `reader`
+pub fn read_samples_from<R: Read>(mut reader: hound::WavReader<R>) -> Result<(hound::WavSpec, Vec<i16>)> {
+    let spec = reader.spec();
+    let samples = reader.samples::<i16>()
+        .collect::<std::result::Result<Vec<i16>, _>>()
+        .context("Failed to read WAV samples")?;
+    Ok((spec, samples))
+}
+
+/// Format and samples of WAV data in memory
+pub fn read_samples(wav: &[u8]) -> Result<(hound::WavSpec, Vec<i16>)> {
+    let reader = hound::WavReader::new(Cursor::new(wav)).context("Failed to read WAV data")?;
+    read_samples_from(reader)
+}
+
+/// Format and samples of a WAV file
+pub fn read_wav_file(path: &Path) -> Result<(hound::WavSpec, Vec<i16>)> {
+    let reader = hound::WavReader::open(path)
+        .with_context(|| format!("Failed to open WAV file {}", path.display()))?;
+    read_samples_from(reader)
+}
+
+/// WAV data holding `samples`
+pub fn write_samples(spec: hound::WavSpec, samples: &[i16]) -> Result<Vec<u8>> {
+    let mut wav = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut wav, spec).context("Failed to create WAV data")?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize().context("Failed to finalize WAV data")?;
+    Ok(wav.into_inner())
+}
+
+/// Replace the WAV file at `path` with `samples`, through a temporary file next to it
+pub fn write_wav_file(path: &Path, spec: hound::WavSpec, samples: &[i16]) -> Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = Path::new(&temp_path);
+    let mut writer = hound::WavWriter::create(temp_path, spec)
+        .with_context(|| format!("Failed to create {}", temp_path.display()))?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize().context("Failed to finalize WAV file")?;
+    std::fs::rename(temp_path, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// Level of one sample, normalized so that `i16::MAX` is 1.0
+fn level(sample: i16) -> f32 {
+    sample as f32 / 32767.0
+}
+
+/// Largest absolute level of `samples`; 0.0 when empty
+pub fn peak(samples: &[i16]) -> f32 {
+    samples.iter().map(|&sample| level(sample).abs()).fold(0.0, f32::max)
+}
+
+/// RMS level of `samples`; 0.0 when empty
+pub fn rms(samples: &[i16]) -> f32 {
+    let sum: f32 = samples.iter()
+        .map(|&sample| level(sample) * level(sample))
+        .sum();
+    (sum / samples.len().max(1) as f32).sqrt()
+}
+
+/// Range of interleaved samples between the first and last window above `threshold` RMS,
+/// widened by `TRIM_MARGIN_MS` on each side; None when nothing is above the threshold
+pub fn voiced_range(samples: &[i16], channels: usize, sample_rate: u32, threshold: f32) -> Option<(usize, usize)> {
+    let frame_len = channels.max(1);
+    let window = (sample_rate as usize * TRIM_WINDOW_MS / 1000).max(1) * frame_len;
+    let margin = sample_rate as usize * TRIM_MARGIN_MS / 1000 * frame_len;
+    
+    let windows: Vec<bool> = samples.chunks(window).map(|chunk| rms(chunk) > threshold).collect();
+    let first = windows.iter().position(|&loud| loud)?;
+    let last = windows.iter().rposition(|&loud| loud)?;
+    
+    let start = (first * window).saturating_sub(margin);
+    let end = ((last + 1) * window + margin).min(samples.len());
+    Some((start, end))
+}
+
+/// `samples` without the leading and trailing audio below `threshold` RMS (see `voiced_range`)
+///
+/// None when nothing is above the threshold, so a silent recording isn't cut to nothing.
+pub fn trim_silence(samples: &[i16], spec: hound::WavSpec, threshold: f32) -> Option<&[i16]> {
+    let (start, end) = voiced_range(samples, spec.channels as usize, spec.sample_rate, threshold)?;
+    Some(&samples[start..end])
+}
+
+/// Average the channels of each frame into one; a trailing partial frame is dropped
+pub fn downmix_to_mono(samples: &[i16], channels: u16) -> Vec<i16> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples.chunks_exact(channels as usize)
+        .map(|frame| (frame.iter().map(|&sample| sample as i32).sum::<i32>() / frame.len() as i32) as i16)
+        .collect()
+}
+
+/// Convert interleaved samples between rates and channel counts
+///
+/// Channels are mixed down to mono before being spread to the target channels, and the
+/// rate is changed by picking the nearest frame, which is plenty for a second of speech.
+pub fn convert(samples: &[i16], from_rate: u32, from_channels: u16, to_rate: u32, to_channels: u16) -> Vec<i16> {
+    if (from_rate, from_channels) == (to_rate, to_channels) {
+        return samples.to_vec();
+    }
+    if from_rate == 0 || from_channels == 0 || to_rate == 0 || to_channels == 0 {
+        return Vec::new();
+    }
+    
+    let mono = downmix_to_mono(samples, from_channels);
+    if mono.is_empty() {
+        return Vec::new();
+    }
+    let frames = (mono.len() as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let mut converted = Vec::with_capacity(frames * to_channels as usize);
+    for frame in 0..frames {
+        let source = (frame as u64 * from_rate as u64 / to_rate as u64) as usize;
+        let sample = mono[source.min(mono.len() - 1)];
+        converted.extend(std::iter::repeat_n(sample, to_channels as usize));
+    }
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_survive_a_write_and_read() {
+        let spec = pcm_spec(16000, 2);
+        let samples = [0i16, -1, i16::MAX, i16::MIN, 123, -456];
+        let (read_spec, read) = read_samples(&write_samples(spec, &samples).unwrap()).unwrap();
+        assert_eq!(read_spec, spec);
+        assert_eq!(read, samples);
+        assert!(read_samples(b"RIFF").is_err());
+    }
+
+    #[test]
+    fn levels_are_normalized_to_full_scale() {
+        assert_eq!(peak(&[]), 0.0);
+        assert_eq!(rms(&[]), 0.0);
+        assert_eq!(peak(&[100, -32767, 5]), 1.0);
+        assert!((rms(&[16384, -16384]) - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn voiced_range_keeps_margin_around_speech() {
+        // 1kHz・モノラル: 無音1秒 + 音声0.5秒 + 無音1秒
+        let mut samples = vec![0i16; 1000];
+        samples.extend(std::iter::repeat_n(8000, 500));
+        samples.extend(vec![0i16; 1000]);
+
+        assert_eq!(voiced_range(&samples, 1, 1000, 0.01), Some((750, 1750)));
+        assert_eq!(voiced_range(&vec![0i16; 1000], 1, 1000, 0.01), None);
+        assert_eq!(trim_silence(&samples, pcm_spec(1000, 1), 0.01).map(<[i16]>::len), Some(1000));
+        assert_eq!(trim_silence(&[0i16; 1000], pcm_spec(1000, 1), 0.01), None);
+    }
+
+    #[test]
+    fn channels_are_averaged_when_downmixing() {
+        assert_eq!(downmix_to_mono(&[10, 20, -30, 30, 7], 2), vec![15, 0]);
+        assert_eq!(downmix_to_mono(&[1, 2, 3], 1), vec![1, 2, 3]);
+        assert_eq!(downmix_to_mono(&[i16::MAX, i16::MAX], 2), vec![i16::MAX]);
+    }
+
+    #[test]
+    fn conversion_changes_rate_and_channels() {
+        // ステレオ 4 Hz → モノラル 2 Hz
+        assert_eq!(convert(&[10, 20, 30, 40, 50, 60, 70, 80], 4, 2, 2, 1), vec![15, 55]);
+        // モノラル → ステレオで倍のレート
+        assert_eq!(convert(&[1, 2], 1, 1, 2, 2), vec![1, 1, 1, 1, 2, 2, 2, 2]);
+        assert!(convert(&[1, 2], 0, 1, 2, 2).is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Context, Result};
+use log::{error, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::api::TranscriptionAPI;
+use crate::audio::{self, Recording};
+
+/// Audio files directly inside `dir`, sorted by name
+///
+/// Subdirectories are not searched, and files the API doesn't accept are skipped.
+pub fn audio_files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && audio::has_supported_audio_extension(path))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Where the transcript of `input` is written: a `.txt` next to it, or in `output_dir`
+fn output_path(input: &Path, output_dir: Option<&Path>) -> PathBuf {
+    let path = input.with_extension("txt");
+    match (output_dir, path.file_name()) {
+        (Some(dir), Some(name)) => dir.join(name),
+        _ => path,
+    }
+}
+
+/// Transcribe one file and write its transcript
+fn transcribe_file(api: &TranscriptionAPI, input: &Path, output_dir: Option<&Path>) -> Result<PathBuf> {
+    let recording = Recording::File(input.to_string_lossy().into_owned());
+    let transcript = api.transcribe_with_processing(&recording, None)?;
+    for notice in transcript.notices {
+        warn!("{}: {}", input.display(), notice);
+    }
+    let output = output_path(input, output_dir);
+    fs::write(&output, format!("{}\n", transcript.text))
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(output)
+}
+
+/// Transcribe every audio file in `dir` with up to `jobs` at a time (`--transcribe-dir`)
+///
+/// A failed file is logged and the rest go on; the summary at the end lists the failures,
+/// and the run returns an error when there were any.
+pub fn run_transcribe_dir(api: TranscriptionAPI, dir: &Path, output_dir: Option<&Path>, jobs: usize) -> Result<()> {
+    let files = audio_files_in(dir)?;
+    if files.is_empty() {
+        println!("No audio files in {} (supported: {})", dir.display(), audio::SUPPORTED_AUDIO_EXTENSIONS.join(", "));
+        return Ok(());
+    }
+    if let Some(output_dir) = output_dir {
+        fs::create_dir_all(output_dir).with_context(|| format!("Failed to create {}", output_dir.display()))?;
+    }
+    let total = files.len();
+    let jobs = jobs.clamp(1, total);
+    info!("Transcribing {} files in {} with {} workers", total, dir.display(), jobs);
+
+    let api = Arc::new(api);
+    let pending = Arc::new(Mutex::new(files.into_iter()));
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let workers: Vec<_> = (0..jobs)
+        .map(|_| {
+            let (api, pending, failures) = (api.clone(), pending.clone(), failures.clone());
+            let output_dir = output_dir.map(Path::to_path_buf);
+            thread::spawn(move || {
+                // ロックはファイルを取り出す間だけ持つ
+                while let Some(input) = pending.lock().ok().and_then(|mut pending| pending.next()) {
+                    match transcribe_file(&api, &input, output_dir.as_deref()) {
+                        Ok(output) => info!("{} -> {}", input.display(), output.display()),
+                        Err(e) => {
+                            error!("Failed to transcribe {}: {:#}", input.display(), e);
+                            if let Ok(mut failures) = failures.lock() {
+                                failures.push((input, format!("{:#}", e)));
+                            }
+                        },
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    let mut failures = failures.lock().map(|failures| failures.clone()).unwrap_or_default();
+    failures.sort();
+    println!("Transcribed {} of {} files ({} failed)", total - failures.len(), total, failures.len());
+    for (input, message) in &failures {
+        println!("  Failed: {}: {}", input.display(), message);
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} of {} files failed to transcribe", failures.len(), total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_audio_files_are_picked_in_name_order() {
+        let dir = std::env::temp_dir().join(format!("wispr_batch_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested.wav")).unwrap();
+        for name in ["b.MP3", "a.wav", "notes.txt", "c.flac"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let names: Vec<_> = audio_files_in(&dir).unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["a.wav", "b.MP3", "c.flac"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn transcripts_go_next_to_the_input_or_into_the_output_dir() {
+        assert_eq!(output_path(Path::new("/memos/day1.m4a"), None), Path::new("/memos/day1.txt"));
+        assert_eq!(output_path(Path::new("/memos/day1.m4a"), Some(Path::new("/out"))), Path::new("/out/day1.txt"));
+    }
+}
@@ -1,16 +1,30 @@
-use anyhow::{Result, anyhow};
-use log::{error, info, debug};
-use std::process::Command;
+use anyhow::{Result, Context, anyhow};
+use log::{error, info, debug, warn};
+use std::process::{Child, Command};
+use std::sync::Mutex;
+use std::time::Duration;
 use std::fs::{self, create_dir_all, File};
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::io;
 use std::process::Stdio;
 
+use crate::config::ClipboardConfig;
+use crate::text_processor::strip_markdown;
+
+lazy_static::lazy_static! {
+    /// The wl-copy process currently serving the Wayland selection
+    static ref WL_COPY_PROCESS: Mutex<Option<Child>> = Mutex::new(None);
+}
+
 /// Clipboard helper for Linux
 pub struct Clipboard;
 
 impl Clipboard {
     /// Copy text to clipboard using xclip or wl-copy based on environment
+    ///
+    /// Fails when no clipboard tool could take the text; `set_text` then retries or falls
+    /// back to the clipboard file.
     pub fn copy_to_clipboard(text: &str) -> Result<()> {
         info!("Copying text to clipboard");
         
@@ -20,36 +34,56 @@ impl Clipboard {
         if is_wayland {
             // Use wl-copy for Wayland
             info!("Using wl-copy for Wayland clipboard");
-            let result = Command::new("wl-copy")
-                .arg(text)
-                .status();
-                
-            match result {
-                Ok(status) if status.success() => {
+            match Self::copy_with_wl_copy(text) {
+                Ok(pid) => {
                     info!("Text copied to clipboard (wl-copy)");
+                    watch_wl_copy(pid, text.to_string());
                     Ok(())
                 },
-                Ok(status) => {
-                    error!("wl-copy exited with status: {}", status);
-                    // Fall back to user clipboard
-                    copy_to_user_clipboard(text)
-                },
                 Err(e) => {
                     // Try xclip as a fallback
-                    info!("wl-copy not available ({}), trying xclip", e);
-                    match Self::copy_with_xclip(text) {
-                        Ok(_) => Ok(()),
-                        Err(_) => copy_to_user_clipboard(text),
-                    }
+                    error!("wl-copy failed ({}), trying xclip", e);
+                    Self::copy_with_xclip(text)
                 }
             }
         } else {
             // Use xclip for X11
-            match Self::copy_with_xclip(text) {
-                Ok(_) => Ok(()),
-                Err(_) => copy_to_user_clipboard(text),
+            Self::copy_with_xclip(text)
+        }
+    }
+    
+    /// Copy text using wl-copy
+    ///
+    /// On Wayland the selection only exists while a client serves it, so wl-copy is run
+    /// with `--foreground` and left running instead of being waited on. The copier keeps
+    /// the transcript available (even after this app exits) until another client takes
+    /// the selection, at the cost of one lingering `wl-copy` process. The previous copier
+    /// is stopped whenever a new copy is made. Returns the process id of the new copier.
+    pub fn copy_with_wl_copy(text: &str) -> Result<u32> {
+        debug!("Attempting to copy using wl-copy");
+        
+        let mut child = Command::new("wl-copy")
+            .arg("--foreground")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+            // Dropping stdin signals the end of the data to wl-copy
+        }
+        
+        // Replace the previous copier; it has already lost the selection to the new one
+        let pid = child.id();
+        if let Ok(mut guard) = WL_COPY_PROCESS.lock() {
+            if let Some(mut previous) = guard.replace(child) {
+                let _ = previous.kill();
+                let _ = previous.wait();
             }
         }
+        
+        Ok(pid)
     }
     
     /// Copy text using xclip
@@ -111,15 +145,75 @@ impl Clipboard {
     }
 }
 
+/// Time wl-copy gets to fail (e.g. when it can't connect to the compositor) before it counts as serving
+const WL_COPY_SETTLE: Duration = Duration::from_millis(50);
+
+/// Pause before reading the Wayland selection back
+const WL_PASTE_DELAY: Duration = Duration::from_millis(100);
+
+/// Whether the wl-copy process `pid` still serves the selection; `Err` with its status once it
+/// has exited, `Ok(false)` after a newer copy replaced it
+fn wl_copy_running(pid: u32) -> std::result::Result<bool, std::process::ExitStatus> {
+    let Ok(mut guard) = WL_COPY_PROCESS.lock() else {
+        return Ok(false);
+    };
+    match guard.as_mut().filter(|child| child.id() == pid).map(|child| child.try_wait()) {
+        Some(Ok(Some(status))) => Err(status),
+        Some(_) => Ok(true),
+        None => Ok(false),
+    }
+}
+
+/// Check from a background thread that the wl-copy process `pid` took the selection
+///
+/// Copies are often made from the GTK thread, so the waits happen here instead. If wl-copy
+/// failed, `text` is copied with xclip (or to the clipboard file) after all; if the selection
+/// doesn't hold `text` a little later, a warning is logged.
+fn watch_wl_copy(pid: u32, text: String) {
+    std::thread::spawn(move || {
+        std::thread::sleep(WL_COPY_SETTLE);
+        match wl_copy_running(pid) {
+            Err(status) if !status.success() => {
+                error!("wl-copy exited with status: {}, trying xclip", status);
+                if Clipboard::copy_with_xclip(&text).is_err() {
+                    if let Err(e) = copy_to_user_clipboard(&text) {
+                        error!("Failed to save to the clipboard file: {}", e);
+                    }
+                }
+                return;
+            },
+            // 新しいコピーに置き換わった場合はそちらが確認する
+            Ok(false) => return,
+            _ => {},
+        }
+        std::thread::sleep(WL_PASTE_DELAY);
+        if wl_copy_running(pid) != Ok(false) && !wayland_selection_matches(&text) {
+            warn!("Copied text is no longer available from the Wayland clipboard; it may have been lost");
+        }
+    });
+}
+
+/// Check that the Wayland clipboard still serves the text we just copied
+fn wayland_selection_matches(text: &str) -> bool {
+    match Command::new("wl-paste").arg("--no-newline").output() {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout) == text,
+        Ok(_) => false,
+        Err(e) => {
+            // wl-paste が無い場合は確認できないので成功扱い
+            debug!("Could not verify clipboard contents with wl-paste: {}", e);
+            true
+        }
+    }
+}
+
 /// Copy text to user-specific clipboard file
 fn copy_to_user_clipboard(text: &str) -> Result<()> {
     info!("Falling back to user clipboard file");
     
     // Check if we have the user-clipboard.sh script
-    let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
-    let script_path = home_dir.join(".local/bin/user-clipboard.sh");
+    let script_path = dirs::home_dir().map(|home_dir| home_dir.join(".local/bin/user-clipboard.sh"));
     
-    if script_path.exists() {
+    if let Some(script_path) = script_path.filter(|path| path.exists()) {
         // Use the script if it exists
         match Command::new(&script_path)
             .arg("--copy")
@@ -140,17 +234,30 @@ fn copy_to_user_clipboard(text: &str) -> Result<()> {
     }
 }
 
+/// File the clipboard fallback writes to: `~/.cache/wispr/clipboard.txt`, or the
+/// fallback directory when there is no home directory (e.g. HOME unset in a container)
+fn clipboard_file_path(home_dir: Option<&Path>) -> PathBuf {
+    match home_dir {
+        Some(home_dir) => home_dir.join(".cache/wispr/clipboard.txt"),
+        None => {
+            let path = crate::config::fallback_dir().join("clipboard.txt");
+            warn!("Could not determine home directory, using {} as the clipboard file", path.display());
+            path
+        }
+    }
+}
+
 /// Write text directly to clipboard file
 fn write_to_clipboard_file(text: &str) -> Result<()> {
     info!("Writing text directly to clipboard file");
-    let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
-    let cache_dir = home_dir.join(".cache/wispr");
+    let clipboard_file = clipboard_file_path(dirs::home_dir().as_deref());
     
     // Ensure cache directory exists
-    create_dir_all(&cache_dir)?;
+    if let Some(cache_dir) = clipboard_file.parent() {
+        create_dir_all(cache_dir)?;
+    }
     
     // Write text to clipboard file
-    let clipboard_file = cache_dir.join("clipboard.txt");
     let mut file = File::create(&clipboard_file)?;
     file.write_all(text.as_bytes())?;
     
@@ -169,7 +276,7 @@ pub fn paste_from_clipboard() -> Result<String> {
         // Use wl-paste for Wayland
         match Command::new("wl-paste").output() {
             Ok(output) if output.status.success() => {
-                let text = String::from_utf8_lossy(&output.stdout).to_string();
+                let text = decode_clipboard_bytes(output.stdout);
                 Ok(text)
             },
             _ => {
@@ -194,16 +301,15 @@ fn paste_from_user_clipboard() -> Result<String> {
     info!("Reading from user clipboard file");
     
     // Check if we have the user-clipboard.sh script
-    let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
-    let script_path = home_dir.join(".local/bin/user-clipboard.sh");
+    let script_path = dirs::home_dir().map(|home_dir| home_dir.join(".local/bin/user-clipboard.sh"));
     
-    if script_path.exists() {
+    if let Some(script_path) = script_path.filter(|path| path.exists()) {
         // Use the script if it exists
         match Command::new(&script_path)
             .arg("--paste")
             .output() {
             Ok(output) if output.status.success() => {
-                let text = String::from_utf8_lossy(&output.stdout).to_string();
+                let text = decode_clipboard_bytes(output.stdout);
                 Ok(text)
             },
             _ => {
@@ -220,17 +326,28 @@ fn paste_from_user_clipboard() -> Result<String> {
 /// Read text directly from clipboard file
 fn read_from_clipboard_file() -> Result<String> {
     info!("Reading text directly from clipboard file");
-    let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
-    let clipboard_file = home_dir.join(".cache/wispr/clipboard.txt");
+    let clipboard_file = clipboard_file_path(dirs::home_dir().as_deref());
     
     if clipboard_file.exists() {
-        let content = fs::read_to_string(&clipboard_file)?;
-        Ok(content)
+        let content = fs::read(&clipboard_file)?;
+        Ok(decode_clipboard_bytes(content))
     } else {
         Err(anyhow!("Clipboard file does not exist"))
     }
 }
 
+/// Text from clipboard tool output; invalid UTF-8 (from another app) becomes U+FFFD
+/// instead of failing the whole paste
+fn decode_clipboard_bytes(bytes: Vec<u8>) -> String {
+    match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Clipboard contents are not valid UTF-8; replacing the invalid bytes");
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+    }
+}
+
 /// Paste text using xclip
 fn paste_with_xclip() -> Result<String> {
     debug!("Attempting to paste using xclip");
@@ -242,14 +359,14 @@ fn paste_with_xclip() -> Result<String> {
         .stdout(Stdio::piped())
         .spawn() {
         Ok(mut child) => {
-            let mut output = String::new();
+            let mut output = Vec::new();
             if let Some(stdout) = &mut child.stdout {
-                io::Read::read_to_string(stdout, &mut output)?;
+                io::Read::read_to_end(stdout, &mut output)?;
             }
             
             let status = child.wait()?;
             if status.success() {
-                Ok(output)
+                Ok(decode_clipboard_bytes(output))
             } else {
                 // Try xsel as fallback
                 paste_with_xsel()
@@ -272,14 +389,14 @@ fn paste_with_xsel() -> Result<String> {
         .stdout(Stdio::piped())
         .spawn() {
         Ok(mut child) => {
-            let mut output = String::new();
+            let mut output = Vec::new();
             if let Some(stdout) = &mut child.stdout {
-                io::Read::read_to_string(stdout, &mut output)?;
+                io::Read::read_to_end(stdout, &mut output)?;
             }
             
             let status = child.wait()?;
             if status.success() {
-                Ok(output)
+                Ok(decode_clipboard_bytes(output))
             } else {
                 Err(anyhow!("xsel command failed"))
             }
@@ -290,15 +407,276 @@ fn paste_with_xsel() -> Result<String> {
     }
 }
 
+/// Pause before copying again after a failed attempt
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Run `attempt` until it succeeds, at most `retries` more times, waiting `delay` in between
+///
+/// Returns the last error when every attempt failed.
+fn with_retries<T>(retries: u32, delay: Duration, mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut tries = 0;
+    loop {
+        match attempt() {
+            Ok(value) => return Ok(value),
+            Err(e) if tries < retries => {
+                tries += 1;
+                debug!("Clipboard copy attempt {} failed ({}), retrying", tries, e);
+                std::thread::sleep(delay);
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 /// Simple function to set text to clipboard
-pub fn set_text(text: &str) -> Result<()> {
-    match Clipboard::copy_to_clipboard(text) {
+///
+/// With `verify`, the clipboard is read back and a mismatch is treated as a failed copy.
+/// A failed copy is tried again up to `retries` times (`clipboard.retries`), since xclip
+/// sometimes loses a race with the X server right after the window gains focus; after
+/// that the text goes to the clipboard file.
+pub fn set_text(text: &str, verify: bool, retries: u32) -> Result<()> {
+    let result = with_retries(retries, RETRY_DELAY, || {
+        Clipboard::copy_to_clipboard(text)?;
+        if verify {
+            verify_clipboard(text)
+        } else {
+            Ok(())
+        }
+    });
+    match result {
         Ok(_) => Ok(()),
-        Err(_) => copy_to_user_clipboard(text),
+        Err(e) => {
+            warn!("Clipboard copy failed ({}), saving to the clipboard file instead", e);
+            copy_to_user_clipboard(text)
+        }
+    }
+}
+
+/// Copy a transcript, applying the clipboard settings
+pub fn copy_transcript(text: &str, config: &ClipboardConfig) -> Result<()> {
+    if config.strip_markdown {
+        set_text(&strip_markdown(text), config.verify, config.retries)
+    } else {
+        set_text(text, config.verify, config.retries)
+    }
+}
+
+/// Copiers that put the selection in a background process of their own, in order of preference
+///
+/// wl-copy forks to the background unless given `--foreground`, and xclip keeps serving the
+/// selection until another client takes it (`-loops 0`). Empty when there is no display.
+fn detached_copiers(wayland: bool, x11: bool) -> Vec<(&'static str, &'static [&'static str])> {
+    let mut copiers: Vec<(&'static str, &'static [&'static str])> = Vec::new();
+    if wayland {
+        copiers.push(("wl-copy", &[]));
+    }
+    if x11 {
+        copiers.push(("xclip", &["-selection", "clipboard", "-loops", "0"]));
+    }
+    copiers
+}
+
+/// Run a copier that forks itself into the background and wait for the foreground part
+///
+/// Its output goes to /dev/null: the background copier would otherwise hold this process's
+/// stdout open and keep a pipeline such as `wispr --transcribe-stdin --copy | tee` waiting.
+fn run_detached_copier(program: &str, args: &[&str], text: &str) -> Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow!("{} exited with status: {}", program, status));
+    }
+    Ok(())
+}
+
+/// Copy a transcript from a command that exits right after copying (`--transcribe-stdin --copy`)
+///
+/// The selection is served by a copier detached from this process, so it stays pasteable
+/// after the command exits. Without `WAYLAND_DISPLAY` or `DISPLAY` (e.g. over SSH) there is
+/// no clipboard to hold it and this fails; the printed transcript has to be used instead.
+pub fn copy_before_exit(text: &str, config: &ClipboardConfig) -> Result<()> {
+    let text = if config.strip_markdown { strip_markdown(text) } else { text.to_string() };
+    let copiers = detached_copiers(
+        std::env::var_os("WAYLAND_DISPLAY").is_some(),
+        std::env::var_os("DISPLAY").is_some(),
+    );
+    let mut last_error = anyhow!("No display to keep the clipboard (neither WAYLAND_DISPLAY nor DISPLAY is set)");
+    for (program, args) in copiers {
+        match run_detached_copier(program, args, &text) {
+            Ok(_) => {
+                info!("Transcript copied to the clipboard ({}, kept after exit)", program);
+                return Ok(());
+            },
+            Err(e) => {
+                warn!("{} failed: {:#}", program, e);
+                last_error = e;
+            },
+        }
+    }
+    Err(last_error)
+}
+
+/// Type a transcript into the focused window, applying the clipboard settings
+///
+/// Uses `wtype` on Wayland and `xdotool type` on X11. Both send the characters themselves
+/// rather than key codes of the current layout, so Japanese text is typed as-is without
+/// going through the input method.
+pub fn type_transcript(text: &str, config: &ClipboardConfig) -> Result<()> {
+    let text = if config.strip_markdown { strip_markdown(text) } else { text.to_string() };
+    if text.is_empty() {
+        return Ok(());
+    }
+    
+    let is_wayland = std::env::var("WAYLAND_DISPLAY").is_ok();
+    let (program, args) = type_command(&text, config.type_delay_ms, is_wayland);
+    let output = Command::new(program)
+        .args(&args)
+        .stdout(Stdio::null())
+        .output()
+        .with_context(|| format!("Failed to run {} (is it installed?)", program))?;
+    if output.status.success() {
+        info!("Typed transcript with {}", program);
+        Ok(())
+    } else {
+        Err(anyhow!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// The program and arguments that type `text` with `delay_ms` between characters
+fn type_command(text: &str, delay_ms: u64, wayland: bool) -> (&'static str, Vec<String>) {
+    // "--" の後に置いて、"-" で始まる文字起こしがオプション扱いされないようにする
+    if wayland {
+        ("wtype", vec!["-d".to_string(), delay_ms.to_string(), "--".to_string(), text.to_string()])
+    } else {
+        (
+            "xdotool",
+            vec![
+                "type".to_string(),
+                "--clearmodifiers".to_string(),
+                "--delay".to_string(),
+                delay_ms.to_string(),
+                "--".to_string(),
+                text.to_string(),
+            ],
+        )
+    }
+}
+
+/// Press Ctrl+V in the focused window after waiting `delay_ms` (`clipboard.paste_delay_ms`)
+///
+/// Some applications ignore a paste sent right after they gain focus, so the wait comes first.
+/// Uses `wtype` on Wayland and `xdotool key` on X11, like `type_transcript`.
+pub fn paste_into_focused_window(delay_ms: u64) -> Result<()> {
+    std::thread::sleep(Duration::from_millis(delay_ms));
+    let is_wayland = std::env::var("WAYLAND_DISPLAY").is_ok();
+    let (program, args) = paste_command(is_wayland);
+    let output = Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .output()
+        .with_context(|| format!("Failed to run {} (is it installed?)", program))?;
+    if output.status.success() {
+        info!("Pasted transcript with {}", program);
+        Ok(())
+    } else {
+        Err(anyhow!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// The program and arguments that press Ctrl+V
+fn paste_command(wayland: bool) -> (&'static str, &'static [&'static str]) {
+    if wayland {
+        ("wtype", &["-M", "ctrl", "v", "-m", "ctrl"])
+    } else {
+        ("xdotool", &["key", "--clearmodifiers", "ctrl+v"])
+    }
+}
+
+/// Check that the clipboard now holds `text`
+fn verify_clipboard(text: &str) -> Result<()> {
+    let pasted = get_text().context("Failed to read the clipboard back")?;
+    // wl-paste などは末尾に改行を付けるので無視して比較
+    if pasted.trim_end_matches('\n') == text.trim_end_matches('\n') {
+        debug!("Clipboard contents verified");
+        Ok(())
+    } else {
+        Err(anyhow!("clipboard contents differ from the copied text"))
     }
 }
 
 /// Simple function to get text from clipboard
 pub fn get_text() -> Result<String> {
     paste_from_clipboard()
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipboard_file_falls_back_without_a_home_directory() {
+        let home = Path::new("/home/user");
+        assert_eq!(clipboard_file_path(Some(home)), home.join(".cache/wispr/clipboard.txt"));
+        
+        let fallback = clipboard_file_path(None);
+        assert!(fallback.ends_with("wispr/clipboard.txt"));
+        assert!(!fallback.starts_with(home));
+    }
+    
+    #[test]
+    fn invalid_utf8_is_replaced_instead_of_failing() {
+        assert_eq!(decode_clipboard_bytes("録音.wav".as_bytes().to_vec()), "録音.wav");
+        let bytes = vec![b'a', 0xff, 0xfe, b'b', 0xe3, 0x81];
+        assert_eq!(decode_clipboard_bytes(bytes), "a\u{fffd}\u{fffd}b\u{fffd}");
+    }
+    
+    #[test]
+    fn failed_copies_are_retried_up_to_the_limit() {
+        let mut attempts = 0;
+        let result = with_retries(2, Duration::ZERO, || {
+            attempts += 1;
+            if attempts < 3 { Err(anyhow!("xclip failed")) } else { Ok(attempts) }
+        });
+        assert_eq!(result.unwrap(), 3);
+        
+        let mut attempts = 0;
+        let result: Result<()> = with_retries(1, Duration::ZERO, || {
+            attempts += 1;
+            Err(anyhow!("xclip failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 2);
+    }
+    
+    #[test]
+    fn copies_before_exit_use_a_copier_for_each_display() {
+        let programs = |wayland, x11| -> Vec<&str> {
+            detached_copiers(wayland, x11).into_iter().map(|(program, _)| program).collect()
+        };
+        assert_eq!(programs(true, true), ["wl-copy", "xclip"]);
+        assert_eq!(programs(false, true), ["xclip"]);
+        assert!(programs(false, false).is_empty());
+        // wl-copy は --foreground なしで自分でバックグラウンドに移る
+        assert!(detached_copiers(true, false)[0].1.is_empty());
+    }
+    
+    #[test]
+    fn type_command_passes_the_text_after_the_options() {
+        let (program, args) = type_command("-こんにちは", 20, true);
+        assert_eq!(program, "wtype");
+        assert_eq!(args, ["-d", "20", "--", "-こんにちは"]);
+        
+        let (program, args) = type_command("こんにちは", 12, false);
+        assert_eq!(program, "xdotool");
+        assert_eq!(args, ["type", "--clearmodifiers", "--delay", "12", "--", "こんにちは"]);
+    }
+}
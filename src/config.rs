@@ -1,15 +1,26 @@
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use log::{info, warn};
 use directories::ProjectDirs;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
-    /// OpenAI API key
+    /// Config file format version, bumped by migrations in `load_config`
+    #[serde(default)]
+    pub config_version: u32,
+    
+    /// OpenAI API key; may be omitted in favour of the keyring or `OPENAI_API_KEY`
+    #[serde(default)]
     pub api_key: String,
     
+    /// Base URL of the OpenAI-compatible API, without a trailing slash
+    #[serde(default = "default_api_base_url")]
+    pub api_base_url: String,
+    
     /// Path to save recordings temporarily
     pub temp_dir: PathBuf,
     
@@ -21,6 +32,57 @@ pub struct Config {
     
     /// Keyboard shortcut settings
     pub shortcuts: ShortcutConfig,
+    
+    /// Transcript post-processing settings
+    #[serde(default)]
+    pub text_processing: TextProcessingConfig,
+    
+    /// Transcription backend settings
+    #[serde(default)]
+    pub transcription: TranscriptionConfig,
+    
+    /// Clipboard settings
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+    
+    /// Transcription API settings
+    #[serde(default)]
+    pub api: ApiConfig,
+    
+    /// Transcription log settings
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    
+    /// Sound files of the `recording.play_sounds` cues
+    #[serde(default)]
+    pub sounds: SoundsConfig,
+    
+    /// Settings for passing transcripts to other tools
+    #[serde(default)]
+    pub integrations: IntegrationsConfig,
+    
+    /// Audio backend settings
+    #[serde(default)]
+    pub audio: AudioConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// cpal host used to list and open devices: "default" for the system default, or a host
+    /// compiled into cpal such as "alsa" or "jack" (case-insensitive)
+    pub host: String,
+    
+    /// Sound server sink the microphone test and the cue sounds play on; the default output when unset
+    ///
+    /// `recording.output_device`, where earlier versions read it from, is still accepted.
+    pub output_device: Option<String>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self { host: "default".to_string(), output_device: None }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,6 +90,10 @@ pub struct RecordingConfig {
     /// Maximum recording duration in seconds
     pub max_duration_secs: u64,
     
+    /// Size in bytes at which the WAV file stops growing and the recording is transcribed; 0 for no cap
+    #[serde(default)]
+    pub max_file_bytes: u64,
+    
     /// Sample rate for audio recording
     pub sample_rate: u32,
     
@@ -37,6 +103,87 @@ pub struct RecordingConfig {
     /// 無音検出機能を無効にするかどうか
     #[serde(default)]
     pub disable_silence_detection: bool,
+    
+    /// RMS level (0.0-1.0) above which input counts as sound
+    #[serde(default = "default_silence_threshold")]
+    pub silence_threshold: f32,
+    
+    /// Voice-activated start: starting a recording arms it, and it begins once speech is heard
+    #[serde(default)]
+    pub vad_enabled: bool,
+    
+    /// Cut audio below `silence_threshold` from the start and end of a recording before upload
+    #[serde(default)]
+    pub trim_silence: bool,
+    
+    /// Warn when a recording is near-silent or heavily clipped
+    #[serde(default = "default_true")]
+    pub warn_on_bad_audio: bool,
+    
+    /// Skip uploading recordings that triggered the bad-audio warning
+    #[serde(default)]
+    pub skip_bad_audio: bool,
+    
+    /// File name for recordings; supports `{date}`, `{time}`, `{timestamp}` and `{counter}`
+    #[serde(default = "default_filename_template")]
+    pub filename_template: String,
+    
+    /// Domain terms sent as the transcription prompt to bias recognition
+    #[serde(default)]
+    pub biasing_terms: Vec<String>,
+    
+    /// Keep recordings after a successful transcription instead of deleting them
+    #[serde(default)]
+    pub save_recordings: bool,
+    
+    /// Where kept recordings are moved; defaults to `recordings/` under `temp_dir`
+    #[serde(default)]
+    pub archive_dir: Option<PathBuf>,
+    
+    /// Keep recordings in memory and upload them directly instead of writing a temp file
+    ///
+    /// Only used with the openai backend; recordings over 64 MiB continue in a file.
+    #[serde(default)]
+    pub in_memory: bool,
+    
+    /// Gain applied to the recorded samples; `--calibrate` suggests a value
+    #[serde(default = "default_input_gain")]
+    pub input_gain: f32,
+    
+    /// Sample format to request from the input device: "auto" (device default), "i16" or "f32"
+    #[serde(default)]
+    pub preferred_format: SampleFormatPreference,
+    
+    /// Seconds counted down in the window before the microphone opens; 0 starts at once
+    #[serde(default)]
+    pub countdown_secs: u64,
+    
+    /// Seconds of input kept by the idle monitor and prepended to each recording; 0 for none
+    #[serde(default)]
+    pub preroll_secs: f32,
+    
+    /// Milliseconds between flushes of the WAV file while recording; 0 flushes on every buffer
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    
+    /// Frames per input buffer requested from the device; 0 lets the system choose
+    #[serde(default)]
+    pub buffer_frames: u32,
+    
+    /// Recordings stopped sooner than this many seconds are discarded instead of transcribed
+    #[serde(default = "default_min_duration_secs")]
+    pub min_duration_secs: f32,
+}
+
+/// Input sample format requested from the device
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleFormatPreference {
+    /// Whatever the device reports as its default
+    #[default]
+    Auto,
+    I16,
+    F32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -46,6 +193,145 @@ pub struct UiConfig {
     
     /// Show notifications for transcription
     pub notification_enabled: bool,
+    
+    /// Maximum characters of transcript shown in notifications and tooltips
+    #[serde(default = "default_preview_chars")]
+    pub preview_chars: usize,
+    
+    /// Keep an input stream open while idle to show the audio level
+    #[serde(default = "default_true")]
+    pub monitor_when_idle: bool,
+    
+    /// Turn idle monitoring back on once the transcriptions finish, if it was switched off
+    #[serde(default)]
+    pub resume_monitoring_after_record: bool,
+    
+    /// Seconds without recording after which idle monitoring stops (releasing the microphone)
+    /// until the next recording or tray click; 0 never
+    #[serde(default)]
+    pub idle_timeout_secs: u64,
+    
+    /// Monitor to open the window on; when unset, the monitor under the pointer
+    #[serde(default)]
+    pub monitor_index: Option<i32>,
+    
+    /// Launch with the window hidden, relying on the tray and notifications (tray builds only)
+    #[serde(default)]
+    pub start_hidden: bool,
+    
+    /// Scroll the transcript view to the end when its text is updated
+    #[serde(default = "default_true")]
+    pub autoscroll: bool,
+    
+    /// Copy text selected with the mouse in the transcript view to the clipboard
+    #[serde(default)]
+    pub copy_on_select: bool,
+    
+    /// Language of the window labels; when unset, taken from `LANG`
+    #[serde(default)]
+    pub language: Option<UiLanguage>,
+    
+    /// Factor the input RMS is multiplied by for the linear level meter
+    #[serde(default = "default_meter_scale")]
+    pub meter_scale: f32,
+    
+    /// Show the level meter in dBFS (-60 to 0 dB) instead of the linear scale
+    #[serde(default)]
+    pub meter_db: bool,
+    
+    /// Share of the previous meter level kept on each update (0.0-0.99); 0 shows the raw level
+    #[serde(default = "default_meter_smoothing")]
+    pub meter_smoothing: f32,
+    
+    /// Show a scrolling waveform of the last few seconds of input below the level meter
+    #[serde(default)]
+    pub show_waveform: bool,
+    
+    /// Window layout: "full" or "minimal" (record button, meter and transcript only)
+    #[serde(default)]
+    pub layout: WindowLayout,
+    
+    /// What a finished transcription does to the transcript shown: "replace", "append" or "new_tab"
+    #[serde(default)]
+    pub on_new_transcript: NewTranscriptMode,
+    
+    /// Longest transcript kept in the window, in characters; the oldest text is dropped beyond it.
+    /// 0 for no limit
+    #[serde(default)]
+    pub max_transcript_chars: usize,
+    
+    /// Keep the window above other windows
+    #[serde(default)]
+    pub always_on_top: bool,
+    
+    /// Font family of the transcript and dictionary views; the GTK default when unset
+    #[serde(default)]
+    pub font_family: Option<String>,
+    
+    /// Font size in points of the transcript and dictionary views; the GTK default when unset
+    #[serde(default)]
+    pub font_size: Option<f32>,
+    
+    /// Tags offered for the next transcription (e.g. "work", "personal"); no selector when empty
+    #[serde(default)]
+    pub tags: Vec<String>,
+    
+    /// Show a small always-on-top indicator while recording, for when the window is hidden
+    #[serde(default)]
+    pub recording_overlay: bool,
+    
+    /// Screen corner of the recording indicator
+    #[serde(default)]
+    pub overlay_corner: OverlayCorner,
+    
+    /// Start each paragraph of the transcript with its time, e.g. "[00:05]"; needs `verbose_json`
+    #[serde(default)]
+    pub inline_timestamps: bool,
+}
+
+/// Screen corner the recording indicator (`ui.recording_overlay`) is shown in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayCorner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Which parts of the main window are shown at startup
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowLayout {
+    /// All controls
+    #[default]
+    Full,
+    /// A small dictation widget; the toggles still reveal the other sections
+    Minimal,
+}
+
+/// What a finished transcription does to the transcript in the window
+///
+/// Results finishing together while the queue is busy are always appended to each other.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NewTranscriptMode {
+    /// Replace the previous transcript
+    #[default]
+    Replace,
+    /// Append below the previous transcript
+    Append,
+    /// Move the previous transcript to a read-only tab and show the new one
+    NewTab,
+}
+
+/// Languages the window labels are available in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UiLanguage {
+    Ja,
+    En,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -59,8 +345,564 @@ pub struct ShortcutConfig {
     /// Key combination to copy transcript to clipboard
     pub copy_to_clipboard: String,
     
+    /// Key combination to transcribe the audio file whose path is on the clipboard
+    #[serde(default = "default_transcribe_clipboard_shortcut")]
+    pub transcribe_clipboard: String,
+    
+    /// Key combination to open the config file in the default editor
+    #[serde(default = "default_open_config_shortcut")]
+    pub open_config: String,
+    
+    /// Key combination to drop a marker into the recording, shown as a separator in the transcript
+    #[serde(default = "default_drop_marker_shortcut")]
+    pub drop_marker: String,
+    
     /// Automatically paste text after transcription
     pub auto_paste: bool,
+    
+    /// Recording shortcut behaviour: "hold" (push-to-talk) or "toggle"
+    #[serde(default)]
+    pub mode: ShortcutMode,
+    
+    /// Recording shortcut events this close together (in ms) are treated as key-repeat glitches
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    
+    /// In hold mode, releases sooner than this (in ms) discard the recording instead of transcribing it
+    #[serde(default = "default_min_hold_ms")]
+    pub min_hold_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TextProcessingConfig {
+    /// Format the transcript with GPT (dictionary is embedded in the prompt)
+    pub gpt_formatting: bool,
+    
+    /// Remove filler words locally after dictionary replacement
+    pub strip_filler_words: bool,
+    
+    /// Filler words removed by the local cleanup step
+    pub filler_words: Vec<String>,
+    
+    /// System message for the GPT formatter; the default is used when empty
+    pub system_prompt: String,
+    
+    /// What the recordings are about (e.g. "a technical meeting about Rust"), given to the
+    /// GPT formatter as context; empty for none
+    pub context: String,
+    
+    /// Prompt preset of the GPT formatter: "verbatim", "light_cleanup", "bullet_summary" or "formal_rewrite"
+    pub style: FormattingStyle,
+    
+    /// Let the GPT formatter add line breaks and paragraph separations
+    pub allow_linebreaks: bool,
+    
+    /// Let the GPT formatter turn the text into bullet points or lists
+    pub allow_bullets: bool,
+    
+    /// Fixed `max_tokens` for the GPT formatter; when unset it scales with the input length
+    pub max_tokens: Option<u32>,
+    
+    /// Longest transcript (in characters) sent to the GPT formatter in one request; 0 for no limit
+    pub max_input_chars: usize,
+    
+    /// What the GPT formatter does with transcripts longer than `max_input_chars`
+    pub long_input: LongInputMode,
+    
+    /// Format transcripts longer than `paragraph_chars` paragraph by paragraph, one request each
+    pub paragraph_split: bool,
+    
+    /// Longest paragraph-sized chunk (in characters) sent in one request with `paragraph_split`
+    pub paragraph_chars: usize,
+    
+    /// Fail the transcription when GPT formatting fails, instead of falling back to the
+    /// dictionary-replaced raw text
+    pub strict: bool,
+    
+    /// Time limit for one GPT formatting request in seconds; 0 for no limit
+    pub timeout_secs: u64,
+    
+    /// Extra attempts after a formatting request times out, hits a server error or is rate limited
+    pub retries: u32,
+    
+    /// Capitalize sentence starts, collapse repeated spaces and add missing terminal
+    /// punctuation; text containing CJK is left as is
+    pub normalize_punctuation: bool,
+    
+    /// Processing steps in order: "dictionary", "fillers", "gpt" and "normalize"
+    ///
+    /// "gpt" applies the dictionary locally instead when GPT formatting is off or
+    /// unavailable and the pipeline has no "dictionary" step. "normalize" runs last
+    /// when it isn't listed.
+    pub pipeline: Vec<String>,
+}
+
+impl Default for TextProcessingConfig {
+    fn default() -> Self {
+        Self {
+            gpt_formatting: true,
+            strip_filler_words: true,
+            filler_words: [
+                "えーと", "えーっと", "えっと", "えー", "あのー", "あの", "まぁ", "まあ", "うーん", "んー",
+                "um", "umm", "uh", "uhh", "er", "erm", "hmm",
+            ].iter().map(|s| s.to_string()).collect(),
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            context: String::new(),
+            style: FormattingStyle::default(),
+            allow_linebreaks: true,
+            allow_bullets: true,
+            max_tokens: None,
+            max_input_chars: 12000,
+            long_input: LongInputMode::default(),
+            paragraph_split: false,
+            paragraph_chars: 1500,
+            strict: false,
+            timeout_secs: 30,
+            retries: 1,
+            normalize_punctuation: false,
+            pipeline: vec!["gpt".to_string(), "fillers".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TranscriptionConfig {
+    /// Which backend transcribes recordings, or several tried in order until one succeeds
+    pub backend: BackendChain,
+    
+    /// Spoken language as an ISO-639-1 code (e.g. "ja"); auto-detected when unset
+    pub language: Option<String>,
+    
+    /// Response format requested from the transcription API
+    pub response_format: ResponseFormat,
+    
+    /// Settings for the `whisper_python` backend
+    pub whisper_python: WhisperPythonConfig,
+    
+    /// With `verbose_json`, warn when the duration-weighted mean `avg_logprob` of the
+    /// segments is below this
+    pub min_avg_logprob: f64,
+    
+    /// With `verbose_json`, warn when the mean `no_speech_prob` of the segments is above this
+    pub max_no_speech_prob: f64,
+}
+
+impl Default for TranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            backend: BackendChain::default(),
+            language: None,
+            response_format: ResponseFormat::default(),
+            whisper_python: WhisperPythonConfig::default(),
+            // Whisper 自身がデコードをやり直す基準と同じ値
+            min_avg_logprob: -1.0,
+            max_no_speech_prob: 0.6,
+        }
+    }
+}
+
+/// Available transcription backends
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptionBackend {
+    /// OpenAI transcription API
+    #[default]
+    Openai,
+    /// Locally installed `whisper` CLI from the openai-whisper Python package
+    WhisperPython,
+}
+
+/// `transcription.backend`: a single backend such as `"openai"`, or a list such as
+/// `["whisper_python", "openai"]` whose entries are tried in order
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum BackendChain {
+    Single(TranscriptionBackend),
+    Chain(Vec<TranscriptionBackend>),
+}
+
+impl Default for BackendChain {
+    fn default() -> Self {
+        Self::Single(TranscriptionBackend::default())
+    }
+}
+
+impl From<TranscriptionBackend> for BackendChain {
+    fn from(backend: TranscriptionBackend) -> Self {
+        Self::Single(backend)
+    }
+}
+
+impl BackendChain {
+    /// The backends in the order they are tried; an empty list means the default backend
+    pub fn backends(&self) -> &[TranscriptionBackend] {
+        match self {
+            Self::Single(backend) => std::slice::from_ref(backend),
+            Self::Chain(backends) if backends.is_empty() => &[TranscriptionBackend::Openai],
+            Self::Chain(backends) => backends,
+        }
+    }
+    
+    /// Whether every backend in the chain is `backend`
+    pub fn only(&self, backend: TranscriptionBackend) -> bool {
+        self.backends().iter().all(|entry| *entry == backend)
+    }
+}
+
+/// Prompt preset of the GPT formatter (`text_processing.style`)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FormattingStyle {
+    /// Fix punctuation and clear recognition errors only, keeping every word
+    Verbatim,
+    /// Remove the worst filler words and keep the speaker's tone
+    #[default]
+    LightCleanup,
+    /// Condense the transcript into a bullet-point summary
+    BulletSummary,
+    /// Rewrite the transcript in a formal, written style
+    FormalRewrite,
+}
+
+impl FormattingStyle {
+    pub const ALL: [FormattingStyle; 4] = [
+        FormattingStyle::Verbatim,
+        FormattingStyle::LightCleanup,
+        FormattingStyle::BulletSummary,
+        FormattingStyle::FormalRewrite,
+    ];
+    
+    /// Name in the config file, also the id in the style menu
+    pub fn id(self) -> &'static str {
+        match self {
+            FormattingStyle::Verbatim => "verbatim",
+            FormattingStyle::LightCleanup => "light_cleanup",
+            FormattingStyle::BulletSummary => "bullet_summary",
+            FormattingStyle::FormalRewrite => "formal_rewrite",
+        }
+    }
+    
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|style| style.id() == id)
+    }
+}
+
+/// Handling of transcripts over `text_processing.max_input_chars`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LongInputMode {
+    /// Format the transcript in several requests, split at sentence ends
+    #[default]
+    Chunk,
+    /// Format only the first `max_input_chars` characters and keep the rest as transcribed
+    Truncate,
+}
+
+/// Body format of the transcription API response
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// `{"text": "..."}`
+    #[default]
+    Json,
+    /// The transcript as a plain-text body, for servers that only implement this format
+    Text,
+    /// `whisper-1` with per-segment confidence, used to warn about low-quality transcripts
+    VerboseJson,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WhisperPythonConfig {
+    /// Command used to run Whisper
+    pub command: String,
+    
+    /// Whisper model name (tiny, base, small, medium, large, ...)
+    pub model: String,
+    
+    /// Extra command line arguments, e.g. ["--language", "ja"]
+    pub extra_args: Vec<String>,
+}
+
+impl Default for WhisperPythonConfig {
+    fn default() -> Self {
+        Self {
+            command: String::from("whisper"),
+            model: String::from("base"),
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    /// Copy every finished transcription to the clipboard automatically
+    pub auto_copy: bool,
+    
+    /// Read the clipboard back after copying and fall back to the clipboard file on mismatch
+    pub verify: bool,
+    
+    /// Strip markdown (bullets, emphasis, headings) from transcripts before copying
+    pub strip_markdown: bool,
+    
+    /// Type each finished transcription into the focused window with `xdotool`/`wtype`
+    pub type_out: bool,
+    
+    /// Delay between typed characters in ms (`type_out`)
+    pub type_delay_ms: u64,
+    
+    /// Extra attempts after a failed copy before falling back to the clipboard file
+    pub retries: u32,
+    
+    /// Wait in ms before the Ctrl+V of `shortcuts.auto_paste`, for applications slow to accept input after focus
+    pub paste_delay_ms: u64,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self { auto_copy: true, verify: false, strip_markdown: false, type_out: false, type_delay_ms: 12, retries: 2, paste_delay_ms: 100 }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Transcriptions allowed to run at once; further recordings wait in a queue
+    pub max_concurrent: usize,
+    
+    /// Look up the API key in the system keyring when `api_key` is empty
+    pub use_keyring: bool,
+    
+    /// Sent as the `OpenAI-Organization` header when set
+    pub organization: Option<String>,
+    
+    /// Sent as the `OpenAI-Project` header when set
+    pub project: Option<String>,
+    
+    /// Additional headers sent with every transcription and formatting request
+    pub extra_headers: BTreeMap<String, String>,
+    
+    /// Skip TLS certificate verification entirely (self-signed local servers only; insecure)
+    pub danger_accept_invalid_certs: bool,
+    
+    /// Extra root certificate (PEM or DER) trusted for the API server
+    pub ca_cert_path: Option<PathBuf>,
+    
+    /// File holding the API key (`~` is expanded); takes precedence over `api_key`
+    pub key_file: Option<PathBuf>,
+    
+    /// Path of the transcription endpoint below `api_base_url`, or a full URL
+    pub transcription_path: String,
+    
+    /// Path of the chat completions endpoint used for GPT formatting below `api_base_url`, or a full URL
+    pub formatting_path: String,
+    
+    /// Content type of the uploaded audio, e.g. "audio/ogg"; guessed from the file extension when unset
+    pub upload_mime: Option<String>,
+    
+    /// Send the sample rate and channel count of WAV uploads as `sample_rate` and `channels` form fields
+    pub send_audio_metadata: bool,
+    
+    /// Check that the API host is reachable before uploading, and fail at once when offline
+    pub offline_check: bool,
+    
+    /// Named accounts (`[api.profiles.<name>]`) that can be switched from the window
+    pub profiles: BTreeMap<String, ApiProfile>,
+    
+    /// Name of the profile in use; None for the account settings above
+    pub active_profile: Option<String>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 1,
+            use_keyring: false,
+            organization: None,
+            project: None,
+            extra_headers: BTreeMap::new(),
+            danger_accept_invalid_certs: false,
+            ca_cert_path: None,
+            key_file: None,
+            transcription_path: "/audio/transcriptions".to_string(),
+            formatting_path: "/chat/completions".to_string(),
+            upload_mime: None,
+            send_audio_metadata: false,
+            offline_check: true,
+            profiles: BTreeMap::new(),
+            active_profile: None,
+        }
+    }
+}
+
+/// One account of `api.profiles`; unset fields keep the top-level settings
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ApiProfile {
+    /// API key of this account; replaces `api_key` and `api.key_file` when set
+    pub api_key: String,
+    
+    /// Replaces `api.organization` when set
+    pub organization: Option<String>,
+    
+    /// Replaces `api_base_url` when set
+    pub base_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Append metadata of every transcription to `temp_dir/transcriptions.jsonl`
+    pub jsonl: bool,
+    
+    /// Append transcription and audio errors, with the config minus secrets, to this JSONL
+    /// file (`~` is expanded) for bug reports
+    pub error_report_file: Option<PathBuf>,
+}
+
+/// WAV files played for the `recording.play_sounds` cues (`~` is expanded)
+///
+/// Unset cues use the built-in ones: a beep on start and stop, two on errors and none when
+/// a transcription is done.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SoundsConfig {
+    pub start: Option<PathBuf>,
+    pub stop: Option<PathBuf>,
+    pub done: Option<PathBuf>,
+    pub error: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct IntegrationsConfig {
+    /// Append every finished transcript to this file (`~` is expanded); parent directories are created
+    pub output_file: Option<PathBuf>,
+    
+    /// Entry appended to `output_file`; `{timestamp}` and `{text}` are replaced
+    pub output_template: String,
+    
+    /// Serve `GET /healthz` with the current status and uptime on this 127.0.0.1 port
+    /// (requires the `health` feature)
+    pub health_port: Option<u16>,
+    
+    /// Start and stop recording over HTTP, e.g. from Stream Deck buttons
+    /// (requires the `http-control` feature)
+    pub http_control: bool,
+    
+    /// Address the `http_control` endpoints listen on; only this machine can reach the default
+    pub http_control_address: String,
+    
+    /// Port of the `http_control` endpoints
+    pub http_control_port: u16,
+    
+    /// Token every `http_control` request must send; the endpoints stay off without one
+    pub http_control_token: Option<String>,
+    
+    /// Transcribe audio files whose path is copied to the clipboard
+    pub watch_clipboard: bool,
+    
+    /// How often the clipboard is read for `watch_clipboard`, in milliseconds
+    pub watch_clipboard_interval_ms: u64,
+}
+
+/// Default entry appended to `integrations.output_file`
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "## {timestamp}\n\n{text}\n\n";
+
+impl Default for IntegrationsConfig {
+    fn default() -> Self {
+        Self {
+            output_file: None,
+            output_template: DEFAULT_OUTPUT_TEMPLATE.to_string(),
+            health_port: None,
+            http_control: false,
+            http_control_address: "127.0.0.1".to_string(),
+            http_control_port: 8788,
+            http_control_token: None,
+            watch_clipboard: false,
+            watch_clipboard_interval_ms: 1000,
+        }
+    }
+}
+
+/// How the recording shortcut starts and stops recording
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ShortcutMode {
+    /// Hold the key to record, release to stop
+    #[default]
+    Hold,
+    /// Press once to start, press again to stop
+    Toggle,
+}
+
+fn default_silence_threshold() -> f32 {
+    0.003
+}
+
+fn default_input_gain() -> f32 {
+    1.0
+}
+
+fn default_api_base_url() -> String {
+    "https://api.openai.com/v1".to_string()
+}
+
+/// Default system message for the GPT formatter
+pub const DEFAULT_SYSTEM_PROMPT: &str = "You are a transcription proofreader. Maintain the original language of the input text. Never translate. Output the corrected text directly without any meta-commentary.";
+
+/// Default recording file name, e.g. `recording_20240101_120000.wav`
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "recording_{timestamp}.wav";
+
+fn default_filename_template() -> String {
+    DEFAULT_FILENAME_TEMPLATE.to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_meter_scale() -> f32 {
+    5.0
+}
+
+fn default_meter_smoothing() -> f32 {
+    0.6
+}
+
+fn default_preview_chars() -> usize {
+    120
+}
+
+fn default_transcribe_clipboard_shortcut() -> String {
+    String::from("Alt+Shift+V")
+}
+
+fn default_open_config_shortcut() -> String {
+    String::from("Alt+Shift+O")
+}
+
+fn default_drop_marker_shortcut() -> String {
+    String::from("Alt+Shift+M")
+}
+
+fn default_flush_interval_ms() -> u64 {
+    1000
+}
+
+fn default_debounce_ms() -> u64 {
+    50
+}
+
+fn default_min_hold_ms() -> u64 {
+    150
+}
+
+fn default_min_duration_secs() -> f32 {
+    0.3
 }
 
 /// Get the config file path
@@ -74,8 +916,9 @@ pub fn get_config_path(custom_path: Option<String>) -> PathBuf {
         fs::create_dir_all(config_dir).ok();
         config_dir.join("config.toml")
     } else {
-        warn!("Could not determine config directory, using current directory");
-        PathBuf::from("config.toml")
+        let path = fallback_dir().join("config.toml");
+        warn!("Could not determine config directory (is HOME set?), using {}", path.display());
+        path
     }
 }
 
@@ -86,34 +929,579 @@ pub fn get_temp_dir() -> PathBuf {
         fs::create_dir_all(cache_dir).ok();
         cache_dir.to_path_buf()
     } else {
-        warn!("Could not determine cache directory, using system temp");
-        std::env::temp_dir().join("wispr")
+        let dir = fallback_dir();
+        warn!("Could not determine cache directory (is HOME set?), using {}", dir.display());
+        dir
+    }
+}
+
+/// Directory used when the home directory is unknown (e.g. no HOME in a container)
+///
+/// `$XDG_RUNTIME_DIR/wispr` when the variable is set, otherwise `wispr` under the system
+/// temp directory (usually `/tmp`). The directory is created if possible.
+pub fn fallback_dir() -> PathBuf {
+    let dir = fallback_dir_in(std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from));
+    fs::create_dir_all(&dir).ok();
+    dir
+}
+
+fn fallback_dir_in(runtime_dir: Option<PathBuf>) -> PathBuf {
+    runtime_dir
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(std::env::temp_dir)
+        .join("wispr")
+}
+
+/// Whether files can be created in `dir`, creating it if needed
+fn is_writable_dir(dir: &Path) -> bool {
+    if fs::create_dir_all(dir).is_err() {
+        return false;
+    }
+    let probe = dir.join(format!(".wispr_write_test_{}", std::process::id()));
+    match fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        },
+        Err(_) => false,
+    }
+}
+
+/// Switch `temp_dir` to `fallback_dir()` when the configured one can't be written
+///
+/// Returns a warning for the user when the fallback was taken. Only the config in memory is
+/// changed: the fallback is checked again on every load and never written to the config file.
+pub fn ensure_writable_temp_dir(config: &mut Config) -> Option<String> {
+    if is_writable_dir(&config.temp_dir) {
+        return None;
+    }
+    let fallback = fallback_dir();
+    warn!("temp_dir {} is not writable, using {} instead", config.temp_dir.display(), fallback.display());
+    if !is_writable_dir(&fallback) {
+        warn!("Fallback temp_dir {} is not writable either", fallback.display());
+    }
+    let message = crate::i18n::text().temp_dir_not_writable(
+        &config.temp_dir.display().to_string(),
+        &fallback.display().to_string(),
+    );
+    config.temp_dir = fallback;
+    Some(message)
+}
+
+/// Why a shortcut string can't be matched; None when it is valid or empty (disabled)
+fn shortcut_problem(shortcut: &str) -> Option<String> {
+    if shortcut.is_empty() {
+        return None;
+    }
+    crate::shortcut::parse_shortcut(shortcut).err().map(|e| e.to_string())
+}
+
+/// Disable malformed shortcuts with a warning instead of registering handlers that never match
+///
+/// Empty (or blank) shortcuts are the documented way to turn a shortcut off.
+fn drop_invalid_shortcuts(shortcuts: &mut ShortcutConfig) {
+    let entries = [
+        ("toggle_recording", &mut shortcuts.toggle_recording),
+        ("clear_transcript", &mut shortcuts.clear_transcript),
+        ("copy_to_clipboard", &mut shortcuts.copy_to_clipboard),
+        ("transcribe_clipboard", &mut shortcuts.transcribe_clipboard),
+        ("open_config", &mut shortcuts.open_config),
+        ("drop_marker", &mut shortcuts.drop_marker),
+    ];
+    for (name, shortcut) in entries {
+        if shortcut.trim().is_empty() {
+            shortcut.clear();
+        } else if let Some(problem) = shortcut_problem(shortcut) {
+            warn!("Disabling shortcuts.{} = {:?}: {}", name, shortcut, problem);
+            shortcut.clear();
+        }
+    }
+}
+
+/// Current config file format version
+pub const CURRENT_CONFIG_VERSION: u32 = 1;
+
+/// Environment variable read when no API key is configured
+const API_KEY_ENV_VAR: &str = "OPENAI_API_KEY";
+/// Secret Service attributes of the keyring entry holding the API key
+const KEYRING_SERVICE: &str = "wispr_linux_rs";
+const KEYRING_USERNAME: &str = "api_key";
+
+/// System-wide config read before the user's file, for settings shared by every user
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/wispr/config.toml";
+
+/// `--set` settings from the command line, applied over the config files (also on reload)
+static CLI_OVERRIDES: OnceLock<toml::Table> = OnceLock::new();
+
+/// Parse the `--set KEY=VALUE` arguments into the last layer of `load_config`
+pub fn set_cli_overrides(settings: &[String]) -> Result<()> {
+    let overrides = parse_overrides(settings)?;
+    let _ = CLI_OVERRIDES.set(overrides);
+    Ok(())
+}
+
+/// `KEY=VALUE` settings as a config table
+///
+/// KEY is a dotted name such as `recording.max_duration_secs`. VALUE is read as a TOML value,
+/// or as a string when it isn't one, so `--set api.model=whisper-1` needs no quotes.
+fn parse_overrides(settings: &[String]) -> Result<toml::Table> {
+    let mut overrides = toml::Table::new();
+    for setting in settings {
+        let (key, value) = setting.split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("--set expects KEY=VALUE, got `{}`", setting))?;
+        let value = value.trim();
+        let value = toml::from_str::<toml::Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.to_string()));
+        
+        let mut path: Vec<&str> = key.trim().split('.').map(str::trim).collect();
+        if path.iter().any(|part| part.is_empty()) {
+            return Err(anyhow::anyhow!("--set: invalid setting name `{}`", key));
+        }
+        let name = path.pop().unwrap_or_default();
+        let mut table = &mut overrides;
+        for part in path {
+            table = table.entry(part)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| anyhow::anyhow!("--set {}: `{}` is not a table", key, part))?;
+        }
+        table.insert(name.to_string(), value);
     }
+    Ok(overrides)
 }
 
 /// Load configuration from file
+///
+/// The system config (`SYSTEM_CONFIG_PATH`), the user's config file and the `--set` settings
+/// are merged in that order, later layers winning key by key. Missing layers are skipped.
 pub fn load_config(custom_path: Option<String>) -> Result<Config> {
-    let config_path = get_config_path(custom_path);
-    
-    if config_path.exists() {
-        info!("Loading config from: {}", config_path.display());
-        let config_str = fs::read_to_string(&config_path)
-            .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
-            
-        let config: Config = toml::from_str(&config_str)
-            .with_context(|| "Failed to parse config file")?;
-            
-        Ok(config)
-    } else {
+    let overrides = CLI_OVERRIDES.get().cloned().unwrap_or_default();
+    load_config_layers(Path::new(SYSTEM_CONFIG_PATH), &get_config_path(custom_path), overrides)
+}
+
+fn load_config_layers(system_path: &Path, config_path: &Path, overrides: toml::Table) -> Result<Config> {
+    let mut system = read_config_table(system_path)?;
+    if let Some(system) = &mut system {
+        info!("Loading system config from: {}", system_path.display());
+        rename_legacy_keys(system);
+    }
+    let user = load_user_layer(config_path, system.is_some())?;
+    
+    let mut table = system.unwrap_or_default();
+    merge_tables(&mut table, user);
+    merge_tables(&mut table, overrides);
+    // どの層にもない項目は既定値で埋める
+    let (mut table, _, _) = migrate_config(table)?;
+    read_legacy_output_device(&mut table);
+    let mut config: Config = toml::Value::Table(table).try_into()
+        .with_context(|| "Failed to parse config file")?;
+    apply_api_profile(&mut config);
+    trim_api_key(&mut config);
+    drop_invalid_extra_headers(&mut config.api);
+    drop_invalid_upload_mime(&mut config.api);
+    drop_invalid_shortcuts(&mut config.shortcuts);
+    
+    // 保存後に解決する（キーリングや環境変数のキーをファイルに書き込まないため）
+    resolve_api_key(&mut config);
+    Ok(config)
+}
+
+/// Read the user's config file, migrating it in place, or create it when it doesn't exist
+///
+/// With a system config (`layered`), settings missing from the user's file are not written
+/// into it, since the written defaults would hide the system-wide values.
+fn load_user_layer(config_path: &Path, layered: bool) -> Result<toml::Table> {
+    let Some(mut table) = read_config_table(config_path)? else {
         info!("Config file not found, creating default at: {}", config_path.display());
+        if layered {
+            let mut table = toml::Table::new();
+            table.insert("config_version".to_string(), toml::Value::Integer(CURRENT_CONFIG_VERSION as i64));
+            write_config_table(&table, config_path)?;
+            return Ok(table);
+        }
         let config = default_config();
-        save_config(&config, &config_path)?;
-        Ok(config)
+        save_config(&config, config_path)?;
+        return config_table(&config);
+    };
+    info!("Loading config from: {}", config_path.display());
+    
+    let version = table_config_version(&table);
+    if layered {
+        rename_legacy_keys(&mut table);
+        if version < CURRENT_CONFIG_VERSION {
+            info!("Config migration: version {} -> {}", version, CURRENT_CONFIG_VERSION);
+            table.insert("config_version".to_string(), toml::Value::Integer(CURRENT_CONFIG_VERSION as i64));
+            back_up_before_migration(config_path, version);
+            write_config_table(&table, config_path)?;
+            info!("Config file updated: {}", config_path.display());
+        }
+        return Ok(table);
+    }
+    
+    let (mut table, version, added) = migrate_config(table)?;
+    if version < CURRENT_CONFIG_VERSION || !added.is_empty() {
+        for key in &added {
+            info!("Config migration: added `{}` with default value", key);
+        }
+        if version < CURRENT_CONFIG_VERSION {
+            info!("Config migration: version {} -> {}", version, CURRENT_CONFIG_VERSION);
+        }
+        table.insert("config_version".to_string(), toml::Value::Integer(CURRENT_CONFIG_VERSION as i64));
+        // 壊れた設定は書き込まずにエラーにする
+        let _: Config = toml::Value::Table(table.clone()).try_into()
+            .with_context(|| "Failed to parse config file")?;
+        
+        // 読み込み時の修正（APIキーの空白や無効なショートカット）はファイルに書き込まない
+        back_up_before_migration(config_path, version);
+        write_config_table(&table, config_path)?;
+        info!("Config file updated: {}", config_path.display());
+    }
+    Ok(table)
+}
+
+/// Parse one config file as a table; None when the file doesn't exist
+fn read_config_table(path: &Path) -> Result<Option<toml::Table>> {
+    let config_str = match fs::read_to_string(path) {
+        Ok(config_str) => config_str,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read config file: {}", path.display())),
+    };
+    let table = toml::from_str(&config_str)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    Ok(Some(table))
+}
+
+fn write_config_table(table: &toml::Table, path: &Path) -> Result<()> {
+    let config_str = toml::to_string(table)
+        .with_context(|| "Failed to serialize configuration")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create config directory: {}", parent.display()))?;
+    }
+    fs::write(path, config_str)
+        .with_context(|| format!("Failed to write config to: {}", path.display()))
+}
+
+fn config_table(config: &Config) -> Result<toml::Table> {
+    match toml::Value::try_from(config).with_context(|| "Failed to serialize configuration")? {
+        toml::Value::Table(table) => Ok(table),
+        _ => Err(anyhow::anyhow!("Configuration is not a table")),
+    }
+}
+
+/// Keep the file as it was before a migration rewrites it (comments are not regenerated)
+fn back_up_before_migration(config_path: &Path, version: u32) {
+    let backup_path = config_path.with_extension(format!("toml.v{}.bak", version));
+    match fs::copy(config_path, &backup_path) {
+        Ok(_) => info!("Config backup saved to: {}", backup_path.display()),
+        Err(e) => warn!("Failed to back up config before migration: {}", e),
+    }
+}
+
+/// Merge `layer` into `base`: tables are merged key by key, any other value replaces the old one
+fn merge_tables(base: &mut toml::Table, layer: toml::Table) {
+    for (key, value) in layer {
+        match value {
+            toml::Value::Table(layer_table) if base.get(&key).is_some_and(toml::Value::is_table) => {
+                if let Some(toml::Value::Table(base_table)) = base.get_mut(&key) {
+                    merge_tables(base_table, layer_table);
+                }
+            },
+            value => {
+                base.insert(key, value);
+            },
+        }
+    }
+}
+
+/// URL of an API endpoint: `path` joined to `base_url`, or `path` itself when it is a full URL
+pub fn endpoint_url(base_url: &str, path: &str) -> String {
+    let path = path.trim();
+    if path.starts_with("http://") || path.starts_with("https://") {
+        return path.to_string();
+    }
+    format!("{}/{}", base_url.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+/// Expand a leading `~/` to the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Read the API key from `api.key_file`, trimmed; None (with a warning) when it can't be used
+fn read_api_key_file(path: &Path) -> Option<String> {
+    let path = expand_home(path);
+    match fs::read_to_string(&path) {
+        Ok(key) if !key.trim().is_empty() => Some(key.trim().to_string()),
+        Ok(_) => {
+            warn!("api.key_file {} is empty", path.display());
+            None
+        },
+        Err(e) => {
+            warn!("Failed to read api.key_file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Apply the `api.active_profile` account over the top-level account settings
+///
+/// An unknown profile name is ignored with a warning, so the default account is used.
+fn apply_api_profile(config: &mut Config) {
+    let Some(name) = config.api.active_profile.clone().filter(|name| !name.trim().is_empty()) else {
+        config.api.active_profile = None;
+        return;
+    };
+    let Some(profile) = config.api.profiles.get(&name).cloned() else {
+        warn!("api.active_profile {:?} is not in [api.profiles], using the default account", name);
+        config.api.active_profile = None;
+        return;
+    };
+    info!("Using API profile {:?}", name);
+    if !profile.api_key.trim().is_empty() {
+        config.api_key = profile.api_key;
+        // プロファイルのキーを key_file より優先する
+        config.api.key_file = None;
+    }
+    if profile.organization.is_some() {
+        config.api.organization = profile.organization;
+    }
+    if let Some(base_url) = profile.base_url {
+        config.api_base_url = base_url;
+    }
+}
+
+/// Fill in `api_key` from the configured sources
+///
+/// Precedence: `api.key_file`, the `api_key` field, then the system keyring (only with
+/// `api.use_keyring`), then the `OPENAI_API_KEY` environment variable.
+fn resolve_api_key(config: &mut Config) {
+    resolve_api_key_from(config, read_keyring_api_key, std::env::var(API_KEY_ENV_VAR).ok());
+}
+
+/// `resolve_api_key` with the keyring lookup and the environment variable's value passed in
+fn resolve_api_key_from(
+    config: &mut Config,
+    read_keyring: impl FnOnce() -> Result<Option<String>>,
+    env_key: Option<String>,
+) {
+    if let Some(key_file) = &config.api.key_file {
+        if let Some(key) = read_api_key_file(key_file) {
+            if !config.api_key.trim().is_empty() {
+                info!("api.key_file is set, ignoring the api_key in the config file");
+            }
+            info!("Using API key from {}", expand_home(key_file).display());
+            config.api_key = key;
+            return;
+        }
+    }
+    
+    if !config.api_key.trim().is_empty() {
+        info!("Using API key from the config file");
+        return;
+    }
+    
+    if config.api.use_keyring {
+        match read_keyring() {
+            Ok(Some(key)) => {
+                info!("Using API key from the system keyring");
+                config.api_key = key;
+                return;
+            },
+            Ok(None) => info!("No API key found in the system keyring"),
+            Err(e) => warn!("Failed to read API key from the system keyring: {}", e),
+        }
+    }
+    
+    match env_key {
+        Some(key) if !key.trim().is_empty() => {
+            info!("Using API key from {}", API_KEY_ENV_VAR);
+            config.api_key = key.trim().to_string();
+        },
+        _ => warn!("No API key configured (config file, keyring or {})", API_KEY_ENV_VAR),
+    }
+}
+
+/// Whether transcription needs an API key that none of the sources provided
+///
+/// A chain with a local backend still works without one, so only an OpenAI-only chain counts.
+pub fn missing_api_key(config: &Config) -> bool {
+    config.transcription.backend.only(TranscriptionBackend::Openai) && config.api_key.trim().is_empty()
+}
+
+/// Strip whitespace pasted along with the API key, which would otherwise break the Authorization header
+fn trim_api_key(config: &mut Config) {
+    let trimmed = config.api_key.trim();
+    if trimmed.len() != config.api_key.len() {
+        warn!("api_key in the config file has surrounding whitespace or newlines; ignoring them");
+        config.api_key = trimmed.to_string();
+    }
+}
+
+/// Remove `api.extra_headers` entries whose name or value can't be sent as an HTTP header
+fn drop_invalid_extra_headers(api: &mut ApiConfig) {
+    api.extra_headers.retain(|name, value| {
+        let valid = reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_ok()
+            && reqwest::header::HeaderValue::from_str(value).is_ok();
+        if !valid {
+            warn!("Ignoring invalid header in api.extra_headers: {:?}", name);
+        }
+        valid
+    });
+}
+
+/// Forget an `api.upload_mime` that isn't a MIME type, so the type is guessed from the file extension
+fn drop_invalid_upload_mime(api: &mut ApiConfig) {
+    let Some(mime) = api.upload_mime.as_deref().map(str::trim).filter(|mime| !mime.is_empty()) else {
+        return;
+    };
+    if let Err(e) = mime.parse::<mime::Mime>() {
+        warn!("Ignoring invalid api.upload_mime {:?}: {}", mime, e);
+        api.upload_mime = None;
+    }
+}
+
+/// Look up the API key in the Secret Service keyring with `secret-tool`
+///
+/// Store it with `secret-tool store --label="Wispr API key" service wispr_linux_rs username api_key`.
+fn read_keyring_api_key() -> Result<Option<String>> {
+    let output = std::process::Command::new("secret-tool")
+        .args(["lookup", "service", KEYRING_SERVICE, "username", KEYRING_USERNAME])
+        .output()
+        .context("Failed to run secret-tool")?;
+    
+    // 該当するエントリがない場合は何も出力せずに失敗する
+    let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if key.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(key))
+    }
+}
+
+/// Apply versioned migrations and fill missing fields with defaults
+///
+/// Returns the migrated table, the version found in the file, and the dotted
+/// paths of the keys that were added.
+fn migrate_config(mut table: toml::Table) -> Result<(toml::Table, u32, Vec<String>)> {
+    let version = table_config_version(&table);
+    rename_legacy_keys(&mut table);
+    
+    let defaults = match toml::Value::try_from(default_config())
+        .with_context(|| "Failed to serialize default configuration")? {
+        toml::Value::Table(defaults) => defaults,
+        _ => return Err(anyhow::anyhow!("Default configuration is not a table")),
+    };
+    
+    let mut added = Vec::new();
+    fill_missing_defaults(&mut table, &defaults, "", &mut added);
+    added.retain(|key| key != "config_version");
+    
+    Ok((table, version, added))
+}
+
+/// `config_version` of a config table; 0 when missing
+fn table_config_version(table: &toml::Table) -> u32 {
+    table.get("config_version")
+        .and_then(|v| v.as_integer())
+        .unwrap_or(0) as u32
+}
+
+/// Move settings renamed since the table's `config_version` to their current names
+fn rename_legacy_keys(table: &mut toml::Table) {
+    let version = table_config_version(table);
+    
+    // v0 -> v1: ui.show_notifications was renamed to ui.notification_enabled
+    if version < 1 {
+        if let Some(toml::Value::Table(ui)) = table.get_mut("ui") {
+            if let Some(value) = ui.remove("show_notifications") {
+                if !ui.contains_key("notification_enabled") {
+                    info!("Config migration: renamed `ui.show_notifications` to `ui.notification_enabled`");
+                    ui.insert("notification_enabled".to_string(), value);
+                }
+            }
+        }
+    }
+}
+
+/// Read `recording.output_device` as `audio.output_device` unless that is set too
+///
+/// The older key is only read, not moved in the file, so configs shared with earlier versions keep working.
+fn read_legacy_output_device(table: &mut toml::Table) {
+    let Some(toml::Value::Table(recording)) = table.get_mut("recording") else {
+        return;
+    };
+    let Some(sink) = recording.remove("output_device") else {
+        return;
+    };
+    let audio = table.entry("audio").or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let toml::Value::Table(audio) = audio {
+        if audio.contains_key("output_device") {
+            warn!("Both recording.output_device and audio.output_device are set; using audio.output_device");
+        } else {
+            info!("Reading recording.output_device as audio.output_device");
+            audio.insert("output_device".to_string(), sink);
+        }
+    }
+}
+
+/// Recursively copy keys that are missing from `table` out of `defaults`
+fn fill_missing_defaults(table: &mut toml::Table, defaults: &toml::Table, prefix: &str, added: &mut Vec<String>) {
+    for (key, default_value) in defaults {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match (table.get_mut(key), default_value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(default_table)) => {
+                fill_missing_defaults(existing, default_table, &path, added);
+            },
+            (Some(_), _) => {},
+            (None, _) => {
+                table.insert(key.clone(), default_value.clone());
+                added.push(path);
+            }
+        }
+    }
+}
+
+/// Dotted names of the settings that differ between two configs (e.g. `shortcuts.mode`)
+///
+/// Only the names are returned so that secrets such as `api_key` never end up in the log.
+pub fn changed_settings(old: &Config, new: &Config) -> Vec<String> {
+    let (Ok(old), Ok(new)) = (toml::Value::try_from(old), toml::Value::try_from(new)) else {
+        return Vec::new();
+    };
+    let mut changed = Vec::new();
+    collect_changed_settings("", &old, &new, &mut changed);
+    changed
+}
+
+fn collect_changed_settings(prefix: &str, old: &toml::Value, new: &toml::Value, changed: &mut Vec<String>) {
+    match (old, new) {
+        (toml::Value::Table(old), toml::Value::Table(new)) => {
+            let keys: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let name = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => collect_changed_settings(&name, old, new, changed),
+                    _ => changed.push(name),
+                }
+            }
+        },
+        _ if old != new => changed.push(prefix.to_string()),
+        _ => {},
     }
 }
 
 /// Save configuration to file
-pub fn save_config(config: &Config, path: &Path) -> Result<()> {
+///
+/// Only used to create the default config file. A loaded config holds values resolved at
+/// runtime (the API key, a `temp_dir` fallback), so it is never written back whole; the
+/// window saves single keys with `save_setting` instead.
+fn save_config(config: &Config, path: &Path) -> Result<()> {
     let config_str = toml::to_string(config)
         .with_context(|| "Failed to serialize configuration")?;
         
@@ -128,26 +1516,402 @@ pub fn save_config(config: &Config, path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Write `recording.input_gain` to the config file, leaving the other settings as they are
+///
+/// Only the one key is changed, so values resolved at load time (like an API key from the
+/// environment) are not written to the file.
+pub fn save_input_gain(path: &Path, gain: f32) -> Result<()> {
+    save_setting(path, "recording", "input_gain", Some(toml::Value::Float(gain as f64)))
+}
+
+/// Write `recording.max_duration_secs` to the config file, like `save_input_gain`
+pub fn save_max_duration(path: &Path, secs: u64) -> Result<()> {
+    save_setting(path, "recording", "max_duration_secs", Some(toml::Value::Integer(secs.min(i64::MAX as u64) as i64)))
+}
+
+/// Write `api.active_profile` to the config file, like `save_input_gain`; None removes it
+pub fn save_active_profile(path: &Path, profile: Option<&str>) -> Result<()> {
+    save_setting(path, "api", "active_profile", profile.map(|name| toml::Value::String(name.to_string())))
+}
+
+/// Set (or with None, remove) one key of a table such as `[recording]` in the config file
+fn save_setting(path: &Path, section: &str, key: &str, value: Option<toml::Value>) -> Result<()> {
+    let config_str = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let mut table: toml::Table = toml::from_str(&config_str)
+        .with_context(|| "Failed to parse config file")?;
+    
+    let section_table = table.entry(section)
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let section_table = section_table.as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("`{}` in the config file is not a table", section))?;
+    match value {
+        Some(value) => section_table.insert(key.to_string(), value),
+        None => section_table.remove(key),
+    };
+    
+    let config_str = toml::to_string(&table)
+        .with_context(|| "Failed to serialize configuration")?;
+    fs::write(path, config_str)
+        .with_context(|| format!("Failed to write config to: {}", path.display()))?;
+    Ok(())
+}
+
 /// Create default configuration
 pub fn default_config() -> Config {
     Config {
+        config_version: CURRENT_CONFIG_VERSION,
         api_key: String::new(),
+        api_base_url: default_api_base_url(),
         temp_dir: get_temp_dir(),
         recording: RecordingConfig {
             max_duration_secs: 1800,
+            max_file_bytes: 0,
             sample_rate: 44100,
             play_sounds: true,
             disable_silence_detection: false,
+            silence_threshold: default_silence_threshold(),
+            vad_enabled: false,
+            trim_silence: false,
+            warn_on_bad_audio: true,
+            skip_bad_audio: false,
+            filename_template: DEFAULT_FILENAME_TEMPLATE.to_string(),
+            biasing_terms: Vec::new(),
+            save_recordings: false,
+            archive_dir: None,
+            in_memory: false,
+            input_gain: default_input_gain(),
+            preferred_format: SampleFormatPreference::Auto,
+            countdown_secs: 0,
+            preroll_secs: 0.0,
+            flush_interval_ms: default_flush_interval_ms(),
+            buffer_frames: 0,
+            min_duration_secs: default_min_duration_secs(),
         },
         ui: UiConfig {
             dark_mode: true,
             notification_enabled: true,
+            preview_chars: default_preview_chars(),
+            monitor_when_idle: true,
+            resume_monitoring_after_record: false,
+            idle_timeout_secs: 0,
+            monitor_index: None,
+            start_hidden: false,
+            autoscroll: true,
+            copy_on_select: false,
+            language: None,
+            meter_scale: default_meter_scale(),
+            meter_db: false,
+            meter_smoothing: default_meter_smoothing(),
+            show_waveform: false,
+            layout: WindowLayout::Full,
+            on_new_transcript: NewTranscriptMode::Replace,
+            max_transcript_chars: 0,
+            always_on_top: false,
+            font_family: None,
+            font_size: None,
+            tags: Vec::new(),
+            recording_overlay: false,
+            overlay_corner: OverlayCorner::TopRight,
+            inline_timestamps: false,
         },
         shortcuts: ShortcutConfig {
             toggle_recording: String::from("Shift+space"),
             clear_transcript: String::from("Alt+Shift+C"),
             copy_to_clipboard: String::from("Alt+Shift+X"),
-            auto_paste: true,
+            transcribe_clipboard: default_transcribe_clipboard_shortcut(),
+            open_config: default_open_config_shortcut(),
+            drop_marker: default_drop_marker_shortcut(),
+            auto_paste: false,
+            mode: ShortcutMode::Hold,
+            debounce_ms: default_debounce_ms(),
+            min_hold_ms: default_min_hold_ms(),
         },
+        text_processing: TextProcessingConfig::default(),
+        transcription: TranscriptionConfig::default(),
+        clipboard: ClipboardConfig::default(),
+        api: ApiConfig::default(),
+        logging: LoggingConfig::default(),
+        sounds: SoundsConfig::default(),
+        integrations: IntegrationsConfig::default(),
+        audio: AudioConfig::default(),
+    }
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn formatting_style_ids_match_the_config_names() {
+        for style in FormattingStyle::ALL {
+            assert_eq!(serde_json::to_value(style).unwrap(), style.id());
+            assert_eq!(FormattingStyle::from_id(style.id()), Some(style));
+        }
+        assert_eq!(FormattingStyle::from_id("casual"), None);
+    }
+    
+    #[test]
+    fn api_key_whitespace_is_trimmed_on_load() {
+        let dir = std::env::temp_dir().join(format!("wispr_config_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(
+            &path,
+            format!("api_key = \"  sk-test\\n\"\ntemp_dir = \"{}\"\n[shortcuts]\ncopy_to_clipboard = \"Super+C\"\n", dir.display()),
+        ).unwrap();
+        
+        // /etc/wispr/config.toml があっても読まない
+        let config = load_config_layers(&dir.join("system.toml"), &path, toml::Table::new()).unwrap();
+        assert_eq!(config.api_key, "sk-test");
+        assert_eq!(config.shortcuts.copy_to_clipboard, "");
+        // 移行で書き足すのは版と足りない項目だけで、読み込み時の修正は書き込まない
+        let saved: toml::Table = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["config_version"].as_integer(), Some(CURRENT_CONFIG_VERSION as i64));
+        assert_eq!(saved["api_key"].as_str(), Some("  sk-test\n"));
+        assert_eq!(saved["shortcuts"]["copy_to_clipboard"].as_str(), Some("Super+C"));
+        assert!(saved["recording"].get("sample_rate").is_some());
+        
+        let _ = fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn config_layers_are_merged_key_by_key() {
+        let dir = std::env::temp_dir().join(format!("wispr_config_layers_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let system_path = dir.join("system.toml");
+        let user_path = dir.join("config.toml");
+        fs::write(&system_path, "api_base_url = \"https://proxy.example/v1\"\n[recording]\nmax_duration_secs = 120\nsample_rate = 16000\n").unwrap();
+        fs::write(&user_path, "config_version = 1\napi_key = \"sk-user\"\n[recording]\nmax_duration_secs = 300\n").unwrap();
+        let overrides = parse_overrides(&["recording.sample_rate=48000".to_string(), "ui.dark_mode=false".to_string()]).unwrap();
+        
+        let config = load_config_layers(&system_path, &user_path, overrides).unwrap();
+        assert_eq!(config.api_base_url, "https://proxy.example/v1");
+        assert_eq!(config.api_key, "sk-user");
+        assert_eq!(config.recording.max_duration_secs, 300);
+        assert_eq!(config.recording.sample_rate, 48000);
+        assert!(!config.ui.dark_mode);
+        // システム設定があるときは、ユーザーの設定ファイルに既定値を書き足さない
+        assert!(!fs::read_to_string(&user_path).unwrap().contains("sample_rate"));
+        
+        // システム設定がなくてもユーザー設定だけで読める
+        let config = load_config_layers(&dir.join("missing.toml"), &user_path, toml::Table::new()).unwrap();
+        assert_eq!(config.api_base_url, default_api_base_url());
+        assert_eq!(config.recording.max_duration_secs, 300);
+        
+        assert!(parse_overrides(&["recording.".to_string()]).is_err());
+        assert!(parse_overrides(&["no_value".to_string()]).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn the_active_api_profile_replaces_the_account_settings() {
+        let mut config = default_config();
+        config.api_key = "sk-personal".to_string();
+        config.api.key_file = Some(PathBuf::from("~/.openai_key"));
+        config.api.profiles.insert("work".to_string(), ApiProfile {
+            api_key: "sk-work".to_string(),
+            organization: Some("org-work".to_string()),
+            base_url: None,
+        });
+        
+        config.api.active_profile = Some("work".to_string());
+        apply_api_profile(&mut config);
+        assert_eq!(config.api_key, "sk-work");
+        assert_eq!(config.api.key_file, None);
+        assert_eq!(config.api.organization.as_deref(), Some("org-work"));
+        assert_eq!(config.api_base_url, default_api_base_url());
+        
+        // 存在しないプロファイルは無視して既定のアカウントを使う
+        let mut config = default_config();
+        config.api_key = "sk-personal".to_string();
+        config.api.active_profile = Some("missing".to_string());
+        apply_api_profile(&mut config);
+        assert_eq!(config.api_key, "sk-personal");
+        assert_eq!(config.api.active_profile, None);
     }
-} 
\ No newline at end of file
+    
+    #[test]
+    fn key_file_takes_precedence_over_inline_api_key() {
+        let dir = std::env::temp_dir().join(format!("wispr_key_file_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let key_file = dir.join("api_key");
+        fs::write(&key_file, "sk-from-file\n").unwrap();
+        
+        let mut config = default_config();
+        config.api_key = "sk-inline".to_string();
+        config.api.key_file = Some(key_file);
+        // キーリングや OPENAI_API_KEY は読まない
+        let no_keyring = || -> Result<Option<String>> { Ok(None) };
+        resolve_api_key_from(&mut config, no_keyring, None);
+        assert_eq!(config.api_key, "sk-from-file");
+        
+        // 読めない場合は設定ファイルのキーを使う
+        config.api_key = "sk-inline".to_string();
+        config.api.key_file = Some(dir.join("missing"));
+        resolve_api_key_from(&mut config, no_keyring, None);
+        assert_eq!(config.api_key, "sk-inline");
+        
+        let _ = fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn keyring_comes_after_the_config_and_before_the_environment() {
+        let unused = || -> Result<Option<String>> { panic!("keyring should not be read") };
+        let env = || Some("sk-env".to_string());
+        let mut config = default_config();
+        config.api.use_keyring = true;
+        
+        resolve_api_key_from(&mut config, || Ok(Some("sk-keyring".to_string())), env());
+        assert_eq!(config.api_key, "sk-keyring");
+        
+        // 設定ファイルのキーがあればキーリングは見ない
+        config.api_key = "sk-inline".to_string();
+        resolve_api_key_from(&mut config, unused, env());
+        assert_eq!(config.api_key, "sk-inline");
+        
+        // 見つからない・読めない場合や無効の場合は環境変数を使う
+        config.api_key.clear();
+        resolve_api_key_from(&mut config, || Ok(None), env());
+        assert_eq!(config.api_key, "sk-env");
+        config.api_key.clear();
+        resolve_api_key_from(&mut config, || Err(anyhow::anyhow!("secret-tool not found")), env());
+        assert_eq!(config.api_key, "sk-env");
+        config.api_key.clear();
+        config.api.use_keyring = false;
+        resolve_api_key_from(&mut config, unused, env());
+        assert_eq!(config.api_key, "sk-env");
+        
+        config.api_key.clear();
+        resolve_api_key_from(&mut config, unused, None);
+        assert_eq!(config.api_key, "");
+    }
+    
+    #[test]
+    fn missing_api_key_only_matters_for_the_openai_backend() {
+        let mut config = default_config();
+        config.api_key = "  ".to_string();
+        assert!(missing_api_key(&config));
+        config.transcription.backend = TranscriptionBackend::WhisperPython.into();
+        assert!(!missing_api_key(&config));
+        config.transcription.backend = BackendChain::Chain(vec![TranscriptionBackend::WhisperPython, TranscriptionBackend::Openai]);
+        assert!(!missing_api_key(&config));
+        config.transcription.backend = TranscriptionBackend::Openai.into();
+        config.api_key = "sk-test".to_string();
+        assert!(!missing_api_key(&config));
+    }
+    
+    #[test]
+    fn fallback_dir_prefers_the_runtime_dir() {
+        assert_eq!(fallback_dir_in(Some(PathBuf::from("/run/user/1000"))), PathBuf::from("/run/user/1000/wispr"));
+        assert_eq!(fallback_dir_in(Some(PathBuf::new())), std::env::temp_dir().join("wispr"));
+        assert_eq!(fallback_dir_in(None), std::env::temp_dir().join("wispr"));
+    }
+    
+    #[test]
+    fn endpoint_paths_join_the_base_url_unless_absolute() {
+        assert_eq!(endpoint_url("https://api.openai.com/v1", "/audio/transcriptions"), "https://api.openai.com/v1/audio/transcriptions");
+        assert_eq!(endpoint_url("http://localhost:8000/", "inference"), "http://localhost:8000/inference");
+        assert_eq!(endpoint_url("https://api.openai.com/v1", " http://127.0.0.1:9000/asr "), "http://127.0.0.1:9000/asr");
+    }
+    
+    #[test]
+    fn malformed_shortcuts_are_disabled() {
+        let mut shortcuts = default_config().shortcuts;
+        shortcuts.toggle_recording = "Shift+space".to_string();
+        shortcuts.clear_transcript = " ".to_string();
+        shortcuts.copy_to_clipboard = "Super+C".to_string();
+        shortcuts.transcribe_clipboard = "Alt+1".to_string();
+        shortcuts.open_config = "Alt+Shift+O".to_string();
+        drop_invalid_shortcuts(&mut shortcuts);
+        
+        assert_eq!(shortcuts.toggle_recording, "Shift+space");
+        assert_eq!(shortcuts.clear_transcript, "");
+        assert_eq!(shortcuts.copy_to_clipboard, "");
+        assert_eq!(shortcuts.transcribe_clipboard, "");
+        assert_eq!(shortcuts.open_config, "Alt+Shift+O");
+    }
+    
+    #[test]
+    fn changed_settings_lists_dotted_names() {
+        let old = default_config();
+        let mut new = old.clone();
+        assert!(changed_settings(&old, &new).is_empty());
+        
+        new.shortcuts.toggle_recording = "Alt+R".to_string();
+        new.api_key = "sk-secret".to_string();
+        new.api.organization = Some("org-test".to_string());
+        assert_eq!(
+            changed_settings(&old, &new),
+            vec!["api.organization", "api_key", "shortcuts.toggle_recording"]
+        );
+    }
+    
+    #[test]
+    fn unwritable_temp_dir_falls_back_to_the_fallback_dir() {
+        let dir = std::env::temp_dir().join(format!("wispr_temp_dir_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // 通常のファイルの下にはディレクトリを作れない（root でも失敗する）
+        let blocker = dir.join("not_a_dir");
+        fs::write(&blocker, "").unwrap();
+        
+        let mut config = default_config();
+        config.temp_dir = blocker.join("recordings");
+        assert!(ensure_writable_temp_dir(&mut config).is_some());
+        assert_eq!(config.temp_dir, fallback_dir());
+        
+        config.temp_dir = dir.clone();
+        assert!(ensure_writable_temp_dir(&mut config).is_none());
+        assert_eq!(config.temp_dir, dir);
+        
+        // 設定を保存しても切り替え先は書き込まれない
+        let config_path = dir.join("config.toml");
+        let unwritable = blocker.join("recordings");
+        fs::write(&config_path, format!("config_version = {}\ntemp_dir = {:?}\n", CURRENT_CONFIG_VERSION, unwritable)).unwrap();
+        let mut config = load_config_layers(&dir.join("system.toml"), &config_path, toml::Table::new()).unwrap();
+        assert!(ensure_writable_temp_dir(&mut config).is_some());
+        save_max_duration(&config_path, 120).unwrap();
+        let saved: toml::Table = toml::from_str(&fs::read_to_string(&config_path).unwrap()).unwrap();
+        assert_eq!(saved["temp_dir"].as_str(), unwritable.to_str());
+        
+        let _ = fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn invalid_extra_headers_are_dropped() {
+        let mut api = ApiConfig::default();
+        api.extra_headers.insert("X-Gateway-Token".to_string(), "secret".to_string());
+        api.extra_headers.insert("Bad Header".to_string(), "value".to_string());
+        api.extra_headers.insert("X-Multiline".to_string(), "a\nb".to_string());
+        
+        drop_invalid_extra_headers(&mut api);
+        assert_eq!(api.extra_headers.keys().collect::<Vec<_>>(), ["X-Gateway-Token"]);
+    }
+    
+    #[test]
+    fn recording_output_device_is_read_as_audio_output_device() {
+        let mut table: toml::Table = toml::from_str("[recording]\noutput_device = \"hdmi\"\n").unwrap();
+        read_legacy_output_device(&mut table);
+        assert_eq!(table["audio"]["output_device"].as_str(), Some("hdmi"));
+        assert!(!table["recording"].as_table().unwrap().contains_key("output_device"));
+        
+        let mut table: toml::Table = toml::from_str("[recording]\noutput_device = \"hdmi\"\n[audio]\noutput_device = \"speakers\"\n").unwrap();
+        read_legacy_output_device(&mut table);
+        assert_eq!(table["audio"]["output_device"].as_str(), Some("speakers"));
+    }
+    
+    #[test]
+    fn invalid_upload_mime_is_dropped() {
+        let mut api = ApiConfig { upload_mime: Some("audio/ogg; codecs=opus".to_string()), ..ApiConfig::default() };
+        drop_invalid_upload_mime(&mut api);
+        assert_eq!(api.upload_mime.as_deref(), Some("audio/ogg; codecs=opus"));
+        
+        api.upload_mime = Some("ogg".to_string());
+        drop_invalid_upload_mime(&mut api);
+        assert_eq!(api.upload_mime, None);
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+use crate::api::TranscriptionError;
+use crate::config::{expand_home, Config};
+
+/// Replacement for secrets and personal text in error reports
+const REDACTED: &str = "[redacted]";
+
+/// Shortest run after "sk-" treated as an API key in error messages
+const MIN_KEY_CHARS: usize = 8;
+
+/// Config values that may hold secrets or what the user talks about, as JSON pointers
+const REDACTED_SETTINGS: &[&str] = &[
+    "/api_key",
+    "/api/organization",
+    "/api/project",
+    "/integrations/http_control_token",
+    "/recording/biasing_terms",
+    "/text_processing/context",
+    "/text_processing/system_prompt",
+];
+
+/// Short name of what went wrong, e.g. "network" or "rate_limited"
+fn error_type(error: &anyhow::Error) -> &'static str {
+    match error.downcast_ref::<TranscriptionError>() {
+        Some(TranscriptionError::NoApiKey) => "no_api_key",
+        Some(TranscriptionError::Network(_)) => "network",
+        Some(TranscriptionError::RateLimited(_)) => "rate_limited",
+        Some(TranscriptionError::Unauthorized { .. }) => "unauthorized",
+        Some(TranscriptionError::Server { .. }) => "server",
+        Some(TranscriptionError::Parse(_)) => "parse",
+        Some(TranscriptionError::Schema(_)) => "schema",
+        Some(TranscriptionError::Io { .. }) => "io",
+        Some(TranscriptionError::Whisper { .. }) => "whisper",
+        Some(TranscriptionError::Offline { .. }) => "offline",
+        Some(TranscriptionError::InvalidRequest(_)) => "invalid_request",
+        None => "other",
+    }
+}
+
+/// Error message without anything that identifies the user
+///
+/// Response excerpts of schema errors may quote the transcript, so they are left out.
+/// The API key, other "sk-" keys and the home directory are masked in the rest.
+fn sanitized_message(error: &anyhow::Error, config: &Config) -> String {
+    let message = match error.downcast_ref::<TranscriptionError>() {
+        Some(TranscriptionError::Schema(_)) => "Unexpected API response (body omitted)".to_string(),
+        _ => format!("{:#}", error),
+    };
+    redact_text(&message, config)
+}
+
+/// Mask the configured API key, "sk-" style keys and the home directory in `text`
+fn redact_text(text: &str, config: &Config) -> String {
+    let mut text = text.to_string();
+    let api_key = config.api_key.trim();
+    if !api_key.is_empty() {
+        text = text.replace(api_key, REDACTED);
+    }
+    text = redact_sk_keys(&text);
+    if let Some(home) = dirs::home_dir().filter(|home| home.as_os_str().len() > 1) {
+        text = text.replace(&*home.to_string_lossy(), "~");
+    }
+    text
+}
+
+/// Mask every "sk-..." run of at least `MIN_KEY_CHARS` key characters
+fn redact_sk_keys(text: &str) -> String {
+    let is_key_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("sk-") {
+        let key_len = rest[start + 3..].find(|c: char| !is_key_char(c)).unwrap_or(rest.len() - start - 3);
+        redacted.push_str(&rest[..start]);
+        if key_len >= MIN_KEY_CHARS {
+            redacted.push_str(REDACTED);
+        } else {
+            redacted.push_str(&rest[start..start + 3 + key_len]);
+        }
+        rest = &rest[start + 3 + key_len..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+/// The config as JSON with secrets, personal text and header values masked
+fn sanitized_config(config: &Config) -> Value {
+    let mut value = serde_json::to_value(config).unwrap_or(Value::Null);
+    for pointer in REDACTED_SETTINGS {
+        if let Some(setting) = value.pointer_mut(pointer) {
+            if !setting.is_null() {
+                *setting = Value::String(REDACTED.to_string());
+            }
+        }
+    }
+    if let Some(Value::Object(headers)) = value.pointer_mut("/api/extra_headers") {
+        for header in headers.values_mut() {
+            *header = Value::String(REDACTED.to_string());
+        }
+    }
+    if let Some(Value::Object(profiles)) = value.pointer_mut("/api/profiles") {
+        for profile in profiles.values_mut() {
+            for field in ["api_key", "organization"] {
+                if let Some(setting) = profile.get_mut(field).filter(|setting| !setting.is_null()) {
+                    *setting = Value::String(REDACTED.to_string());
+                }
+            }
+        }
+    }
+    // パスに含まれるユーザー名も伏せる
+    serde_json::from_str(&redact_text(&value.to_string(), config)).unwrap_or(value)
+}
+
+/// One JSON line of the error report
+fn report_entry(config: &Config, context: &str, error: &anyhow::Error) -> Value {
+    json!({
+        "timestamp": chrono::Local::now().to_rfc3339(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "error_type": error_type(error),
+        "context": context,
+        "error": sanitized_message(error, config),
+        "config": sanitized_config(config),
+    })
+}
+
+/// Append a transcription or audio error to `logging.error_report_file`, if one is configured
+///
+/// `context` says what was being done, e.g. "transcription". Transcripts are never written.
+pub fn append_error_report(config: &Config, context: &str, error: &anyhow::Error) -> Result<()> {
+    let Some(path) = &config.logging.error_report_file else {
+        return Ok(());
+    };
+    let path = expand_home(path);
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let mut line = report_entry(config, context, error).to_string();
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write to {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::default_config;
+
+    fn config_with_secrets() -> Config {
+        let mut config = default_config();
+        config.api_key = "sk-proj-secret123456".to_string();
+        config.api.organization = Some("org-private".to_string());
+        config.api.extra_headers.insert("X-Gateway-Token".to_string(), "token-value".to_string());
+        config.api.profiles.insert("work".to_string(), crate::config::ApiProfile {
+            api_key: "work-key-value".to_string(),
+            organization: Some("org-work".to_string()),
+            base_url: None,
+        });
+        config.text_processing.context = "田中さんとの面談".to_string();
+        config
+    }
+
+    #[test]
+    fn reports_leave_out_secrets_and_transcripts() {
+        let config = config_with_secrets();
+        let error = anyhow::Error::new(TranscriptionError::Schema("no transcript field: 今日の議事録".to_string()));
+        let entry = report_entry(&config, "transcription", &error).to_string();
+
+        assert!(entry.contains("\"error_type\":\"schema\""));
+        assert!(entry.contains("\"context\":\"transcription\""));
+        for secret in ["sk-proj-secret123456", "org-private", "token-value", "work-key-value", "org-work", "田中さん", "今日の議事録"] {
+            assert!(!entry.contains(secret), "{} leaked into {}", secret, entry);
+        }
+        assert!(entry.contains("X-Gateway-Token"));
+    }
+
+    #[test]
+    fn api_keys_in_messages_are_masked() {
+        let mut config = default_config();
+        config.api_key = "my-local-key".to_string();
+        assert_eq!(
+            redact_text("401: Incorrect API key provided: sk-abcdefgh1234. Also my-local-key", &config),
+            "401: Incorrect API key provided: [redacted]. Also [redacted]"
+        );
+        assert_eq!(redact_sk_keys("task-sk-1 and sk-"), "task-sk-1 and sk-");
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long a client may take to send its request line
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// HTTP response to `request_line` for an app in `status`, running for `uptime`
+///
+/// Only `GET /healthz` is served (a query string is ignored); anything else is a 404,
+/// or a 405 for other methods on `/healthz`.
+fn response(request_line: &str, status: &str, uptime: Duration) -> String {
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or("");
+    let (code, body) = match (method, path) {
+        ("GET", "/healthz") => (
+            "200 OK",
+            serde_json::json!({ "status": status, "uptime_secs": uptime.as_secs() }).to_string(),
+        ),
+        (_, "/healthz") => ("405 Method Not Allowed", r#"{"error":"method not allowed"}"#.to_string()),
+        _ => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+    };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        body.len(),
+        body
+    )
+}
+
+/// Answer one connection; `status` is read only when the request arrives
+fn handle_connection(stream: TcpStream, status: &dyn Fn() -> String, started: Instant) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut stream = stream;
+    stream.write_all(response(&request_line, &status(), started.elapsed()).as_bytes())
+}
+
+/// Serve `/healthz` on `127.0.0.1:port` from a background thread (`integrations.health_port`)
+///
+/// `status` reports the current `AppStatus`; it is called from the server thread.
+pub fn spawn_health_server<F>(port: u16, status: F) -> Result<JoinHandle<()>>
+where
+    F: Fn() -> String + Send + 'static,
+{
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("Failed to listen on 127.0.0.1:{}", port))?;
+    info!("Health check listening on http://127.0.0.1:{}/healthz", port);
+    let started = Instant::now();
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            // 1 件の失敗で監視を止めない
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle_connection(stream, &status, started) {
+                        warn!("Health check request failed: {}", e);
+                    }
+                },
+                Err(e) => warn!("Health check connection failed: {}", e),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn healthz_reports_status_and_uptime() {
+        let reply = response("GET /healthz HTTP/1.1\r\n", "Transcribing", Duration::from_secs(42));
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(reply.ends_with(r#"{"status":"Transcribing","uptime_secs":42}"#));
+        assert!(reply.contains("Content-Length: 42\r\n"));
+        assert!(response("GET /healthz?verbose=1 HTTP/1.1", "Idle", Duration::ZERO).starts_with("HTTP/1.1 200"));
+    }
+
+    #[test]
+    fn other_requests_are_rejected() {
+        assert!(response("GET / HTTP/1.1", "Idle", Duration::ZERO).starts_with("HTTP/1.1 404"));
+        assert!(response("POST /healthz HTTP/1.1", "Idle", Duration::ZERO).starts_with("HTTP/1.1 405"));
+        assert!(response("", "Idle", Duration::ZERO).starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn server_answers_over_tcp() {
+        use std::io::Read;
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        spawn_health_server(port, || "Idle".to_string()).unwrap();
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        assert!(reply.contains(r#""status":"Idle""#));
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long a client may take to send its whole request, and to take the response
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Largest request line and headers read; the rest of an oversized request is left unread
+const MAX_HEAD_BYTES: u64 = 8192;
+
+/// Largest request body read (and thrown away) before answering
+const MAX_BODY_BYTES: u64 = 4096;
+
+/// Header that carries `integrations.http_control_token`, besides `Authorization: Bearer`
+pub const TOKEN_HEADER: &str = "X-Wispr-Token";
+
+/// What a macro button asked the recorder to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlAction {
+    Start,
+    Stop,
+    Toggle,
+}
+
+impl ControlAction {
+    fn id(self) -> &'static str {
+        match self {
+            ControlAction::Start => "start",
+            ControlAction::Stop => "stop",
+            ControlAction::Toggle => "toggle",
+        }
+    }
+}
+
+/// The parts of a request the endpoints look at
+#[derive(Debug, Default)]
+struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+    content_length: u64,
+}
+
+/// What to answer a request with
+#[derive(Debug, PartialEq)]
+enum Route {
+    Action(ControlAction),
+    Transcript,
+    Error(&'static str, &'static str),
+}
+
+/// Method and path from the request line, and the token and body length from the headers
+///
+/// A query string is ignored. The token is taken from `X-Wispr-Token` or `Authorization: Bearer`.
+fn parse_request(request_line: &str, headers: &[String]) -> Request {
+    let mut parts = request_line.split_whitespace();
+    let mut request = Request {
+        method: parts.next().unwrap_or("").to_string(),
+        path: parts.next().unwrap_or("").split('?').next().unwrap_or("").to_string(),
+        ..Request::default()
+    };
+    for header in headers {
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        if name.eq_ignore_ascii_case(TOKEN_HEADER) {
+            request.token = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("Authorization") {
+            if let Some(token) = value.strip_prefix("Bearer ") {
+                request.token = Some(token.trim().to_string());
+            }
+        } else if name.eq_ignore_ascii_case("Content-Length") {
+            request.content_length = value.parse().unwrap_or(0);
+        }
+    }
+    request
+}
+
+/// Which endpoint `request` reaches; a missing or wrong token is a 401 for every path
+fn route(request: &Request, token: &str) -> Route {
+    if request.token.as_deref() != Some(token) {
+        return Route::Error("401 Unauthorized", "missing or wrong token");
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/record/start") => Route::Action(ControlAction::Start),
+        ("POST", "/record/stop") => Route::Action(ControlAction::Stop),
+        ("POST", "/record/toggle") => Route::Action(ControlAction::Toggle),
+        ("GET", "/transcript") => Route::Transcript,
+        (_, "/record/start" | "/record/stop" | "/record/toggle" | "/transcript") => {
+            Route::Error("405 Method Not Allowed", "method not allowed")
+        },
+        _ => Route::Error("404 Not Found", "not found"),
+    }
+}
+
+/// A JSON response with `code` and `body`
+fn json_response(code: &str, body: &serde_json::Value) -> String {
+    let body = body.to_string();
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        body.len(),
+        body
+    )
+}
+
+/// Reads from a stream until `deadline`, however slowly the data trickles in
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "request not received in time"));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Answer one connection
+///
+/// Connections are answered one at a time, so the whole request must arrive within
+/// `READ_TIMEOUT` and fit in `MAX_HEAD_BYTES` and `MAX_BODY_BYTES`; a slow or endless
+/// request can't hold up the other clients for longer than that.
+fn handle_connection(
+    stream: TcpStream,
+    token: &str,
+    on_action: &dyn Fn(ControlAction),
+    transcript: &dyn Fn() -> String,
+) -> std::io::Result<()> {
+    stream.set_write_timeout(Some(READ_TIMEOUT))?;
+    let deadline = DeadlineReader { stream: &stream, deadline: Instant::now() + READ_TIMEOUT };
+    let mut reader = BufReader::new(deadline.take(MAX_HEAD_BYTES));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        headers.push(header);
+    }
+    let request = parse_request(&request_line, &headers);
+    // 読み残した本文があると閉じたときに接続がリセットされるので読み捨てる
+    let body_bytes = request.content_length.min(MAX_BODY_BYTES);
+    reader.get_mut().set_limit(body_bytes);
+    std::io::copy(&mut reader.take(body_bytes), &mut std::io::sink())?;
+
+    let response = match route(&request, token) {
+        Route::Action(action) => {
+            info!("HTTP control: {}", action.id());
+            on_action(action);
+            json_response("202 Accepted", &serde_json::json!({ "action": action.id() }))
+        },
+        Route::Transcript => json_response("200 OK", &serde_json::json!({ "text": transcript() })),
+        Route::Error(code, message) => json_response(code, &serde_json::json!({ "error": message })),
+    };
+    let mut stream = stream;
+    stream.write_all(response.as_bytes())
+}
+
+/// Serve the recording endpoints on `address:port` from a background thread (`integrations.http_control`)
+///
+/// Every request must carry `token`. `on_action` is called for the `POST /record/*` endpoints
+/// and `transcript` for `GET /transcript`, both from the server thread.
+pub fn spawn_control_server<F, G>(address: &str, port: u16, token: String, on_action: F, transcript: G) -> Result<JoinHandle<()>>
+where
+    F: Fn(ControlAction) + Send + 'static,
+    G: Fn() -> String + Send + 'static,
+{
+    let listener = TcpListener::bind((address, port))
+        .with_context(|| format!("Failed to listen on {}:{}", address, port))?;
+    info!("HTTP control listening on http://{}:{}", address, port);
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            // 1 件の失敗で待ち受けを止めない
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle_connection(stream, &token, &on_action, &transcript) {
+                        warn!("HTTP control request failed: {}", e);
+                    }
+                },
+                Err(e) => warn!("HTTP control connection failed: {}", e),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(request_line: &str, headers: &[&str]) -> Request {
+        let headers: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+        parse_request(request_line, &headers)
+    }
+
+    #[test]
+    fn endpoints_need_the_token() {
+        let authorized = |line| request(line, &["Host: localhost", "x-wispr-token: secret"]);
+        assert_eq!(route(&authorized("POST /record/start HTTP/1.1"), "secret"), Route::Action(ControlAction::Start));
+        assert_eq!(route(&authorized("POST /record/toggle?from=deck HTTP/1.1"), "secret"), Route::Action(ControlAction::Toggle));
+        assert_eq!(route(&authorized("GET /transcript HTTP/1.1"), "secret"), Route::Transcript);
+        let bearer = request("POST /record/stop HTTP/1.1", &["Authorization: Bearer secret"]);
+        assert_eq!(route(&bearer, "secret"), Route::Action(ControlAction::Stop));
+
+        assert!(matches!(route(&request("POST /record/start HTTP/1.1", &[]), "secret"), Route::Error("401 Unauthorized", _)));
+        assert!(matches!(route(&authorized("POST /record/start HTTP/1.1"), "other"), Route::Error("401 Unauthorized", _)));
+    }
+
+    #[test]
+    fn other_requests_are_rejected() {
+        let authorized = |line| request(line, &["X-Wispr-Token: secret"]);
+        assert!(matches!(route(&authorized("GET /record/start HTTP/1.1"), "secret"), Route::Error("405 Method Not Allowed", _)));
+        assert!(matches!(route(&authorized("POST /transcript HTTP/1.1"), "secret"), Route::Error("405 Method Not Allowed", _)));
+        assert!(matches!(route(&authorized("GET / HTTP/1.1"), "secret"), Route::Error("404 Not Found", _)));
+    }
+
+    #[test]
+    fn server_forwards_actions_and_serves_the_transcript() {
+        use std::sync::mpsc;
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        spawn_control_server("127.0.0.1", port, "secret".to_string(), move |action| {
+            let _ = tx.send(action);
+        }, || "こんにちは".to_string()).unwrap();
+        let send = |raw: &str| {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            stream.write_all(raw.as_bytes()).unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            reply
+        };
+
+        let reply = send("POST /record/toggle HTTP/1.1\r\nX-Wispr-Token: secret\r\nContent-Length: 2\r\n\r\n{}");
+        assert!(reply.starts_with("HTTP/1.1 202"));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), ControlAction::Toggle);
+        assert!(send("GET /transcript HTTP/1.1\r\nX-Wispr-Token: secret\r\n\r\n").ends_with(r#"{"text":"こんにちは"}"#));
+        assert!(send("POST /record/start HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 401"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn a_stalled_client_does_not_block_the_next_request() {
+        use std::sync::mpsc;
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        spawn_control_server("127.0.0.1", port, "secret".to_string(), move |action| {
+            let _ = tx.send(action);
+        }, String::new).unwrap();
+
+        // 要求行を少しずつ送り続け、最後まで送らないクライアント
+        let stalled = thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            for _ in 0..50 {
+                if stream.write_all(b"x").is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(b"POST /record/toggle HTTP/1.1\r\nX-Wispr-Token: secret\r\n\r\n").unwrap();
+        assert_eq!(rx.recv_timeout(READ_TIMEOUT * 2).unwrap(), ControlAction::Toggle);
+        stalled.join().unwrap();
+    }
+}
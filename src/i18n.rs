@@ -0,0 +1,808 @@
+use std::sync::OnceLock;
+
+use crate::config::{FormattingStyle, ShortcutConfig, ShortcutMode, UiLanguage};
+
+/// Strings of the selected UI language, set once at startup
+static STRINGS: OnceLock<&'static Strings> = OnceLock::new();
+
+/// Labels, tooltips and status texts shown in the main window
+pub struct Strings {
+    language: UiLanguage,
+    pub record: &'static str,
+    pub dormant_title: &'static str,
+    pub waiting_for_voice: &'static str,
+    pub stop: &'static str,
+    pub record_hint_armed: &'static str,
+    pub processing: &'static str,
+    pub device_settings: (&'static str, &'static str),
+    pub shortcuts: (&'static str, &'static str),
+    pub dictionary: (&'static str, &'static str),
+    pub history: (&'static str, &'static str),
+    pub verbatim: (&'static str, &'static str),
+    pub tag: (&'static str, &'static str),
+    pub tag_none: &'static str,
+    pub api_profile: (&'static str, &'static str),
+    pub api_profile_default: &'static str,
+    pub device_label: &'static str,
+    pub device: (&'static str, &'static str),
+    pub device_default_format: &'static str,
+    pub device_supported_formats: &'static str,
+    pub output_device: (&'static str, &'static str),
+    pub refresh_devices: (&'static str, &'static str),
+    pub output_default: &'static str,
+    pub monitor_when_idle: (&'static str, &'static str),
+    pub always_on_top: (&'static str, &'static str),
+    pub max_duration: (&'static str, &'static str),
+    pub save_max_duration: (&'static str, &'static str),
+    pub language_auto: &'static str,
+    pub transcription_language: (&'static str, &'static str),
+    pub formatting_context: (&'static str, &'static str),
+    pub formatting_context_placeholder: &'static str,
+    pub formatting_style: (&'static str, &'static str),
+    pub mic_test: &'static str,
+    pub mic_test_recording: &'static str,
+    pub mic_test_failed: &'static str,
+    pub open_config: (&'static str, &'static str),
+    pub reload_config: (&'static str, &'static str),
+    pub level_label: &'static str,
+    pub level: (&'static str, &'static str),
+    pub waveform: (&'static str, &'static str),
+    pub dictionary_title: &'static str,
+    pub add_word: &'static str,
+    pub add_word_description: (&'static str, &'static str),
+    pub import_csv: (&'static str, &'static str),
+    pub export_csv: (&'static str, &'static str),
+    pub reset_frequencies: (&'static str, &'static str),
+    pub clear_words: (&'static str, &'static str),
+    pub restore_backup: (&'static str, &'static str),
+    pub dictionary_list_description: &'static str,
+    pub dictionary_not_loaded: &'static str,
+    pub history_search: (&'static str, &'static str),
+    pub history_list: (&'static str, &'static str),
+    pub history_empty: &'static str,
+    pub usage_stats: (&'static str, &'static str),
+    pub transcript: (&'static str, &'static str),
+    pub transcript_placeholder: &'static str,
+    pub latest_transcript_tab: &'static str,
+    pub abort: &'static str,
+    pub abort_description: (&'static str, &'static str),
+    pub copy: &'static str,
+    pub copy_selection: &'static str,
+    pub open_audio_file: (&'static str, &'static str),
+    pub reformat: (&'static str, &'static str),
+    pub reformat_placeholder: &'static str,
+    pub clear: &'static str,
+    pub shortcut_disabled: &'static str,
+    pub api_key_missing: &'static str,
+    pub api_key_invalid: &'static str,
+    pub segments_need_verbose_json: &'static str,
+    pub recovered_recordings_buttons: (&'static str, &'static str),
+    pub recording_queued: &'static str,
+    pub notify_transcription_done: &'static str,
+    pub notify_copied: &'static str,
+    pub notify_transcription_failed: &'static str,
+    pub config_opened: &'static str,
+    pub usage_today: &'static str,
+    pub usage_week: &'static str,
+    pub usage_not_logged: &'static str,
+    pub dialog_cancel: &'static str,
+    pub dialog_delete: &'static str,
+    pub dictionary_empty: &'static str,
+    pub dictionary_no_backups: &'static str,
+    pub choose_dictionary_backup: &'static str,
+    pub dialog_restore: &'static str,
+    pub open_audio_file_title: &'static str,
+    pub dialog_transcribe: &'static str,
+    pub all_files_filter: &'static str,
+    pub recording_empty: &'static str,
+    pub error_no_api_key: &'static str,
+    pub error_rate_limited: &'static str,
+    pub reformat_needs_api_key: &'static str,
+    pub gpt_timed_out_dictionary_only: &'static str,
+    pub gpt_failed_dictionary_only: &'static str,
+    pub import_dictionary_title: &'static str,
+    pub dialog_import: &'static str,
+    pub overwrite_existing_words: &'static str,
+    pub export_dictionary_title: &'static str,
+    pub dialog_save: &'static str,
+    pub quit_while_recording: &'static str,
+    pub quit_while_transcribing: &'static str,
+    pub quit_save_recording: &'static str,
+    pub quit_discard_recording: &'static str,
+    pub quit_button: &'static str,
+    pub mic_test_no_recording: &'static str,
+    pub mic_test_analysis_failed: &'static str,
+    pub clipboard_has_no_path: &'static str,
+}
+
+static JA: Strings = Strings {
+    language: UiLanguage::Ja,
+    record: "● 録音",
+    dormant_title: "Wispr（休止中）",
+    waiting_for_voice: "◎ 音声待ち",
+    stop: "■ 停止",
+    record_hint_armed: "話すと録音を開始します",
+    processing: "処理中",
+    device_settings: ("デバイス設定", "録音デバイスと言語の設定を表示/非表示"),
+    shortcuts: ("ショートカット", "キーボードショートカットの一覧を表示/非表示"),
+    dictionary: ("辞書", "登録済み単語の一覧を表示/非表示"),
+    history: ("履歴", "文字起こしの履歴と検索を表示/非表示"),
+    verbatim: ("整形なし", "オンの間は辞書の置換や GPT 整形を行わず、文字起こしをそのまま使います"),
+    tag: ("タグ", "これからの文字起こしに付けるタグ（履歴とログに記録されます）"),
+    tag_none: "タグなし",
+    api_profile: ("APIプロファイル", "文字起こしと整形に使うAPIアカウント（設定ファイルの api.active_profile に保存されます）"),
+    api_profile_default: "既定のアカウント",
+    device_label: "デバイス:",
+    device: ("録音デバイス", "録音に使う入力デバイス"),
+    device_default_format: "既定の形式",
+    device_supported_formats: "対応している形式",
+    output_device: ("出力デバイス", "マイクテストの再生に使う出力先"),
+    refresh_devices: ("デバイスを再読み込み", "接続したばかりのデバイスが一覧に出るよう、入力と出力のデバイスを読み込み直します"),
+    output_default: "出力: 既定",
+    monitor_when_idle: ("待機中もモニター", "オフにすると録音中以外はマイクを使用しません"),
+    always_on_top: ("常に手前に表示", "ウィンドウを他のウィンドウより手前に表示します"),
+    max_duration: ("最大録音時間（分）", "この時間が経つと録音を自動で止めます。録音中の変更も反映されます"),
+    save_max_duration: ("保存", "最大録音時間を設定ファイルの recording.max_duration_secs に保存します"),
+    language_auto: "言語: 自動",
+    transcription_language: ("文字起こしの言語", "次の文字起こしの言語を一時的に指定します"),
+    formatting_context: ("録音の状況", "GPT整形に伝える録音の状況（Enter で反映、設定ファイルには保存しません）"),
+    formatting_context_placeholder: "録音の状況（例: 技術ミーティング）",
+    formatting_style: ("整形スタイル", "GPT整形のスタイル（設定ファイルには保存しません）"),
+    mic_test: "マイクテスト",
+    mic_test_recording: "録音中...",
+    mic_test_failed: "マイクテストを開始できませんでした",
+    open_config: ("設定を開く", "設定ファイルを既定のエディタで開きます"),
+    reload_config: ("再読み込み", "編集した設定ファイルを再起動せずに読み込みます"),
+    level_label: "レベル:",
+    level: ("入力レベル", "マイクの入力レベル"),
+    waveform: ("入力レベルの波形", "直近 5 秒の入力レベル"),
+    dictionary_title: "登録済み単語",
+    add_word: "+ 単語登録",
+    add_word_description: ("単語登録", "辞書に単語の置換を登録します"),
+    import_csv: ("CSV取込", "CSVファイルから単語を取り込みます"),
+    export_csv: ("CSV出力", "辞書をCSVファイルに書き出します"),
+    reset_frequencies: ("頻度をリセット", "学習した単語の頻度を消去します（登録単語は残ります）"),
+    clear_words: ("全単語を削除", "登録済みの単語をすべて削除します（確認あり）"),
+    restore_backup: ("バックアップから復元", "自動で保存された辞書のバックアップを選んで復元します"),
+    dictionary_list_description: "辞書に登録されている単語の一覧",
+    dictionary_not_loaded: "辞書が読み込まれていません...",
+    history_search: ("履歴を検索", "入力した文字を含む文字起こしだけを表示します（大文字小文字は区別しません）"),
+    history_list: ("文字起こしの履歴", "完了した文字起こしの一覧（新しい順）"),
+    history_empty: "まだ文字起こしがありません",
+    usage_stats: ("使用状況", "今日と今週の録音数・音声の長さ・文字数・推定料金を表示します"),
+    transcript: ("文字起こし結果", "文字起こしの結果（編集できます）"),
+    transcript_placeholder: "録音するとここに文字起こしが表示されます...",
+    latest_transcript_tab: "最新",
+    abort: "中止",
+    abort_description: ("文字起こしを中止", "実行中と待機中の文字起こしを中止します"),
+    copy: "コピー",
+    copy_selection: "選択範囲をコピー",
+    open_audio_file: ("音声ファイルを開く…", "録音済みの音声ファイルを選んで文字起こしします"),
+    reformat: ("再整形", "入力した指示を加えて、今の文字起こしを GPT で整形し直します（元に戻すで戻せます）"),
+    reformat_placeholder: "再整形の指示（例: もっと簡潔に）",
+    clear: "クリア",
+    shortcut_disabled: "無効",
+    api_key_missing: "APIキーが設定されていません。設定ファイルの api_key（または api.key_file）、キーリング、環境変数 OPENAI_API_KEY のいずれかで設定してください",
+    api_key_invalid: "APIキーが無効なようです。設定を確認してください",
+    segments_need_verbose_json: "マーカーやタイムスタンプを入れるには transcription.response_format = \"verbose_json\" が必要です。なしで文字起こししました",
+    recovered_recordings_buttons: ("あとで", "文字起こし"),
+    recording_queued: "次の録音を待機中",
+    notify_transcription_done: "文字起こし完了",
+    notify_copied: "クリップボードにコピーしました",
+    notify_transcription_failed: "文字起こしに失敗しました",
+    config_opened: "設定ファイルを開きました。編集後は「再読み込み」で反映できます",
+    usage_today: "今日",
+    usage_week: "今週",
+    usage_not_logged: "logging.jsonl が無効なため、新しい文字起こしは集計されません",
+    dialog_cancel: "キャンセル",
+    dialog_delete: "削除",
+    dictionary_empty: "登録済みの単語はありません",
+    dictionary_no_backups: "辞書のバックアップはまだありません",
+    choose_dictionary_backup: "復元するバックアップを選んでください。現在の辞書は置き換えられます。",
+    dialog_restore: "復元",
+    open_audio_file_title: "音声ファイルを開く",
+    dialog_transcribe: "文字起こし",
+    all_files_filter: "すべてのファイル",
+    recording_empty: "録音が空でした（音声データがありません）",
+    error_no_api_key: "エラー: APIキーが設定されていません。config.toml の api_key、キーリング、または環境変数 OPENAI_API_KEY を設定してください",
+    error_rate_limited: "エラー: APIのレート制限に達しました。しばらく待ってから再度お試しください",
+    reformat_needs_api_key: "APIキーが未設定のため、再整形できません",
+    gpt_timed_out_dictionary_only: "GPT整形がタイムアウトしたため、辞書の置換のみ適用しました",
+    gpt_failed_dictionary_only: "GPT整形に失敗したため、辞書の置換のみ適用しました",
+    import_dictionary_title: "辞書CSVの取り込み",
+    dialog_import: "取り込む",
+    overwrite_existing_words: "既存の単語を上書きする",
+    export_dictionary_title: "辞書CSVの書き出し",
+    dialog_save: "保存",
+    quit_while_recording: "録音中です。終了すると録音は文字起こしされません。",
+    quit_while_transcribing: "文字起こし中です。終了すると結果は失われます。",
+    quit_save_recording: "録音を保存して終了",
+    quit_discard_recording: "破棄して終了",
+    quit_button: "終了",
+    mic_test_no_recording: "録音できませんでした",
+    mic_test_analysis_failed: "録音を解析できませんでした",
+    clipboard_has_no_path: "クリップボードにファイルパスがありません",
+};
+
+static EN: Strings = Strings {
+    language: UiLanguage::En,
+    record: "● Record",
+    dormant_title: "Wispr (dormant)",
+    waiting_for_voice: "◎ Waiting for voice",
+    stop: "■ Stop",
+    record_hint_armed: "Start speaking to record",
+    processing: "Processing",
+    device_settings: ("Device settings", "Show or hide the recording device and language settings"),
+    shortcuts: ("Shortcuts", "Show or hide the keyboard shortcuts"),
+    dictionary: ("Dictionary", "Show or hide the registered words"),
+    history: ("History", "Show or hide the transcript history and search"),
+    verbatim: ("Verbatim", "While on, transcripts are used as is, without the dictionary or GPT formatting"),
+    tag: ("Tag", "Tag for the following transcripts, kept in the history and the log"),
+    tag_none: "No tag",
+    api_profile: ("API profile", "API account used for transcription and formatting (saved as api.active_profile in the config file)"),
+    api_profile_default: "Default account",
+    device_label: "Device:",
+    device: ("Recording device", "Input device used for recording"),
+    device_default_format: "Default format",
+    device_supported_formats: "Supported formats",
+    output_device: ("Output device", "Output the microphone test is played back on"),
+    refresh_devices: ("Refresh devices", "Reload the input and output devices so a just-plugged-in device is listed"),
+    output_default: "Output: default",
+    monitor_when_idle: ("Monitor while idle", "When off, the microphone is only used while recording"),
+    always_on_top: ("Always on top", "Keep the window above other windows"),
+    max_duration: ("Max minutes", "Recordings stop automatically after this long; changes apply to a running recording too"),
+    save_max_duration: ("Save", "Save the maximum recording time to recording.max_duration_secs in the config file"),
+    language_auto: "Language: auto",
+    transcription_language: ("Transcription language", "Language of the next transcription only"),
+    formatting_context: ("Recording context", "Situation passed to the GPT formatter (applied on Enter, not saved to the config file)"),
+    formatting_context_placeholder: "Context (e.g. technical meeting)",
+    formatting_style: ("Formatting style", "Style of the GPT formatting (not saved to the config file)"),
+    mic_test: "Mic test",
+    mic_test_recording: "Recording...",
+    mic_test_failed: "Could not start the microphone test",
+    open_config: ("Open config", "Open the config file in the default editor"),
+    reload_config: ("Reload", "Load the edited config file without restarting"),
+    level_label: "Level:",
+    level: ("Input level", "Microphone input level"),
+    waveform: ("Input waveform", "Input level over the last 5 seconds"),
+    dictionary_title: "Registered words",
+    add_word: "+ Add word",
+    add_word_description: ("Add word", "Register a word replacement in the dictionary"),
+    import_csv: ("Import CSV", "Import words from a CSV file"),
+    export_csv: ("Export CSV", "Export the dictionary to a CSV file"),
+    reset_frequencies: ("Reset frequencies", "Forget the learned word frequencies (registered words are kept)"),
+    clear_words: ("Clear words", "Delete all registered words (asks first)"),
+    restore_backup: ("Restore backup", "Pick one of the automatic dictionary backups and restore it"),
+    dictionary_list_description: "Words registered in the dictionary",
+    dictionary_not_loaded: "Dictionary not loaded...",
+    history_search: ("Search history", "Show only transcripts containing the text (case-insensitive)"),
+    history_list: ("Transcript history", "Finished transcripts, newest first"),
+    history_empty: "No transcripts yet",
+    usage_stats: ("Usage", "Show recordings, audio length, characters and estimated cost for today and this week"),
+    transcript: ("Transcript", "Transcription result (editable)"),
+    transcript_placeholder: "Record audio to see transcription here...",
+    latest_transcript_tab: "Latest",
+    abort: "Abort",
+    abort_description: ("Abort transcription", "Abort running and queued transcriptions"),
+    copy: "Copy",
+    copy_selection: "Copy selection",
+    open_audio_file: ("Open Audio File…", "Pick a recorded audio file and transcribe it"),
+    reformat: ("Reformat", "Format the current transcript again with GPT, following the instruction (Undo brings the previous text back)"),
+    reformat_placeholder: "Instruction for reformatting (e.g. make it more concise)",
+    clear: "Clear",
+    shortcut_disabled: "disabled",
+    api_key_missing: "No API key is set. Add api_key (or api.key_file) to the config file, store it in the keyring or set OPENAI_API_KEY.",
+    api_key_invalid: "Your API key appears invalid. Check the settings.",
+    segments_need_verbose_json: "Markers and timestamps need transcription.response_format = \"verbose_json\". Transcribed without them",
+    recovered_recordings_buttons: ("Later", "Transcribe"),
+    recording_queued: "Next recording queued",
+    notify_transcription_done: "Transcription complete",
+    notify_copied: "Copied to the clipboard",
+    notify_transcription_failed: "Transcription failed",
+    config_opened: "Opened the config file. Use \"Reload\" to apply your edits",
+    usage_today: "Today",
+    usage_week: "This week",
+    usage_not_logged: "logging.jsonl is off, so new transcriptions are not counted",
+    dialog_cancel: "Cancel",
+    dialog_delete: "Delete",
+    dictionary_empty: "No words are registered",
+    dictionary_no_backups: "There are no dictionary backups yet",
+    choose_dictionary_backup: "Choose the backup to restore. The current dictionary will be replaced.",
+    dialog_restore: "Restore",
+    open_audio_file_title: "Open an audio file",
+    dialog_transcribe: "Transcribe",
+    all_files_filter: "All files",
+    recording_empty: "The recording is empty (no audio data)",
+    error_no_api_key: "Error: No API key is set. Set api_key in config.toml, the keyring or the OPENAI_API_KEY environment variable",
+    error_rate_limited: "Error: The API rate limit was reached. Please wait a while and try again",
+    reformat_needs_api_key: "No API key is set, so the transcript can't be reformatted",
+    gpt_timed_out_dictionary_only: "GPT formatting timed out, so only the dictionary replacements were applied",
+    gpt_failed_dictionary_only: "GPT formatting failed, so only the dictionary replacements were applied",
+    import_dictionary_title: "Import dictionary CSV",
+    dialog_import: "Import",
+    overwrite_existing_words: "Overwrite existing words",
+    export_dictionary_title: "Export dictionary CSV",
+    dialog_save: "Save",
+    quit_while_recording: "A recording is in progress. It won't be transcribed if you quit.",
+    quit_while_transcribing: "A transcription is in progress. Its result is lost if you quit.",
+    quit_save_recording: "Save the recording and quit",
+    quit_discard_recording: "Discard and quit",
+    quit_button: "Quit",
+    mic_test_no_recording: "Nothing was recorded",
+    mic_test_analysis_failed: "Could not analyze the recording",
+    clipboard_has_no_path: "The clipboard holds no file path",
+};
+
+impl Strings {
+    pub fn record_tooltip(&self, shortcut: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("録音の開始/停止（{}）", shortcut),
+            UiLanguage::En => format!("Start or stop recording ({})", shortcut),
+        }
+    }
+
+    pub fn mic_test_description(&self, secs: u64) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("{}秒録音して再生します", secs),
+            UiLanguage::En => format!("Record {} seconds and play them back", secs),
+        }
+    }
+
+    pub fn copy_description(&self, shortcut: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("文字起こし結果をクリップボードにコピー（{}）", shortcut),
+            UiLanguage::En => format!("Copy the transcript to the clipboard ({})", shortcut),
+        }
+    }
+
+    pub fn clear_description(&self, shortcut: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("文字起こし結果を消去（{}）", shortcut),
+            UiLanguage::En => format!("Clear the transcript ({})", shortcut),
+        }
+    }
+
+    pub fn detected_language(&self, language: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("検出言語: {}", language),
+            UiLanguage::En => format!("Detected: {}", language),
+        }
+    }
+
+    pub fn history_no_match(&self, query: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("「{}」を含む文字起こしはありません", query),
+            UiLanguage::En => format!("No transcripts contain \"{}\"", query),
+        }
+    }
+
+    /// Confirmation for the `number`th marker of a recording, dropped `secs` into it
+    pub fn marker_dropped(&self, number: usize, secs: u64) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("マーカー {} を {:02}:{:02} に入れました", number, secs / 60, secs % 60),
+            UiLanguage::En => format!("Marker {} dropped at {:02}:{:02}", number, secs / 60, secs % 60),
+        }
+    }
+
+    /// One period of the usage statistics dialog; `cost_usd` is the estimated transcription cost
+    pub fn usage_line(&self, label: &str, recordings: usize, audio_secs: f64, chars: usize, cost_usd: f64) -> String {
+        match self.language {
+            UiLanguage::Ja => format!(
+                "{}: 録音 {} 件 / 音声 {:.1} 分 / {} 文字 / 推定 ${:.2}",
+                label, recordings, audio_secs / 60.0, chars, cost_usd
+            ),
+            UiLanguage::En => format!(
+                "{}: {} recordings / {:.1} min of audio / {} characters / est. ${:.2}",
+                label, recordings, audio_secs / 60.0, chars, cost_usd
+            ),
+        }
+    }
+
+    /// How the usage statistics estimate the cost, at `usd_per_minute` of OpenAI transcription
+    pub fn usage_cost_note(&self, usd_per_minute: f64) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("料金は OpenAI の文字起こし ${}/分 で概算しています（GPT 整形は含みません）", usd_per_minute),
+            UiLanguage::En => format!("The cost is estimated at ${}/min of OpenAI transcription (formatting not included)", usd_per_minute),
+        }
+    }
+
+    /// Error shown when the transcription log can't be read for the usage statistics
+    pub fn usage_stats_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("使用状況を読み込めませんでした: {}", error),
+            UiLanguage::En => format!("Failed to read the usage statistics: {}", error),
+        }
+    }
+
+    /// Warning for a recording discarded by `recording.min_duration_secs`
+    pub fn recording_too_short(&self, min_secs: f32) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("録音が短すぎるため破棄しました（{:.1}秒未満）", min_secs),
+            UiLanguage::En => format!("Recording too short (under {:.1}s), discarded", min_secs),
+        }
+    }
+
+    /// Name of a `text_processing.style` preset in the style menu
+    pub fn formatting_style_name(&self, style: FormattingStyle) -> &'static str {
+        match (self.language, style) {
+            (UiLanguage::Ja, FormattingStyle::Verbatim) => "そのまま",
+            (UiLanguage::Ja, FormattingStyle::LightCleanup) => "軽く整える",
+            (UiLanguage::Ja, FormattingStyle::BulletSummary) => "箇条書きで要約",
+            (UiLanguage::Ja, FormattingStyle::FormalRewrite) => "丁寧な文章に",
+            (UiLanguage::En, FormattingStyle::Verbatim) => "Verbatim",
+            (UiLanguage::En, FormattingStyle::LightCleanup) => "Light cleanup",
+            (UiLanguage::En, FormattingStyle::BulletSummary) => "Bullet summary",
+            (UiLanguage::En, FormattingStyle::FormalRewrite) => "Formal rewrite",
+        }
+    }
+
+    pub fn queue_status(&self, active: usize, pending: usize) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("文字起こし中 {}件 / 待ち {}件", active, pending),
+            UiLanguage::En => format!("Transcribing {} / queued {}", active, pending),
+        }
+    }
+
+    /// Pango markup listing the keyboard shortcuts; empty (disabled) ones are shown as such
+    pub fn shortcut_help(&self, shortcuts: &ShortcutConfig) -> String {
+        let show = |key: &str| if key.is_empty() { self.shortcut_disabled.to_string() } else { key.to_string() };
+        let key = &shortcuts.toggle_recording;
+        let (clear, copy) = (show(&shortcuts.clear_transcript), show(&shortcuts.copy_to_clipboard));
+        let (clipboard, open_config) = (show(&shortcuts.transcribe_clipboard), show(&shortcuts.open_config));
+        let marker = show(&shortcuts.drop_marker);
+        match self.language {
+            UiLanguage::Ja => {
+                let record_hint = match shortcuts.mode {
+                    _ if key.is_empty() => format!("{}</b>", self.shortcut_disabled),
+                    ShortcutMode::Hold => format!("{}</b> を押している間録音\n離すと文字起こし", key),
+                    ShortcutMode::Toggle => format!("{}</b> で録音開始\nもう一度押すと文字起こし", key),
+                };
+                format!(
+                    "<small>録音: <b>{}\nクリア: <b>{}</b>\nコピー: <b>{}</b>\nクリップボードのパスを文字起こし: <b>{}</b>\n設定を開く: <b>{}</b>\n録音中のマーカー: <b>{}</b>\n元に戻す / やり直す: <b>Control+Z</b> / <b>Control+Y</b></small>",
+                    record_hint, clear, copy, clipboard, open_config, marker
+                )
+            },
+            UiLanguage::En => {
+                let record_hint = match shortcuts.mode {
+                    _ if key.is_empty() => format!("{}</b>", self.shortcut_disabled),
+                    ShortcutMode::Hold => format!("Press and hold {}</b>\nRelease to transcribe.", key),
+                    ShortcutMode::Toggle => format!("Press {}</b> to start\nPress again to transcribe.", key),
+                };
+                format!(
+                    "<small>Record: <b>{}\nClear: <b>{}</b>\nCopy: <b>{}</b>\nTranscribe clipboard path: <b>{}</b>\nOpen config: <b>{}</b>\nMarker while recording: <b>{}</b>\nUndo / Redo: <b>Control+Z</b> / <b>Control+Y</b></small>",
+                    record_hint, clear, copy, clipboard, open_config, marker
+                )
+            },
+        }
+    }
+
+    /// Question after `count` recordings left by an interrupted session were repaired; `names` lists them
+    pub fn recovered_recordings(&self, count: usize, names: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!(
+                "前回中断された録音を {} 件復元しました。文字起こししますか？\n\n{}\n\n文字起こししない場合もファイルはそのまま残ります。",
+                count, names
+            ),
+            UiLanguage::En => format!(
+                "Recovered {} recording(s) from an interrupted session. Transcribe them?\n\n{}\n\nThe files are kept either way.",
+                count, names
+            ),
+        }
+    }
+
+    pub fn config_open_failed(&self, path: &str, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("設定ファイルを開けませんでした（{}）: {}", path, error),
+            UiLanguage::En => format!("Could not open the config file ({}): {}", path, error),
+        }
+    }
+
+    /// Message after a config reload; `changed` lists the dotted names of the changed settings
+    pub fn config_reloaded(&self, changed: &[String]) -> String {
+        let message = match self.language {
+            UiLanguage::Ja => "設定を再読み込みしました（ウィンドウの配置、表示言語、audio.host は再起動後に反映されます）",
+            UiLanguage::En => "Config reloaded (the window layout, the UI language and audio.host apply after a restart)",
+        };
+        match (self.language, changed.is_empty()) {
+            (_, true) => message.to_string(),
+            (UiLanguage::Ja, false) => format!("{}\n変更: {}", message, changed.join(", ")),
+            (UiLanguage::En, false) => format!("{}\nChanged: {}", message, changed.join(", ")),
+        }
+    }
+
+    pub fn config_reload_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("設定を再読み込みできませんでした（現在の設定を使用します）: {}", error),
+            UiLanguage::En => format!("Could not reload the config; keeping the current settings: {}", error),
+        }
+    }
+
+    pub fn max_duration_saved(&self, secs: u64) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("最大録音時間（{}秒）を設定ファイルに保存しました", secs),
+            UiLanguage::En => format!("Saved the maximum recording time ({} s) to the config file", secs),
+        }
+    }
+
+    pub fn max_duration_save_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("最大録音時間を保存できませんでした: {}", error),
+            UiLanguage::En => format!("Could not save the maximum recording time: {}", error),
+        }
+    }
+
+    /// Warning when switching the API profile couldn't be saved
+    pub fn api_profile_switch_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("APIプロファイルを切り替えられませんでした: {}", error),
+            UiLanguage::En => format!("Failed to switch the API profile: {}", error),
+        }
+    }
+
+    /// Warning when `temp_dir` can't be written and `fallback` is used instead
+    pub fn temp_dir_not_writable(&self, temp_dir: &str, fallback: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("temp_dir（{}）に書き込めないため、{} を使用します。設定を確認してください。", temp_dir, fallback),
+            UiLanguage::En => format!("temp_dir ({}) is not writable, using {} instead. Please check the config.", temp_dir, fallback),
+        }
+    }
+
+    /// Result of resetting the learned word frequencies
+    pub fn dictionary_frequencies_cleared(&self, count: usize) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("学習した {} 件の単語の頻度を消去しました", count),
+            UiLanguage::En => format!("Cleared the learned frequencies of {} words", count),
+        }
+    }
+
+    /// Error shown when the user dictionary couldn't be saved
+    pub fn dictionary_save_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("辞書の保存に失敗しました: {}", error),
+            UiLanguage::En => format!("Failed to save the dictionary: {}", error),
+        }
+    }
+
+    /// Confirmation before deleting all `count` dictionary words
+    pub fn confirm_clear_dictionary(&self, count: usize) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("登録済みの {} 件の単語をすべて削除しますか？この操作は元に戻せません。", count),
+            UiLanguage::En => format!("Delete all {} registered words? This can't be undone.", count),
+        }
+    }
+
+    /// Result of deleting the dictionary words
+    pub fn dictionary_words_cleared(&self, count: usize) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("{} 件の単語を削除しました", count),
+            UiLanguage::En => format!("Deleted {} words", count),
+        }
+    }
+
+    /// Result of restoring a dictionary backup of `count` words
+    pub fn dictionary_backup_restored(&self, count: usize) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("{} 件の単語を含むバックアップを復元しました", count),
+            UiLanguage::En => format!("Restored a backup with {} words", count),
+        }
+    }
+
+    /// Error shown when a dictionary backup couldn't be restored
+    pub fn dictionary_restore_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("辞書の復元に失敗しました: {}", error),
+            UiLanguage::En => format!("Failed to restore the dictionary: {}", error),
+        }
+    }
+
+    /// Name of the file chooser filter for the supported audio `extensions`
+    pub fn audio_files_filter(&self, extensions: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("音声ファイル（{}）", extensions),
+            UiLanguage::En => format!("Audio files ({})", extensions),
+        }
+    }
+
+    /// Notice for a transcript that is probably not speech, with the `verbose_json` probability of that
+    pub fn transcript_no_speech(&self, no_speech_prob: f64) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("文字起こしの品質が低い可能性があります（音声でない確率 {:.0}%）。録音し直すことをおすすめします", no_speech_prob * 100.0),
+            UiLanguage::En => format!("The transcript may be of low quality ({:.0}% chance of no speech). Consider recording again", no_speech_prob * 100.0),
+        }
+    }
+
+    /// Notice for a transcript with a low mean `avg_logprob`
+    pub fn transcript_low_confidence(&self, avg_logprob: f64) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("文字起こしの信頼度が低い可能性があります（平均 logprob {:.2}）。録音し直すことをおすすめします", avg_logprob),
+            UiLanguage::En => format!("The transcript may be unreliable (mean logprob {:.2}). Consider recording again", avg_logprob),
+        }
+    }
+
+    /// Error for a recording whose WAV data can't be read
+    pub fn recording_corrupt(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("録音ファイルが壊れています（{}）", error),
+            UiLanguage::En => format!("The recording file is corrupt ({})", error),
+        }
+    }
+
+    /// Error shown when the API couldn't be reached
+    pub fn error_network(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("エラー: APIに接続できませんでした。ネットワーク接続を確認してください（{}）", error),
+            UiLanguage::En => format!("Error: Couldn't connect to the API. Please check the network connection ({})", error),
+        }
+    }
+
+    /// Error shown for a transcription failure without more specific guidance
+    pub fn error_other(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("エラー: {}", error),
+            UiLanguage::En => format!("Error: {}", error),
+        }
+    }
+
+    /// Error shown when the API rejected the key with `status`
+    pub fn error_unauthorized(&self, status: u16) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("エラー: APIキーが無効なようです（{}）。設定ファイルの api_key を確認してください", status),
+            UiLanguage::En => format!("Error: The API key seems to be invalid ({}). Please check api_key in the config file", status),
+        }
+    }
+
+    /// Error shown when the connectivity check couldn't reach `host`
+    pub fn error_offline(&self, host: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("エラー: オフラインのようです（{} に接続できません）。ネットワーク接続を確認するか、transcription.backend にローカルのバックエンド（\"whisper_python\"）を追加してください", host),
+            UiLanguage::En => format!("Error: You seem to be offline (can't reach {}). Check the network connection or add a local backend (\"whisper_python\") to transcription.backend", host),
+        }
+    }
+
+    /// Warning when reformatting the transcript failed
+    pub fn reformat_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("再整形に失敗しました: {}", error),
+            UiLanguage::En => format!("Reformatting failed: {}", error),
+        }
+    }
+
+    /// Notice for a transcript of `chars` characters formatted in `chunks` requests (`long_input = "chunk"`)
+    pub fn formatted_in_chunks(&self, chars: usize, chunks: usize) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("文字起こしが長いため（{}文字）、{}回に分けてGPT整形しました", chars, chunks),
+            UiLanguage::En => format!("The transcript was long ({} characters), so it was formatted in {} parts", chars, chunks),
+        }
+    }
+
+    /// Notice for a transcript of `chars` characters of which only the first `head_chars` were formatted
+    pub fn formatted_head_only(&self, chars: usize, head_chars: usize) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("文字起こしが長いため（{}文字）、先頭の{}文字だけをGPT整形しました。残りは整形されていません", chars, head_chars),
+            UiLanguage::En => format!("The transcript was long ({} characters), so only its first {} characters were formatted. The rest is left as is", chars, head_chars),
+        }
+    }
+
+    /// Result of a dictionary CSV import
+    pub fn dictionary_imported(&self, imported: usize, skipped: usize, invalid: usize) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("{} 件の単語を取り込みました（スキップ: {} 件、無効な行: {} 件）", imported, skipped, invalid),
+            UiLanguage::En => format!("Imported {} words ({} skipped, {} invalid lines)", imported, skipped, invalid),
+        }
+    }
+
+    /// Error shown when a dictionary CSV couldn't be imported
+    pub fn dictionary_import_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("CSVの取り込みに失敗しました: {}", error),
+            UiLanguage::En => format!("Failed to import the CSV: {}", error),
+        }
+    }
+
+    /// Result of a dictionary CSV export
+    pub fn dictionary_exported(&self, count: usize) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("{} 件の単語を書き出しました", count),
+            UiLanguage::En => format!("Exported {} words", count),
+        }
+    }
+
+    /// Error shown when the dictionary couldn't be exported
+    pub fn dictionary_export_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("CSVの書き出しに失敗しました: {}", error),
+            UiLanguage::En => format!("Failed to export the CSV: {}", error),
+        }
+    }
+
+    /// Result of a microphone test whose loudest sample reached `peak` (0.0 to 1.0)
+    pub fn mic_test_peak(&self, peak: f32) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("ピーク {:.0}%", peak * 100.0),
+            UiLanguage::En => format!("Peak {:.0}%", peak * 100.0),
+        }
+    }
+
+    /// Warning for an essentially silent recording, with its `peak` and `rms` levels
+    pub fn recording_silent(&self, peak: f32, rms: f32) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("録音がほぼ無音です（ピーク {:.3}, RMS {:.4}）。マイクの設定を確認してください。", peak, rms),
+            UiLanguage::En => format!("The recording is nearly silent (peak {:.3}, RMS {:.4}). Please check the microphone settings.", peak, rms),
+        }
+    }
+
+    /// Warning for a recording with `clipped_ratio` of its samples at full scale
+    pub fn recording_clipped(&self, clipped_ratio: f32) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("録音が音割れしています（{:.1}% のサンプルが最大レベル）。入力音量を下げてください。", clipped_ratio * 100.0),
+            UiLanguage::En => format!("The recording is clipped ({:.1}% of the samples at full scale). Please lower the input volume.", clipped_ratio * 100.0),
+        }
+    }
+
+    /// Error shown when the clipboard couldn't be read
+    pub fn clipboard_read_failed(&self, error: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("クリップボードを読み取れませんでした: {}", error),
+            UiLanguage::En => format!("Could not read the clipboard: {}", error),
+        }
+    }
+
+    /// Error shown when the audio file at `path` doesn't exist
+    pub fn audio_file_not_found(&self, path: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("ファイルが見つかりません: {}", path),
+            UiLanguage::En => format!("File not found: {}", path),
+        }
+    }
+
+    /// Error shown when `path` isn't one of the supported audio `extensions`
+    pub fn not_an_audio_file(&self, extensions: &str, path: &str) -> String {
+        match self.language {
+            UiLanguage::Ja => format!("音声ファイルではありません（対応形式: {}）: {}", extensions, path),
+            UiLanguage::En => format!("Not an audio file (supported: {}): {}", extensions, path),
+        }
+    }
+}
+
+/// UI language from a locale name such as `LANG`; Japanese unless it names another language
+fn language_from_locale(locale: Option<&str>) -> UiLanguage {
+    match locale.map(str::trim).filter(|locale| !locale.is_empty()) {
+        // 未設定の場合は従来どおり日本語
+        None => UiLanguage::Ja,
+        Some(locale) if locale.starts_with("ja") => UiLanguage::Ja,
+        Some(_) => UiLanguage::En,
+    }
+}
+
+/// Select the UI language: `ui.language` when set, otherwise `LC_ALL`, `LC_MESSAGES` or `LANG`
+pub fn init(configured: Option<UiLanguage>) {
+    let language = configured.unwrap_or_else(|| {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty());
+        language_from_locale(locale.as_deref())
+    });
+    let _ = STRINGS.set(match language {
+        UiLanguage::Ja => &JA,
+        UiLanguage::En => &EN,
+    });
+}
+
+/// Strings of the selected UI language (Japanese before `init`)
+pub fn text() -> &'static Strings {
+    STRINGS.get().copied().unwrap_or(&JA)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_follows_the_locale() {
+        assert_eq!(language_from_locale(Some("ja_JP.UTF-8")), UiLanguage::Ja);
+        assert_eq!(language_from_locale(Some("en_US.UTF-8")), UiLanguage::En);
+        assert_eq!(language_from_locale(Some("C")), UiLanguage::En);
+        assert_eq!(language_from_locale(None), UiLanguage::Ja);
+        assert_eq!(language_from_locale(Some("")), UiLanguage::Ja);
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+use crate::config::{expand_home, IntegrationsConfig};
+
+/// Fill `{timestamp}` and `{text}` in the output template
+fn render_entry(template: &str, timestamp: &str, text: &str) -> String {
+    // 文字起こし結果に {timestamp} が含まれていても置き換えないよう、日時を先に埋める
+    template.replace("{timestamp}", timestamp).replace("{text}", text)
+}
+
+/// Append a transcript to `integrations.output_file`, if one is configured
+pub fn append_to_output_file(config: &IntegrationsConfig, text: &str) -> Result<()> {
+    let Some(path) = &config.output_file else {
+        return Ok(());
+    };
+    let path = expand_home(path);
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    
+    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let entry = render_entry(&config.output_template, &timestamp, text);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.write_all(entry.as_bytes())
+        .with_context(|| format!("Failed to write to {}", path.display()))?;
+    
+    info!("Transcript appended to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn transcripts_are_appended_with_the_template() {
+        let dir = std::env::temp_dir().join(format!("wispr_output_file_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("notes").join("dictation.md");
+        let config = IntegrationsConfig {
+            output_file: Some(path.clone()),
+            output_template: "- {text}\n".to_string(),
+            ..IntegrationsConfig::default()
+        };
+        
+        append_to_output_file(&config, "一つ目").unwrap();
+        append_to_output_file(&config, "二つ目").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "- 一つ目\n- 二つ目\n");
+        
+        let _ = fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn template_does_not_expand_placeholders_inside_the_text() {
+        assert_eq!(render_entry("## {timestamp}\n{text}", "2024-01-01", "{timestamp}"), "## 2024-01-01\n{timestamp}");
+    }
+}
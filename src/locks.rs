@@ -0,0 +1,38 @@
+use log::warn;
+use std::sync::{Mutex, MutexGuard};
+
+/// Lock `mutex`, taking the guard back if an earlier panic poisoned it
+///
+/// The shared UI state stays usable after a panic in one thread (the panic itself is
+/// logged by the hook in `main`), so a single failure doesn't make every later lock panic.
+pub fn lock_or_recover<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        warn!("Recovering a mutex poisoned by an earlier panic");
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn poisoned_lock_is_recovered() {
+        let shared = Arc::new(Mutex::new(1));
+        let poisoner = shared.clone();
+        let result = std::thread::spawn(move || {
+            let mut value = poisoner.lock().unwrap();
+            *value = 2;
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(shared.is_poisoned());
+
+        *lock_or_recover(&shared) += 1;
+        assert_eq!(*lock_or_recover(&shared), 3);
+        assert!(!shared.is_poisoned());
+    }
+}
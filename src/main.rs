@@ -25,6 +25,18 @@ mod audio;
 mod clipboard;
 mod window;
 mod text_processor;
+mod integrations;
+mod error_report;
+mod batch;
+mod locks;
+mod shortcut;
+mod status;
+mod stats;
+#[cfg(feature = "health")]
+mod health;
+#[cfg(feature = "http-control")]
+mod http_control;
+mod i18n;
 
 /// Wispr Linux - 音声文字起こしアプリケーション
 #[derive(Parser, Debug)]
@@ -33,6 +45,135 @@ struct Args {
     /// 設定ファイルのパス
     #[arg(short, long)]
     config: Option<String>,
+    
+    /// ログレベル (off, error, warn, info, debug, trace)。未指定時は RUST_LOG を参照
+    #[arg(long)]
+    log_level: Option<LevelFilter>,
+    
+    /// ログファイルへの出力を無効にする（コンソールのみ）
+    #[arg(long)]
+    no_log_file: bool,
+    
+    /// ローテーションした古いログを gzip 圧縮せず wispr.N.log のまま残す
+    #[arg(long, conflicts_with = "no_log_file")]
+    plain_logs: bool,
+    
+    /// 数秒間録音して入力レベルを測定し、推奨の input_gain を表示する
+    #[arg(long)]
+    calibrate: bool,
+    
+    /// --calibrate の推奨値を設定ファイルの recording.input_gain に書き込む
+    #[arg(long, requires = "calibrate")]
+    apply_gain: bool,
+    
+    /// 標準入力の音声（WAV または raw PCM s16le）を文字起こしして標準出力に書き出す
+    #[arg(long)]
+    transcribe_stdin: bool,
+    
+    /// --transcribe-stdin で raw PCM を読む場合のサンプルレート（WAV では不要）
+    #[arg(long, requires = "transcribe_stdin")]
+    sample_rate: Option<u32>,
+    
+    /// --transcribe-stdin で raw PCM を読む場合のチャンネル数（既定: 1）
+    #[arg(long, requires = "transcribe_stdin")]
+    channels: Option<u16>,
+    
+    /// --transcribe-stdin の結果をクリップボードにもコピーする（終了後も残るようにバックグラウンドで保持）
+    #[arg(long, requires = "transcribe_stdin")]
+    copy: bool,
+    
+    /// ディレクトリ内の音声ファイルをすべて文字起こしし、それぞれの横に .txt を書き出す
+    #[arg(long, value_name = "DIR")]
+    transcribe_dir: Option<String>,
+    
+    /// --transcribe-dir の .txt の書き出し先（未指定時は音声ファイルと同じディレクトリ）
+    #[arg(long, value_name = "DIR", requires = "transcribe_dir")]
+    output_dir: Option<String>,
+    
+    /// --transcribe-dir で同時に文字起こしするファイル数（未指定時は api.max_concurrent）
+    #[arg(long, requires = "transcribe_dir")]
+    jobs: Option<usize>,
+    
+    /// 設定ファイルの値を上書きする（例: --set recording.max_duration_secs=600、複数指定可）
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    set: Vec<String>,
+}
+
+/// Transcribe audio piped to stdin and print the processed transcript
+fn run_transcribe_stdin(config: config::Config, sample_rate: Option<u32>, channels: Option<u16>, copy: bool) -> Result<()> {
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut std::io::stdin().lock(), &mut bytes).context("Failed to read audio from stdin")?;
+    info!("Read {} bytes of audio from stdin", bytes.len());
+    
+    let path = config.temp_dir.join("stdin.wav");
+    let recording = audio::recording_from_stdin_bytes(bytes, sample_rate, channels, &path)?;
+    let clipboard_config = config.clipboard.clone();
+    let api = api::TranscriptionAPI::new(config);
+    let transcript = api.transcribe_with_processing(&recording, None)?;
+    for notice in transcript.notices {
+        log::warn!("{}", notice);
+    }
+    println!("{}", transcript.text);
+    if copy {
+        // すぐに終了するので、コピーはこのプロセスから切り離したコピー元に任せる
+        if let Err(e) = clipboard::copy_before_exit(&transcript.text, &clipboard_config) {
+            log::warn!("クリップボードにコピーできませんでした。標準出力の文字起こしを使ってください: {:#}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Length of the `--calibrate` recording
+const CALIBRATION_SECS: u64 = 5;
+
+/// Measure the input level, print the recommended gain and optionally save it
+fn run_calibration(config: &config::Config, config_path: &Path, apply: bool) -> Result<()> {
+    println!("Speak at your usual volume for {} seconds...", CALIBRATION_SECS);
+    let calibration = match audio::run_calibration(config, CALIBRATION_SECS)? {
+        Some(calibration) => calibration,
+        None => {
+            println!("No speech was detected. Check the microphone connection and silence_threshold.");
+            return Ok(());
+        }
+    };
+    
+    println!("Current level: RMS {:.4} (peak {:.0}%)", calibration.speech_rms, calibration.peak * 100.0);
+    println!("Target level:  RMS {:.4}", audio::CALIBRATION_TARGET_RMS);
+    println!("Recommended input_gain = {:.2} (currently {:.2})", calibration.recommended_gain, config.recording.input_gain);
+    
+    if apply {
+        config::save_input_gain(config_path, calibration.recommended_gain)?;
+        println!("Saved to {}", config_path.display());
+    }
+    Ok(())
+}
+
+/// CLI引数 → RUST_LOG → Info の順でログレベルを決定
+fn resolve_log_level(cli_level: Option<LevelFilter>) -> LevelFilter {
+    if let Some(level) = cli_level {
+        return level;
+    }
+    
+    match std::env::var("RUST_LOG") {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!("Ignoring unsupported RUST_LOG value: {}", value);
+            LevelFilter::Info
+        }),
+        Err(_) => LevelFilter::Info,
+    }
+}
+
+/// Log panics through the logger
+///
+/// A panic in a helper thread otherwise only reaches stderr. Releasing the microphone is left
+/// to the places that catch the panic.
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        let thread = std::thread::current();
+        error!("Thread '{}' panicked: {}", thread.name().unwrap_or("<unnamed>"), panic_info);
+        default_hook(panic_info);
+    }));
 }
 
 fn main() -> Result<()> {
@@ -44,49 +185,84 @@ fn main() -> Result<()> {
     if config_path.exists() {
         log4rs::init_file(config_path, Default::default())
             .context("Failed to initialize logger from config file")?;
+        if args.log_level.is_some() || args.no_log_file || args.plain_logs {
+            log::warn!("log4rs.yaml found; --log-level, --no-log-file and --plain-logs are ignored");
+        }
     } else {
-        // ホームディレクトリにログディレクトリを作成
-        let home_dir = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
-        let log_dir = home_dir.join(".local/log");
-        std::fs::create_dir_all(&log_dir).context("Failed to create log directory")?;
-        
-        // 設定ファイルが存在しない場合は、プログラム内で設定
-        let log_file = log_dir.join("wispr.log");
+        let log_level = resolve_log_level(args.log_level);
+        let pattern = "{d(%Y-%m-%d %H:%M:%S)} {h({l})} {t} - {m}{n}";
         
-        // ファイルアペンダー設定
-        let file_appender = log4rs::append::rolling_file::RollingFileAppender::builder()
-            .encoder(Box::new(log4rs::encode::pattern::PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S)} {h({l})} {t} - {m}{n}")))
-            .build(log_file, Box::new(log4rs::append::rolling_file::policy::compound::CompoundPolicy::new(
-                Box::new(SizeTrigger::new(10 * 1024 * 1024)), // 10MB
-                Box::new(FixedWindowRoller::builder()
-                    .build(&log_dir.join("wispr.{}.log.gz").to_string_lossy(), 5)
-                    .context("Failed to build roller")?)
-            )))
-            .context("Failed to build file appender")?;
-            
         // コンソールアペンダー設定
         let console_appender = log4rs::append::console::ConsoleAppender::builder()
-            .encoder(Box::new(log4rs::encode::pattern::PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S)} {h({l})} {t} - {m}{n}")))
+            .encoder(Box::new(log4rs::encode::pattern::PatternEncoder::new(pattern)))
             .build();
+        
+        let mut config_builder = log4rs::Config::builder()
+            .appender(log4rs::config::Appender::builder().build("console", Box::new(console_appender)));
+        let mut root_builder = log4rs::config::Root::builder().appender("console");
+        
+        if !args.no_log_file {
+            // ホームディレクトリにログディレクトリを作成（HOME が無い環境では一時ディレクトリ）
+            let log_dir = match dirs::home_dir() {
+                Some(home_dir) => home_dir.join(".local/log"),
+                None => {
+                    let log_dir = config::fallback_dir().join("log");
+                    eprintln!("Could not determine home directory, writing logs to {}", log_dir.display());
+                    log_dir
+                }
+            };
+            std::fs::create_dir_all(&log_dir).context("Failed to create log directory")?;
+            
+            // 設定ファイルが存在しない場合は、プログラム内で設定
+            let log_file = log_dir.join("wispr.log");
+            
+            // 拡張子が .gz の場合だけ FixedWindowRoller が圧縮する
+            let roll_pattern = if args.plain_logs { "wispr.{}.log" } else { "wispr.{}.log.gz" };
+            
+            // ファイルアペンダー設定
+            let file_appender = log4rs::append::rolling_file::RollingFileAppender::builder()
+                .encoder(Box::new(log4rs::encode::pattern::PatternEncoder::new(pattern)))
+                .build(log_file, Box::new(log4rs::append::rolling_file::policy::compound::CompoundPolicy::new(
+                    Box::new(SizeTrigger::new(10 * 1024 * 1024)), // 10MB
+                    Box::new(FixedWindowRoller::builder()
+                        .build(&log_dir.join(roll_pattern).to_string_lossy(), 5)
+                        .context("Failed to build roller")?)
+                )))
+                .context("Failed to build file appender")?;
+            
+            config_builder = config_builder
+                .appender(log4rs::config::Appender::builder().build("file", Box::new(file_appender)));
+            root_builder = root_builder.appender("file");
+        }
             
         // ロガー設定
-        let config = log4rs::Config::builder()
-            .appender(log4rs::config::Appender::builder().build("file", Box::new(file_appender)))
-            .appender(log4rs::config::Appender::builder().build("console", Box::new(console_appender)))
-            .build(log4rs::config::Root::builder()
-                .appender("file")
-                .appender("console")
-                .build(LevelFilter::Info))
+        let config = config_builder
+            .build(root_builder.build(log_level))
             .context("Failed to build log config")?;
             
         log4rs::init_config(config).context("Failed to initialize logger from built config")?;
     }
 
     info!("Starting Wispr Linux");
+    install_panic_hook();
 
     // Load configuration with custom path if provided
+    let config_file = config::get_config_path(args.config.clone());
+    config::set_cli_overrides(&args.set)?;
     let config = config::load_config(args.config)?;
     info!("Configuration loaded");
+    
+    if args.calibrate {
+        return run_calibration(&config, &config_file, args.apply_gain);
+    }
+    if args.transcribe_stdin {
+        return run_transcribe_stdin(config, args.sample_rate, args.channels, args.copy);
+    }
+    if let Some(dir) = &args.transcribe_dir {
+        let jobs = args.jobs.unwrap_or(config.api.max_concurrent);
+        let output_dir = args.output_dir.as_deref().map(Path::new);
+        return batch::run_transcribe_dir(api::TranscriptionAPI::new(config), Path::new(dir), output_dir, jobs);
+    }
 
     // Initialize GTK on the main thread
     if let Err(e) = gtk::init() {
@@ -98,11 +274,11 @@ fn main() -> Result<()> {
     #[cfg(feature = "tray")]
     let (window_thread, window_sender, tray_thread, tray_sender) = {
         info!("Starting tray application");
-        let (tray_thread, tray_sender) = tray::run_tray_application(config.clone())?;
+        let (tray_thread, tray_sender, tray_events) = tray::run_tray_application(config.clone())?;
         info!("Tray application started");
         
         info!("Starting window application with tray");
-        let (window_thread, window_sender) = window::run_window_application(config.clone(), tray_sender.clone())?;
+        let (window_thread, window_sender) = window::run_window_application(config.clone(), config_file.clone(), tray_sender.clone(), tray_events)?;
         info!("Window application started");
         
         (window_thread, window_sender, tray_thread, tray_sender)
@@ -111,7 +287,7 @@ fn main() -> Result<()> {
     #[cfg(not(feature = "tray"))]
     let (window_thread, window_sender) = {
         info!("Starting window application");
-        let result = window::run_window_application(config.clone())?;
+        let result = window::run_window_application(config.clone(), config_file.clone())?;
         info!("Window application started");
         result
     };
@@ -164,39 +340,48 @@ fn main() -> Result<()> {
         }
     }
     
+    // メインループ終了後はExitメッセージが処理されないため、直接モニタリングを止める
+    window::stop_audio_monitoring();
+    
     // スレッドの終了を待機
     info!("Waiting for threads to complete...");
     
     // スレッドの終了をタイムアウト付きで待機
-    use std::time::Duration;
-    let _timeout = Duration::from_secs(5);
+    use std::time::{Duration, Instant};
+    let timeout = Duration::from_secs(5);
+    let deadline = Instant::now() + timeout;
     
-    let window_handle = std::thread::spawn(move || {
-        if let Err(e) = window_thread.join() {
-            error!("Failed to join window thread: {:?}", e);
-        }
-    });
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
     
-    #[cfg(feature = "tray")]
-    let tray_handle = std::thread::spawn(move || {
-        if let Err(e) = tray_thread.join() {
-            error!("Failed to join tray thread: {:?}", e);
-        }
+    let window_done_tx = done_tx.clone();
+    std::thread::spawn(move || {
+        let result = window_thread.join();
+        let _ = window_done_tx.send(("window", result.is_ok()));
     });
     
-    // タイムアウト付きでウィンドウスレッドの終了を待機
-    match window_handle.join() {
-        Ok(_) => info!("Window thread joined successfully"),
-        Err(e) => error!("Error joining window thread: {:?}", e),
-    }
-    
     #[cfg(feature = "tray")]
     {
-        // タイムアウト付きでトレイスレッドの終了を待機
-        match tray_handle.join() {
-            Ok(_) => info!("Tray thread joined successfully"),
-            Err(e) => error!("Error joining tray thread: {:?}", e),
+        let tray_done_tx = done_tx.clone();
+        std::thread::spawn(move || {
+            let result = tray_thread.join();
+            let _ = tray_done_tx.send(("tray", result.is_ok()));
+        });
+    }
+    drop(done_tx);
+    
+    // 終了を待つスレッド数
+    let mut remaining = if cfg!(feature = "tray") { 2 } else { 1 };
+    while remaining > 0 {
+        let wait = deadline.saturating_duration_since(Instant::now());
+        match done_rx.recv_timeout(wait) {
+            Ok((name, true)) => info!("{} thread joined successfully", name),
+            Ok((name, false)) => error!("Error joining {} thread", name),
+            Err(_) => {
+                error!("Timed out after {:?} waiting for {} thread(s) to finish", timeout, remaining);
+                break;
+            }
         }
+        remaining -= 1;
     }
     
     info!("Application shutdown complete");
@@ -0,0 +1,112 @@
+use anyhow::{bail, Result};
+
+/// Modifier names understood in shortcut strings such as "Alt+Shift+V"
+pub const MODIFIERS: &[&str] = &["Shift", "Alt", "Control", "Ctrl"];
+
+/// A shortcut string like "Alt+Shift+C" split into its key and modifiers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedShortcut {
+    /// Lowercase GDK key name, e.g. "c", "space" or "f1"
+    pub key: String,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+/// Whether `key` (already lowercased) is a key shortcuts can use
+fn is_supported_key(key: &str) -> bool {
+    if key == "space" {
+        return true;
+    }
+    if let Some(number) = key.strip_prefix('f') {
+        return matches!(number.parse::<u8>(), Ok(1..=12)) && !number.starts_with('0');
+    }
+    key.len() == 1 && key.chars().all(|c| c.is_ascii_lowercase())
+}
+
+/// Parse a shortcut string: any of `MODIFIERS` joined by "+", ending in a letter, space or F1-F12
+///
+/// Key names are case-insensitive, modifier names are not.
+pub fn parse_shortcut(shortcut: &str) -> Result<ParsedShortcut> {
+    let parts: Vec<&str> = shortcut.split('+').collect();
+    let Some((key, modifiers)) = parts.split_last() else {
+        bail!("empty shortcut");
+    };
+    if let Some(modifier) = modifiers.iter().find(|modifier| !MODIFIERS.contains(modifier)) {
+        bail!("unknown modifier {:?} (use {})", modifier, MODIFIERS.join(", "));
+    }
+    let key = key.to_lowercase();
+    if key.is_empty() {
+        bail!("missing key in {:?}", shortcut);
+    }
+    if !is_supported_key(&key) {
+        bail!("unsupported key {:?} (use a letter, space or F1-F12)", key);
+    }
+    Ok(ParsedShortcut {
+        key,
+        ctrl: modifiers.iter().any(|modifier| matches!(*modifier, "Control" | "Ctrl")),
+        alt: modifiers.contains(&"Alt"),
+        shift: modifiers.contains(&"Shift"),
+    })
+}
+
+/// Whether a key press of `keyval` with `modifiers` held triggers `shortcut`
+///
+/// Holding Shift turns "c" into "C", so the key is compared case-insensitively;
+/// the modifiers must match exactly.
+pub fn matches(shortcut: &ParsedShortcut, keyval: gdk::keys::Key, modifiers: gdk::ModifierType) -> bool {
+    let key_matches = keyval
+        .to_lower()
+        .name()
+        .is_some_and(|name| name.to_lowercase() == shortcut.key);
+    key_matches
+        && modifiers.contains(gdk::ModifierType::SHIFT_MASK) == shortcut.shift
+        && modifiers.contains(gdk::ModifierType::MOD1_MASK) == shortcut.alt
+        && modifiers.contains(gdk::ModifierType::CONTROL_MASK) == shortcut.ctrl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gdk::keys::constants as keys;
+    use gdk::ModifierType;
+
+    fn parsed(key: &str, ctrl: bool, alt: bool, shift: bool) -> ParsedShortcut {
+        ParsedShortcut { key: key.to_string(), ctrl, alt, shift }
+    }
+
+    #[test]
+    fn parses_keys_and_modifiers() {
+        assert_eq!(parse_shortcut("Shift+space").unwrap(), parsed("space", false, false, true));
+        assert_eq!(parse_shortcut("Alt+Shift+C").unwrap(), parsed("c", false, true, true));
+        assert_eq!(parse_shortcut("Ctrl+F12").unwrap(), parsed("f12", true, false, false));
+        assert_eq!(parse_shortcut("Control+z").unwrap(), parsed("z", true, false, false));
+        assert_eq!(parse_shortcut("x").unwrap(), parsed("x", false, false, false));
+    }
+
+    #[test]
+    fn rejects_invalid_shortcuts() {
+        for shortcut in ["", "Shift+", "Super+C", "shift+C", "Alt+1", "Alt+F0", "Alt+F13", "Alt+Enter", "Alt++"] {
+            assert!(parse_shortcut(shortcut).is_err(), "{:?} should be rejected", shortcut);
+        }
+    }
+
+    #[test]
+    fn matches_ignore_the_case_shift_produces() {
+        let shortcut = parse_shortcut("Alt+Shift+C").unwrap();
+        let held = ModifierType::MOD1_MASK | ModifierType::SHIFT_MASK;
+        assert!(matches(&shortcut, keys::C, held));
+        assert!(matches(&shortcut, keys::c, held));
+        assert!(!matches(&shortcut, keys::c, ModifierType::MOD1_MASK));
+        assert!(!matches(&shortcut, keys::v, held));
+    }
+
+    #[test]
+    fn matches_require_exact_modifiers() {
+        let shortcut = parse_shortcut("Shift+space").unwrap();
+        assert!(matches(&shortcut, keys::space, ModifierType::SHIFT_MASK));
+        assert!(!matches(&shortcut, keys::space, ModifierType::empty()));
+        assert!(!matches(&shortcut, keys::space, ModifierType::SHIFT_MASK | ModifierType::CONTROL_MASK));
+        assert!(matches(&parse_shortcut("F1").unwrap(), keys::F1, ModifierType::empty()));
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, Days, NaiveDate};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::config::TranscriptionBackend;
+
+/// Estimated price of a minute of audio on OpenAI's transcription API (whisper-1), in US dollars
+pub const OPENAI_USD_PER_MINUTE: f64 = 0.006;
+
+/// The fields of a `logging.jsonl` line that the statistics use
+#[derive(Deserialize)]
+struct LogLine {
+    timestamp: String,
+    #[serde(default)]
+    duration_secs: Option<f64>,
+    #[serde(default)]
+    chars: usize,
+    #[serde(default)]
+    backend: TranscriptionBackend,
+}
+
+/// Totals over the transcriptions of a period
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct UsageStats {
+    pub recordings: usize,
+    pub audio_secs: f64,
+    pub chars: usize,
+    /// Estimated with `OPENAI_USD_PER_MINUTE`; local backends cost nothing and formatting isn't counted
+    pub cost_usd: f64,
+}
+
+impl UsageStats {
+    fn add(&mut self, line: &LogLine) {
+        let secs = line.duration_secs.unwrap_or(0.0).max(0.0);
+        self.recordings += 1;
+        self.audio_secs += secs;
+        self.chars += line.chars;
+        if line.backend == TranscriptionBackend::Openai {
+            self.cost_usd += secs / 60.0 * OPENAI_USD_PER_MINUTE;
+        }
+    }
+}
+
+/// Usage of the current day and of the week so far (from Monday)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct UsageSummary {
+    pub today: UsageStats,
+    pub week: UsageStats,
+}
+
+/// Add up the lines of a transcription log for `today` and its week
+///
+/// Entries are dated in the offset they were written with, i.e. the local time then.
+/// Lines that don't parse are skipped.
+fn summarize(log: &str, today: NaiveDate) -> UsageSummary {
+    let week_start = today - Days::new(today.weekday().num_days_from_monday() as u64);
+    let mut summary = UsageSummary::default();
+    for line in log.lines().filter(|line| !line.trim().is_empty()) {
+        let Ok(entry) = serde_json::from_str::<LogLine>(line) else {
+            continue;
+        };
+        let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(&entry.timestamp) else {
+            continue;
+        };
+        let date = timestamp.date_naive();
+        if date == today {
+            summary.today.add(&entry);
+        }
+        if (week_start..=today).contains(&date) {
+            summary.week.add(&entry);
+        }
+    }
+    summary
+}
+
+/// Usage of today and this week from the transcription log at `path`; nothing logged yet is all zeros
+pub fn usage_summary(path: &Path) -> Result<UsageSummary> {
+    let log = match fs::read_to_string(path) {
+        Ok(log) => log,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(UsageSummary::default()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    Ok(summarize(&log, chrono::Local::now().date_naive()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_is_counted_for_today_and_the_week_so_far() {
+        let log = [
+            r#"{"timestamp":"2024-01-03T09:00:00+09:00","duration_secs":90.0,"chars":300,"backend":"openai"}"#,
+            r#"{"timestamp":"2024-01-03T18:30:00+09:00","duration_secs":30.0,"chars":100,"backend":"whisper_python"}"#,
+            r#"{"timestamp":"2024-01-01T08:00:00+09:00","duration_secs":60.0,"chars":50,"backend":"openai"}"#,
+            // 先週の分と壊れた行は数えない
+            r#"{"timestamp":"2023-12-31T23:59:00+09:00","duration_secs":600.0,"chars":999,"backend":"openai"}"#,
+            "not json",
+            r#"{"timestamp":"2024-01-02T12:00:00+09:00","duration_secs":null,"chars":20,"backend":"openai"}"#,
+        ]
+        .join("\n");
+        // 2024-01-03 は水曜日
+        let summary = summarize(&log, NaiveDate::from_ymd_opt(2024, 1, 3).unwrap());
+
+        assert_eq!(summary.today.recordings, 2);
+        assert_eq!(summary.today.audio_secs, 120.0);
+        assert_eq!(summary.today.chars, 400);
+        assert!((summary.today.cost_usd - 1.5 * OPENAI_USD_PER_MINUTE).abs() < 1e-9);
+
+        assert_eq!(summary.week.recordings, 4);
+        assert_eq!(summary.week.audio_secs, 180.0);
+        assert_eq!(summary.week.chars, 470);
+        assert!((summary.week.cost_usd - 2.5 * OPENAI_USD_PER_MINUTE).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,12 @@
+/// What the application is doing, shared by the window and the tray
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppStatus {
+    /// Nothing is being recorded or transcribed
+    Idle,
+    /// 録音ストリームは開いているが、音声を検出するまで待機中
+    Armed,
+    /// A recording is running
+    Recording,
+    /// A recording is being transcribed (a new one may already be queued)
+    Transcribing,
+}
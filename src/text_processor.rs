@@ -1,20 +1,107 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{BufReader, BufWriter};
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 use anyhow::{Result, Context};
 use reqwest::blocking::Client;
+use reqwest::StatusCode;
 use serde_json::{json, Value};
 use log::{info, error, warn};
 use regex;
 
-use crate::config::Config;
+use crate::api::{blocking_client_builder, request_headers, retry_after, with_retries, AttemptError};
+use crate::config::{ApiConfig, Config, FormattingStyle, LongInputMode, TextProcessingConfig, DEFAULT_SYSTEM_PROMPT};
+use crate::locks::lock_or_recover;
+
+/// Number of timestamped dictionary backups kept in `dictionaries/backups`
+const DICTIONARY_BACKUPS_KEPT: usize = 10;
+
+/// Minimum time between two automatic dictionary backups
+const DICTIONARY_BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Held while a dictionary file is written or restored, so saves from transcription
+/// workers and a restore from the window don't interleave
+static DICTIONARY_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Folder of the timestamped backups of the dictionary at `path`, next to it
+pub fn dictionary_backup_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new(".")).join("dictionaries").join("backups")
+}
+
+/// Backups of the dictionary at `path`, newest first
+pub fn list_dictionary_backups(path: &Path) -> Vec<PathBuf> {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let prefix = format!("{}-", stem);
+    let Ok(entries) = fs::read_dir(dictionary_backup_dir(path)) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|backup| {
+            backup.file_name().and_then(|name| name.to_str()).is_some_and(|name| {
+                name.starts_with(&prefix) && name.ends_with(".json")
+            })
+        })
+        .collect();
+    // ファイル名の日時は辞書順に並べると古い順になる
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+/// Copy the dictionary at `path` to the backup folder if the newest backup is older than
+/// `DICTIONARY_BACKUP_INTERVAL`, then drop all but the newest `DICTIONARY_BACKUPS_KEPT`
+///
+/// A file that does not parse is not backed up, so a corrupted dictionary never pushes
+/// out the good backups.
+fn backup_dictionary(path: &Path, now: SystemTime) -> Result<()> {
+    if UserDictionary::read(path).is_err() {
+        return Ok(());
+    }
+    let backups = list_dictionary_backups(path);
+    let latest = backups.first().and_then(|backup| fs::metadata(backup).and_then(|meta| meta.modified()).ok());
+    let due = latest.is_none_or(|latest| {
+        now.duration_since(latest).is_ok_and(|age| age >= DICTIONARY_BACKUP_INTERVAL)
+    });
+    if !due {
+        return Ok(());
+    }
+
+    let dir = dictionary_backup_dir(path);
+    fs::create_dir_all(&dir).context("辞書バックアップディレクトリの作成に失敗")?;
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let timestamp = chrono::DateTime::<chrono::Local>::from(now).format("%Y%m%d-%H%M%S");
+    let backup = dir.join(format!("{}-{}.json", stem, timestamp));
+    fs::copy(path, &backup).with_context(|| format!("辞書のバックアップに失敗: {}", backup.display()))?;
+    info!("辞書をバックアップしました: {}", backup.display());
+
+    for old in list_dictionary_backups(path).iter().skip(DICTIONARY_BACKUPS_KEPT) {
+        if let Err(e) = fs::remove_file(old) {
+            warn!("古い辞書バックアップを削除できませんでした ({}): {}", old.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Regex replacement applied after the literal words, e.g. `(\d{3})(\d{4})` → `$1-$2`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegexRule {
+    pub pattern: String,
+    pub replacement: String,
+}
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct UserDictionary {
     words: HashMap<String, String>,
     frequent_terms: HashMap<String, u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    regex_rules: Vec<RegexRule>,
+    /// `regex_rules` that compiled, in order; invalid patterns are left out
+    #[serde(skip)]
+    compiled_rules: Vec<(regex::Regex, String)>,
 }
 
 impl UserDictionary {
@@ -22,24 +109,17 @@ impl UserDictionary {
         Self {
             words: HashMap::new(),
             frequent_terms: HashMap::new(),
+            regex_rules: Vec::new(),
+            compiled_rules: Vec::new(),
         }
     }
 
     pub fn load(path: &Path) -> Self {
         if path.exists() {
-            match File::open(path) {
-                Ok(file) => {
-                    let reader = BufReader::new(file);
-                    match serde_json::from_reader(reader) {
-                        Ok(dict) => return dict,
-                        Err(e) => {
-                            error!("辞書ファイルの読み込みに失敗しました: {}", e);
-                            Self::new()
-                        }
-                    }
-                },
+            match Self::read(path) {
+                Ok(dict) => dict,
                 Err(e) => {
-                    error!("辞書ファイルを開けませんでした: {}", e);
+                    error!("{:#}", e);
                     Self::new()
                 }
             }
@@ -48,34 +128,177 @@ impl UserDictionary {
         }
     }
 
+    /// Read the dictionary at `path`, failing if it is missing or does not parse
+    fn read(path: &Path) -> Result<Self> {
+        let file = File::open(path).context("辞書ファイルを開けませんでした")?;
+        let mut dictionary: Self = serde_json::from_reader(BufReader::new(file))
+            .context("辞書ファイルの読み込みに失敗しました")?;
+        dictionary.compile_regex_rules();
+        Ok(dictionary)
+    }
+
+    /// Compile `regex_rules`, warning about and skipping the ones that are not valid regexes
+    ///
+    /// Invalid rules stay in the file so they can be fixed rather than silently lost on save.
+    fn compile_regex_rules(&mut self) {
+        self.compiled_rules = self.regex_rules
+            .iter()
+            .filter_map(|rule| match regex::Regex::new(&rule.pattern) {
+                Ok(regex) => Some((regex, rule.replacement.clone())),
+                Err(e) => {
+                    warn!("辞書の正規表現ルールが不正なためスキップします ({}): {}", rule.pattern, e);
+                    None
+                }
+            })
+            .collect();
+    }
+
+    /// Append a regex rule; an invalid pattern is kept but skipped like one loaded from the file
+    #[cfg(test)]
+    pub fn add_regex_rule(&mut self, pattern: String, replacement: String) {
+        self.regex_rules.push(RegexRule { pattern, replacement });
+        self.compile_regex_rules();
+    }
+
+    /// Write the dictionary to `path` atomically, backing up the previous file first
+    ///
+    /// The JSON goes to a temporary file that replaces `path` only once fully written,
+    /// so a crash mid-write leaves the old dictionary intact.
     pub fn save(&self, path: &Path) -> Result<()> {
+        let _file_lock = lock_or_recover(&DICTIONARY_FILE_LOCK);
+        self.write_file(path)
+    }
+
+    /// `save` for a caller that holds `DICTIONARY_FILE_LOCK`
+    fn write_file(&self, path: &Path) -> Result<()> {
         if let Some(dir) = path.parent() {
             if !dir.exists() {
                 fs::create_dir_all(dir).context("辞書ディレクトリの作成に失敗")?;
             }
         }
         
-        let file = File::create(path).context("辞書ファイルの作成に失敗")?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, self).context("辞書の保存に失敗")?;
+        // バックアップに失敗しても保存は続ける
+        if let Err(e) = backup_dictionary(path, SystemTime::now()) {
+            warn!("{:#}", e);
+        }
+        
+        let temp_path = path.with_extension("json.tmp");
+        let file = File::create(&temp_path).context("辞書ファイルの作成に失敗")?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self).context("辞書の保存に失敗")?;
+        writer.flush().context("辞書の保存に失敗")?;
+        writer.get_ref().sync_all().context("辞書の保存に失敗")?;
+        fs::rename(&temp_path, path).context("辞書ファイルの置き換えに失敗")?;
         Ok(())
     }
 
+    /// Replace the dictionary at `path` with `backup` and return the restored dictionary
+    ///
+    /// The current dictionary is backed up first like any other save.
+    pub fn restore_backup(path: &Path, backup: &Path) -> Result<Self> {
+        let _file_lock = lock_or_recover(&DICTIONARY_FILE_LOCK);
+        let dictionary = Self::read(backup)
+            .with_context(|| format!("バックアップを読み込めませんでした: {}", backup.display()))?;
+        dictionary.write_file(path)?;
+        info!("辞書をバックアップから復元しました: {}", backup.display());
+        Ok(dictionary)
+    }
+
     pub fn add_word(&mut self, original: String, replacement: String) {
         self.words.insert(original, replacement);
     }
 
+    /// 登録されている置換元の単語（ソート済み）
+    pub fn originals(&self) -> Vec<&str> {
+        let mut originals: Vec<&str> = self.words.keys().map(|s| s.as_str()).collect();
+        originals.sort();
+        originals
+    }
+
+    /// CSV（original, replacement の2列）から単語を取り込む
+    ///
+    /// 既存の単語と衝突した場合、`overwrite` が true なら置き換え、false なら既存を残す。
+    pub fn import_csv(&mut self, path: &Path, overwrite: bool) -> Result<CsvImportSummary> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("CSVファイルの読み込みに失敗: {}", path.display()))?;
+        // 表計算ソフトが付与するBOMを取り除く
+        let rows = parse_csv(content.trim_start_matches('\u{feff}'))?;
+
+        let mut summary = CsvImportSummary::default();
+        for (index, row) in rows.into_iter().enumerate() {
+            // ヘッダー行は読み飛ばす
+            if index == 0 && is_csv_header(&row) {
+                continue;
+            }
+
+            let (original, replacement) = match (row.first(), row.get(1)) {
+                (Some(original), Some(replacement)) if !original.trim().is_empty() => {
+                    (original.clone(), replacement.clone())
+                },
+                _ => {
+                    summary.invalid += 1;
+                    continue;
+                }
+            };
+
+            if self.words.contains_key(&original) && !overwrite {
+                summary.skipped += 1;
+                continue;
+            }
+
+            self.words.insert(original, replacement);
+            summary.imported += 1;
+        }
+
+        info!(
+            "CSVから辞書を取り込みました: {} 件追加, {} 件スキップ, {} 件無効",
+            summary.imported, summary.skipped, summary.invalid
+        );
+        Ok(summary)
+    }
+
+    /// 登録単語を CSV（original, replacement の2列）として書き出す
+    pub fn export_csv(&self, path: &Path) -> Result<usize> {
+        let mut entries: Vec<(&String, &String)> = self.words.iter().collect();
+        entries.sort();
+
+        let mut content = String::from("original,replacement\n");
+        for (original, replacement) in &entries {
+            content.push_str(&format!("{},{}\n", escape_csv_field(original), escape_csv_field(replacement)));
+        }
+
+        fs::write(path, content)
+            .with_context(|| format!("CSVファイルの書き込みに失敗: {}", path.display()))?;
+
+        info!("辞書をCSVに書き出しました: {} 件 ({})", entries.len(), path.display());
+        Ok(entries.len())
+    }
+
+    /// 学習した頻出語を消去し、消去した件数を返す（登録単語は残す）
+    pub fn clear_frequent_terms(&mut self) -> usize {
+        let count = self.frequent_terms.len();
+        self.frequent_terms.clear();
+        count
+    }
+
+    /// 登録単語をすべて消去し、消去した件数を返す（学習した頻出語は残す）
+    pub fn clear_words(&mut self) -> usize {
+        let count = self.words.len();
+        self.words.clear();
+        count
+    }
+
     pub fn update_frequency(&mut self, term: String) {
         let count = self.frequent_terms.entry(term).or_insert(0);
         *count += 1;
     }
 
     pub fn apply_dictionary(&self, text: &str) -> String {
-        if self.words.is_empty() {
+        if self.words.is_empty() && self.compiled_rules.is_empty() {
             return text.to_string();
         }
         
-        info!("辞書を適用します: {} 件の登録単語", self.words.len());
+        info!("辞書を適用します: {} 件の登録単語, {} 件の正規表現ルール", self.words.len(), self.compiled_rules.len());
         let mut result = text.to_string();
         
         // 単語を適切に分離して処理
@@ -83,71 +306,707 @@ impl UserDictionary {
             // 単語の前後に空白や句読点があるかチェックして、単語単位での置換を行う
             let pattern = format!(r"(^|\s|、|。|「|」){}($|\s|、|。|「|」)", regex::escape(original));
             if let Ok(regex) = regex::Regex::new(&pattern) {
-                result = regex.replace_all(&result, format!("$1{}$2", replacement)).to_string();
+                // ${1} と明示しないと "$1Rust" のようにグループ名と連結されてしまう
+                result = regex.replace_all(&result, format!("${{1}}{}${{2}}", replacement.replace('$', "$$"))).to_string();
                 continue;
             }
             
             // 正規表現エラーの場合は単純な文字列置換を行う
             result = result.replace(original, replacement);
         }
+
+        // 正規表現ルールは単語の置換後に登録順で適用する（置換先では $1 などのキャプチャを使える）
+        for (regex, replacement) in &self.compiled_rules {
+            result = regex.replace_all(&result, replacement.as_str()).to_string();
+        }
         
         info!("辞書適用後: {}", result);
         result
     }
 }
 
+/// Modification time and size of the file at `path`, to notice when it changes
+///
+/// The size is compared too, since two saves within the file system's timestamp
+/// granularity can share a modification time.
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// CSV取り込みの結果
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CsvImportSummary {
+    /// 追加・上書きした行数
+    pub imported: usize,
+    /// 既存の単語と衝突してスキップした行数
+    pub skipped: usize,
+    /// 列が足りない等で取り込めなかった行数
+    pub invalid: usize,
+}
+
+fn is_csv_header(row: &[String]) -> bool {
+    matches!(
+        (row.first(), row.get(1)),
+        (Some(a), Some(b)) if a.trim().eq_ignore_ascii_case("original") && b.trim().eq_ignore_ascii_case("replacement")
+    )
+}
+
+/// RFC 4180 形式のCSVを行ごとのフィールドに分解する
+///
+/// ダブルクォートで囲まれたフィールド内のカンマ・改行、`""` によるエスケープに対応する。
+fn parse_csv(content: &str) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                },
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {},
+            '\n' | '\r' => {
+                row.push(std::mem::take(&mut field));
+                // 空行は無視する
+                if !(row.len() == 1 && row[0].is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                } else {
+                    row.clear();
+                }
+            },
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(anyhow::anyhow!("CSVの引用符が閉じられていません"));
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// CSVフィールドを必要に応じてダブルクォートで囲む
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) || field.trim() != field {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// フィラー語を単語境界を考慮して除去する
+///
+/// 前後が文頭・文末・空白・句読点の場合、または直後に読点が続く場合のみ除去するため、
+/// 「あの人」のような語は残る。
+pub fn strip_filler_words(text: &str, filler_words: &[String]) -> String {
+    let mut fillers: Vec<&String> = filler_words.iter().filter(|w| !w.trim().is_empty()).collect();
+    if fillers.is_empty() {
+        return text.to_string();
+    }
+    // 長い語を優先してマッチさせる（「えーと」を「えー」より先に）
+    fillers.sort_by_key(|w| std::cmp::Reverse(w.chars().count()));
+    
+    let alternation = fillers.iter()
+        .map(|w| regex::escape(w))
+        .collect::<Vec<_>>()
+        .join("|");
+    // 前後が区切り文字のフィラー
+    let bounded = format!(
+        r"(?i)(^|[\s、。,.!?！？「」])(?:{})(\s+|$|[。.!?！？」])",
+        alternation
+    );
+    // 直後に読点が続くフィラー（日本語は前に区切りがないことが多い）
+    let comma = format!(r"(?i)(^|[^A-Za-z0-9])(?:{})[、,]\s*", alternation);
+    let (bounded, comma) = match (regex::Regex::new(&bounded), regex::Regex::new(&comma)) {
+        (Ok(bounded), Ok(comma)) => (bounded, comma),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("フィラー除去の正規表現の作成に失敗: {}", e);
+            return text.to_string();
+        }
+    };
+    
+    // 連続するフィラーは区切り文字を共有するため、変化がなくなるまで繰り返す
+    let mut result = text.to_string();
+    loop {
+        let replaced = comma.replace_all(&result, "$1").to_string();
+        let replaced = bounded.replace_all(&replaced, |caps: &regex::Captures| {
+            let (prefix, suffix) = (&caps[1], &caps[2]);
+            if suffix.trim().is_empty() {
+                prefix.to_string()
+            } else if prefix.trim().is_empty() {
+                // 「so um.」→「so.」のように句読点の前の空白も詰める
+                suffix.to_string()
+            } else {
+                format!("{}{}", prefix, suffix)
+            }
+        }).to_string();
+        
+        if replaced == result {
+            break;
+        }
+        result = replaced;
+    }
+    
+    result.trim().to_string()
+}
+
+lazy_static::lazy_static! {
+    static ref MARKDOWN_HEADING: regex::Regex = regex::Regex::new(r"^(\s*)#{1,6}\s+").unwrap();
+    static ref MARKDOWN_QUOTE: regex::Regex = regex::Regex::new(r"^(\s*)>\s?").unwrap();
+    static ref MARKDOWN_BULLET: regex::Regex = regex::Regex::new(r"^(\s*)[-*+]\s+").unwrap();
+    static ref MARKDOWN_STRONG: regex::Regex = regex::Regex::new(r"(\*\*|__)(\S(?:.*?\S)?)(\*\*|__)").unwrap();
+    static ref MARKDOWN_EMPHASIS: regex::Regex = regex::Regex::new(r"\*(\S(?:[^*]*?\S)?)\*").unwrap();
+    static ref MARKDOWN_CODE: regex::Regex = regex::Regex::new(r"`([^`]+)`").unwrap();
+    static ref MARKDOWN_LINK: regex::Regex = regex::Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").unwrap();
+}
+
+/// Markdown の記法（見出し・引用・箇条書きの記号、強調、インラインコード、リンク）を取り除く
+///
+/// 段落と改行、番号付きリストの番号はそのまま残す。
+/// 単一の `_` は snake_case などを壊さないよう強調として扱わない。
+pub fn strip_markdown(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = MARKDOWN_HEADING.replace(line, "${1}");
+            let line = MARKDOWN_QUOTE.replace(&line, "${1}");
+            let line = MARKDOWN_BULLET.replace(&line, "${1}");
+            let line = MARKDOWN_STRONG.replace_all(&line, "${2}");
+            let line = MARKDOWN_EMPHASIS.replace_all(&line, "${1}");
+            let line = MARKDOWN_CODE.replace_all(&line, "${1}");
+            MARKDOWN_LINK.replace_all(&line, "${1} (${2})").into_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 文頭の大文字化、連続する空白の圧縮、文末の句点の補完を行う
+///
+/// 大文字・小文字の区別がない日本語などの CJK を含むテキストはそのまま返す。
+/// 文の区切りは後ろに空白が続く `.` `!` `?` のみとし、URL や小数は変更しない。
+pub fn normalize_punctuation(text: &str) -> String {
+    if text.chars().any(is_cjk_char) {
+        return text.to_string();
+    }
+    text.lines()
+        .map(normalize_line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn normalize_line(line: &str) -> String {
+    let collapsed = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    
+    // 0: 文の途中, 1: 句読点の直後, 2: 文頭
+    let mut state = 2;
+    let mut normalized = String::with_capacity(collapsed.len() + 1);
+    for c in collapsed.chars() {
+        if state == 2 && c.is_alphabetic() {
+            normalized.extend(c.to_uppercase());
+            state = 0;
+            continue;
+        }
+        state = match (state, c) {
+            (_, '.' | '!' | '?') => 1,
+            (1, ' ') => 2,
+            // 文頭の引用符や括弧の後ろも大文字にする
+            (2, '"' | '\'' | '(' | '“' | '‘' | ' ') => 2,
+            _ => 0,
+        };
+        normalized.push(c);
+    }
+    
+    if normalized.chars().last().is_some_and(|c| c.is_alphanumeric()) {
+        normalized.push('.');
+    }
+    normalized
+}
+
+/// 文末（句点・改行など）で区切り、それぞれ max_chars 文字以内のチャンクに分ける
+///
+/// チャンクをつなげると元のテキストに戻る。一文が max_chars を超える場合は文字数で分割する。
+fn split_into_chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    
+    let mut sentences = Vec::new();
+    let mut sentence = String::new();
+    for c in text.chars() {
+        sentence.push(c);
+        if matches!(c, '。' | '．' | '.' | '!' | '?' | '！' | '？' | '\n') {
+            sentences.push(std::mem::take(&mut sentence));
+        }
+    }
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+    
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    let mut chunk_chars = 0;
+    for sentence in sentences {
+        let sentence_chars = sentence.chars().count();
+        if chunk_chars + sentence_chars > max_chars && !chunk.is_empty() {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_chars = 0;
+        }
+        if sentence_chars > max_chars {
+            // 長すぎる一文は文字数で区切る
+            let chars: Vec<char> = sentence.chars().collect();
+            for piece in chars.chunks(max_chars) {
+                chunks.push(piece.iter().collect());
+            }
+            continue;
+        }
+        chunk.push_str(&sentence);
+        chunk_chars += sentence_chars;
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// 空行で段落に分け、max_chars 文字以内の段落単位のチャンクにまとめる
+///
+/// 短い段落は次の段落と同じチャンクにまとめ、max_chars を超える段落は文末で区切る。
+/// 順番は元のテキストのまま。
+fn split_into_paragraphs(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let paragraphs = text
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+        .flat_map(|paragraph| split_into_chunks(paragraph, max_chars));
+    
+    let mut chunks: Vec<String> = Vec::new();
+    for paragraph in paragraphs {
+        let paragraph = paragraph.trim().to_string();
+        match chunks.last_mut() {
+            Some(chunk) if chunk.chars().count() + paragraph.chars().count() + 2 <= max_chars => {
+                chunk.push_str("\n\n");
+                chunk.push_str(&paragraph);
+            },
+            _ => chunks.push(paragraph),
+        }
+    }
+    chunks
+}
+
+/// 整形済みのチャンクをつなげる（英語などは空白を挟み、日本語はそのまま続ける）
+fn append_chunk(text: &mut String, chunk: &str) {
+    let chunk = chunk.trim();
+    let needs_space = match (text.chars().last(), chunk.chars().next()) {
+        (Some(last), Some(first)) => !last.is_whitespace() && !is_cjk_char(last) && !is_cjk_char(first),
+        _ => false,
+    };
+    if needs_space {
+        text.push(' ');
+    }
+    text.push_str(chunk);
+}
+
+fn is_cjk_char(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xFF00..=0xFFEF)
+}
+
+/// 通知などに表示するためにテキストを指定文字数以内に切り詰める
+///
+/// 文字単位で数えるためマルチバイト文字の途中で切れることはない。
+/// 末尾付近に空白や句読点があればそこで切り、省略記号を付ける。
+pub fn truncate_preview(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max_chars {
+        return text.to_string();
+    }
+    if max_chars == 0 {
+        return String::new();
+    }
+    
+    // 省略記号の分を空けておく
+    let mut cut = max_chars - 1;
+    
+    // 結合文字（濁点・異体字セレクタ等）を基底文字から切り離さない
+    while cut > 0 && is_combining_char(chars[cut]) {
+        cut -= 1;
+    }
+    
+    // 後ろ 1/4 の範囲に単語の区切りがあればそこで切る
+    let min_cut = cut - cut / 4;
+    if let Some(boundary) = (min_cut..cut).rev().find(|&i| is_preview_boundary(chars[i])) {
+        cut = boundary + 1;
+    }
+    
+    let mut preview: String = chars[..cut].iter().collect();
+    preview.truncate(preview.trim_end().len());
+    preview.push('…');
+    preview
+}
+
+fn is_combining_char(c: char) -> bool {
+    matches!(c as u32,
+        0x0300..=0x036F | 0x3099..=0x309A | 0xFE00..=0xFE0F | 0x200D | 0x1F3FB..=0x1F3FF)
+}
+
+fn is_preview_boundary(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '、' | '。' | ',' | '.' | '!' | '?' | '！' | '？')
+}
+
+/// Longest connection setup allowed for a formatting request
+const FORMATTER_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
 pub struct TextFormatter {
     client: Client,
+    retries: u32,
 }
 
 impl TextFormatter {
-    pub fn new() -> Self {
-        Self {
-            client: Client::new(),
+    /// Client for the formatting requests, with the same TLS settings as transcription
+    /// and the timeout of `text_processing.timeout_secs`
+    pub fn new(api: &ApiConfig, text_processing: &TextProcessingConfig) -> Self {
+        let mut builder = blocking_client_builder(api).connect_timeout(FORMATTER_CONNECT_TIMEOUT);
+        // reqwest の blocking クライアントは既定で 30 秒のタイムアウトがあるので、0 は明示的に無効にする
+        builder = match text_processing.timeout_secs {
+            0 => builder.timeout(None),
+            secs => builder.timeout(Duration::from_secs(secs)),
+        };
+        let client = builder
+            .build()
+            .unwrap_or_else(|_| {
+                warn!("Failed to build custom formatter client, using default");
+                Client::new()
+            });
+        Self { client, retries: text_processing.retries }
+    }
+    
+    /// POST `body` to `url`, retrying timeouts, connection errors, server errors and rate limits
+    fn post_with_retries(&self, url: &str, config: &Config, body: &Value) -> Result<Value> {
+        let max_attempts = self.retries as usize + 1;
+        let response = with_retries("GPT formatting request", max_attempts, |_| {
+            let response = self.client
+                .post(url)
+                .header("Authorization", format!("Bearer {}", config.api_key))
+                .headers(request_headers(&config.api))
+                .header("Content-Type", "application/json")
+                .json(body)
+                .send()
+                .map_err(|e| {
+                    let retryable = e.is_timeout() || e.is_connect();
+                    let e = anyhow::Error::new(e).context("APIリクエスト失敗");
+                    if retryable { AttemptError::Retry(e, None) } else { AttemptError::Fatal(e) }
+                })?;
+            let status = response.status();
+            if is_retryable_status(status) {
+                let requested_wait = retry_after(response.headers());
+                let error_text = response.text().unwrap_or_default();
+                return Err(AttemptError::Retry(anyhow::anyhow!("API エラー ({}): {}", status, error_text.trim()), requested_wait));
+            }
+            Ok(response)
+        })?;
+        response.json().context("JSONパース失敗")
+    }
+}
+
+/// Whether a formatting response is worth sending again
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Whether formatting failed because the request ran out of time
+fn is_timeout(error: &anyhow::Error) -> bool {
+    error.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(reqwest::Error::is_timeout)
+}
+
+/// Something the user should know about how a transcript was processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessingNotice {
+    /// GPT formatting failed and only the dictionary was applied; `timed_out` when it took too long
+    GptFailed { timed_out: bool },
+    /// A transcript of `chars` characters was formatted in `chunks` requests (`long_input = "chunk"`)
+    Chunked { chars: usize, chunks: usize },
+    /// Only the first `head_chars` of a transcript of `chars` characters were formatted
+    Truncated { chars: usize, head_chars: usize },
+}
+
+impl ProcessingNotice {
+    /// Message for the user in the UI language
+    pub fn message(self) -> String {
+        let t = crate::i18n::text();
+        match self {
+            ProcessingNotice::GptFailed { timed_out: true } => t.gpt_timed_out_dictionary_only.to_string(),
+            ProcessingNotice::GptFailed { timed_out: false } => t.gpt_failed_dictionary_only.to_string(),
+            ProcessingNotice::Chunked { chars, chunks } => t.formatted_in_chunks(chars, chunks),
+            ProcessingNotice::Truncated { chars, head_chars } => t.formatted_head_only(chars, head_chars),
+        }
+    }
+}
+
+/// One step of `text_processing.pipeline`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessingStep {
+    /// Apply the user dictionary locally
+    Dictionary,
+    /// Remove filler words locally
+    Fillers,
+    /// Format with GPT, with the dictionary embedded in the prompt
+    Gpt,
+    /// Fix sentence casing, spacing and terminal punctuation locally
+    Normalize,
+}
+
+impl ProcessingStep {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "dictionary" => Some(Self::Dictionary),
+            "fillers" => Some(Self::Fillers),
+            "gpt" => Some(Self::Gpt),
+            "normalize" => Some(Self::Normalize),
+            _ => None,
         }
     }
 }
 
+/// Parse the configured step names, skipping unknown ones with a warning
+pub fn parse_pipeline(names: &[String]) -> Vec<ProcessingStep> {
+    names.iter()
+        .filter_map(|name| {
+            let step = ProcessingStep::from_name(name);
+            if step.is_none() {
+                warn!("text_processing.pipeline の不明なステップ \"{}\" をスキップします", name);
+            }
+            step
+        })
+        .collect()
+}
+
 pub struct TranscriptionProcessor {
     dictionary: UserDictionary,
     formatter: TextFormatter,
     dictionary_path: std::path::PathBuf,
+    /// Modification time and size of the dictionary file when it was last read or written
+    dictionary_stamp: Option<(SystemTime, u64)>,
     config: Config,
 }
 
+/// Why `TranscriptionProcessor::reformat` couldn't start
+#[derive(Debug, thiserror::Error)]
+pub enum ReformatError {
+    /// Reformatting always goes through GPT, which needs the API key
+    #[error("No API key is set, so the transcript can't be reformatted")]
+    NoApiKey,
+}
+
 impl TranscriptionProcessor {
     pub fn new(config: Config) -> Self {
         let dictionary_path = config.temp_dir.join("user_dictionary.json");
+        let dictionary_stamp = file_stamp(&dictionary_path);
         let dictionary = UserDictionary::load(&dictionary_path);
-        let formatter = TextFormatter::new();
+        let formatter = TextFormatter::new(&config.api, &config.text_processing);
         
         Self {
             dictionary,
             formatter,
             dictionary_path,
+            dictionary_stamp,
             config,
         }
     }
     
-    pub fn process_transcription(&mut self, raw_text: &str) -> Result<String> {
+    /// Read the dictionary again if the file changed since this processor last read or wrote it,
+    /// e.g. after a word was added in the dictionary panel
+    fn reload_dictionary_if_changed(&mut self) {
+        let stamp = file_stamp(&self.dictionary_path);
+        if stamp != self.dictionary_stamp {
+            info!("辞書ファイルが更新されたため読み込み直します");
+            self.dictionary = UserDictionary::load(&self.dictionary_path);
+            self.dictionary_stamp = stamp;
+        }
+    }
+    
+    /// Process `raw_text` like `process_shared`, with a processor of its own
+    #[cfg(test)]
+    fn process_transcription(&mut self, raw_text: &str) -> Result<(String, Vec<ProcessingNotice>)> {
+        let mut run = self.start_run(raw_text);
+        while let Some(mut request) = self.advance(&mut run) {
+            let result = request.format_within_input_limit(&run.text);
+            self.finish_gpt_step(&mut run, request, result)?;
+        }
+        Ok(self.finish_run(run))
+    }
+    
+    /// Run `f` with the processor in `shared`, creating it from `config` the first time
+    fn with_shared<T>(shared: &Mutex<Option<Self>>, config: &Config, f: impl FnOnce(&mut Self) -> T) -> T {
+        f(lock_or_recover(shared).get_or_insert_with(|| Self::new(config.clone())))
+    }
+    
+    /// Run `raw_text` through `text_processing.pipeline` with the processor in `shared`, and
+    /// return the text with its notices
+    ///
+    /// The lock is held only while the dictionary is applied, learned from and saved; the GPT
+    /// requests run without it, so concurrent transcriptions don't wait for each other.
+    pub fn process_shared(shared: &Mutex<Option<Self>>, config: &Config, raw_text: &str) -> Result<(String, Vec<ProcessingNotice>)> {
         if raw_text.trim().is_empty() {
-            return Ok(String::new());
+            return Ok((String::new(), Vec::new()));
         }
         
         info!("文字起こしテキストの処理を開始: \"{}\"", raw_text);
-        
-        // GPTでテキスト整形（辞書情報をプロンプトに埋め込む）
-        let formatted = self.format_with_dictionary_embedded(raw_text)?;
-        
+        let mut run = Self::with_shared(shared, config, |processor| processor.start_run(raw_text));
+        while let Some(mut request) = Self::with_shared(shared, config, |processor| processor.advance(&mut run)) {
+            let result = request.format_within_input_limit(&run.text);
+            Self::with_shared(shared, config, |processor| processor.finish_gpt_step(&mut run, request, result))?;
+        }
+        Ok(Self::with_shared(shared, config, |processor| processor.finish_run(run)))
+    }
+    
+    /// Start processing `raw_text`, reading the dictionary again first if the file changed
+    fn start_run(&mut self, raw_text: &str) -> PipelineRun {
+        self.reload_dictionary_if_changed();
+        let pipeline = parse_pipeline(&self.config.text_processing.pipeline);
+        let mut steps = pipeline.clone();
+        // パイプラインに無い場合は最後に正規化する
+        if !pipeline.contains(&ProcessingStep::Normalize) {
+            steps.push(ProcessingStep::Normalize);
+        }
+        steps.reverse();
+        PipelineRun {
+            raw_text: raw_text.to_string(),
+            text: raw_text.to_string(),
+            pipeline,
+            steps,
+            notices: Vec::new(),
+        }
+    }
+    
+    /// Run the steps of `run` up to its next GPT request, which is returned to be sent without
+    /// holding the processor; None once the pipeline is done
+    fn advance(&self, run: &mut PipelineRun) -> Option<FormattingRequest> {
+        while let Some(step) = run.steps.pop() {
+            if step == ProcessingStep::Gpt && self.use_gpt() {
+                return Some(self.formatting_request());
+            }
+            run.text = self.run_local_step(step, &run.text, &run.pipeline);
+        }
+        None
+    }
+    
+    /// Whether the GPT step formats with GPT; without an API key (e.g. with a local backend) it doesn't
+    fn use_gpt(&self) -> bool {
+        self.config.text_processing.gpt_formatting && !self.config.api_key.is_empty()
+    }
+    
+    /// Run one pipeline step that doesn't send a GPT request on `text`
+    fn run_local_step(&self, step: ProcessingStep, text: &str, pipeline: &[ProcessingStep]) -> String {
+        let text_config = &self.config.text_processing;
+        match step {
+            ProcessingStep::Dictionary => self.dictionary.apply_dictionary(text),
+            ProcessingStep::Fillers => {
+                // フィラー（えー、あの等）をローカルで除去
+                if text_config.strip_filler_words {
+                    strip_filler_words(text, &text_config.filler_words)
+                } else {
+                    text.to_string()
+                }
+            },
+            ProcessingStep::Gpt => {
+                // APIキーが無い場合（ローカルのバックエンド利用時など）はGPT整形を行わない
+                if text_config.gpt_formatting {
+                    info!("APIキーが未設定のため、GPT整形をスキップします");
+                }
+                if !pipeline.contains(&ProcessingStep::Dictionary) {
+                    // GPTを使わない場合は辞書をローカルで適用
+                    self.dictionary.apply_dictionary(text)
+                } else {
+                    text.to_string()
+                }
+            },
+            ProcessingStep::Normalize => {
+                if text_config.normalize_punctuation {
+                    normalize_punctuation(text)
+                } else {
+                    text.to_string()
+                }
+            },
+        }
+    }
+    
+    /// Take the `result` of the GPT `request` of `run`
+    fn finish_gpt_step(&self, run: &mut PipelineRun, request: FormattingRequest, result: Result<String>) -> Result<()> {
+        run.notices.extend(request.notices);
+        match result {
+            Ok(formatted) => run.text = formatted,
+            Err(e) if self.config.text_processing.strict => return Err(e),
+            Err(e) => {
+                // 整形に失敗しても文字起こし自体は使えるので、辞書だけ適用して返す
+                warn!("GPT整形に失敗したため、辞書の置換のみ適用します: {}", e);
+                run.notices.push(ProcessingNotice::GptFailed { timed_out: is_timeout(&e) });
+                if !run.pipeline.contains(&ProcessingStep::Dictionary) {
+                    run.text = self.dictionary.apply_dictionary(&run.text);
+                }
+            },
+        }
+        Ok(())
+    }
+    
+    /// Learn the word frequencies of `run` and save the dictionary, returning the text and notices
+    fn finish_run(&mut self, run: PipelineRun) -> (String, Vec<ProcessingNotice>) {
         // 単語の頻度学習
-        self.learn_from_text(raw_text);
+        self.learn_from_text(&run.raw_text);
         
-        // 辞書保存
-        if let Err(e) = self.dictionary.save(&self.dictionary_path) {
-            warn!("辞書の保存に失敗: {}", e);
+        // 辞書保存（自分で書いた内容は次回読み込み直さない）
+        match self.dictionary.save(&self.dictionary_path) {
+            Ok(_) => self.dictionary_stamp = file_stamp(&self.dictionary_path),
+            Err(e) => warn!("辞書の保存に失敗: {}", e),
         }
         
-        Ok(formatted)
+        (run.text, run.notices)
+    }
+    
+    /// Copy what a GPT formatting request needs from this processor
+    fn formatting_request(&self) -> FormattingRequest {
+        FormattingRequest {
+            formatter: self.formatter.clone(),
+            config: self.config.clone(),
+            words: self.dictionary.words.clone(),
+            instruction: String::new(),
+            notices: Vec::new(),
+        }
+    }
+    
+    /// Format existing transcript text again with the dictionary, following `instruction`
+    /// (e.g. "make it more concise") on top of the usual prompt
+    ///
+    /// Fails with `ReformatError::NoApiKey` without an API key.
+    pub fn reformat(&self, text: &str, instruction: &str) -> Result<String> {
+        if self.config.api_key.is_empty() {
+            return Err(ReformatError::NoApiKey.into());
+        }
+        info!("追加の指示で再整形します: \"{}\"", instruction.trim());
+        // 長い文字起こしは通常の整形と同じく段落や max_input_chars で分けて送る
+        let mut request = self.formatting_request();
+        request.instruction = instruction.to_string();
+        request.format_within_input_limit(text)
     }
     
     pub fn add_custom_word(&mut self, original: String, replacement: String) -> Result<()> {
@@ -165,9 +1024,94 @@ impl TranscriptionProcessor {
             }
         }
     }
+}
+
+/// One transcript on its way through `text_processing.pipeline`
+struct PipelineRun {
+    raw_text: String,
+    /// The text after the steps run so far
+    text: String,
+    pipeline: Vec<ProcessingStep>,
+    /// Steps still to run, the last one first
+    steps: Vec<ProcessingStep>,
+    notices: Vec<ProcessingNotice>,
+}
+
+/// What a GPT formatting request needs from a `TranscriptionProcessor`, copied so the request
+/// can be sent without holding the shared processor
+struct FormattingRequest {
+    formatter: TextFormatter,
+    config: Config,
+    words: HashMap<String, String>,
+    /// One-off instruction from the "Reformat" field, sent with every chunk
+    instruction: String,
+    /// Messages for the user about the request, e.g. chunked formatting
+    notices: Vec<ProcessingNotice>,
+}
+
+impl FormattingRequest {
+    /// GPT整形。max_input_chars を超える場合は long_input に従い、分割するか先頭だけを整形する
+    fn format_within_input_limit(&mut self, text: &str) -> Result<String> {
+        let max_chars = self.config.text_processing.max_input_chars;
+        let length = text.chars().count();
+        if self.config.text_processing.paragraph_split {
+            let paragraph_chars = match max_chars {
+                0 => self.config.text_processing.paragraph_chars,
+                max_chars => self.config.text_processing.paragraph_chars.min(max_chars),
+            };
+            if length > paragraph_chars {
+                return self.format_by_paragraphs(text, paragraph_chars);
+            }
+        }
+        if max_chars == 0 || length <= max_chars {
+            return self.format_with_dictionary_embedded(text);
+        }
+        
+        let chunks = split_into_chunks(text, max_chars);
+        match self.config.text_processing.long_input {
+            LongInputMode::Chunk => {
+                warn!("文字起こしが max_input_chars ({}) を超えたため、{}回に分けて整形します", max_chars, chunks.len());
+                let mut formatted = String::new();
+                for chunk in &chunks {
+                    append_chunk(&mut formatted, &self.format_with_dictionary_embedded(chunk)?);
+                }
+                self.notices.push(ProcessingNotice::Chunked { chars: length, chunks: chunks.len() });
+                Ok(formatted)
+            },
+            LongInputMode::Truncate => {
+                let head = &chunks[0];
+                let head_chars = head.chars().count();
+                warn!("文字起こしが max_input_chars ({}) を超えたため、先頭の{}文字だけを整形します", max_chars, head_chars);
+                let formatted = self.format_with_dictionary_embedded(head)?;
+                self.notices.push(ProcessingNotice::Truncated { chars: length, head_chars });
+                Ok(format!("{}\n\n{}", formatted.trim_end(), text[head.len()..].trim()))
+            },
+        }
+    }
+
+    /// Format `text` one paragraph-sized chunk at a time and join the results in order
+    ///
+    /// Every request carries the full prompt, dictionary included.
+    fn format_by_paragraphs(&mut self, text: &str, paragraph_chars: usize) -> Result<String> {
+        let chunks = split_into_paragraphs(text, paragraph_chars);
+        info!("文字起こしを{}個の段落に分けて整形します", chunks.len());
+        let mut formatted = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            let paragraph = self.format_with_dictionary_embedded(chunk)?;
+            if !paragraph.trim().is_empty() {
+                formatted.push(paragraph.trim().to_string());
+            }
+        }
+        Ok(formatted.join("\n\n"))
+    }
 
     // 辞書情報をプロンプトに埋め込んだGPT処理
     fn format_with_dictionary_embedded(&self, input_text: &str) -> Result<String> {
+        self.format_with_instruction(input_text, &self.instruction)
+    }
+
+    /// Format `input_text` with GPT, with the dictionary and an optional one-off instruction in the prompt
+    fn format_with_instruction(&self, input_text: &str, instruction: &str) -> Result<String> {
         if input_text.trim().is_empty() {
             return Ok(String::new());
         }
@@ -175,47 +1119,66 @@ impl TranscriptionProcessor {
         // 辞書の内容をプロンプトに埋め込む
         let mut dictionary_instructions = String::new();
         
-        if !self.dictionary.words.is_empty() {
+        if !self.words.is_empty() {
             dictionary_instructions.push_str("When the following words or expressions appear, make sure to modify them exactly as specified:\n");
             
-            for (original, replacement) in &self.dictionary.words {
+            for (original, replacement) in &self.words {
                 dictionary_instructions.push_str(&format!("- Replace \"{}\" with \"{}\"\n", original, replacement));
             }
             
             dictionary_instructions.push_str("\nEnsure to apply these word replacements exactly as specified while maintaining the word usage context.\n\n");
         }
         
+        // 録音の状況を前提として伝える
+        let context_instructions = context_instructions(&self.config.text_processing.context);
+        let extra_instructions = extra_instructions(instruction);
+        let style = self.config.text_processing.style;
+        // 箇条書きの要約では改行・箇条書きの設定より要約の指示を優先する
+        let layout_instructions = match style {
+            FormattingStyle::BulletSummary => "",
+            _ => layout_instructions(
+                self.config.text_processing.allow_linebreaks,
+                self.config.text_processing.allow_bullets,
+            ),
+        };
+        
         let prompt = format!(
             "Enhance this transcribed text while preserving the original language:\n\
             - Keep the text in its original language - do not translate\n\
-            - Remove excessive filler words (like えー, あの) only if they are overly frequent\n\
-            - Preserve casual speech patterns and tone\n\
-            - Keep the original writing style and expressions\n\
-            - Add line breaks and paragraph separations only where necessary\n\
-            - Add bullet points or lists where contextually appropriate\n\
+            {}\
+            {}\
+            {}\
+            {}\
             {}\
             Input text: {}", 
-            dictionary_instructions, input_text
+            style_instructions(style), layout_instructions, dictionary_instructions, context_instructions, extra_instructions, input_text
         );
 
-        info!("GPTによるテキスト整形とワード置換を開始（辞書単語数: {}）", self.dictionary.words.len());
-        let response = self.formatter.client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
-            .header("Content-Type", "application/json")
-            .json(&json!({
-                "model": "gpt-4o-mini",
-                "messages": [
-                    {"role": "system", "content": "You are a transcription proofreader. Maintain the original language of the input text. Never translate. Output the corrected text directly without any meta-commentary."},
-                    {"role": "user", "content": prompt}
-                ],
-                "temperature": 0.5,
-                "max_tokens": 1000
-            }))
-            .send()
-            .context("APIリクエスト失敗")?;
-
-        let response_json: Value = response.json().context("JSONパース失敗")?;
+        // システムプロンプトが空の場合はデフォルトを使う
+        let system_prompt = match self.config.text_processing.system_prompt.trim() {
+            "" => {
+                warn!("text_processing.system_prompt が空のため、デフォルトを使用します");
+                DEFAULT_SYSTEM_PROMPT
+            },
+            prompt => prompt,
+        };
+
+        // 長い入力の末尾が切れないよう、max_tokens は入力の長さに合わせる
+        let max_tokens = self.config.text_processing.max_tokens
+            .unwrap_or_else(|| auto_max_tokens(input_text));
+
+        info!("GPTによるテキスト整形とワード置換を開始（辞書単語数: {}, max_tokens: {}）", self.words.len(), max_tokens);
+        let url = crate::config::endpoint_url(&self.config.api_base_url, &self.config.api.formatting_path);
+        let body = json!({
+            "model": "gpt-4o-mini",
+            "messages": [
+                {"role": "system", "content": system_prompt},
+                {"role": "user", "content": prompt}
+            ],
+            "temperature": 0.5,
+            "max_tokens": max_tokens
+        });
+        let response_json = self.formatter.post_with_retries(&url, &self.config, &body)?;
         
         if let Some(error) = response_json.get("error") {
             let error_message = error.get("message").and_then(|m| m.as_str()).unwrap_or("Unknown error");
@@ -231,4 +1194,379 @@ impl TranscriptionProcessor {
         info!("テキスト整形完了");
         Ok(formatted_text)
     }
-} 
\ No newline at end of file
+}
+
+/// Output token limit of the formatter model
+const FORMATTER_MAX_OUTPUT_TOKENS: u32 = 16384;
+/// Smallest `max_tokens` sent to the formatter
+const FORMATTER_MIN_TOKENS: u32 = 256;
+
+/// Rough token count: about four ASCII characters per token, one token per other character
+fn estimate_tokens(text: &str) -> u32 {
+    let ascii = text.chars().filter(char::is_ascii).count();
+    let other = text.chars().count() - ascii;
+    (ascii.div_ceil(4) + other) as u32
+}
+
+/// Prompt lines describing the situation of the recording; empty without a context
+fn context_instructions(context: &str) -> String {
+    match context.trim() {
+        "" => String::new(),
+        context => format!(
+            "Context of this recording (use it to interpret terms and format the text; do not add it to the output): {}\n\n",
+            context
+        ),
+    }
+}
+
+/// Prompt lines for a one-off instruction from the "Reformat" field; empty when blank
+fn extra_instructions(instruction: &str) -> String {
+    match instruction.trim() {
+        "" => String::new(),
+        instruction => format!("Additionally, follow this instruction from the user: {}\n\n", instruction),
+    }
+}
+
+/// Prompt lines of the `text_processing.style` preset
+fn style_instructions(style: FormattingStyle) -> &'static str {
+    match style {
+        FormattingStyle::Verbatim => "- Fix only punctuation and clear recognition errors\n\
+            - Keep every word, including filler words and repetitions\n\
+            - Do not rephrase, reorder or shorten anything\n",
+        FormattingStyle::LightCleanup => "- Remove excessive filler words (like えー, あの) only if they are overly frequent\n\
+            - Preserve casual speech patterns and tone\n\
+            - Keep the original writing style and expressions\n",
+        FormattingStyle::BulletSummary => "- Summarize the content as a concise bullet-point list, one point per line\n\
+            - Keep names, numbers and decisions exactly as said\n\
+            - Leave out filler words, repetitions and small talk\n",
+        FormattingStyle::FormalRewrite => "- Rewrite it in a polite, formal written style suitable for business documents\n\
+            - Keep all of the content and its meaning\n\
+            - Remove filler words and spoken-language expressions\n",
+    }
+}
+
+/// Prompt lines on the output shape for `text_processing.allow_linebreaks` and `allow_bullets`
+fn layout_instructions(allow_linebreaks: bool, allow_bullets: bool) -> &'static str {
+    match (allow_linebreaks, allow_bullets) {
+        (true, true) => "- Add line breaks and paragraph separations only where necessary\n\
+            - Add bullet points or lists where contextually appropriate\n",
+        (true, false) => "- Add line breaks and paragraph separations only where necessary\n\
+            - Do not use bullet points or lists\n",
+        (false, true) => "- Add bullet points or lists where contextually appropriate, one item per line\n\
+            - Do not add line breaks or paragraph separations otherwise\n",
+        // チャットアプリなどにそのまま貼れる 1 段落にする
+        (false, false) => "- Return a single clean paragraph: no line breaks, bullet points or lists\n",
+    }
+}
+
+/// `max_tokens` for formatting `input_text`: the input estimate plus room for
+/// added punctuation and line breaks, capped at the model limit
+fn auto_max_tokens(input_text: &str) -> u32 {
+    let estimate = estimate_tokens(input_text);
+    (estimate + estimate / 2 + FORMATTER_MIN_TOKENS).min(FORMATTER_MAX_OUTPUT_TOKENS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_back_up_hourly_and_restore() {
+        let dir = std::env::temp_dir().join(format!("wispr_dictionary_backup_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("user_dictionary.json");
+        let mut dictionary = UserDictionary::new();
+        dictionary.add_word("ラスト".to_string(), "Rust".to_string());
+        dictionary.save(&path).unwrap();
+        assert!(list_dictionary_backups(&path).is_empty());
+
+        // 2回目以降の保存で1時間に1回だけバックアップされる
+        dictionary.add_word("ギット".to_string(), "Git".to_string());
+        dictionary.save(&path).unwrap();
+        dictionary.save(&path).unwrap();
+        let backups = list_dictionary_backups(&path);
+        assert_eq!(backups.len(), 1);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let restored = UserDictionary::restore_backup(&path, &backups[0]).unwrap();
+        assert_eq!(restored.originals(), vec!["ラスト"]);
+        assert_eq!(UserDictionary::load(&path).originals(), vec!["ラスト"]);
+
+        for hours in 1..=DICTIONARY_BACKUPS_KEPT as u64 + 2 {
+            backup_dictionary(&path, SystemTime::now() + Duration::from_secs(hours * 3600)).unwrap();
+        }
+        assert_eq!(list_dictionary_backups(&path).len(), DICTIONARY_BACKUPS_KEPT);
+
+        // 壊れた辞書はバックアップしない
+        fs::write(&path, "{ broken").unwrap();
+        let newest = list_dictionary_backups(&path)[0].clone();
+        backup_dictionary(&path, SystemTime::now() + Duration::from_secs(100 * 3600)).unwrap();
+        assert_eq!(list_dictionary_backups(&path)[0], newest);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn clearing_frequencies_keeps_words_and_vice_versa() {
+        let mut dictionary = UserDictionary::new();
+        dictionary.add_word("ラスト".to_string(), "Rust".to_string());
+        dictionary.update_frequency("音声".to_string());
+        dictionary.update_frequency("認識".to_string());
+
+        assert_eq!(dictionary.clear_frequent_terms(), 2);
+        assert!(dictionary.frequent_terms.is_empty());
+        assert_eq!(dictionary.originals(), vec!["ラスト"]);
+
+        dictionary.update_frequency("音声".to_string());
+        assert_eq!(dictionary.clear_words(), 1);
+        assert!(dictionary.words.is_empty());
+        assert_eq!(dictionary.frequent_terms.len(), 1);
+    }
+
+    #[test]
+    fn parse_csv_handles_quoted_newlines_and_crlf() {
+        let content = "original,replacement\r\n\"a,b\",\"1行目\n2行目\"\r\n\"say \"\"hi\"\"\",x\r\n\r\nlast,row";
+        let rows = parse_csv(content).unwrap();
+        assert_eq!(rows, vec![
+            vec!["original".to_string(), "replacement".to_string()],
+            vec!["a,b".to_string(), "1行目\n2行目".to_string()],
+            vec!["say \"hi\"".to_string(), "x".to_string()],
+            vec!["last".to_string(), "row".to_string()],
+        ]);
+        assert!(parse_csv("\"unterminated,x\n").is_err());
+    }
+
+    #[test]
+    fn csv_import_skips_bom_and_header_and_round_trips_the_export() {
+        let dir = std::env::temp_dir().join(format!("wispr_dictionary_csv_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("words.csv");
+        fs::write(&path, "\u{feff}original,replacement\r\nラスト,Rust\r\n\"改行\",\"1行目\r\n2行目\"\r\nonly\r\n").unwrap();
+
+        let mut dictionary = UserDictionary::new();
+        let summary = dictionary.import_csv(&path, false).unwrap();
+        assert_eq!((summary.imported, summary.skipped, summary.invalid), (2, 0, 1));
+        assert_eq!(dictionary.words["改行"], "1行目\r\n2行目");
+
+        let exported = dir.join("exported.csv");
+        assert_eq!(dictionary.export_csv(&exported).unwrap(), 2);
+        let mut reimported = UserDictionary::new();
+        assert_eq!(reimported.import_csv(&exported, false).unwrap().imported, 2);
+        assert_eq!(reimported.words, dictionary.words);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn regex_rules_use_capture_groups_and_skip_invalid_patterns() {
+        let mut dictionary = UserDictionary::new();
+        dictionary.add_word("ラスト".to_string(), "Rust".to_string());
+        dictionary.add_regex_rule(r"(\d{3})(\d{4})(\d{4})".to_string(), "${1}-${2}-${3}".to_string());
+        dictionary.add_regex_rule("(unclosed".to_string(), "x".to_string());
+        assert_eq!(dictionary.compiled_rules.len(), 1);
+        assert_eq!(
+            dictionary.apply_dictionary("電話は 09012345678 です ラスト で書く"),
+            "電話は 090-1234-5678 です Rust で書く"
+        );
+
+        // 不正なルールも保存後に残り、読み込み時にスキップされる
+        let json = serde_json::to_string(&dictionary).unwrap();
+        let mut loaded: UserDictionary = serde_json::from_str(&json).unwrap();
+        loaded.compile_regex_rules();
+        assert_eq!(loaded.regex_rules.len(), 2);
+        assert_eq!(loaded.apply_dictionary("09012345678"), "090-1234-5678");
+
+        // 既存の辞書ファイルにはルールがなくてもよい
+        let old: UserDictionary = serde_json::from_str(r#"{"words":{},"frequent_terms":{}}"#).unwrap();
+        assert!(old.regex_rules.is_empty());
+    }
+
+    #[test]
+    fn truncate_preview_keeps_short_text() {
+        assert_eq!(truncate_preview("こんにちは", 10), "こんにちは");
+    }
+
+    #[test]
+    fn truncate_preview_does_not_split_multibyte_chars() {
+        let text = "今日は音声認識の精度について話をしたいと思います".repeat(5);
+        for max in 0..40 {
+            let preview = truncate_preview(&text, max);
+            assert!(preview.chars().count() <= max);
+        }
+        assert!(truncate_preview(&text, 20).ends_with('…'));
+    }
+
+    #[test]
+    fn truncate_preview_prefers_word_boundary() {
+        assert_eq!(truncate_preview("hello wonderful world", 18), "hello wonderful…");
+        assert_eq!(truncate_preview("えーと、今日はいい天気ですね", 5), "えーと、…");
+    }
+
+    #[test]
+    fn truncate_preview_keeps_combining_marks() {
+        // 「か」+ 結合用濁点
+        let text = "か\u{3099}か\u{3099}か\u{3099}か\u{3099}";
+        let preview = truncate_preview(text, 4);
+        assert!(!preview.trim_end_matches('…').ends_with('か'));
+    }
+
+    #[test]
+    fn only_transient_formatting_errors_are_retried() {
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable_status(StatusCode::OK));
+        assert!(!is_timeout(&anyhow::anyhow!("API エラー: timeout")));
+    }
+
+    #[test]
+    fn context_is_added_only_when_set() {
+        assert_eq!(context_instructions("  "), "");
+        let instructions = context_instructions(" Rust の技術ミーティング\n");
+        assert!(instructions.contains(": Rust の技術ミーティング\n\n"));
+        assert_eq!(extra_instructions(" \n"), "");
+        assert!(extra_instructions("もっと簡潔に").ends_with(": もっと簡潔に\n\n"));
+    }
+
+    #[test]
+    fn layout_instructions_follow_the_toggles() {
+        assert!(layout_instructions(true, true).contains("Add bullet points"));
+        assert!(layout_instructions(true, false).contains("Do not use bullet points"));
+        assert!(layout_instructions(false, true).contains("Do not add line breaks"));
+        let single = layout_instructions(false, false);
+        assert!(single.contains("single clean paragraph"));
+        assert!(!single.contains("Add "));
+        assert!(single.ends_with('\n'));
+    }
+
+    #[test]
+    fn every_style_has_its_own_instructions() {
+        let instructions: Vec<_> = FormattingStyle::ALL.into_iter().map(style_instructions).collect();
+        for (i, lines) in instructions.iter().enumerate() {
+            assert!(lines.ends_with('\n'));
+            assert!(!instructions[..i].contains(lines));
+        }
+        assert!(style_instructions(FormattingStyle::LightCleanup).contains("filler words (like えー, あの)"));
+        assert!(style_instructions(FormattingStyle::BulletSummary).contains("bullet-point"));
+    }
+
+    #[test]
+    fn auto_max_tokens_scales_with_input() {
+        let short = auto_max_tokens("hello");
+        let long = auto_max_tokens(&"あ".repeat(3000));
+        assert!((FORMATTER_MIN_TOKENS..300).contains(&short));
+        assert!(long > 3000);
+        assert_eq!(auto_max_tokens(&"あ".repeat(50000)), FORMATTER_MAX_OUTPUT_TOKENS);
+    }
+
+    #[test]
+    fn strip_markdown_removes_list_and_emphasis_markers() {
+        let text = "## 議事録\n- **決定**: リリースは *来週*\n  * `cargo test` を実行\n> 引用\n1. 番号は残す";
+        assert_eq!(
+            strip_markdown(text),
+            "議事録\n決定: リリースは 来週\n  cargo test を実行\n引用\n1. 番号は残す"
+        );
+    }
+
+    #[test]
+    fn strip_markdown_keeps_plain_text() {
+        let text = "snake_case_name と 2 * 3 * 4 はそのまま\n\n次の段落";
+        assert_eq!(strip_markdown(text), text);
+        assert_eq!(strip_markdown("[資料](https://example.com)"), "資料 (https://example.com)");
+    }
+
+    #[test]
+    fn parse_pipeline_skips_unknown_steps() {
+        let names: Vec<String> = ["dictionary", "spellcheck", "Fillers", "gpt"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            parse_pipeline(&names),
+            vec![ProcessingStep::Dictionary, ProcessingStep::Fillers, ProcessingStep::Gpt]
+        );
+    }
+
+    #[test]
+    fn normalize_punctuation_capitalizes_english_sentences() {
+        assert_eq!(
+            normalize_punctuation("hello   world. this is a test!  see example.com or 3.5 more"),
+            "Hello world. This is a test! See example.com or 3.5 more."
+        );
+        assert_eq!(normalize_punctuation("first line\n\n\"quoted\" second?"), "First line.\n\n\"Quoted\" second?");
+    }
+
+    #[test]
+    fn normalize_punctuation_leaves_japanese_unchanged() {
+        let text = "今日は  Rust の話をします\nよろしくお願いします";
+        assert_eq!(normalize_punctuation(text), text);
+    }
+
+    #[test]
+    fn long_text_is_split_at_sentence_ends() {
+        let text = "一つ目の文です。二つ目の文です。三つ目の文です。";
+        let chunks = split_into_chunks(text, 17);
+        assert_eq!(chunks, vec!["一つ目の文です。二つ目の文です。", "三つ目の文です。"]);
+        
+        // 長すぎる一文は文字数で区切り、つなげると元に戻る
+        let long = "a".repeat(25);
+        let chunks = split_into_chunks(&long, 10);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 10));
+        assert_eq!(chunks.concat(), long);
+        
+        let mut joined = String::new();
+        append_chunk(&mut joined, "First part.");
+        append_chunk(&mut joined, "Second part.");
+        append_chunk(&mut joined, "次の部分。");
+        assert_eq!(joined, "First part. Second part.次の部分。");
+    }
+
+    #[test]
+    fn paragraphs_are_grouped_up_to_the_limit() {
+        let text = "最初の段落です。\n\n二つ目。\n\n\n三つ目の段落はとても長い文です。続きの文です。";
+        let chunks = split_into_paragraphs(text, 16);
+        assert_eq!(chunks, vec!["最初の段落です。\n\n二つ目。", "三つ目の段落はとても長い文です。", "続きの文です。"]);
+        assert!(split_into_paragraphs("\n\n", 10).is_empty());
+    }
+
+    fn test_processor(name: &str, pipeline: &[&str]) -> TranscriptionProcessor {
+        let dir = std::env::temp_dir().join(format!("wispr_pipeline_steps_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = crate::config::default_config();
+        config.temp_dir = dir;
+        config.api_key.clear();
+        config.text_processing.pipeline = pipeline.iter().map(|s| s.to_string()).collect();
+        
+        let mut processor = TranscriptionProcessor::new(config);
+        processor.dictionary.add_word("ラスト".to_string(), "Rust".to_string());
+        processor
+    }
+
+    #[test]
+    fn pipeline_runs_only_the_configured_steps() {
+        let text = "えーと、今日は「ラスト」の話";
+        
+        let mut dictionary_only = test_processor("dictionary", &["dictionary"]);
+        assert_eq!(dictionary_only.process_transcription(text).unwrap().0, "えーと、今日は「Rust」の話");
+        
+        let mut fillers_only = test_processor("fillers", &["fillers"]);
+        assert_eq!(fillers_only.process_transcription(text).unwrap().0, "今日は「ラスト」の話");
+        
+        // GPTが使えない場合は辞書をローカルで適用する（従来の動作）
+        let mut default_pipeline = test_processor("default", &["gpt", "fillers"]);
+        assert_eq!(default_pipeline.process_transcription(text).unwrap().0, "今日は「Rust」の話");
+    }
+    
+    #[test]
+    fn gpt_failure_falls_back_to_the_dictionary_unless_strict() {
+        let text = "えーと、今日は「ラスト」の話";
+        
+        let mut processor = test_processor("fallback", &["gpt", "fillers"]);
+        // 接続できないAPIで整形を失敗させる
+        processor.config.api_key = "test-key".to_string();
+        processor.config.api_base_url = "http://127.0.0.1:9".to_string();
+        let (formatted, notices) = processor.process_transcription(text).unwrap();
+        assert_eq!(formatted, "今日は「Rust」の話");
+        assert_eq!(notices, [ProcessingNotice::GptFailed { timed_out: false }]);
+        
+        processor.config.text_processing.strict = true;
+        assert!(processor.process_transcription(text).is_err());
+    }
+}
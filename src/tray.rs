@@ -5,25 +5,23 @@ use gtk::prelude::*;
 use log::{info, error};
 use std::sync::mpsc::{self, Sender, Receiver};
 use anyhow::{Result, anyhow};
-use tray_icon::{TrayIconBuilder, Icon, menu::{Menu, MenuItem, MenuId}};
-use crate::config::Config;
+use std::cell::RefCell;
+use tray_icon::{ClickType, TrayIcon, TrayIconBuilder, TrayIconEvent, Icon, menu::{Menu, MenuId, MenuItem, Submenu}};
+use crate::clipboard;
+use crate::config::{ClipboardConfig, Config};
+use crate::locks::lock_or_recover;
+use crate::status::AppStatus;
+use crate::text_processor::truncate_preview;
 
-/// Application status representation
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum AppStatus {
-    /// Application is idle
-    Idle,
-    /// Application is recording
-    Recording,
-    /// Application is transcribing
-    Transcribing,
-}
+/// Characters shown for each entry of the "Recent" submenu
+const RECENT_LABEL_CHARS: usize = 40;
 
 impl AppStatus {
     /// Get the icon name based on the status
     pub fn icon_name(&self) -> &'static str {
         match self {
             AppStatus::Idle => "microphone-sensitivity-muted-symbolic",
+            AppStatus::Armed => "microphone-sensitivity-low-symbolic",
             AppStatus::Recording => "microphone-sensitivity-high-symbolic",
             AppStatus::Transcribing => "system-run-symbolic",
         }
@@ -33,8 +31,9 @@ impl AppStatus {
     pub fn tooltip(&self) -> &'static str {
         match self {
             AppStatus::Idle => "Wispr - Click to start recording",
+            AppStatus::Armed => "Wispr - Waiting for voice... Click to stop",
             AppStatus::Recording => "Wispr - Recording... Click to stop",
-            AppStatus::Transcribing => "Wispr - Processing audio...",
+            AppStatus::Transcribing => "Wispr - Processing audio... Click to record the next one",
         }
     }
     
@@ -42,10 +41,42 @@ impl AppStatus {
     pub fn menu_item_label(&self) -> &'static str {
         match self {
             AppStatus::Idle => "Start Recording",
+            AppStatus::Armed => "Stop Waiting for Voice",
             AppStatus::Recording => "Stop Recording",
-            AppStatus::Transcribing => "Processing...",
+            AppStatus::Transcribing => "Start Next Recording (processing...)",
+        }
+    }
+    
+    /// Get the tray icon color (RGBA) based on the status
+    pub fn icon_color(&self) -> (u8, u8, u8, u8) {
+        match self {
+            AppStatus::Idle => (0, 0, 255, 255),
+            AppStatus::Armed => (128, 0, 128, 255),
+            AppStatus::Recording => (255, 0, 0, 255),
+            AppStatus::Transcribing => (255, 165, 0, 255),
         }
     }
+    
+    /// Create the tray icon for the status
+    fn icon(&self) -> Result<Icon> {
+        let (r, g, b, a) = self.icon_color();
+        let icon_data = create_default_icon(r, g, b, a);
+        Icon::from_rgba(icon_data.data, icon_data.width, icon_data.height)
+            .map_err(|e| anyhow!("Failed to create tray icon: {}", e))
+    }
+}
+
+/// Tray widgets that change after setup
+struct TrayHandles {
+    icon: TrayIcon,
+    record_item: MenuItem,
+    recent_menu: Submenu,
+}
+
+thread_local! {
+    /// Tray icon and the menu items updated at runtime.
+    /// These are not Send, so they live on the GTK main thread that created them.
+    static TRAY: RefCell<Option<TrayHandles>> = const { RefCell::new(None) };
 }
 
 /// Application state
@@ -54,6 +85,8 @@ struct AppState {
     pub status: AppStatus,
     config: Config,
     tx_main: Sender<TrayMessage>,
+    /// Menu items of the "Recent" submenu and the transcript each one copies
+    recent: Vec<(MenuId, String)>,
 }
 
 impl AppState {
@@ -62,6 +95,7 @@ impl AppState {
             status: AppStatus::Idle,
             config: config.clone(),
             tx_main,
+            recent: Vec::new(),
         }
     }
     
@@ -71,7 +105,7 @@ impl AppState {
                 self.status = AppStatus::Recording;
                 let _ = self.tx_main.send(TrayMessage::StartRecording);
             },
-            AppStatus::Recording => {
+            AppStatus::Armed | AppStatus::Recording => {
                 self.status = AppStatus::Transcribing;
                 let _ = self.tx_main.send(TrayMessage::StopRecording);
             },
@@ -96,16 +130,30 @@ pub enum TrayMessage {
     StopRecording,
     /// Show transcript
     ShowTranscript,
-    /// Update UI with new status
+    /// Transcribe the audio file whose path is on the clipboard
+    TranscribeClipboard,
+    /// Open the config file in the default editor
+    OpenConfig,
+    /// Reload the config file without restarting
+    ReloadConfig,
+    /// Show the window's status in the icon, tooltip and record item
     UpdateStatus(AppStatus),
+    /// Replace the "Recent" submenu with these transcripts (newest first)
+    UpdateRecent(Vec<String>),
     /// Request to exit the application
     Exit,
 }
 
-/// Runs the tray application and returns a join handle and a sender for communication
-pub fn run_tray_application(config: Config) -> Result<(JoinHandle<Result<()>>, Sender<TrayMessage>)> {
+/// The tray thread, a sender to it, and a receiver of the menu actions for the window
+pub type TrayChannels = (JoinHandle<Result<()>>, Sender<TrayMessage>, Receiver<TrayMessage>);
+
+/// Runs the tray application
+///
+/// Returns a join handle, a sender for communication, and a receiver for the
+/// menu actions the window should carry out.
+pub fn run_tray_application(config: Config) -> Result<TrayChannels> {
     // Channel for communication with the main thread
-    let (tx_main, _rx_main) = mpsc::channel();
+    let (tx_main, rx_main) = mpsc::channel();
     let (tx_handler, rx_handler) = mpsc::channel();
     
     // Set up app state
@@ -113,6 +161,7 @@ pub fn run_tray_application(config: Config) -> Result<(JoinHandle<Result<()>>, S
         status: AppStatus::Idle,
         config: config.clone(),
         tx_main: tx_main.clone(),
+        recent: Vec::new(),
     }));
     
     // Create and setup the tray icon in the main thread
@@ -121,7 +170,7 @@ pub fn run_tray_application(config: Config) -> Result<(JoinHandle<Result<()>>, S
     // Create a thread to handle commands
     let handler_thread = create_handler_thread(app_state.clone(), rx_handler, tx_main.clone());
     
-    Ok((handler_thread, tx_handler))
+    Ok((handler_thread, tx_handler, rx_main))
 }
 
 /// Create a thread to handle commands from the main application
@@ -136,15 +185,14 @@ fn create_handler_thread(app_state: Arc<Mutex<AppState>>, rx: Receiver<TrayMessa
                             info!("Exiting tray application");
                             break;
                         },
+                        // 状態はウィンドウが実際に切り替えた後に UpdateStatus で届く
                         TrayMessage::StartRecording => {
                             info!("Starting recording");
-                            update_tray_status(app_state.clone(), AppStatus::Recording);
                             // Forward to main thread
                             let _ = tx_main.send(TrayMessage::StartRecording);
                         },
                         TrayMessage::StopRecording => {
                             info!("Stopping recording");
-                            update_tray_status(app_state.clone(), AppStatus::Transcribing);
                             // Forward to main thread
                             let _ = tx_main.send(TrayMessage::StopRecording);
                         },
@@ -153,9 +201,27 @@ fn create_handler_thread(app_state: Arc<Mutex<AppState>>, rx: Receiver<TrayMessa
                             // Forward to main thread
                             let _ = tx_main.send(TrayMessage::ShowTranscript);
                         },
+                        TrayMessage::TranscribeClipboard => {
+                            info!("Transcribing clipboard audio path");
+                            // Forward to main thread
+                            let _ = tx_main.send(TrayMessage::TranscribeClipboard);
+                        },
+                        TrayMessage::OpenConfig => {
+                            info!("Opening config file");
+                            // Forward to main thread
+                            let _ = tx_main.send(TrayMessage::OpenConfig);
+                        },
+                        TrayMessage::ReloadConfig => {
+                            info!("Reloading config file");
+                            // Forward to main thread
+                            let _ = tx_main.send(TrayMessage::ReloadConfig);
+                        },
                         TrayMessage::UpdateStatus(status) => {
                             update_tray_status(app_state.clone(), status);
                         },
+                        TrayMessage::UpdateRecent(transcripts) => {
+                            update_tray_recent(app_state.clone(), transcripts);
+                        },
                     }
                 },
                 Err(e) => {
@@ -185,18 +251,35 @@ fn setup_tray_icon(app_state: Arc<Mutex<AppState>>, tx: Sender<TrayMessage>) ->
     let _ = menu.append(&record_item);
     
     // Transcript item
-    let transcript_item = MenuItem::new("Show Transcript", true, None);
+    let transcript_item = MenuItem::new("Show/Hide Window", true, None);
     let transcript_id = transcript_item.id().clone();
     let _ = menu.append(&transcript_item);
     
+    // Clipboard audio item
+    let clipboard_item = MenuItem::new("Transcribe Clipboard Audio", true, None);
+    let clipboard_id = clipboard_item.id().clone();
+    let _ = menu.append(&clipboard_item);
+    
+    // Config file items
+    let open_config_item = MenuItem::new("Open Config", true, None);
+    let open_config_id = open_config_item.id().clone();
+    let _ = menu.append(&open_config_item);
+    let reload_config_item = MenuItem::new("Reload Config", true, None);
+    let reload_config_id = reload_config_item.id().clone();
+    let _ = menu.append(&reload_config_item);
+    
+    // Recent transcripts, filled in as transcriptions complete
+    let recent_menu = Submenu::new("Recent", true);
+    let _ = recent_menu.append(&MenuItem::new("No transcripts yet", false, None));
+    let _ = menu.append(&recent_menu);
+    
     // Quit item
     let quit_item = MenuItem::new("Quit", true, None);
     let quit_id = quit_item.id().clone();
     let _ = menu.append(&quit_item);
     
     // Create tray icon
-    let idle_icon = create_default_icon(0, 0, 255, 255);
-    let icon = Icon::from_rgba(idle_icon.data, idle_icon.width, idle_icon.height).unwrap();
+    let icon = AppStatus::Idle.icon()?;
     
     let tray_icon = TrayIconBuilder::new()
         .with_menu(Box::new(menu))
@@ -204,6 +287,13 @@ fn setup_tray_icon(app_state: Arc<Mutex<AppState>>, tx: Sender<TrayMessage>) ->
         .with_icon(icon)
         .build()?;
     
+    // Keep the tray icon alive on the main thread so it can be updated later
+    TRAY.with(|tray| *tray.borrow_mut() = Some(TrayHandles {
+        icon: tray_icon,
+        record_item,
+        recent_menu,
+    }));
+    
     // Set up menu item event handlers using the menu channel
     let menu_channel = tray_icon::menu::MenuEvent::receiver();
     let tx_clone = tx.clone();
@@ -213,18 +303,20 @@ fn setup_tray_icon(app_state: Arc<Mutex<AppState>>, tx: Sender<TrayMessage>) ->
     thread::spawn(move || {
         while let Ok(event) = menu_channel.recv() {
             if *event.id() == record_id {
-                let mut state = app_state_clone.lock().unwrap();
-                match state.status {
-                    AppStatus::Idle => {
-                        let _ = tx_clone.send(TrayMessage::StartRecording);
-                    },
-                    AppStatus::Recording => {
-                        let _ = tx_clone.send(TrayMessage::StopRecording);
-                    },
-                    _ => {}
-                }
+                request_toggle_recording(&app_state_clone, &tx_clone);
             } else if *event.id() == transcript_id {
                 let _ = tx_clone.send(TrayMessage::ShowTranscript);
+            } else if *event.id() == clipboard_id {
+                let _ = tx_clone.send(TrayMessage::TranscribeClipboard);
+            } else if *event.id() == open_config_id {
+                let _ = tx_clone.send(TrayMessage::OpenConfig);
+            } else if *event.id() == reload_config_id {
+                let _ = tx_clone.send(TrayMessage::ReloadConfig);
+            } else if let Some((text, clipboard_config)) = recent_transcript(&app_state_clone, event.id()) {
+                match clipboard::copy_transcript(&text, &clipboard_config) {
+                    Ok(_) => info!("Recent transcript copied to clipboard"),
+                    Err(e) => error!("Failed to copy recent transcript: {}", e),
+                }
             } else if *event.id() == quit_id {
                 let _ = tx_clone.send(TrayMessage::Exit);
                 gtk::main_quit();
@@ -232,19 +324,99 @@ fn setup_tray_icon(app_state: Arc<Mutex<AppState>>, tx: Sender<TrayMessage>) ->
         }
     });
     
+    // 左クリックで録音を開始/停止する（クリックイベントを送らないプラットフォームではメニューのみ）
+    let tray_events = TrayIconEvent::receiver();
+    let tx_clone = tx.clone();
+    let app_state_clone = app_state.clone();
+    thread::spawn(move || {
+        while let Ok(event) = tray_events.recv() {
+            if event.click_type == ClickType::Left {
+                request_toggle_recording(&app_state_clone, &tx_clone);
+            }
+        }
+    });
+    
     Ok(())
 }
 
+/// Ask the handler thread to start or stop recording, as the "Start/Stop Recording" item does
+///
+/// While transcribing a new recording starts at once; the window queues its transcription
+/// behind the running one, so a press during a slow transcription isn't lost.
+fn request_toggle_recording(app_state: &Arc<Mutex<AppState>>, tx: &Sender<TrayMessage>) {
+    let status = lock_or_recover(app_state).status;
+    match status {
+        AppStatus::Idle | AppStatus::Transcribing => {
+            let _ = tx.send(TrayMessage::StartRecording);
+        },
+        AppStatus::Armed | AppStatus::Recording => {
+            let _ = tx.send(TrayMessage::StopRecording);
+        },
+    }
+}
+
 fn update_tray_status(app_state: Arc<Mutex<AppState>>, status: AppStatus) {
-    let mut state = app_state.lock().unwrap();
+    let mut state = lock_or_recover(&app_state);
     state.status = status;
     
-    // Update tray icon based on status
-    // This is a placeholder - the actual implementation would update the icon
-    // through GTK's main thread
+    // The tray icon lives on the GTK main thread, so apply the change from an idle callback
+    glib::idle_add_once(move || {
+        TRAY.with(|tray| {
+            if let Some(TrayHandles { icon: tray_icon, record_item, .. }) = tray.borrow().as_ref() {
+                match status.icon() {
+                    Ok(icon) => {
+                        if let Err(e) = tray_icon.set_icon(Some(icon)) {
+                            error!("Failed to update tray icon: {}", e);
+                        }
+                    },
+                    Err(e) => error!("{}", e),
+                }
+                if let Err(e) = tray_icon.set_tooltip(Some(status.tooltip())) {
+                    error!("Failed to update tray tooltip: {}", e);
+                }
+                record_item.set_text(status.menu_item_label());
+            }
+        });
+    });
+    
     info!("Tray status updated to: {:?}", status);
 }
 
+/// Look up the transcript behind a "Recent" menu item, with the clipboard settings
+fn recent_transcript(app_state: &Arc<Mutex<AppState>>, id: &MenuId) -> Option<(String, ClipboardConfig)> {
+    let state = app_state.lock().ok()?;
+    let (_, text) = state.recent.iter().find(|(item_id, _)| item_id == id)?;
+    Some((text.clone(), state.config.clipboard.clone()))
+}
+
+/// Rebuild the "Recent" submenu; clicking an entry copies the full transcript
+fn update_tray_recent(app_state: Arc<Mutex<AppState>>, transcripts: Vec<String>) {
+    // Menu items must be created on the GTK main thread
+    glib::idle_add_once(move || {
+        TRAY.with(|tray| {
+            let tray = tray.borrow();
+            let Some(handles) = tray.as_ref() else {
+                return;
+            };
+            
+            while handles.recent_menu.remove_at(0).is_some() {}
+            
+            let mut recent = Vec::new();
+            for text in transcripts {
+                let label = truncate_preview(&text.replace('\n', " "), RECENT_LABEL_CHARS);
+                let item = MenuItem::new(label, true, None);
+                let _ = handles.recent_menu.append(&item);
+                recent.push((item.id().clone(), text));
+            }
+            if recent.is_empty() {
+                let _ = handles.recent_menu.append(&MenuItem::new("No transcripts yet", false, None));
+            }
+            
+            lock_or_recover(&app_state).recent = recent;
+        });
+    });
+}
+
 struct IconData {
     data: Vec<u8>,
     width: u32,
@@ -271,4 +443,26 @@ fn create_default_icon(r: u8, g: u8, b: u8, a: u8) -> IconData {
         height,
         channels: 4,
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::default_config;
+
+    #[test]
+    fn window_status_updates_the_stored_status() {
+        let (tx_main, rx_main) = mpsc::channel();
+        let app_state = Arc::new(Mutex::new(AppState::new(default_config(), tx_main.clone())));
+        let (tx, rx) = mpsc::channel();
+        let handler = create_handler_thread(app_state.clone(), rx, tx_main);
+
+        // メニューからの録音開始はウィンドウに転送するだけで、状態は変えない
+        tx.send(TrayMessage::StartRecording).unwrap();
+        tx.send(TrayMessage::UpdateStatus(AppStatus::Armed)).unwrap();
+        tx.send(TrayMessage::Exit).unwrap();
+        handler.join().unwrap().unwrap();
+
+        assert!(matches!(rx_main.try_recv(), Ok(TrayMessage::StartRecording)));
+        assert_eq!(lock_or_recover(&app_state).status, AppStatus::Armed);
+    }
+}
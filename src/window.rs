@@ -1,47 +1,73 @@
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 use std::sync::mpsc::{self, Sender};
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
 use gtk::{self, prelude::*};
 use gtk::{Button, Label, Window, WindowType, Box as GtkBox, Orientation, ScrolledWindow, TextView, TextBuffer};
 use gtk::{ComboBoxText, LevelBar, Frame, ToggleButton};
 use glib;
 use glib::ControlFlow;
 use gdk;
-use log::{info, error};
+use log::{debug, info, error, warn};
 use anyhow::Result;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
 
-use crate::config::Config;
-use crate::audio::AudioRecorder;
-use crate::api::TranscriptionAPI;
+use crate::config::{ApiConfig, ClipboardConfig, Config, FormattingStyle, NewTranscriptMode, OverlayCorner, ShortcutMode, SoundsConfig, TranscriptionBackend, UiConfig, WindowLayout};
+use crate::audio::{self, AudioRecorder, LevelScale, Recording, VoiceActivityDetector};
+use crate::api::{Markers, ProcessedTranscript, TranscriptionAPI, TranscriptionError};
 use crate::clipboard;
-use crate::text_processor::TranscriptionProcessor;
+use crate::integrations;
+use crate::error_report;
+use crate::stats;
+use crate::locks::lock_or_recover;
+use crate::shortcut::{self, ParsedShortcut};
+use crate::status::AppStatus;
+#[cfg(feature = "health")]
+use crate::health;
+#[cfg(feature = "http-control")]
+use crate::http_control::{self, ControlAction};
+use crate::text_processor::{list_dictionary_backups, truncate_preview, UserDictionary};
 
 #[cfg(feature = "tray")]
 use crate::tray;
 
 // Global static to hold the audio recorder between messages
 static mut GLOBAL_RECORDER: Option<AudioRecorder> = None;
-// Global flag for audio monitoring
+// Global flag for audio monitoring (cleared on exit to stop the handler thread)
 static AUDIO_MONITORING: AtomicBool = AtomicBool::new(false);
-// Global flag to track if shortcut key is currently pressed
-static SHORTCUT_KEY_PRESSED: AtomicBool = AtomicBool::new(false);
+// Whether the input stream is kept open to show the level while idle
+static IDLE_MONITORING: AtomicBool = AtomicBool::new(true);
+// Asks the monitoring loop to reopen its stream (stream error, or resume from suspend)
+static MONITOR_RESTART: AtomicBool = AtomicBool::new(false);
+// When the monitoring stream last delivered data, in milliseconds since the Unix epoch
+static LAST_MONITOR_DATA: AtomicU64 = AtomicU64::new(0);
+// Set by the monitoring stream when voice-activated start hears sustained speech
+static VAD_TRIGGERED: AtomicBool = AtomicBool::new(false);
 // Global audio level for monitoring (shared between threads)
 lazy_static::lazy_static! {
     static ref AUDIO_LEVEL: Arc<Mutex<f64>> = Arc::new(Mutex::new(0.0));
+    // Recent idle monitor input prepended to recordings (recording.preroll_secs)
+    static ref PREROLL: Arc<Mutex<audio::PrerollBuffer>> = Arc::new(Mutex::new(audio::PrerollBuffer::default()));
+    // Input device selected in the window, which the monitoring stream opens (None for the default)
+    static ref MONITOR_DEVICE: Mutex<Option<String>> = Mutex::new(None);
     static ref RECORDING_START_TIME: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
+    // Maximum recording duration in seconds set in the window, read by running recordings
+    static ref MAX_DURATION_SECS: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
     static ref BUTTON_UPDATE_TIMER_ID: Arc<Mutex<Option<glib::SourceId>>> = Arc::new(Mutex::new(None));
     static ref PROCESSING_STATUS_TIMER_ID: Arc<Mutex<Option<glib::SourceId>>> = Arc::new(Mutex::new(None));
     static ref PROCESSING_DOTS: Arc<Mutex<usize>> = Arc::new(Mutex::new(0));
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum AppStatus {
-    Idle,
-    Recording,
-    Transcribing,
+#[cfg(feature = "tray")]
+lazy_static::lazy_static! {
+    // トレイへの通知用（トレイ機能が有効な場合のみ）
+    static ref TRAY_SENDER: Mutex<Option<Sender<tray::TrayMessage>>> = Mutex::new(None);
 }
 
 #[derive(Debug, Clone)]
@@ -52,14 +78,79 @@ pub enum WindowMessage {
     StartRecording,
     /// Stop recording and process
     StopRecording,
-    /// Show transcript
-    ShowTranscript,
-    /// Update UI with new status
-    UpdateStatus(AppStatus),
+    /// Stop recording and discard it without transcribing
+    CancelRecording,
+    /// Copy the current transcript to the clipboard
+    CopyTranscript,
     /// Update transcript text
     UpdateTranscript(String),
-    /// Stop processing timer
-    StopProcessingTimer,
+    /// A queued transcription of the given queue generation finished (None when the recording was skipped),
+    /// with the tag chosen for it
+    TranscriptionFinished(u64, Option<Result<String, String>>, Option<String>),
+    /// A transcription of the given queue generation was copied and can be pasted (`shortcuts.auto_paste`)
+    PasteTranscript(u64),
+    /// Drop all running and queued transcriptions and return to idle
+    AbortTranscription,
+    /// Show a warning message above the transcript
+    ShowWarning(String),
+    /// Transcribe the audio file whose path is on the clipboard
+    TranscribeClipboard,
+    /// Transcribe an audio file picked in the file chooser
+    TranscribeFile(String),
+    /// Transcribe an audio file whose path was just copied (`integrations.watch_clipboard`)
+    TranscribeCopiedFile(String),
+    /// Offer to transcribe the recordings an earlier crash left in `temp_dir`, now repaired
+    RecoveredRecordings(Vec<PathBuf>),
+    /// Transcribe the recovered recordings the user agreed to
+    TranscribeRecoveredRecordings(Vec<PathBuf>),
+    /// Format the current transcript again, following this extra instruction
+    Reformat(String),
+    /// Reformatting of this transcript text finished with the new text or an error message
+    ReformatFinished(String, Result<String, String>),
+    /// Restore the transcript text before the last replacement
+    UndoTranscript,
+    /// Re-apply a transcript replacement that was undone
+    RedoTranscript,
+    /// Refresh the history panel for the current search text
+    FilterHistory,
+    /// Record a short clip and play it back to check the microphone
+    TestMicrophone,
+    /// The microphone test finished with this result text
+    MicTestFinished(String),
+    /// Tooltip describing the formats of this input device (None for the default device)
+    DeviceCapabilities(Option<String>, String),
+    /// Input and output devices listed again after the refresh button was clicked
+    AudioDevicesListed(AudioDeviceList),
+    /// The countdown before recording reached zero
+    CountdownFinished,
+    /// Voice-activated start: speech heard while idle starts a recording without the countdown
+    VoiceDetected,
+    /// Mark the current point of the recording (`shortcuts.drop_marker`)
+    DropMarker,
+    /// Open the config file in the default editor
+    OpenConfig,
+    /// Reload the config file and apply it without restarting
+    ReloadConfig,
+    /// Use this `text_processing.context` for the following transcriptions
+    SetFormattingContext(String),
+    /// Use this `text_processing.style` for the following transcriptions
+    SetFormattingStyle(FormattingStyle),
+    /// Play the microphone test and the cue sounds on this sink (None for the default output)
+    SetOutputDevice(Option<String>),
+    /// Save this `api.active_profile` (None for the default account) and reload the config
+    SetApiProfile(Option<String>),
+    /// Stop recordings after this many seconds, including the one running
+    SetMaxDuration(u64),
+    /// Write the current maximum recording duration to the config file
+    SaveMaxDuration,
+    /// The API rejected the API key; show the banner pointing to the settings
+    ApiKeyRejected,
+    /// Language the API reported for the transcription that just finished (`verbose_json`);
+    /// None when it reported none
+    DetectedLanguage(Option<String>),
+    /// Show the window if it is hidden or in the background, otherwise hide it
+    #[cfg(feature = "tray")]
+    ToggleVisibility,
 }
 
 /// Shared state that is thread-safe and can be sent between threads
@@ -67,35 +158,362 @@ struct ThreadSafeState {
     status: AppStatus,
     config: Config,
     transcript: String,
-    api: TranscriptionAPI,
+    // Shared with transcription workers so they don't hold the state lock during API calls
+    api: Arc<TranscriptionAPI>,
 }
 
 /// UI state that contains GTK widgets and cannot be sent between threads
 struct UiState {
+    window: Window,
     state: Arc<Mutex<ThreadSafeState>>,
     tx_main: Sender<WindowMessage>,
     record_button: Button,
+    /// Transcribe without the dictionary and formatting while active
+    verbatim_toggle: ToggleButton,
+    /// Tag for the following transcripts (`ui.tags`)
+    tag_combo: ComboBoxText,
+    profile_combo: ComboBoxText,
+    transcript_view: TextView,
     transcript_buffer: TextBuffer,
+    /// The live transcript view first, then earlier transcripts (`ui.on_new_transcript = "new_tab"`)
+    transcript_tabs: gtk::Notebook,
+    /// Scroll the transcript to the end whenever it is replaced
+    autoscroll: bool,
     device_combo: ComboBoxText,
+    /// Changed handler of `device_combo`, blocked while a refresh refills it
+    device_changed: glib::SignalHandlerId,
+    output_combo: ComboBoxText,
+    /// Changed handler of `output_combo`, blocked while a refresh refills it
+    output_changed: glib::SignalHandlerId,
+    language_combo: ComboBoxText,
+    context_entry: gtk::Entry,
+    style_combo: ComboBoxText,
     audio_level: LevelBar,
+    /// Scrolling level history below the meter (`ui.show_waveform`)
+    waveform: gtk::DrawingArea,
     device_box: GtkBox,
+    monitor_check: gtk::CheckButton,
     shortcut_frame: Frame,
     dict_frame: Frame,
     dict_buffer: TextBuffer,
     timer_label: Label,
+    warning_label: Label,
+    /// Shown while the OpenAI backend has no API key, or after the API rejected it
+    api_key_banner: GtkBox,
+    api_key_label: Label,
+    queue_label: Label,
+    /// Language detected in the last transcription, when the API reports it
+    detected_language_label: Label,
+    abort_button: Button,
+    mic_test_button: Button,
+    /// Disabled while a reformat request runs
+    reformat_button: Button,
+    mic_test_label: Label,
+    queue: TranscriptionQueue,
+    history: TranscriptHistory,
+    /// Completed transcripts, newest first (the tray shows the first few)
+    recent_transcripts: VecDeque<HistoryEntry>,
+    history_search: gtk::SearchEntry,
+    history_buffer: TextBuffer,
+    /// Config file the settings were loaded from (opened and reloaded from the UI)
+    config_path: PathBuf,
+    shortcut_label: Label,
+    /// Key handlers of the current shortcuts, disconnected when the config is reloaded
+    shortcut_handlers: Vec<glib::SignalHandlerId>,
+    /// Cancel flag of the countdown running before a recording starts
+    countdown: Option<Rc<Cell<bool>>>,
+    /// Status the record button and timer show; `None` after something else changed them
+    shown_status: Cell<Option<AppStatus>>,
+    /// A start request arrived during a recording; it starts once that recording is stopped
+    pending_recording: Cell<bool>,
+    /// The microphone test holds the input device until `MicTestFinished` arrives
+    mic_testing: bool,
+    /// Font of the transcript and dictionary views (`ui.font_family` / `ui.font_size`)
+    font_css: gtk::CssProvider,
+    /// Last recording or wake-up, counted from for `ui.idle_timeout_secs`
+    last_activity: Instant,
+    /// Idle monitoring was stopped by `ui.idle_timeout_secs` and comes back on wake-up
+    dormant: bool,
+    /// Markers dropped during the current recording and its pre-roll
+    markers: Markers,
+    /// Maximum recording duration in minutes; starts from `recording.max_duration_secs`
+    max_duration_spin: gtk::SpinButton,
+    /// Value-changed handler of `max_duration_spin`, blocked while a reload sets the value
+    max_duration_changed: glib::SignalHandlerId,
+    /// Indicator shown while recording (`ui.recording_overlay`)
+    recording_overlay: Option<Window>,
+    overlay_corner: OverlayCorner,
+}
+
+/// Make `recorder` the active recorder in `slot` unless one is already there
+///
+/// A refused recorder is handed back so the caller releases its stream instead of
+/// replacing (and leaking) the running one.
+fn store_recorder<R>(slot: &mut Option<R>, recorder: R) -> std::result::Result<(), R> {
+    if slot.is_some() {
+        return Err(recorder);
+    }
+    *slot = Some(recorder);
+    Ok(())
+}
+
+/// Signal number of SIGHUP on Linux, which reloads the config file
+const SIGHUP: i32 = 1;
+
+/// Number of completed transcripts offered for re-copy in the tray
+#[cfg(feature = "tray")]
+const RECENT_TRANSCRIPTS_LIMIT: usize = 5;
+/// Number of completed transcripts kept for the history panel
+const TRANSCRIPT_LIST_LIMIT: usize = 100;
+
+/// How a settled hold-mode release ends the recording
+#[derive(Debug, PartialEq, Eq)]
+enum HoldRelease {
+    /// Held long enough: stop and transcribe
+    Held,
+    /// Released before `shortcuts.min_hold_ms`: discard the recording
+    Tap,
+}
+
+/// Filters recording shortcut events from key auto-repeat and release/re-press glitches
+///
+/// Repeated presses while held are ignored, as is a press within `window` of the last
+/// accepted one. A release only counts once it settles: a press arriving within `window`
+/// of it cancels the release, so hold mode keeps recording through the glitch.
+struct ShortcutDebouncer {
+    window: Duration,
+    pressed: bool,
+    last_press: Option<Instant>,
+    /// Release waiting to settle, with its id
+    pending_release: Option<(u64, Instant)>,
+    next_release_id: u64,
+}
+
+impl ShortcutDebouncer {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pressed: false,
+            last_press: None,
+            pending_release: None,
+            next_release_id: 0,
+        }
+    }
+    
+    /// Returns true when the press should start (or toggle) recording
+    fn press(&mut self, now: Instant) -> bool {
+        if let Some((_, released_at)) = self.pending_release {
+            if now.duration_since(released_at) < self.window {
+                // 離してすぐ押し直した場合はキーリピートとみなし、離したことを取り消す
+                self.pending_release = None;
+                self.pressed = true;
+                return false;
+            }
+        }
+        if self.pressed {
+            return false;
+        }
+        if self.last_press.is_some_and(|last| now.duration_since(last) < self.window) {
+            return false;
+        }
+        self.pressed = true;
+        self.pending_release = None;
+        self.last_press = Some(now);
+        true
+    }
+    
+    /// Record a release; returns its id for `settle` once the window has passed
+    fn release(&mut self, now: Instant) -> Option<u64> {
+        if !self.pressed {
+            return None;
+        }
+        self.pressed = false;
+        self.next_release_id += 1;
+        self.pending_release = Some((self.next_release_id, now));
+        Some(self.next_release_id)
+    }
+    
+    /// Settle a hold-mode release: None if it was cancelled by a re-press, otherwise whether
+    /// the key was held for at least `min_hold`
+    fn settle_hold(&mut self, id: u64, min_hold: Duration) -> Option<HoldRelease> {
+        let held = match (self.pending_release, self.last_press) {
+            (Some((_, released_at)), Some(pressed_at)) => released_at.duration_since(pressed_at),
+            _ => Duration::ZERO,
+        };
+        if !self.settle(id) {
+            return None;
+        }
+        Some(if held < min_hold { HoldRelease::Tap } else { HoldRelease::Held })
+    }
+    
+    /// Returns true when release `id` wasn't cancelled by a re-press
+    fn settle(&mut self, id: u64) -> bool {
+        match self.pending_release {
+            Some((pending, _)) if pending == id => {
+                self.pending_release = None;
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Maximum number of transcript snapshots kept for undo
+const TRANSCRIPT_HISTORY_LIMIT: usize = 50;
+
+/// Undo/redo snapshots of the transcript text, taken before each replacement or clear
+#[derive(Default)]
+struct TranscriptHistory {
+    undo: Vec<String>,
+    redo: Vec<String>,
+}
+
+impl TranscriptHistory {
+    /// Save the current text before it is replaced
+    fn record(&mut self, current: String) {
+        if self.undo.last() != Some(&current) {
+            self.undo.push(current);
+            if self.undo.len() > TRANSCRIPT_HISTORY_LIMIT {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+    }
+    
+    fn undo(&mut self, current: String) -> Option<String> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        Some(previous)
+    }
+    
+    fn redo(&mut self, current: String) -> Option<String> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        Some(next)
+    }
+}
+
+/// A completed transcript in the history panel
+struct HistoryEntry {
+    text: String,
+    tag: Option<String>,
+}
+
+impl HistoryEntry {
+    /// The text shown in the history panel, prefixed with the tag
+    fn display_text(&self) -> String {
+        match &self.tag {
+            Some(tag) => format!("[{}] {}", tag, self.text),
+            None => self.text.clone(),
+        }
+    }
+}
+
+/// A recording waiting to be transcribed
+struct TranscriptionJob {
+    recording: Recording,
+    language: Option<String>,
+    /// Recorded by this app rather than picked from the clipboard
+    own_recording: bool,
+    /// Input device shown in the device menu when the recording was made
+    device: Option<String>,
+    /// Skip the dictionary and formatting (the "verbatim" toggle)
+    verbatim: bool,
+    /// Tag chosen in the window for this transcript (`ui.tags`)
+    tag: Option<String>,
+    /// Where `shortcuts.drop_marker` was pressed during the recording
+    markers: Markers,
+}
+
+/// FIFO of recordings waiting for a transcription worker
+#[derive(Default)]
+struct TranscriptionQueue {
+    pending: VecDeque<TranscriptionJob>,
+    active: usize,
+    // Results delivered since the queue was last empty
+    delivered: usize,
+    /// Bumped on abort so results from abandoned workers are ignored
+    generation: u64,
+    /// Set on abort; shared with the workers of the current generation
+    cancel: Arc<AtomicBool>,
+}
+
+impl TranscriptionQueue {
+    fn push(&mut self, job: TranscriptionJob) {
+        self.pending.push_back(job);
+    }
+    
+    /// Take the next job if fewer than `max_concurrent` are running
+    fn next_job(&mut self, max_concurrent: usize) -> Option<TranscriptionJob> {
+        if self.active >= max_concurrent.max(1) {
+            return None;
+        }
+        let job = self.pending.pop_front()?;
+        self.active += 1;
+        Some(job)
+    }
+    
+    fn finish(&mut self) {
+        self.active = self.active.saturating_sub(1);
+    }
+    
+    fn is_busy(&self) -> bool {
+        self.active > 0 || !self.pending.is_empty()
+    }
+    
+    /// Abandon the running workers and drop the pending jobs, returning the dropped jobs
+    fn abort(&mut self) -> Vec<TranscriptionJob> {
+        self.cancel.store(true, Ordering::SeqCst);
+        self.cancel = Arc::new(AtomicBool::new(false));
+        self.generation += 1;
+        self.active = 0;
+        self.delivered = 0;
+        self.pending.drain(..).collect()
+    }
+    
+    /// Whether the next result is the first since the queue was empty
+    fn starts_batch(&self) -> bool {
+        self.delivered == 0
+    }
+    
+    /// Combine a finished result with the current transcript
+    ///
+    /// The first result after the queue was empty replaces the transcript unless `append` is
+    /// set; later ones are appended.
+    fn merge_result(&mut self, current: &str, text: String, append: bool) -> String {
+        let merged = if (self.starts_batch() && !append) || current.trim().is_empty() {
+            text
+        } else if text.trim().is_empty() {
+            current.to_string()
+        } else {
+            format!("{}\n\n{}", current.trim_end(), text)
+        };
+        self.delivered += 1;
+        if !self.is_busy() {
+            self.delivered = 0;
+        }
+        merged
+    }
 }
 
 impl ThreadSafeState {
     fn is_recording(&self) -> bool {
-        self.status == AppStatus::Recording
+        matches!(self.status, AppStatus::Recording | AppStatus::Armed)
     }
     
+    /// Move to Recording (or Armed in voice-activated mode)
+    ///
+    /// Transcriptions run in the background queue, so only an active recording rejects the request.
     fn start(&mut self) -> Result<()> {
-        if self.is_recording() {
-            return Ok(());
+        if !matches!(self.status, AppStatus::Idle | AppStatus::Transcribing) {
+            return Err(anyhow::anyhow!("cannot start recording while {:?}", self.status));
         }
         
-        self.status = AppStatus::Recording;
+        self.status = if self.config.recording.vad_enabled {
+            AppStatus::Armed
+        } else {
+            AppStatus::Recording
+        };
         
         Ok(())
     }
@@ -110,46 +528,299 @@ impl ThreadSafeState {
         Ok(None) // This will be handled in the message handler
     }
     
-    fn transcribe(&mut self, recording_path: &str) -> Result<String> {
-        // 文字起こし処理と同時に整形まで行う
-        let transcript = self.api.transcribe_with_processing(recording_path)?;
-        
-        // Always copy to clipboard regardless of auto_paste setting
-        match clipboard::set_text(&transcript) {
-            Ok(_) => info!("Auto-copied transcript to clipboard"),
+}
+
+/// File under `temp_dir` that `logging.jsonl` appends to
+const TRANSCRIPTION_LOG_FILE: &str = "transcriptions.jsonl";
+
+/// One line of the transcription log
+#[derive(serde::Serialize)]
+struct TranscriptionLogEntry {
+    timestamp: String,
+    /// Length of the recording, if its header could be read
+    duration_secs: Option<f64>,
+    device: Option<String>,
+    chars: usize,
+    backend: TranscriptionBackend,
+    tag: Option<String>,
+}
+
+/// Append an entry to the transcription log as a single JSON line
+fn append_transcription_log(temp_dir: &std::path::Path, entry: &TranscriptionLogEntry) -> Result<()> {
+    use std::io::Write;
+    
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(temp_dir.join(TRANSCRIPTION_LOG_FILE))?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// How often the auto-stop thread compares the recording time with the maximum duration
+const AUTO_STOP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Largest value of the maximum recording duration spin button, in minutes
+const MAX_DURATION_MINUTES: f64 = 600.0;
+
+/// `recording.max_duration_secs` in whole minutes for the spin button, rounded up
+fn duration_minutes(secs: u64) -> f64 {
+    secs.div_ceil(60).clamp(1, MAX_DURATION_MINUTES as u64) as f64
+}
+
+/// Maximum recording duration in seconds shown by the spin button
+fn spin_duration_secs(spin: &gtk::SpinButton) -> u64 {
+    spin.value_as_int().max(1) as u64 * 60
+}
+
+/// Stop recordings after `secs` seconds, including the one running
+fn set_max_duration(state: &Arc<Mutex<ThreadSafeState>>, secs: u64) {
+    let mut state = lock_or_recover(state);
+    if state.config.recording.max_duration_secs != secs {
+        info!("Maximum recording duration set to {} seconds", secs);
+        state.config.recording.max_duration_secs = secs;
+    }
+    MAX_DURATION_SECS.store(secs, Ordering::SeqCst);
+}
+
+/// Whether a recording stopped after `elapsed` falls under `recording.min_duration_secs`
+///
+/// Without a start time (still waiting for voice) there is nothing to measure.
+fn is_too_short(elapsed: Option<Duration>, min_secs: f32) -> bool {
+    elapsed.is_some_and(|elapsed| elapsed.as_secs_f32() < min_secs)
+}
+
+/// How often a transcription worker checks whether it was aborted
+const ABORT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Transcribe a recording and copy the result to the clipboard when `clipboard.auto_copy` is set
+///
+/// With `clipboard.type_out` the result is also typed into the focused window.
+/// `verbatim` skips the dictionary and formatting, and `markers` (`shortcuts.drop_marker`) become
+/// separators in the transcript.
+///
+/// Runs on a worker thread without the state lock, so the UI keeps responding during the API calls.
+/// Returns the transcript and whether it was copied, or None once `cancel` is set.
+fn transcribe_recording(
+    api: &Arc<TranscriptionAPI>,
+    clipboard_config: &ClipboardConfig,
+    recording: &Recording,
+    language: Option<&str>,
+    verbatim: bool,
+    markers: &Markers,
+    cancel: &AtomicBool,
+) -> Result<Option<(ProcessedTranscript, bool)>> {
+    // 文字起こし処理と同時に整形まで行う（整形なしの場合は文字起こしのみ）
+    let transcript = match request_transcript(api, recording, language, verbatim, markers, cancel)? {
+        Some(transcript) => transcript,
+        None => return Ok(None),
+    };
+    if cancel.load(Ordering::SeqCst) {
+        return Ok(None);
+    }
+    
+    // auto_copy が無効な場合はコピーボタン/ショートカットでのみコピーする
+    let mut copied = false;
+    if clipboard_config.auto_copy {
+        match clipboard::copy_transcript(&transcript.text, clipboard_config) {
+            Ok(_) => {
+                info!("Auto-copied transcript to clipboard");
+                copied = true;
+            },
             Err(e) => error!("Failed to copy to clipboard: {}", e),
         }
-        
-        Ok(transcript)
     }
+    
+    // コピーとは別に、フォーカスのあるウィンドウへ直接打ち込む
+    if clipboard_config.type_out {
+        if let Err(e) = clipboard::type_transcript(&transcript.text, clipboard_config) {
+            error!("Failed to type transcript: {}", e);
+        }
+    }
+    
+    Ok(Some((transcript, copied)))
+}
+
+/// Paste the transcript the worker just copied into the focused window when `shortcuts.auto_paste` is set
+///
+/// This only sends Ctrl+V, after `clipboard.paste_delay_ms`. Nothing is pasted while Wispr
+/// itself has focus, or with `clipboard.type_out`, which has typed the transcript already.
+fn auto_paste_transcript(window: &Window, config: &Config) {
+    if !config.shortcuts.auto_paste || config.clipboard.type_out {
+        return;
+    }
+    // 自分の文字起こし欄に貼り付けて二重にならないようにする
+    if window.is_active() {
+        debug!("Not auto-pasting while the Wispr window has focus");
+        return;
+    }
+    let delay_ms = config.clipboard.paste_delay_ms;
+    thread::spawn(move || {
+        if let Err(e) = clipboard::paste_into_focused_window(delay_ms) {
+            error!("Failed to paste transcript: {}", e);
+        }
+    });
+}
+
+/// Run the transcription request, giving up once `cancel` is set
+///
+/// The blocking request itself can't be interrupted, so it is left to finish or time out on its
+/// own thread and its result is discarded.
+#[cfg(not(feature = "async"))]
+fn request_transcript(
+    api: &Arc<TranscriptionAPI>,
+    recording: &Recording,
+    language: Option<&str>,
+    verbatim: bool,
+    markers: &Markers,
+    cancel: &AtomicBool,
+) -> Result<Option<ProcessedTranscript>> {
+    let (result_tx, result_rx) = mpsc::channel();
+    let request_api = api.clone();
+    let request_recording = recording.clone();
+    let request_language = language.map(str::to_string);
+    let markers = markers.clone();
+    thread::spawn(move || {
+        let result = if request_api.needs_segments(&markers) {
+            request_api.transcribe_with_markers(&request_recording, request_language.as_deref(), &markers, verbatim)
+        } else if verbatim {
+            request_api.transcribe_verbatim(&request_recording, request_language.as_deref())
+        } else {
+            request_api.transcribe_with_processing(&request_recording, request_language.as_deref())
+        };
+        let _ = result_tx.send(result);
+    });
+    
+    // 中止されたらリクエストの完了を待たずに戻る
+    loop {
+        match result_rx.recv_timeout(ABORT_POLL_INTERVAL) {
+            Ok(result) => return result.map(Some),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if cancel.load(Ordering::SeqCst) {
+                    return Ok(None);
+                }
+            },
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(anyhow::anyhow!("Transcription request thread exited without a result"));
+            },
+        }
+    }
+}
+
+/// Run the transcription request, giving up once `cancel` is set
+///
+/// The request runs on a small single-threaded runtime owned by this worker; dropping its
+/// future on abort closes the connection instead of leaving the request running.
+#[cfg(feature = "async")]
+fn request_transcript(
+    api: &Arc<TranscriptionAPI>,
+    recording: &Recording,
+    language: Option<&str>,
+    verbatim: bool,
+    markers: &Markers,
+    cancel: &AtomicBool,
+) -> Result<Option<ProcessedTranscript>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let cancelled = async {
+            let mut interval = tokio::time::interval(ABORT_POLL_INTERVAL);
+            while !cancel.load(Ordering::SeqCst) {
+                interval.tick().await;
+            }
+        };
+        // 中止されたらリクエストの future を破棄して接続を切る
+        let request = async {
+            if api.needs_segments(markers) {
+                api.transcribe_with_markers_async(recording, language, markers, verbatim).await
+            } else if verbatim {
+                api.transcribe_verbatim_async(recording, language).await
+            } else {
+                api.transcribe_with_processing_async(recording, language).await
+            }
+        };
+        tokio::select! {
+            result = request => result.map(Some),
+            _ = cancelled => Ok(None),
+        }
+    })
 }
 
 /// Runs the window application and returns a join handle and a sender for communication
 #[cfg(not(feature = "tray"))]
-pub fn run_window_application(config: Config) -> Result<(JoinHandle<()>, Sender<WindowMessage>)> {
-    run_window_application_internal(config)
+pub fn run_window_application(config: Config, config_path: PathBuf) -> Result<(JoinHandle<()>, Sender<WindowMessage>)> {
+    run_window_application_internal(config, config_path)
 }
 
 #[cfg(feature = "tray")]
-pub fn run_window_application(config: Config, _tray_sender: Sender<tray::TrayMessage>) -> Result<(JoinHandle<()>, Sender<WindowMessage>)> {
-    run_window_application_internal(config)
+pub fn run_window_application(
+    config: Config,
+    config_path: PathBuf,
+    tray_sender: Sender<tray::TrayMessage>,
+    tray_events: mpsc::Receiver<tray::TrayMessage>,
+) -> Result<(JoinHandle<()>, Sender<WindowMessage>)> {
+    if let Ok(mut sender) = TRAY_SENDER.lock() {
+        *sender = Some(tray_sender);
+    }
+    let (handler_thread, tx_main) = run_window_application_internal(config, config_path)?;
+    
+    // トレイのメニュー操作をウィンドウのメッセージに変換する
+    let tx_clone = tx_main.clone();
+    thread::spawn(move || {
+        for event in tray_events {
+            let message = match event {
+                tray::TrayMessage::StartRecording => WindowMessage::StartRecording,
+                tray::TrayMessage::StopRecording => WindowMessage::StopRecording,
+                tray::TrayMessage::ShowTranscript => WindowMessage::ToggleVisibility,
+                tray::TrayMessage::TranscribeClipboard => WindowMessage::TranscribeClipboard,
+                tray::TrayMessage::OpenConfig => WindowMessage::OpenConfig,
+                tray::TrayMessage::ReloadConfig => WindowMessage::ReloadConfig,
+                tray::TrayMessage::Exit => WindowMessage::Exit,
+                tray::TrayMessage::UpdateStatus(_) | tray::TrayMessage::UpdateRecent(_) => continue,
+            };
+            if tx_clone.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    
+    Ok((handler_thread, tx_main))
 }
 
 // 内部実装（トレイ機能の有無に関わらず共通）
-fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Sender<WindowMessage>)> {
+fn run_window_application_internal(mut config: Config, config_path: PathBuf) -> Result<(JoinHandle<()>, Sender<WindowMessage>)> {
     // Initialize GTK
     if gtk::init().is_err() {
         return Err(anyhow::anyhow!("Failed to initialize GTK."));
     }
     
+    // 表示言語は起動時に一度だけ決める
+    crate::i18n::init(config.ui.language);
+    let t = crate::i18n::text();
+    
     // Channel for communication with the main thread
     let (tx_main, rx_main) = mpsc::channel();
     
+    // temp_dir に書き込めない場合は起動時に一時ディレクトリへ切り替えて警告する
+    if let Some(warning) = crate::config::ensure_writable_temp_dir(&mut config) {
+        let _ = tx_main.send(WindowMessage::ShowWarning(warning));
+    }
+    
     // Create the main window
     let window = Window::new(WindowType::Toplevel);
     window.set_title("Wispr");
-    window.set_default_size(400, 300);
+    let minimal = config.ui.layout == WindowLayout::Minimal;
+    if minimal {
+        window.set_default_size(260, 160);
+    } else {
+        window.set_default_size(400, 300);
+    }
     window.set_position(gtk::WindowPosition::Center);
+    center_on_monitor(&window, config.ui.monitor_index);
+    window.set_keep_above(config.ui.always_on_top);
     
     // Create UI components
     let main_box = GtkBox::new(Orientation::Vertical, 5);
@@ -160,51 +831,249 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
     let device_toggle_button = ToggleButton::with_label("⚙"); // アイコンのみに
     let shortcut_toggle_button = ToggleButton::with_label("⌨"); // アイコンのみに
     let dict_toggle_button = ToggleButton::with_label("📚"); // 辞書トグルボタン追加
-    let record_button = Button::with_label("● 録音"); // Recordボタンをここに移動し、ラベル変更
+    let history_toggle_button = ToggleButton::with_label("🕘");
+    let record_button = Button::with_label(t.record); // Recordボタンをここに移動し、ラベル変更
+    // 辞書・整形を飛ばして文字起こしだけを使う（設定ファイルには保存しない）
+    let verbatim_toggle = ToggleButton::with_label(t.verbatim.0);
+    // これからの文字起こしに付けるタグ（ui.tags、録音ごとには戻さない）
+    let tag_combo = ComboBoxText::new();
+    populate_tags(&tag_combo, &config.ui.tags);
+    // 使用中のAPIアカウント（api.profiles がある場合だけ表示）
+    let profile_combo = ComboBoxText::new();
+    populate_api_profiles(&profile_combo, &config.api);
+    let tx_clone = tx_main.clone();
+    profile_combo.connect_changed(move |combo| {
+        // 一覧を作り直している間（選択なし）は送らない
+        if let Some(id) = combo.active_id() {
+            let profile = Some(id.to_string()).filter(|id| !id.is_empty());
+            let _ = tx_clone.send(WindowMessage::SetApiProfile(profile));
+        }
+    });
     
     control_toggle_box.pack_start(&device_toggle_button, false, false, 0);
     control_toggle_box.pack_start(&shortcut_toggle_button, false, false, 0);
     control_toggle_box.pack_start(&dict_toggle_button, false, false, 0); // 辞書ボタン追加
+    control_toggle_box.pack_start(&history_toggle_button, false, false, 0);
     control_toggle_box.pack_start(&record_button, true, true, 0); // Recordボタンを中央寄せに
+    control_toggle_box.pack_start(&verbatim_toggle, false, false, 0);
+    control_toggle_box.pack_start(&tag_combo, false, false, 0);
+    control_toggle_box.pack_start(&profile_combo, false, false, 0);
     main_box.pack_start(&control_toggle_box, false, false, 0);
+    
+    // アイコンだけのボタンはスクリーンリーダー向けに名前を付ける
+    describe_widget(&device_toggle_button, t.device_settings.0, t.device_settings.1);
+    describe_widget(&shortcut_toggle_button, t.shortcuts.0, t.shortcuts.1);
+    describe_widget(&dict_toggle_button, t.dictionary.0, t.dictionary.1);
+    describe_widget(&history_toggle_button, t.history.0, t.history.1);
+    describe_widget(&verbatim_toggle, t.verbatim.0, t.verbatim.1);
+    describe_widget(&tag_combo, t.tag.0, t.tag.1);
+    describe_widget(&profile_combo, t.api_profile.0, t.api_profile.1);
+    // 録音ボタンの名前はラベル（録音中は表示が変わる）をそのまま使う
+    record_button.set_tooltip_text(Some(&t.record_tooltip(&config.shortcuts.toggle_recording)));
+    // Tab では録音ボタンを最初にする
+    control_toggle_box.set_focus_chain(&[
+        record_button.clone().upcast(),
+        verbatim_toggle.clone().upcast(),
+        tag_combo.clone().upcast(),
+        profile_combo.clone().upcast(),
+        device_toggle_button.clone().upcast(),
+        shortcut_toggle_button.clone().upcast(),
+        dict_toggle_button.clone().upcast(),
+        history_toggle_button.clone().upcast(),
+    ]);
     // --- ここまで --- 
     
     // Audio device section
     let device_box = GtkBox::new(Orientation::Horizontal, 5);
-    let device_label = Label::new(Some("Device:"));
+    let device_label = Label::new(Some(t.device_label));
     let device_combo = ComboBoxText::new();
+    device_label.set_mnemonic_widget(Some(&device_combo));
+    describe_widget(&device_combo, t.device.0, t.device.1);
     
     // Populate audio devices
-    populate_audio_devices(&device_combo);
+    populate_audio_devices(&device_combo, &config.audio.host);
+    
+    // Connect device combo box
+    // 選択したデバイスが対応する形式をツールチップに表示する
+    let audio_host = config.audio.host.clone();
+    let tx_clone = tx_main.clone();
+    let device_changed = device_combo.connect_changed(move |combo| {
+        if let Some(device_id) = combo.active_text() {
+            info!("Selected audio device: {}", device_id);
+            let device = selected_device(combo);
+            query_device_capabilities(&audio_host, device.clone(), tx_clone.clone());
+            set_monitor_device(device);
+        }
+    });
+    
+    // マイクテストの再生先（設定ファイルには保存しない）
+    let output_combo = ComboBoxText::new();
+    describe_widget(&output_combo, t.output_device.0, t.output_device.1);
+    populate_output_devices(&output_combo, config.audio.output_device.as_deref());
+    let tx_clone = tx_main.clone();
+    let output_changed = output_combo.connect_changed(move |combo| {
+        if let Some(id) = combo.active_id() {
+            let _ = tx_clone.send(WindowMessage::SetOutputDevice(Some(id.to_string()).filter(|id| id != "default")));
+        }
+    });
+    
+    // 接続したばかりのデバイスを選べるよう一覧を読み込み直す（選択は維持）
+    let refresh_devices_button = Button::from_icon_name(Some("view-refresh-symbolic"), gtk::IconSize::Button);
+    describe_widget(&refresh_devices_button, t.refresh_devices.0, t.refresh_devices.1);
+    let audio_host = config.audio.host.clone();
+    let tx_clone = tx_main.clone();
+    refresh_devices_button.connect_clicked(move |_| {
+        // デバイスの列挙や pactl は時間がかかることがあるので別スレッドで行う
+        let audio_host = audio_host.clone();
+        let tx = tx_clone.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(WindowMessage::AudioDevicesListed(list_audio_devices(&audio_host)));
+        });
+    });
+    
+    // アイドル時のレベルモニタリング（オフにするとマイクを解放する）
+    let monitor_check = gtk::CheckButton::with_label(t.monitor_when_idle.0);
+    monitor_check.set_active(config.ui.monitor_when_idle);
+    describe_widget(&monitor_check, t.monitor_when_idle.0, t.monitor_when_idle.1);
+    monitor_check.connect_toggled(|check| {
+        info!("Idle audio monitoring {}", if check.is_active() { "enabled" } else { "disabled" });
+        IDLE_MONITORING.store(check.is_active(), Ordering::SeqCst);
+    });
+    
+    // 常に手前に表示（設定ファイルには保存しない）
+    let always_on_top_check = gtk::CheckButton::with_label(t.always_on_top.0);
+    always_on_top_check.set_active(config.ui.always_on_top);
+    describe_widget(&always_on_top_check, t.always_on_top.0, t.always_on_top.1);
+    let window_clone = window.clone();
+    always_on_top_check.connect_toggled(move |check| {
+        info!("Always on top {}", if check.is_active() { "enabled" } else { "disabled" });
+        window_clone.set_keep_above(check.is_active());
+    });
+    
+    // 最大録音時間（分単位で変更し、保存ボタンで設定ファイルに書き込む）
+    MAX_DURATION_SECS.store(config.recording.max_duration_secs, Ordering::SeqCst);
+    let max_duration_label = Label::new(Some(t.max_duration.0));
+    let max_duration_spin = gtk::SpinButton::with_range(1.0, MAX_DURATION_MINUTES, 1.0);
+    max_duration_spin.set_value(duration_minutes(config.recording.max_duration_secs));
+    describe_widget(&max_duration_spin, t.max_duration.0, t.max_duration.1);
+    let tx_clone = tx_main.clone();
+    let max_duration_changed = max_duration_spin.connect_value_changed(move |spin| {
+        let _ = tx_clone.send(WindowMessage::SetMaxDuration(spin_duration_secs(spin)));
+    });
+    let save_max_duration_button = Button::with_label(t.save_max_duration.0);
+    describe_widget(&save_max_duration_button, t.save_max_duration.0, t.save_max_duration.1);
+    let tx_clone = tx_main.clone();
+    save_max_duration_button.connect_clicked(move |_| {
+        let _ = tx_clone.send(WindowMessage::SaveMaxDuration);
+    });
+    
+    // 次の文字起こしだけに使う言語（設定ファイルには保存しない）
+    let language_combo = ComboBoxText::new();
+    language_combo.append(Some("auto"), t.language_auto);
+    for (id, label) in LANGUAGE_OVERRIDES {
+        language_combo.append(Some(id), label);
+    }
+    language_combo.set_active_id(Some("auto"));
+    describe_widget(&language_combo, t.transcription_language.0, t.transcription_language.1);
+    
+    // GPT整形に伝える録音の状況（Enter かフォーカスを外すと反映、設定ファイルには保存しない）
+    let context_entry = gtk::Entry::new();
+    context_entry.set_text(&config.text_processing.context);
+    context_entry.set_placeholder_text(Some(t.formatting_context_placeholder));
+    context_entry.set_width_chars(20);
+    describe_widget(&context_entry, t.formatting_context.0, t.formatting_context.1);
+    let tx_clone = tx_main.clone();
+    context_entry.connect_activate(move |entry| {
+        let _ = tx_clone.send(WindowMessage::SetFormattingContext(entry.text().trim().to_string()));
+    });
+    let tx_clone = tx_main.clone();
+    context_entry.connect_focus_out_event(move |entry, _| {
+        let _ = tx_clone.send(WindowMessage::SetFormattingContext(entry.text().trim().to_string()));
+        glib::Propagation::Proceed
+    });
+    
+    // GPT整形のスタイル（設定ファイルには保存しない）
+    let style_combo = ComboBoxText::new();
+    for style in FormattingStyle::ALL {
+        style_combo.append(Some(style.id()), t.formatting_style_name(style));
+    }
+    style_combo.set_active_id(Some(config.text_processing.style.id()));
+    describe_widget(&style_combo, t.formatting_style.0, t.formatting_style.1);
+    let tx_clone = tx_main.clone();
+    style_combo.connect_changed(move |combo| {
+        if let Some(style) = combo.active_id().and_then(|id| FormattingStyle::from_id(&id)) {
+            let _ = tx_clone.send(WindowMessage::SetFormattingStyle(style));
+        }
+    });
     
     device_box.pack_start(&device_label, false, false, 0);
     device_box.pack_start(&device_combo, true, true, 0);
+    device_box.pack_start(&refresh_devices_button, false, false, 0);
+    device_box.pack_start(&output_combo, false, false, 0);
+    device_box.pack_start(&language_combo, false, false, 0);
+    device_box.pack_start(&context_entry, false, false, 0);
+    device_box.pack_start(&style_combo, false, false, 0);
+    device_box.pack_start(&monitor_check, false, false, 0);
+    device_box.pack_start(&always_on_top_check, false, false, 0);
+    device_box.pack_start(&max_duration_label, false, false, 0);
+    device_box.pack_start(&max_duration_spin, false, false, 0);
+    device_box.pack_start(&save_max_duration_button, false, false, 0);
+    
+    // マイクテスト（短く録音して再生し、ピークレベルを表示）
+    let mic_test_button = Button::with_label(t.mic_test);
+    describe_widget(&mic_test_button, t.mic_test, &t.mic_test_description(MIC_TEST_SECS));
+    let mic_test_label = Label::new(None);
+    let tx_clone = tx_main.clone();
+    mic_test_button.connect_clicked(move |_| {
+        let _ = tx_clone.send(WindowMessage::TestMicrophone);
+    });
+    device_box.pack_start(&mic_test_button, false, false, 0);
+    device_box.pack_start(&mic_test_label, false, false, 0);
+    
+    // 設定ファイルをエディタで開き、編集後に再起動せず読み込み直す
+    let open_config_button = Button::with_label(t.open_config.0);
+    let reload_config_button = Button::with_label(t.reload_config.0);
+    describe_widget(&open_config_button, t.open_config.0, &format!("{}（{}）", t.open_config.1, config.shortcuts.open_config));
+    describe_widget(&reload_config_button, t.reload_config.0, t.reload_config.1);
+    let tx_clone = tx_main.clone();
+    open_config_button.connect_clicked(move |_| {
+        let _ = tx_clone.send(WindowMessage::OpenConfig);
+    });
+    let tx_clone = tx_main.clone();
+    reload_config_button.connect_clicked(move |_| {
+        let _ = tx_clone.send(WindowMessage::ReloadConfig);
+    });
+    device_box.pack_start(&open_config_button, false, false, 0);
+    device_box.pack_start(&reload_config_button, false, false, 0);
     
     main_box.pack_start(&device_box, false, false, 0);
     
     // Audio level monitoring
     let level_box = GtkBox::new(Orientation::Horizontal, 5);
-    let level_label = Label::new(Some("Level:"));
+    let level_label = Label::new(Some(t.level_label));
     let audio_level = LevelBar::new();
     audio_level.set_min_value(0.0);
     audio_level.set_max_value(1.0);
+    level_label.set_mnemonic_widget(Some(&audio_level));
+    describe_widget(&audio_level, t.level.0, t.level.1);
     
     level_box.pack_start(&level_label, false, false, 0);
     level_box.pack_start(&audio_level, true, true, 0);
     
     main_box.pack_start(&level_box, false, false, 0);
     
+    // 直近のレベルを波形で表示（ui.show_waveform）
+    let waveform_history = Rc::new(RefCell::new(VecDeque::with_capacity(WAVEFORM_SAMPLES)));
+    let waveform = build_waveform(waveform_history.clone());
+    describe_widget(&waveform, t.waveform.0, t.waveform.1);
+    main_box.pack_start(&waveform, false, false, 0);
+    
     // --- ショートカット情報 (復活) ---
     let shortcut_frame = Frame::new(None); // ラベルなし
     let shortcut_vbox = GtkBox::new(Orientation::Vertical, 2);
     shortcut_vbox.set_margin(5);
     let shortcut_label = Label::new(None);
-    shortcut_label.set_markup(&format!(
-        "<small>Record: <b>Press and hold {}</b>\nRelease to transcribe.\nClear: <b>{}</b>\nCopy: <b>{}</b></small>",
-        config.shortcuts.toggle_recording,
-        config.shortcuts.clear_transcript,
-        config.shortcuts.copy_to_clipboard
-    ));
+    shortcut_label.set_markup(&t.shortcut_help(&config.shortcuts));
     shortcut_label.set_halign(gtk::Align::Start);
     shortcut_vbox.pack_start(&shortcut_label, false, false, 0);
     shortcut_frame.add(&shortcut_vbox);
@@ -218,14 +1087,33 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
 
     // 辞書ヘッダー
     let dict_header_box = GtkBox::new(Orientation::Horizontal, 5);
-    let dict_label = Label::new(Some("登録済み単語"));
+    let dict_label = Label::new(Some(t.dictionary_title));
     dict_label.set_halign(gtk::Align::Start);
     dict_label.set_hexpand(true);
 
     // 単語登録ボタン
-    let add_word_button = Button::with_label("+ 単語登録");
+    let add_word_button = Button::with_label(t.add_word);
+    // CSV取り込み・書き出しボタン
+    let import_csv_button = Button::with_label(t.import_csv.0);
+    let export_csv_button = Button::with_label(t.export_csv.0);
+    describe_widget(&add_word_button, t.add_word_description.0, t.add_word_description.1);
+    describe_widget(&import_csv_button, t.import_csv.0, t.import_csv.1);
+    describe_widget(&export_csv_button, t.export_csv.0, t.export_csv.1);
+    // 学習した頻度の消去・登録単語の全削除
+    let reset_frequencies_button = Button::with_label(t.reset_frequencies.0);
+    let clear_words_button = Button::with_label(t.clear_words.0);
+    describe_widget(&reset_frequencies_button, t.reset_frequencies.0, t.reset_frequencies.1);
+    describe_widget(&clear_words_button, t.clear_words.0, t.clear_words.1);
+    // 自動バックアップからの復元
+    let restore_backup_button = Button::with_label(t.restore_backup.0);
+    describe_widget(&restore_backup_button, t.restore_backup.0, t.restore_backup.1);
 
     dict_header_box.pack_start(&dict_label, true, true, 0);
+    dict_header_box.pack_start(&import_csv_button, false, false, 0);
+    dict_header_box.pack_start(&export_csv_button, false, false, 0);
+    dict_header_box.pack_start(&reset_frequencies_button, false, false, 0);
+    dict_header_box.pack_start(&clear_words_button, false, false, 0);
+    dict_header_box.pack_start(&restore_backup_button, false, false, 0);
     dict_header_box.pack_start(&add_word_button, false, false, 0);
     dict_vbox.pack_start(&dict_header_box, false, false, 0);
 
@@ -240,8 +1128,9 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
     dict_view.set_editable(false);
     dict_view.set_cursor_visible(false);
     dict_view.set_wrap_mode(gtk::WrapMode::Word);
+    describe_widget(&dict_view, t.dictionary_title, t.dictionary_list_description);
     let dict_buffer = dict_view.buffer().unwrap();
-    dict_buffer.set_text("辞書が読み込まれていません...");
+    dict_buffer.set_text(t.dictionary_not_loaded);
 
     dict_scroll.add(&dict_view);
     dict_vbox.pack_start(&dict_scroll, true, true, 0);
@@ -249,6 +1138,71 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
     main_box.pack_start(&dict_frame, false, false, 0);
     // --- ここまで ---
     
+    // --- 履歴フレーム（検索で絞り込み） ---
+    let history_frame = Frame::new(None);
+    let history_vbox = GtkBox::new(Orientation::Vertical, 5);
+    history_vbox.set_margin(5);
+    
+    let history_search = gtk::SearchEntry::new();
+    history_search.set_placeholder_text(Some(t.history_search.0));
+    describe_widget(&history_search, t.history_search.0, t.history_search.1);
+    let tx_clone = tx_main.clone();
+    history_search.connect_search_changed(move |_| {
+        let _ = tx_clone.send(WindowMessage::FilterHistory);
+    });
+    let usage_stats_button = Button::with_label(t.usage_stats.0);
+    describe_widget(&usage_stats_button, t.usage_stats.0, t.usage_stats.1);
+    let history_header_box = GtkBox::new(Orientation::Horizontal, 5);
+    history_header_box.pack_start(&history_search, true, true, 0);
+    history_header_box.pack_start(&usage_stats_button, false, false, 0);
+    history_vbox.pack_start(&history_header_box, false, false, 0);
+    
+    let history_scroll = ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    history_scroll.set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
+    history_scroll.set_min_content_height(100);
+    history_scroll.set_max_content_height(200);
+    
+    let history_view = TextView::new();
+    history_view.set_editable(false);
+    history_view.set_cursor_visible(false);
+    history_view.set_wrap_mode(gtk::WrapMode::Word);
+    describe_widget(&history_view, t.history_list.0, t.history_list.1);
+    let history_buffer = history_view.buffer().unwrap();
+    // 検索に一致した部分の強調表示
+    let match_tag = gtk::TextTag::builder().name(HISTORY_MATCH_TAG).background("#807000").weight(700).build();
+    if let Some(tag_table) = history_buffer.tag_table() {
+        tag_table.add(&match_tag);
+    }
+    history_buffer.set_text(t.history_empty);
+    
+    history_scroll.add(&history_view);
+    history_vbox.pack_start(&history_scroll, true, true, 0);
+    history_frame.add(&history_vbox);
+    main_box.pack_start(&history_frame, false, false, 0);
+    // --- ここまで ---
+    
+    // 録音品質などの警告表示
+    let warning_label = Label::new(None);
+    warning_label.set_line_wrap(true);
+    warning_label.set_halign(gtk::Align::Start);
+    main_box.pack_start(&warning_label, false, false, 0);
+    
+    // APIキー未設定・無効の案内（キーが設定されるまで表示し続ける）
+    let api_key_banner = GtkBox::new(Orientation::Horizontal, 5);
+    let api_key_label = Label::new(None);
+    set_api_key_banner_text(&api_key_label, t.api_key_missing);
+    api_key_label.set_line_wrap(true);
+    api_key_label.set_halign(gtk::Align::Start);
+    let api_key_button = Button::with_label(t.open_config.0);
+    describe_widget(&api_key_button, t.open_config.0, t.open_config.1);
+    let tx_clone = tx_main.clone();
+    api_key_button.connect_clicked(move |_| {
+        let _ = tx_clone.send(WindowMessage::OpenConfig);
+    });
+    api_key_banner.pack_start(&api_key_label, true, true, 0);
+    api_key_banner.pack_end(&api_key_button, false, false, 0);
+    main_box.pack_start(&api_key_banner, false, false, 0);
+    
     // Transcript section
     let scrolled_window = ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
     scrolled_window.set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
@@ -257,12 +1211,52 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
     let transcript_view = TextView::new();
     transcript_view.set_editable(true);
     transcript_view.set_wrap_mode(gtk::WrapMode::Word);
+    describe_widget(&transcript_view, t.transcript.0, t.transcript.1);
     
     let transcript_buffer = transcript_view.buffer().unwrap();
-    transcript_buffer.set_text("Record audio to see transcription here...");
+    transcript_buffer.set_text(t.transcript_placeholder);
     
     scrolled_window.add(&transcript_view);
-    main_box.pack_start(&scrolled_window, true, true, 0);
+    // 前の文字起こしをタブに残す場合に備えてノートブックに入れる（タブは 2 つ以上で表示）
+    let transcript_tabs = gtk::Notebook::new();
+    transcript_tabs.set_show_tabs(false);
+    transcript_tabs.set_scrollable(true);
+    transcript_tabs.append_page(&scrolled_window, Some(&Label::new(Some(t.latest_transcript_tab))));
+    main_box.pack_start(&transcript_tabs, true, true, 0);
+    
+    // 指示を加えて今の文字起こしを整形し直す（元に戻すで戻せる）
+    let reformat_box = GtkBox::new(Orientation::Horizontal, 5);
+    let reformat_entry = gtk::Entry::new();
+    reformat_entry.set_placeholder_text(Some(t.reformat_placeholder));
+    describe_widget(&reformat_entry, t.reformat.0, t.reformat_placeholder);
+    let reformat_button = Button::with_label(t.reformat.0);
+    describe_widget(&reformat_button, t.reformat.0, t.reformat.1);
+    let tx_clone = tx_main.clone();
+    let entry_clone = reformat_entry.clone();
+    reformat_button.connect_clicked(move |_| {
+        let _ = tx_clone.send(WindowMessage::Reformat(entry_clone.text().trim().to_string()));
+    });
+    let button_clone = reformat_button.clone();
+    reformat_entry.connect_activate(move |_| {
+        button_clone.clicked();
+    });
+    reformat_box.pack_start(&reformat_entry, true, true, 0);
+    reformat_box.pack_start(&reformat_button, false, false, 0);
+    main_box.pack_start(&reformat_box, false, false, 0);
+    
+    // 文字起こし結果と辞書のフォント（再読み込み時はこのプロバイダを更新する）
+    let font_css = gtk::CssProvider::new();
+    load_font_css(&font_css, &config.ui);
+    for view in [&transcript_view, &dict_view] {
+        view.style_context().add_provider(&font_css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    }
+    
+    // 文字起こし失敗時に録音ボタンを赤く点滅させる
+    let error_css = gtk::CssProvider::new();
+    if let Err(e) = error_css.load_from_data(ERROR_FLASH_CSS.as_bytes()) {
+        warn!("Failed to load the error flash style: {}", e);
+    }
+    record_button.style_context().add_provider(&error_css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
     
     // Control buttons
     let control_box = GtkBox::new(Orientation::Horizontal, 5);
@@ -273,47 +1267,169 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
     timer_label.set_halign(gtk::Align::Start);
     timer_label.set_margin_start(5);
     
-    let copy_button = Button::with_label("Copy");
-    let clear_button = Button::with_label("Clear");
+    // 文字起こし待ちの件数
+    let queue_label = Label::new(None);
+    queue_label.set_halign(gtk::Align::Start);
+    
+    // 直前の文字起こしで検出された言語（verbose_json の場合のみ）
+    let detected_language_label = Label::new(None);
+    detected_language_label.style_context().add_class("dim-label");
+    
+    // 応答が返ってこない文字起こしを中止する
+    let abort_button = Button::with_label(t.abort);
+    describe_widget(&abort_button, t.abort_description.0, t.abort_description.1);
+    let tx_clone = tx_main.clone();
+    abort_button.connect_clicked(move |_| {
+        let _ = tx_clone.send(WindowMessage::AbortTranscription);
+    });
+    
+    // 録音済みの音声ファイルを選んで文字起こしする
+    let open_file_button = Button::with_label(t.open_audio_file.0);
+    describe_widget(&open_file_button, t.open_audio_file.0, t.open_audio_file.1);
+    let tx_clone = tx_main.clone();
+    let window_clone = window.clone();
+    open_file_button.connect_clicked(move |_| {
+        if let Some(path) = choose_audio_file(&window_clone) {
+            let _ = tx_clone.send(WindowMessage::TranscribeFile(path));
+        }
+    });
+    
+    let copy_button = Button::with_label(t.copy);
+    let clear_button = Button::with_label(t.clear);
+    describe_widget(&copy_button, t.copy, &t.copy_description(&config.shortcuts.copy_to_clipboard));
+    describe_widget(&clear_button, t.clear, &t.clear_description(&config.shortcuts.clear_transcript));
     
     // タイマーはコントロールボックスの左側、残りのボタンは右側に
     control_box.pack_start(&timer_label, true, true, 0);
+    control_box.pack_start(&queue_label, false, false, 0);
+    control_box.pack_start(&detected_language_label, false, false, 0);
+    control_box.pack_start(&abort_button, false, false, 0);
     control_box.pack_end(&clear_button, false, false, 0);
     control_box.pack_end(&copy_button, false, false, 0);
+    control_box.pack_end(&open_file_button, false, false, 0);
     
     main_box.pack_start(&control_box, false, false, 0);
     
     // Add everything to the window
     window.add(&main_box);
-    window.show_all();
+    main_box.show_all();
+    warning_label.set_visible(false);
+    api_key_banner.set_visible(crate::config::missing_api_key(&config));
+    queue_label.set_visible(false);
+    detected_language_label.set_visible(false);
+    abort_button.set_visible(false);
+    waveform.set_visible(config.ui.show_waveform);
+    tag_combo.set_visible(!config.ui.tags.is_empty());
+    profile_combo.set_visible(!config.api.profiles.is_empty());
+    // 最小レイアウトでは録音ボタン・メーター・文字起こし結果以外を隠す（コピーとクリアはショートカットで使える）
+    if minimal {
+        level_label.set_visible(false);
+        copy_button.set_visible(false);
+        clear_button.set_visible(false);
+        open_file_button.set_visible(false);
+        reformat_box.set_visible(false);
+    }
+    
+    // トレイ機能がない場合は非表示のウィンドウを表示する手段がないため無視する
+    let start_hidden = config.ui.start_hidden && cfg!(feature = "tray");
+    if config.ui.start_hidden && !start_hidden {
+        warn!("ui.start_hidden requires the tray feature; showing the window");
+    }
+    // 起動時は録音ボタンにフォーカスし、Enter でも録音できるようにする
+    record_button.set_can_default(true);
+    window.set_default(Some(&record_button));
+    record_button.grab_focus();
+    if start_hidden {
+        info!("Starting with the window hidden; use the tray menu to show it");
+    } else {
+        window.show();
+    }
     
     // Set up thread-safe state
     let thread_safe_state = Arc::new(Mutex::new(ThreadSafeState {
         status: AppStatus::Idle,
         config: config.clone(),
         transcript: String::new(),
-        api: TranscriptionAPI::new(config.clone()),
+        api: Arc::new(TranscriptionAPI::new(config.clone())),
     }));
     
+    if let Some(port) = config.integrations.health_port {
+        start_health_server(port, &thread_safe_state);
+    }
+    if config.integrations.http_control {
+        start_http_control(&config.integrations, &thread_safe_state, tx_main.clone());
+    }
+    start_clipboard_watcher(thread_safe_state.clone(), tx_main.clone());
+    // 録音を始められるようになる前に、前回中断された録音を探して直しておく
+    let recovered = audio::recover_interrupted_recordings(&config.temp_dir, &config.recording.filename_template);
+    if !recovered.is_empty() {
+        let _ = tx_main.send(WindowMessage::RecoveredRecordings(recovered));
+    }
+    
     // Set up UI state
-    let ui_state = UiState {
+    let mut ui_state = UiState {
+        window: window.clone(),
         state: thread_safe_state.clone(),
         tx_main: tx_main.clone(),
         record_button: record_button.clone(),
+        verbatim_toggle: verbatim_toggle.clone(),
+        tag_combo: tag_combo.clone(),
+        profile_combo: profile_combo.clone(),
+        transcript_view: transcript_view.clone(),
         transcript_buffer: transcript_buffer.clone(),
+        transcript_tabs: transcript_tabs.clone(),
+        autoscroll: config.ui.autoscroll,
         device_combo: device_combo.clone(),
+        device_changed,
+        output_combo: output_combo.clone(),
+        output_changed,
+        language_combo: language_combo.clone(),
+        context_entry: context_entry.clone(),
+        style_combo: style_combo.clone(),
         audio_level: audio_level.clone(),
+        waveform: waveform.clone(),
         device_box: device_box.clone(),
+        monitor_check: monitor_check.clone(),
         shortcut_frame: shortcut_frame.clone(),
         dict_frame: dict_frame.clone(),
         dict_buffer: dict_buffer.clone(),
         timer_label: timer_label.clone(),
+        warning_label: warning_label.clone(),
+        api_key_banner: api_key_banner.clone(),
+        api_key_label: api_key_label.clone(),
+        queue_label: queue_label.clone(),
+        detected_language_label: detected_language_label.clone(),
+        abort_button: abort_button.clone(),
+        mic_test_button: mic_test_button.clone(),
+        reformat_button: reformat_button.clone(),
+        mic_test_label: mic_test_label.clone(),
+        queue: TranscriptionQueue::default(),
+        history: TranscriptHistory::default(),
+        recent_transcripts: VecDeque::new(),
+        history_search: history_search.clone(),
+        history_buffer: history_buffer.clone(),
+        config_path,
+        shortcut_label: shortcut_label.clone(),
+        shortcut_handlers: Vec::new(),
+        countdown: None,
+        shown_status: Cell::new(None),
+        pending_recording: Cell::new(false),
+        mic_testing: false,
+        font_css,
+        last_activity: Instant::now(),
+        dormant: false,
+        markers: Markers::default(),
+        max_duration_spin,
+        max_duration_changed,
+        recording_overlay: config.ui.recording_overlay.then(build_recording_overlay),
+        overlay_corner: config.ui.overlay_corner,
     };
     
     // --- トグルボタンの初期状態と接続 ---
     device_box.set_visible(false);
     shortcut_frame.set_visible(false);
     dict_frame.set_visible(false);
+    history_frame.set_visible(false);
     device_toggle_button.set_active(false);
     shortcut_toggle_button.set_active(false);
     dict_toggle_button.set_active(false);
@@ -328,6 +1444,15 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
         shortcut_frame_clone.set_visible(btn.is_active());
     });
 
+    let history_frame_clone = history_frame.clone();
+    let history_search_clone = history_search.clone();
+    history_toggle_button.connect_toggled(move |btn| {
+        history_frame_clone.set_visible(btn.is_active());
+        if btn.is_active() {
+            history_search_clone.grab_focus();
+        }
+    });
+
     let dict_frame_clone = dict_frame.clone();
     let thread_safe_state_clone = thread_safe_state.clone();
     let dict_buffer_clone = dict_buffer.clone();
@@ -336,10 +1461,58 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
         
         // 辞書ボタンをアクティブにしたときに辞書内容を更新
         if btn.is_active() {
-            let config_clone = thread_safe_state_clone.lock().unwrap().config.clone();
+            let config_clone = lock_or_recover(&thread_safe_state_clone).config.clone();
             update_dictionary_view(&dict_buffer_clone, &config_clone);
         }
     });
+
+    // CSV取り込みボタン
+    let window_clone = window.clone();
+    let thread_safe_state_clone = thread_safe_state.clone();
+    let dict_buffer_clone = dict_buffer.clone();
+    import_csv_button.connect_clicked(move |_| {
+        let config_clone = lock_or_recover(&thread_safe_state_clone).config.clone();
+        import_dictionary_csv(&window_clone, &config_clone);
+        update_dictionary_view(&dict_buffer_clone, &config_clone);
+    });
+
+    // CSV書き出しボタン
+    let window_clone = window.clone();
+    let thread_safe_state_clone = thread_safe_state.clone();
+    export_csv_button.connect_clicked(move |_| {
+        let config_clone = lock_or_recover(&thread_safe_state_clone).config.clone();
+        export_dictionary_csv(&window_clone, &config_clone);
+    });
+
+    // 頻度リセット・全単語削除ボタン
+    let window_clone = window.clone();
+    let thread_safe_state_clone = thread_safe_state.clone();
+    reset_frequencies_button.connect_clicked(move |_| {
+        let config_clone = lock_or_recover(&thread_safe_state_clone).config.clone();
+        reset_dictionary_frequencies(&window_clone, &config_clone);
+    });
+    let window_clone = window.clone();
+    let thread_safe_state_clone = thread_safe_state.clone();
+    let dict_buffer_clone = dict_buffer.clone();
+    clear_words_button.connect_clicked(move |_| {
+        let config_clone = lock_or_recover(&thread_safe_state_clone).config.clone();
+        clear_dictionary_words(&window_clone, &config_clone);
+        update_dictionary_view(&dict_buffer_clone, &config_clone);
+    });
+    let window_clone = window.clone();
+    let thread_safe_state_clone = thread_safe_state.clone();
+    let dict_buffer_clone = dict_buffer.clone();
+    restore_backup_button.connect_clicked(move |_| {
+        let config_clone = lock_or_recover(&thread_safe_state_clone).config.clone();
+        restore_dictionary_backup(&window_clone, &config_clone);
+        update_dictionary_view(&dict_buffer_clone, &config_clone);
+    });
+    let window_clone = window.clone();
+    let thread_safe_state_clone = thread_safe_state.clone();
+    usage_stats_button.connect_clicked(move |_| {
+        let config_clone = lock_or_recover(&thread_safe_state_clone).config.clone();
+        show_usage_stats(&window_clone, &config_clone);
+    });
     // --- ここまで ---
     
     // Set up Ctrl+C handler
@@ -351,7 +1524,13 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
 
     // Connect window close event
     let tx_clone = tx_main.clone();
-    window.connect_delete_event(move |_, _| {
+    let state_clone = thread_safe_state.clone();
+    window.connect_delete_event(move |window, _| {
+        let status = state_clone.lock().map(|state| state.status).unwrap_or(AppStatus::Idle);
+        if !confirm_quit(window, status) {
+            info!("Quit cancelled");
+            return glib::Propagation::Stop;
+        }
         let _ = tx_clone.send(WindowMessage::Exit);
         AUDIO_MONITORING.store(false, Ordering::SeqCst);
         gtk::main_quit();
@@ -362,69 +1541,112 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
     let _tx_clone = tx_main.clone();
     let state_clone = thread_safe_state.clone();
     record_button.connect_clicked(move |_| {
-        let status = state_clone.lock().unwrap().status;
+        let status = lock_or_recover(&state_clone).status;
         match status {
-            AppStatus::Idle => {
+            // 文字起こしはキューで処理されるので、処理中でも次の録音を開始できる
+            AppStatus::Idle | AppStatus::Transcribing => {
                 let _ = _tx_clone.send(WindowMessage::StartRecording);
             },
-            AppStatus::Recording => {
+            AppStatus::Armed | AppStatus::Recording => {
                 let _ = _tx_clone.send(WindowMessage::StopRecording);
             },
-            AppStatus::Transcribing => {
-                // Do nothing during transcription
-            }
         }
     });
     
-    // Connect device combo box
-    let tx_clone = tx_main.clone();
-    device_combo.connect_changed(move |combo| {
-        if let Some(device_id) = combo.active_text() {
-            info!("Selected audio device: {}", device_id);
-            // You would store this selection for use in audio recording
-        }
-    });
+    let device = selected_device(&device_combo);
+    query_device_capabilities(&config.audio.host, device.clone(), tx_main.clone());
+    set_monitor_device(device);
     
     // Connect copy button
     let state_clone = thread_safe_state.clone();
     copy_button.connect_clicked(move |_| {
-        let state = state_clone.lock().unwrap();
-        if !state.transcript.is_empty() {
-            match clipboard::set_text(&state.transcript) {
-                Ok(_) => {
-                    info!("Transcript copied to clipboard");
-                },
-                Err(e) => {
-                    error!("Failed to copy to clipboard: {}", e);
-                }
-            }
-        }
+        let state = lock_or_recover(&state_clone);
+        copy_transcript(&state);
     });
     
-    // Connect clear button
+    // 選択した部分だけをコピーする（ui.copy_on_select ではマウスで選択し終えた時点で）
+    let state_clone = thread_safe_state.clone();
+    transcript_view.connect_button_release_event(move |view, event| {
+        let clipboard_config = match state_clone.lock() {
+            Ok(state) if state.config.ui.copy_on_select => Some(state.config.clipboard.clone()),
+            _ => None,
+        };
+        if let (1, Some(clipboard_config), Some(buffer)) = (event.button(), clipboard_config, view.buffer()) {
+            copy_selection(&buffer, &clipboard_config);
+        }
+        glib::Propagation::Proceed
+    });
     let state_clone = thread_safe_state.clone();
-    let transcript_buffer_clone = transcript_buffer.clone();
+    transcript_view.connect_populate_popup(move |view, popup| {
+        let (Some(menu), Some(buffer)) = (popup.downcast_ref::<gtk::Menu>(), view.buffer()) else {
+            return;
+        };
+        let clipboard_config = state_clone.lock().map(|state| state.config.clipboard.clone()).unwrap_or_default();
+        let item = gtk::MenuItem::with_label(t.copy_selection);
+        item.set_sensitive(buffer.has_selection());
+        item.connect_activate(move |_| {
+            copy_selection(&buffer, &clipboard_config);
+        });
+        menu.prepend(&gtk::SeparatorMenuItem::new());
+        menu.prepend(&item);
+        menu.show_all();
+    });
+    
+    // Connect clear button (goes through UpdateTranscript so the clear can be undone)
+    let tx_clone = tx_main.clone();
     clear_button.connect_clicked(move |_| {
-        let mut state = state_clone.lock().unwrap();
-        state.transcript = String::new();
-        update_transcript_text(&transcript_buffer_clone, "");
+        let _ = tx_clone.send(WindowMessage::UpdateTranscript(String::new()));
     });
     
+    // サスペンドからの復帰時にモニター用ストリームを開き直す
+    watch_resume_from_suspend();
+    
     // Add simplified keyboard shortcuts
-    setup_keyboard_shortcuts(&window, &config, tx_main.clone());
+    ui_state.shortcut_handlers = setup_keyboard_shortcuts(&window, &config, thread_safe_state.clone(), tx_main.clone());
+    
+    // SIGHUP（kill -HUP）でも設定を再読み込みする
+    let tx_clone = tx_main.clone();
+    glib::unix_signal_add_local(SIGHUP, move || {
+        info!("Received SIGHUP, reloading config");
+        let _ = tx_clone.send(WindowMessage::ReloadConfig);
+        ControlFlow::Continue
+    });
     
     // Set up a timer to check for messages
     let ui_state_arc = Arc::new(Mutex::new(ui_state));
     let ui_state_arc_clone = ui_state_arc.clone();
     glib::timeout_add_local(std::time::Duration::from_millis(100), move || {
-        process_messages(&rx_main, &ui_state_arc_clone)
+        match std::panic::catch_unwind(AssertUnwindSafe(|| process_messages(&rx_main, &ui_state_arc_clone))) {
+            Ok(flow) => flow,
+            Err(panic) => {
+                stop_recorder_after_panic();
+                std::panic::resume_unwind(panic)
+            },
+        }
     });
     
-    // Set up a timer to update the audio level bar
+    // Set up a timer to update the audio level bar and the waveform
     let audio_level_clone = audio_level.clone();
     glib::timeout_add_local(std::time::Duration::from_millis(100), move || {
         if let Ok(level) = AUDIO_LEVEL.lock() {
             audio_level_clone.set_value(*level);
+            if waveform.is_visible() {
+                let mut history = waveform_history.borrow_mut();
+                if history.len() == WAVEFORM_SAMPLES {
+                    history.pop_front();
+                }
+                history.push_back(*level);
+                waveform.queue_draw();
+            }
+        }
+        ControlFlow::Continue
+    });
+    
+    // 一定時間録音がなければモニタリングを止めて休止する（ui.idle_timeout_secs）
+    let ui_state_arc_for_idle = ui_state_arc.clone();
+    glib::timeout_add_seconds_local(1, move || {
+        if let Ok(mut ui_state) = ui_state_arc_for_idle.try_lock() {
+            check_idle_timeout(&mut ui_state);
         }
         ControlFlow::Continue
     });
@@ -432,8 +1654,8 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
     // Set up a timer to update the timer label during recording
     let ui_state_arc_for_timer = ui_state_arc.clone();
     glib::timeout_add_local(std::time::Duration::from_millis(1000), move || {
-        let ui_state = ui_state_arc_for_timer.lock().unwrap();
-        let state = ui_state.state.lock().unwrap();
+        let ui_state = lock_or_recover(&ui_state_arc_for_timer);
+        let state = lock_or_recover(&ui_state.state);
         
         if state.status == AppStatus::Recording {
             if let Ok(mut start_time) = RECORDING_START_TIME.lock() {
@@ -467,8 +1689,8 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
                     ));
                 }
             }
-        } else {
-            // 録音していないときはタイマーをリセット
+        } else if state.status == AppStatus::Idle && ui_state.countdown.is_none() {
+            // 待機中はタイマーをリセット（音声待ち・処理中・カウントダウンの表示は上書きしない）
             if let Ok(mut start_time) = RECORDING_START_TIME.lock() {
                 *start_time = None;
             }
@@ -478,32 +1700,57 @@ fn run_window_application_internal(config: Config) -> Result<(JoinHandle<()>, Se
         ControlFlow::Continue
     });
     
-    // Start audio level monitoring using a separate thread
+    // Start audio level monitoring on the handler thread.
+    // The loop ends when AUDIO_MONITORING is cleared on Exit, so the thread can be joined.
     AUDIO_MONITORING.store(true, Ordering::SeqCst);
-    thread::spawn(move || {
-        monitor_audio_input();
-    });
-    
-    // Create a thread that will be joined when the application exits
+    IDLE_MONITORING.store(config.ui.monitor_when_idle, Ordering::SeqCst);
+    // 音声での録音開始はモニター用のストリームで検出する
+    let vad_threshold = config.recording.vad_enabled.then_some(config.recording.silence_threshold);
+    if vad_threshold.is_some() && !config.ui.monitor_when_idle {
+        warn!("recording.vad_enabled needs ui.monitor_when_idle; voice will only be detected after pressing record");
+    }
+    let preroll_secs = config.recording.preroll_secs;
+    if preroll_secs > 0.0 && !config.ui.monitor_when_idle {
+        warn!("recording.preroll_secs needs idle monitoring; recordings start without pre-roll while it is off");
+    }
+    let level_scale = LevelScale::from_config(&config.ui);
+    let audio_host = config.audio.host.clone();
     let handler_thread = thread::spawn(move || {
-        // Just a placeholder thread that does nothing but can be joined
         info!("Handler thread started");
-        
-        // Sleep until application exit
-        loop {
-            std::thread::sleep(std::time::Duration::from_secs(10));
-        }
+        monitor_audio_input(vad_threshold, level_scale, preroll_secs, &audio_host);
+        info!("Handler thread finished");
     });
     
     Ok((handler_thread, tx_main))
 }
 
+/// Signal the handler thread to stop audio monitoring and finish.
+///
+/// Used after the GTK main loop has exited, when `WindowMessage::Exit` can no longer be processed.
+pub fn stop_audio_monitoring() {
+    AUDIO_MONITORING.store(false, Ordering::SeqCst);
+}
+
+/// Stop the recorder after a panic in the message handling on the GTK thread
+///
+/// The panic ends the process, so the recording is finalized and the device closed first.
+fn stop_recorder_after_panic() {
+    let recorder = unsafe { (*std::ptr::addr_of_mut!(GLOBAL_RECORDER)).take() };
+    if let Some(mut recorder) = recorder {
+        match recorder.stop() {
+            Ok(Some(recording)) => error!("Recorder stopped after the panic, unsaved recording: {}", recording),
+            Ok(None) => info!("Recorder stopped after the panic"),
+            Err(e) => error!("Failed to stop the recorder after the panic: {}", e),
+        }
+    }
+}
+
 /// Process incoming messages from the UI and other threads
 fn process_messages(rx: &mpsc::Receiver<WindowMessage>, ui_state_arc: &Arc<Mutex<UiState>>) -> ControlFlow {
     // Try to receive a message without blocking
     match rx.try_recv() {
         Ok(message) => {
-            let ui_state = ui_state_arc.lock().unwrap();
+            let mut ui_state = lock_or_recover(ui_state_arc);
             let state_arc = ui_state.state.clone();
             
             match message {
@@ -530,80 +1777,219 @@ fn process_messages(rx: &mpsc::Receiver<WindowMessage>, ui_state_arc: &Arc<Mutex
                     gtk::main_quit();
                     return ControlFlow::Break;
                 },
-                WindowMessage::StartRecording => {
-                    info!("Starting recording");
-                    update_ui_status(&ui_state, AppStatus::Recording);
+                message @ (WindowMessage::StartRecording | WindowMessage::CountdownFinished | WindowMessage::VoiceDetected) => {
+                    wake_from_idle(&mut ui_state);
+                    // マイクテストと同じデバイスで 2 つ目のレコーダーを開かない
+                    if ui_state.mic_testing {
+                        info!("Ignoring start request during the microphone test");
+                        return ControlFlow::Continue;
+                    }
+                    // 既にレコーダーが存在する場合は新しいストリームを作らない
+                    if unsafe { (*std::ptr::addr_of!(GLOBAL_RECORDER)).is_some() } {
+                        if matches!(message, WindowMessage::StartRecording) {
+                            queue_next_recording(&ui_state);
+                        } else {
+                            info!("Recorder already active, ignoring start request");
+                        }
+                        return ControlFlow::Continue;
+                    }
+                    
+                    let mut state = match state_arc.lock() {
+                        Ok(state) => state,
+                        Err(e) => {
+                            error!("Failed to lock state: {}", e);
+                            return ControlFlow::Continue;
+                        }
+                    };
+                    
+                    if matches!(message, WindowMessage::CountdownFinished) {
+                        // 中止済みのカウントダウンは無視する
+                        if ui_state.countdown.take().is_none() {
+                            return ControlFlow::Continue;
+                        }
+                    } else if matches!(message, WindowMessage::VoiceDetected) {
+                        // 話し始めてから数えても遅いのでカウントダウンはしない
+                        if ui_state.countdown.is_some() {
+                            return ControlFlow::Continue;
+                        }
+                    } else if ui_state.countdown.is_some() {
+                        // カウントダウン中にもう一度押した場合は中止
+                        cancel_countdown(&mut ui_state, state.status);
+                        return ControlFlow::Continue;
+                    } else if state.config.recording.countdown_secs > 0 && !state.is_recording() {
+                        start_countdown(&mut ui_state, state.config.recording.countdown_secs, state.config.recording.play_sounds);
+                        return ControlFlow::Continue;
+                    }
+                    
+                    // 録音中の開始要求は捨てずに、停止後に始める
+                    if let Err(e) = state.start() {
+                        if matches!(message, WindowMessage::StartRecording) {
+                            queue_next_recording(&ui_state);
+                        } else {
+                            info!("Ignoring start request: {}", e);
+                        }
+                        return ControlFlow::Continue;
+                    }
+                    
+                    let status = state.status;
+                    info!("Starting recording ({:?})", status);
+                    update_ui_status(&ui_state, status);
+                    ui_state.warning_label.set_visible(false);
+                    ui_state.markers = Markers::default();
+                    // 前回の検出結果を新しい録音のものと見間違えないよう消す
+                    ui_state.detected_language_label.set_visible(false);
                     
                     // Get selected device
-                    let selected_device = ui_state.device_combo.active_text()
-                        .map(|text| {
-                            info!("Using selected audio device: {}", text);
-                            if text.contains("(Default)") {
-                                None // Use default device
-                            } else {
-                                Some(text.to_string())
-                            }
-                        })
-                        .unwrap_or(None);
+                    let selected_device = selected_device(&ui_state.device_combo);
+                    
+                    // 起動後に書き込めなくなった場合（マウントの変更など）もマイクを開く前に切り替える
+                    if let Some(warning) = crate::config::ensure_writable_temp_dir(&mut state.config) {
+                        ui_state.warning_label.set_markup(&format!("<b>⚠ {}</b>", glib::markup_escape_text(&warning)));
+                        ui_state.warning_label.set_visible(true);
+                    }
                     
                     // Create and start a new recorder
-                    let mut recorder = AudioRecorder::new(state_arc.lock().unwrap().config.clone());
+                    let mut recorder = AudioRecorder::new(state.config.clone());
+                    recorder.set_armed(status == AppStatus::Armed);
+                    // アイドル時モニタリングがオフの場合は録音ストリームからレベルを表示
+                    if !IDLE_MONITORING.load(Ordering::SeqCst) {
+                        recorder.set_level_meter(AUDIO_LEVEL.clone());
+                    } else if state.config.recording.preroll_secs > 0.0 {
+                        recorder.set_preroll(PREROLL.clone());
+                    }
                     
-                    if let Ok(mut state) = state_arc.lock() {
-                        match state.start() {
-                            Ok(_) => {
-                                match recorder.start_with_device(selected_device) {
-                                    Ok(_) => {
-                                        info!("Recording started successfully");
-                                        
-                                        // Store recorder in global static
-                                        unsafe {
-                                            GLOBAL_RECORDER = Some(recorder);
-                                        }
-                                        
-                                        // Spawn a new thread to wait for stop signal
-                                        let tx_clone = ui_state.tx_main.clone();
-                                        let max_duration = state.config.recording.max_duration_secs;
-                                        std::thread::spawn(move || {
-                                            // Wait for maximum recording duration
-                                            std::thread::sleep(std::time::Duration::from_secs(max_duration));
-                                            
-                                            // Send signal to stop recording after timeout
-                                            info!("Sending auto-stop signal after {} seconds", max_duration);
-                                            let _ = tx_clone.send(WindowMessage::StopRecording);
-                                        });
-                                    },
-                                    Err(e) => {
-                                        error!("Failed to start recording: {}", e);
-                                        update_ui_status(&ui_state, AppStatus::Idle);
-                                    }
+                    match recorder.start_with_device(selected_device) {
+                        Ok(_) => {
+                            info!("Recording started successfully");
+                            play_cue(&state.config, SoundCue::Start);
+                            
+                            ui_state.markers.preroll_secs = recorder.preroll_secs();
+                            
+                            // Store recorder in global static
+                            let recorder_flag = recorder.recording_flag();
+                            let refused = unsafe { store_recorder(&mut *std::ptr::addr_of_mut!(GLOBAL_RECORDER), recorder) };
+                            if let Err(mut recorder) = refused {
+                                // 上の確認があるので通常は起きないが、2つ目のストリームを残さない
+                                warn!("A recorder is already active, stopping the new one");
+                                if let Ok(Some(recording)) = recorder.stop() {
+                                    recording.discard();
                                 }
-                            },
-                            Err(e) => {
-                                error!("Failed to update state: {}", e);
-                                update_ui_status(&ui_state, AppStatus::Idle);
+                                return ControlFlow::Continue;
                             }
+                            
+                            // Spawn a new thread to wait for stop signal
+                            let tx_clone = ui_state.tx_main.clone();
+                            let recording_flag = recorder_flag;
+                            std::thread::spawn(move || {
+                                // 録音中に最大時間を変えても反映されるよう毎秒確認する
+                                let started = Instant::now();
+                                while recording_flag.load(Ordering::SeqCst) {
+                                    let max_duration = MAX_DURATION_SECS.load(Ordering::SeqCst);
+                                    if started.elapsed() >= Duration::from_secs(max_duration) {
+                                        info!("Sending auto-stop signal after {} seconds", max_duration);
+                                        let _ = tx_clone.send(WindowMessage::StopRecording);
+                                        return;
+                                    }
+                                    thread::sleep(AUTO_STOP_POLL_INTERVAL);
+                                }
+                            });
+                        },
+                        Err(e) => {
+                            error!("Failed to start recording: {}", e);
+                            report_error(&state.config, "recording_start", &e);
+                            state.status = AppStatus::Idle;
+                            update_ui_status(&ui_state, AppStatus::Idle);
                         }
                     }
                 },
-                WindowMessage::StopRecording => {
+                message @ (WindowMessage::StopRecording | WindowMessage::CancelRecording) => {
+                    let discard = matches!(message, WindowMessage::CancelRecording);
+                    // 取り消した場合は待っている次の録音も始めない
+                    if discard && ui_state.pending_recording.replace(false) {
+                        info!("Recording cancelled, dropping the queued recording");
+                        update_queue_label(&ui_state);
+                    }
+                    // カウントダウン中に離した場合は録音を始めない
+                    if ui_state.countdown.is_some() {
+                        let status = state_arc.lock().map(|state| state.status).unwrap_or(AppStatus::Idle);
+                        cancel_countdown(&mut ui_state, status);
+                        return ControlFlow::Continue;
+                    }
+                    // 録音していない時の停止要求（古い自動停止など）は無視する
+                    if !state_arc.lock().map(|state| state.is_recording()).unwrap_or(false) {
+                        info!("Not recording, ignoring stop request");
+                        return ControlFlow::Continue;
+                    }
+                    
                     info!("Stopping recording");
+                    // 停止後はタイマーが消えるので、録音の長さは先に見ておく
+                    let min_duration = match state_arc.lock() {
+                        Ok(state) => {
+                            play_cue(&state.config, SoundCue::Stop);
+                            state.config.recording.min_duration_secs
+                        },
+                        Err(_) => 0.0,
+                    };
+                    let elapsed = RECORDING_START_TIME.lock().ok().and_then(|start_time| *start_time).map(|start| start.elapsed());
+                    let too_short = is_too_short(elapsed, min_duration);
+                    if !IDLE_MONITORING.load(Ordering::SeqCst) {
+                        if let Ok(mut level) = AUDIO_LEVEL.lock() {
+                            *level = 0.0;
+                        }
+                    }
                     update_ui_status(&ui_state, AppStatus::Transcribing);
                     
-                    // Get recording path from the global recorder
-                    let recording_path = unsafe {
+                    // Get the recording from the global recorder
+                    let recording = unsafe {
                         if let Some(mut recorder) = GLOBAL_RECORDER.take() {
+                            let heard_voice = recorder.activity_detected();
                             match recorder.stop() {
-                                Ok(Some(path)) => {
-                                    info!("Recording stopped, saved to {}", path);
-                                    Some(path)
+                                Ok(Some(recording)) if !heard_voice => {
+                                    // 音声待ちのまま停止した場合は何も録音されていない
+                                    info!("Stopped before any voice was detected, discarding {}", recording);
+                                    recording.discard();
+                                    None
+                                },
+                                Ok(Some(recording)) if discard => {
+                                    info!("Recording cancelled, discarding {}", recording);
+                                    recording.discard();
+                                    None
+                                },
+                                Ok(Some(recording)) if too_short => {
+                                    // 誤タップの短い録音は Whisper が拒否したり幻聴を返したりする
+                                    info!("Recording shorter than {}s, discarding {}", min_duration, recording);
+                                    recording.discard();
+                                    let message = crate::i18n::text().recording_too_short(min_duration);
+                                    ui_state.warning_label.set_markup(&format!("<b>⚠ {}</b>", glib::markup_escape_text(&message)));
+                                    ui_state.warning_label.set_visible(true);
+                                    None
+                                },
+                                Ok(Some(recording)) => {
+                                    info!("Recording stopped: {}", recording);
+                                    Some(recording)
                                 },
                                 Ok(None) => {
                                     info!("No recording to stop");
                                     None
                                 },
+                                Err(e) if !heard_voice || discard || too_short => {
+                                    info!("Nothing usable was recorded: {}", e);
+                                    None
+                                },
                                 Err(e) => {
+                                    // 空の録音などは API エラーではなく理由をそのまま表示する
                                     error!("Failed to stop recording: {}", e);
+                                    if let Ok(state) = state_arc.lock() {
+                                        report_error(&state.config, "recording_stop", &e);
+                                    }
+                                    let t = crate::i18n::text();
+                                    let message = match e.downcast_ref::<audio::InvalidRecording>() {
+                                        Some(audio::InvalidRecording::Empty) => t.recording_empty.to_string(),
+                                        Some(audio::InvalidRecording::Corrupt(reason)) => t.recording_corrupt(reason),
+                                        None => e.to_string(),
+                                    };
+                                    ui_state.warning_label.set_markup(&format!("<b>⚠ {}</b>", glib::markup_escape_text(&message)));
+                                    ui_state.warning_label.set_visible(true);
                                     None
                                 }
                             }
@@ -617,46 +2003,16 @@ fn process_messages(rx: &mpsc::Receiver<WindowMessage>, ui_state_arc: &Arc<Mutex
                     if let Ok(mut state) = state_arc.lock() {
                         match state.stop() {
                             Ok(_) => {
-                                // Process transcription if we have a recording path
-                                if let Some(path) = recording_path.clone() {
-                                    // スレッドを分離してトランスクリプション処理を行う
-                                    let tx_clone = ui_state.tx_main.clone();
-                                    let state_clone = state_arc.clone();
-                                    
-                                    // 処理中のインジケーターを更新するタイマー
-                                    setup_processing_status_timer(&ui_state);
-                                    
-                                    // トランスクリプション処理用スレッド
-                                    std::thread::spawn(move || {
-                                        info!("Starting transcription in background thread");
-                                        let result = if let Ok(mut state) = state_clone.lock() {
-                                            state.transcribe(&path)
-                                        } else {
-                                            Err(anyhow::anyhow!("Could not lock state for transcription"))
-                                        };
-                                        
-                                        // 処理完了後、結果をメインスレッドに送信
-                                        match result {
-                                            Ok(transcript) => {
-                                                info!("Transcription complete, sending result to main thread");
-                                                let _ = tx_clone.send(WindowMessage::UpdateTranscript(transcript));
-                                            },
-                                            Err(e) => {
-                                                error!("Transcription error: {}", e);
-                                                let error_text = format!("Error: {}", e);
-                                                let _ = tx_clone.send(WindowMessage::UpdateTranscript(error_text));
-                                            }
-                                        }
-                                        
-                                        // 処理完了後、ステータスをIdleに戻す
-                                        let _ = tx_clone.send(WindowMessage::UpdateStatus(AppStatus::Idle));
-                                        
-                                        // 処理中タイマーを停止 - glib_idle_add_localは使わず、タイマーIDを送信
-                                        let _ = tx_clone.send(WindowMessage::StopProcessingTimer);
-                                    });
+                                // Process transcription if we have a recording
+                                if let Some(recording) = recording {
+                                    // キューに追加し、ワーカースレッドで文字起こしを行う
+                                    enqueue_transcription(&mut ui_state, &state, recording, true);
                                     
                                     // メインスレッドはブロックせず即座に戻る
                                     return ControlFlow::Continue;
+                                } else if ui_state.queue.is_busy() {
+                                    // 録音ファイルがなくても、キューの処理は続く
+                                    update_ui_status(&ui_state, AppStatus::Transcribing);
                                 } else {
                                     // 録音ファイルがない場合はすぐにIdleに戻す
                                     state.status = AppStatus::Idle;
@@ -675,33 +2031,370 @@ fn process_messages(rx: &mpsc::Receiver<WindowMessage>, ui_state_arc: &Arc<Mutex
                         update_ui_status(&ui_state, AppStatus::Idle);
                     }
                 },
-                WindowMessage::ShowTranscript => {
-                    // Nothing to do - transcript is already visible in the window
+                WindowMessage::TestMicrophone => {
+                    let config = match state_arc.lock() {
+                        Ok(state) if state.status == AppStatus::Idle => state.config.clone(),
+                        _ => {
+                            info!("Ignoring microphone test while recording or transcribing");
+                            return ControlFlow::Continue;
+                        }
+                    };
+                    let output = ui_state.output_combo.active_id().filter(|id| id != "default").map(|id| id.to_string());
+                    match start_mic_test(&config, selected_device(&ui_state.device_combo), output, ui_state.tx_main.clone()) {
+                        Ok(_) => {
+                            ui_state.mic_testing = true;
+                            ui_state.mic_test_button.set_sensitive(false);
+                            ui_state.mic_test_label.set_text(crate::i18n::text().mic_test_recording);
+                        },
+                        Err(e) => {
+                            error!("Failed to start microphone test: {}", e);
+                            ui_state.mic_test_label.set_text(crate::i18n::text().mic_test_failed);
+                        }
+                    }
                 },
-                WindowMessage::UpdateStatus(status) => {
-                    update_ui_status(&ui_state, status);
+                #[cfg(feature = "tray")]
+                WindowMessage::ToggleVisibility => {
+                    wake_from_idle(&mut ui_state);
+                    // 非表示中もモニタリングや録音・文字起こしはそのまま動作する
+                    if ui_state.window.is_visible() && ui_state.window.is_active() {
+                        info!("Hiding window");
+                        ui_state.window.hide();
+                    } else {
+                        info!("Showing window");
+                        ui_state.window.show();
+                        ui_state.window.present();
+                    }
+                    update_recording_overlay(&ui_state);
+                },
+                WindowMessage::FilterHistory => {
+                    render_history(&ui_state);
+                },
+                WindowMessage::DetectedLanguage(language) => match language {
+                    Some(language) => {
+                        ui_state.detected_language_label.set_text(&crate::i18n::text().detected_language(&language));
+                        ui_state.detected_language_label.set_visible(true);
+                    },
+                    // 言語が返らなかった文字起こしに前回の言語を残さない
+                    None => ui_state.detected_language_label.set_visible(false),
+                },
+                WindowMessage::ApiKeyRejected => {
+                    set_api_key_banner_text(&ui_state.api_key_label, crate::i18n::text().api_key_invalid);
+                    ui_state.api_key_banner.set_visible(true);
+                },
+                WindowMessage::Reformat(instruction) => {
+                    let text = buffer_text(&ui_state.transcript_buffer);
+                    if text.trim().is_empty() || text == crate::i18n::text().transcript_placeholder {
+                        info!("Transcript is empty, nothing to reformat");
+                        return ControlFlow::Continue;
+                    }
+                    let config = lock_or_recover(&state_arc).config.clone();
+                    ui_state.reformat_button.set_sensitive(false);
+                    let tx = ui_state.tx_main.clone();
+                    thread::spawn(move || {
+                        let processor = crate::text_processor::TranscriptionProcessor::new(config);
+                        let result = processor.reformat(&text, &instruction).map_err(|e| {
+                            error!("Reformatting failed: {:#}", e);
+                            let t = crate::i18n::text();
+                            match e.downcast_ref::<crate::text_processor::ReformatError>() {
+                                Some(crate::text_processor::ReformatError::NoApiKey) => t.reformat_needs_api_key.to_string(),
+                                None => t.reformat_failed(&format!("{:#}", e)),
+                            }
+                        });
+                        let _ = tx.send(WindowMessage::ReformatFinished(text, result));
+                    });
+                },
+                WindowMessage::ReformatFinished(source, result) => {
+                    ui_state.reformat_button.set_sensitive(true);
+                    // 整形中に新しい文字起こしや編集で内容が変わった場合は上書きしない
+                    if buffer_text(&ui_state.transcript_buffer) != source {
+                        info!("Transcript changed while reformatting; dropping the reformatted text");
+                        return ControlFlow::Continue;
+                    }
+                    match result {
+                        Ok(text) if !text.trim().is_empty() => {
+                            set_transcript(&mut ui_state, text.trim().to_string());
+                            info!("Transcript reformatted");
+                        },
+                        Ok(_) => warn!("Reformatting returned no text; keeping the transcript"),
+                        Err(message) => {
+                            ui_state.warning_label.set_markup(&format!("<b>⚠ {}</b>", glib::markup_escape_text(&message)));
+                            ui_state.warning_label.set_visible(true);
+                        },
+                    }
+                },
+                WindowMessage::MicTestFinished(result) => {
+                    ui_state.mic_testing = false;
+                    ui_state.mic_test_button.set_sensitive(true);
+                    ui_state.mic_test_label.set_text(&result);
+                },
+                WindowMessage::DeviceCapabilities(device, tooltip) => {
+                    // 問い合わせ中に別のデバイスを選んだ場合は古い結果を捨てる
+                    let selected = ui_state.device_combo.active_text().filter(|text| !text.contains("(Default)"));
+                    if selected.as_deref() == device.as_deref() {
+                        ui_state.device_combo.set_tooltip_text(Some(&tooltip));
+                    }
+                },
+                WindowMessage::AudioDevicesListed(devices) => {
+                    refresh_audio_devices(&ui_state, &devices);
+                },
+                WindowMessage::DropMarker => {
+                    let recording = state_arc.lock().is_ok_and(|state| state.status == AppStatus::Recording);
+                    let elapsed = RECORDING_START_TIME.lock().ok().and_then(|start_time| *start_time).map(|start| start.elapsed());
+                    if let Some(elapsed) = elapsed.filter(|_| recording) {
+                        // 先頭に付け足した直前の入力の分は文字起こしのときにずらす
+                        ui_state.markers.times.push(elapsed.as_secs_f64());
+                        info!("Marker {} dropped at {:.1}s", ui_state.markers.times.len(), elapsed.as_secs_f64());
+                        let message = crate::i18n::text().marker_dropped(ui_state.markers.times.len(), elapsed.as_secs());
+                        ui_state.warning_label.set_markup(&format!("<b>{}</b>", glib::markup_escape_text(&message)));
+                        ui_state.warning_label.set_visible(true);
+                    } else {
+                        info!("Not recording, ignoring the marker");
+                    }
+                },
+                WindowMessage::OpenConfig => {
+                    match open_config_file(&ui_state.config_path) {
+                        Ok(_) => {
+                            ui_state.warning_label.set_markup(&format!(
+                                "<b>{}</b>",
+                                glib::markup_escape_text(crate::i18n::text().config_opened)
+                            ));
+                            ui_state.warning_label.set_visible(true);
+                        },
+                        Err(e) => {
+                            error!("Failed to open the config file: {}", e);
+                            let message = crate::i18n::text().config_open_failed(&ui_state.config_path.display().to_string(), &e.to_string());
+                            ui_state.warning_label.set_markup(&format!("<b>⚠ {}</b>", glib::markup_escape_text(&message)));
+                            ui_state.warning_label.set_visible(true);
+                        }
+                    }
+                },
+                WindowMessage::ReloadConfig => {
+                    reload_config(&mut ui_state);
+                },
+                WindowMessage::SetMaxDuration(secs) => {
+                    set_max_duration(&state_arc, secs);
+                },
+                WindowMessage::SaveMaxDuration => {
+                    // 表示している分数を保存する（設定の秒数が表示と違っても、見えている値にそろえる）
+                    let secs = spin_duration_secs(&ui_state.max_duration_spin);
+                    set_max_duration(&state_arc, secs);
+                    let t = crate::i18n::text();
+                    let message = match crate::config::save_max_duration(&ui_state.config_path, secs) {
+                        Ok(_) => {
+                            info!("Saved recording.max_duration_secs = {} to {}", secs, ui_state.config_path.display());
+                            format!("<b>{}</b>", glib::markup_escape_text(&t.max_duration_saved(secs)))
+                        },
+                        Err(e) => {
+                            error!("Failed to save the maximum recording duration: {:#}", e);
+                            let message = t.max_duration_save_failed(&format!("{:#}", e));
+                            format!("<b>⚠ {}</b>", glib::markup_escape_text(&message))
+                        },
+                    };
+                    ui_state.warning_label.set_markup(&message);
+                    ui_state.warning_label.set_visible(true);
+                },
+                WindowMessage::SetFormattingContext(context) => {
+                    if let Ok(mut state) = state_arc.lock() {
+                        if state.config.text_processing.context != context {
+                            info!("Formatting context set to \"{}\"", context);
+                            state.config.text_processing.context = context;
+                            state.api = Arc::new(TranscriptionAPI::new(state.config.clone()));
+                        }
+                    }
+                },
+                WindowMessage::SetFormattingStyle(style) => {
+                    if let Ok(mut state) = state_arc.lock() {
+                        if state.config.text_processing.style != style {
+                            info!("Formatting style set to {}", style.id());
+                            state.config.text_processing.style = style;
+                            state.api = Arc::new(TranscriptionAPI::new(state.config.clone()));
+                        }
+                    }
+                },
+                WindowMessage::SetOutputDevice(sink) => {
+                    if let Ok(mut state) = state_arc.lock() {
+                        if state.config.audio.output_device != sink {
+                            info!("Output device set to {:?}", sink);
+                            state.config.audio.output_device = sink;
+                        }
+                    }
+                },
+                WindowMessage::SetApiProfile(profile) => {
+                    let current = lock_or_recover(&state_arc).config.api.clone();
+                    if current.active_profile != profile {
+                        match crate::config::save_active_profile(&ui_state.config_path, profile.as_deref()) {
+                            Ok(_) => {
+                                info!("API profile set to {:?}", profile);
+                                reload_config(&mut ui_state);
+                            },
+                            Err(e) => {
+                                error!("Failed to save the API profile: {:#}", e);
+                                populate_api_profiles(&ui_state.profile_combo, &current);
+                                let message = crate::i18n::text().api_profile_switch_failed(&format!("{:#}", e));
+                                ui_state.warning_label.set_markup(&format!("<b>⚠ {}</b>", glib::markup_escape_text(&message)));
+                                ui_state.warning_label.set_visible(true);
+                            },
+                        }
+                    }
+                },
+                WindowMessage::TranscribeClipboard => {
+                    if let Ok(mut state) = state_arc.lock() {
+                        if state.is_recording() {
+                            info!("Ignoring clipboard transcription while recording");
+                        } else {
+                            transcribe_audio_file(&mut ui_state, &mut state, clipboard_audio_path(), "clipboard");
+                        }
+                    }
+                },
+                WindowMessage::TranscribeFile(path) => {
+                    if let Ok(mut state) = state_arc.lock() {
+                        if state.is_recording() {
+                            info!("Ignoring file transcription while recording");
+                        } else {
+                            transcribe_audio_file(&mut ui_state, &mut state, audio_file_path(&path), "file chooser");
+                        }
+                    }
+                },
+                WindowMessage::TranscribeCopiedFile(path) => {
+                    if let Ok(mut state) = state_arc.lock() {
+                        if state.is_recording() {
+                            info!("Ignoring copied audio file while recording: {}", path);
+                        } else {
+                            transcribe_audio_file(&mut ui_state, &mut state, audio_file_path(&path), "clipboard watcher");
+                        }
+                    }
+                },
+                WindowMessage::RecoveredRecordings(paths) => {
+                    // ここで dialog.run() すると UiState を握ったまま入れ子のメインループが回り、
+                    // 毎秒のタイマーが同じロックを待って固まるので、返事はメッセージで受け取る
+                    confirm_recovered_recordings(&ui_state.window, paths, ui_state.tx_main.clone());
+                },
+                WindowMessage::TranscribeRecoveredRecordings(paths) => {
                     if let Ok(mut state) = state_arc.lock() {
-                        state.status = status;
+                        if state.is_recording() {
+                            info!("Ignoring recovered recordings while recording");
+                        } else {
+                            for path in &paths {
+                                let path = audio_file_path(&path.to_string_lossy());
+                                transcribe_audio_file(&mut ui_state, &mut state, path, "recovered recording");
+                            }
+                        }
+                    }
+                },
+                WindowMessage::CopyTranscript => {
+                    if let Ok(state) = state_arc.lock() {
+                        if copy_transcript(&state) && state.config.ui.notification_enabled {
+                            send_notification(
+                                crate::i18n::text().notify_copied,
+                                &truncate_preview(&state.transcript, state.config.ui.preview_chars),
+                            );
+                        }
                     }
                 },
                 WindowMessage::UpdateTranscript(text) => {
+                    set_transcript(&mut ui_state, text);
+                },
+                WindowMessage::TranscriptionFinished(generation, _, _) if generation != ui_state.queue.generation => {
+                    info!("Ignoring the result of an aborted transcription");
+                },
+                WindowMessage::PasteTranscript(generation) => {
+                    if generation == ui_state.queue.generation {
+                        if let Ok(state) = state_arc.lock() {
+                            auto_paste_transcript(&ui_state.window, &state.config);
+                        }
+                    }
+                },
+                WindowMessage::TranscriptionFinished(_, result, tag) => {
+                    ui_state.queue.finish();
+                    ui_state.last_activity = Instant::now();
+                    if let Some(result) = result {
+                        let text = match result {
+                            Ok(transcript) => {
+                                remember_recent_transcript(&mut ui_state, &transcript, tag);
+                                if let Ok(state) = state_arc.lock() {
+                                    play_cue(&state.config, SoundCue::Done);
+                                }
+                                transcript
+                            },
+                            Err(message) => {
+                                if let Ok(state) = state_arc.lock() {
+                                    signal_transcription_error(&ui_state, &message, &state.config);
+                                }
+                                message
+                            },
+                        };
+                        let mode = state_arc.lock().map(|state| state.config.ui.on_new_transcript).unwrap_or_default();
+                        let mut current = buffer_text(&ui_state.transcript_buffer);
+                        if current == crate::i18n::text().transcript_placeholder {
+                            current.clear();
+                        }
+                        if mode == NewTranscriptMode::NewTab && ui_state.queue.starts_batch() && !current.trim().is_empty() {
+                            archive_transcript_tab(&ui_state, &current);
+                        }
+                        let merged = ui_state.queue.merge_result(&current, text, mode == NewTranscriptMode::Append);
+                        set_transcript(&mut ui_state, merged);
+                    }
+                    
+                    if let Ok(mut state) = state_arc.lock() {
+                        dispatch_transcriptions(&mut ui_state, &state);
+                        if !ui_state.queue.is_busy() {
+                            stop_processing_timer();
+                            // 録音中に終わった場合はステータスを変えない
+                            if state.status == AppStatus::Transcribing {
+                                state.status = AppStatus::Idle;
+                                update_ui_status(&ui_state, AppStatus::Idle);
+                                if state.config.ui.resume_monitoring_after_record {
+                                    resume_idle_monitoring(&ui_state);
+                                }
+                            }
+                        }
+                    }
+                    update_queue_label(&ui_state);
+                },
+                WindowMessage::AbortTranscription => {
+                    let dropped = ui_state.queue.abort();
+                    info!("Transcription aborted ({} queued recordings dropped)", dropped.len());
+                    for job in dropped.iter().filter(|job| job.own_recording) {
+                        if let Some(path) = job.recording.path() {
+                            info!("Recording kept at {} after the aborted transcription", path);
+                        }
+                    }
+                    stop_processing_timer();
                     if let Ok(mut state) = state_arc.lock() {
-                        state.transcript = text.clone();
+                        // 録音中に中止した場合はステータスを変えない
+                        if state.status == AppStatus::Transcribing {
+                            state.status = AppStatus::Idle;
+                            update_ui_status(&ui_state, AppStatus::Idle);
+                            if state.config.ui.resume_monitoring_after_record {
+                                resume_idle_monitoring(&ui_state);
+                            }
+                        }
                     }
-                    update_transcript_text(&ui_state.transcript_buffer, &text);
+                    update_queue_label(&ui_state);
                 },
-                WindowMessage::StopProcessingTimer => {
-                    // 処理中タイマーを停止
-                    if let Ok(mut timer_id) = PROCESSING_STATUS_TIMER_ID.lock() {
-                        if let Some(id) = timer_id.take() {
-                            id.remove();
+                WindowMessage::UndoTranscript | WindowMessage::RedoTranscript => {
+                    let current = buffer_text(&ui_state.transcript_buffer);
+                    let restored = if matches!(message, WindowMessage::UndoTranscript) {
+                        ui_state.history.undo(current)
+                    } else {
+                        ui_state.history.redo(current)
+                    };
+                    if let Some(text) = restored {
+                        if let Ok(mut state) = state_arc.lock() {
+                            state.transcript = text.clone();
                         }
+                        update_transcript_text(&ui_state, &text);
                     }
                 },
+                WindowMessage::ShowWarning(text) => {
+                    ui_state.warning_label.set_markup(&format!("<b>⚠ {}</b>", glib::markup_escape_text(&text)));
+                    ui_state.warning_label.set_visible(true);
+                },
             }
         },
         Err(mpsc::TryRecvError::Empty) => {
-            // No message, continue
+            // No message, check on the recorder
+            poll_recorder(ui_state_arc);
         },
         Err(mpsc::TryRecvError::Disconnected) => {
             error!("Message channel disconnected");
@@ -714,145 +2407,940 @@ fn process_messages(rx: &mpsc::Receiver<WindowMessage>, ui_state_arc: &Arc<Mutex
     ControlFlow::Continue
 }
 
-/// Add simplified keyboard shortcuts
-fn setup_keyboard_shortcuts(window: &Window, config: &Config, tx: Sender<WindowMessage>) {
-    // For recording - handle key press event
-    let tx_clone = tx.clone();
-    let key = config.shortcuts.toggle_recording.clone();
-    window.connect_key_press_event(move |_, event| {
-        if is_shortcut_key(event, &key) && !SHORTCUT_KEY_PRESSED.load(Ordering::SeqCst) {
-            info!("Shortcut key pressed - starting recording");
-            SHORTCUT_KEY_PRESSED.store(true, Ordering::SeqCst);
-            let _ = tx_clone.send(WindowMessage::StartRecording);
-            return glib::Propagation::Stop;
-        }
-        glib::Propagation::Proceed
-    });
-    
-    // For recording - handle key release event
-    let tx_clone = tx.clone();
-    let key = config.shortcuts.toggle_recording.clone();
-    window.connect_key_release_event(move |_, event| {
-        if is_shortcut_key(event, &key) && SHORTCUT_KEY_PRESSED.load(Ordering::SeqCst) {
-            info!("Shortcut key released - stopping recording and transcribing");
-            SHORTCUT_KEY_PRESSED.store(false, Ordering::SeqCst);
-            let _ = tx_clone.send(WindowMessage::StopRecording);
-            return glib::Propagation::Stop;
-        }
-        glib::Propagation::Proceed
-    });
-    
-    // For clearing transcript
-    let tx_clone = tx.clone();
-    let key = config.shortcuts.clear_transcript.clone();
-    window.connect_key_press_event(move |_, event| {
-        if is_shortcut_key(event, &key) {
-            let _ = tx_clone.send(WindowMessage::UpdateTranscript(String::new()));
-            return glib::Propagation::Stop;
-        }
-        glib::Propagation::Proceed
-    });
+/// Follow recorder events that happen outside the message flow
+///
+/// - voice-activated start: sustained speech while idle starts a recording
+/// - Armed switches to Recording once the recorder has detected voice
+/// - a recording the recorder stopped on silence is transcribed
+fn poll_recorder(ui_state_arc: &Arc<Mutex<UiState>>) {
+    let ui_state = lock_or_recover(ui_state_arc);
+    let mut state = lock_or_recover(&ui_state.state);
     
-    // For copying to clipboard
-    let tx_clone = tx.clone();
-    let key = config.shortcuts.copy_to_clipboard.clone();
-    window.connect_key_press_event(move |_, event| {
-        if is_shortcut_key(event, &key) {
-            let _ = tx_clone.send(WindowMessage::ShowTranscript);
-            return glib::Propagation::Stop;
-        }
-        glib::Propagation::Proceed
-    });
+    // 録音中に検出した音声では開始しない（文字起こし中はキューに追加されるので可）
+    if VAD_TRIGGERED.swap(false, Ordering::SeqCst) && matches!(state.status, AppStatus::Idle | AppStatus::Transcribing) {
+        info!("Starting voice-activated recording");
+        let _ = ui_state.tx_main.send(WindowMessage::VoiceDetected);
+        return;
+    }
     
-    info!("Keyboard shortcuts configured");
-}
-
-/// Check if a key event matches a shortcut string like "Alt+Shift+R"
-fn is_shortcut_key(event: &gdk::EventKey, shortcut: &str) -> bool {
-    let parts: Vec<&str> = shortcut.split('+').collect();
-    let key_part = parts.last().unwrap().to_lowercase();
-    
-    // Check if the key matches
-    let key_matches = match key_part.as_str() {
-        "r" => event.keyval() == gdk::keys::constants::r,
-        "c" => event.keyval() == gdk::keys::constants::c,
-        "x" => event.keyval() == gdk::keys::constants::x,
-        "f1" => event.keyval() == gdk::keys::constants::F1,
-        "f2" => event.keyval() == gdk::keys::constants::F2,
-        "space" => event.keyval() == gdk::keys::constants::space,
-        // ... add more key mappings as needed
-        _ => {
-            // Try to match a single character key directly
-            if key_part.len() == 1 {
-                let c = key_part.chars().next().unwrap();
-                let keyval = event.keyval();
-                
-                let low_char = c.to_lowercase().next().unwrap();
-                let key_code = match low_char {
-                    'a' => gdk::keys::constants::a,
-                    'b' => gdk::keys::constants::b,
-                    'd' => gdk::keys::constants::d,
-                    'e' => gdk::keys::constants::e,
-                    'f' => gdk::keys::constants::f,
-                    'g' => gdk::keys::constants::g,
-                    'h' => gdk::keys::constants::h,
-                    'i' => gdk::keys::constants::i,
-                    'j' => gdk::keys::constants::j,
-                    'k' => gdk::keys::constants::k,
-                    'l' => gdk::keys::constants::l,
-                    'm' => gdk::keys::constants::m,
-                    'n' => gdk::keys::constants::n,
-                    'o' => gdk::keys::constants::o,
-                    'p' => gdk::keys::constants::p,
-                    'q' => gdk::keys::constants::q,
-                    'r' => gdk::keys::constants::r,
-                    's' => gdk::keys::constants::s,
-                    't' => gdk::keys::constants::t,
-                    'u' => gdk::keys::constants::u,
-                    'v' => gdk::keys::constants::v,
-                    'w' => gdk::keys::constants::w,
-                    'x' => gdk::keys::constants::x,
-                    'y' => gdk::keys::constants::y,
-                    'z' => gdk::keys::constants::z,
-                    _ => keyval, // If not found, use the keyval from the event
-                };
-                
-                keyval == key_code
-            } else {
-                false
-            }
-        }
+    let recorder = unsafe { (*std::ptr::addr_of!(GLOBAL_RECORDER)).as_ref() };
+    let Some(recorder) = recorder else {
+        return;
     };
     
-    // Check modifiers
-    let shift_needed = parts.contains(&"Shift");
-    let alt_needed = parts.contains(&"Alt");
-    let ctrl_needed = parts.contains(&"Control") || parts.contains(&"Ctrl");
-    
-    let state = event.state();
-    let shift_pressed = state.contains(gdk::ModifierType::SHIFT_MASK);
-    let alt_pressed = state.contains(gdk::ModifierType::MOD1_MASK);
-    let ctrl_pressed = state.contains(gdk::ModifierType::CONTROL_MASK);
-    
-    key_matches && 
-        shift_pressed == shift_needed && 
-        alt_pressed == alt_needed && 
-        ctrl_pressed == ctrl_needed
+    match state.status {
+        AppStatus::Armed if recorder.activity_detected() => {
+            info!("Voice detected, switching from armed to recording");
+            state.status = AppStatus::Recording;
+            drop(state);
+            update_ui_status(&ui_state, AppStatus::Recording);
+        },
+        AppStatus::Recording if !recorder.is_recording() => {
+            info!("Recorder stopped on its own, transcribing");
+            let _ = ui_state.tx_main.send(WindowMessage::StopRecording);
+        },
+        _ => {}
+    }
 }
 
-/// Update the UI status (button and label)
-fn update_ui_status(ui_state: &UiState, status: AppStatus) {
-    // 既存のタイマーがあれば削除
-    if let Ok(mut timer_id) = BUTTON_UPDATE_TIMER_ID.lock() {
-        if let Some(id) = timer_id.take() {
-            // source_removeではなく、SourceIdのメソッドを使用
-            id.remove();
-        }
-    }
+/// Languages offered by the per-recording language override (id, native name), after "auto"
+const LANGUAGE_OVERRIDES: &[(&str, &str)] = &[
+    ("ja", "日本語"),
+    ("en", "English"),
+    ("zh", "中文"),
+    ("ko", "한국어"),
+    ("es", "Español"),
+    ("fr", "Français"),
+    ("de", "Deutsch"),
+];
+
+/// Fill the tag selector with "no tag" and `tags`, keeping the selection if it is still offered
+fn populate_tags(combo: &ComboBoxText, tags: &[String]) {
+    let selected = combo.active_id();
+    combo.remove_all();
+    combo.append(Some(""), crate::i18n::text().tag_none);
+    for tag in tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+        combo.append(Some(tag), tag);
+    }
+    if !selected.is_some_and(|id| combo.set_active_id(Some(&id))) {
+        combo.set_active_id(Some(""));
+    }
+}
+
+/// Fill the API profile selector with `api.profiles` and select the active one
+fn populate_api_profiles(combo: &ComboBoxText, api: &ApiConfig) {
+    combo.remove_all();
+    combo.append(Some(""), crate::i18n::text().api_profile_default);
+    for name in api.profiles.keys() {
+        combo.append(Some(name), name);
+    }
+    if !api.active_profile.as_deref().is_some_and(|name| combo.set_active_id(Some(name))) {
+        combo.set_active_id(Some(""));
+    }
+}
+
+/// The tag chosen in the selector, if any
+fn selected_tag(combo: &ComboBoxText) -> Option<String> {
+    combo.active_id()
+        .map(|id| id.to_string())
+        .filter(|id| !id.is_empty())
+}
+
+/// Read the one-shot language override and reset the selection to "auto"
+fn take_language_override(combo: &ComboBoxText) -> Option<String> {
+    let language = combo.active_id()
+        .map(|id| id.to_string())
+        .filter(|id| id != "auto");
+    combo.set_active_id(Some("auto"));
+    language
+}
+
+/// Read an audio file path from the clipboard and validate it
+fn clipboard_audio_path() -> std::result::Result<String, String> {
+    let text = clipboard::get_text()
+        .map_err(|e| crate::i18n::text().clipboard_read_failed(&e.to_string()))?;
+    
+    let path = copied_path(&text);
+    if path.is_empty() {
+        return Err(crate::i18n::text().clipboard_has_no_path.to_string());
+    }
+    audio_file_path(&path)
+}
+
+/// File path in copied text, which may be a `file://` URI from a file manager
+///
+/// The URI is percent-decoded, so "file:///home/me/my%20memo.wav" becomes "/home/me/my memo.wav".
+fn copied_path(text: &str) -> String {
+    let text = text.trim();
+    if text.starts_with("file://") {
+        match glib::filename_from_uri(text) {
+            Ok((path, _)) => return path.to_string_lossy().into_owned(),
+            Err(e) => debug!("Could not decode file URI {:?}: {}", text, e),
+        }
+    }
+    text.strip_prefix("file://").unwrap_or(text).to_string()
+}
+
+/// Shortest interval between two clipboard reads of `integrations.watch_clipboard`
+const MIN_CLIPBOARD_WATCH_INTERVAL_MS: u64 = 200;
+/// How often a disabled clipboard watcher checks whether it was turned on by a reload
+const CLIPBOARD_WATCH_DISABLED_POLL: Duration = Duration::from_secs(2);
+
+/// Poll the clipboard for `integrations.watch_clipboard` and queue newly copied audio files
+///
+/// The setting is read on every round, so reloading the config turns the watcher on and off.
+/// The clipboard is read on this thread since the clipboard tools can take a while.
+fn start_clipboard_watcher(state: Arc<Mutex<ThreadSafeState>>, tx: Sender<WindowMessage>) {
+    thread::spawn(move || {
+        let mut watcher = ClipboardWatcher::default();
+        loop {
+            let integrations = lock_or_recover(&state).config.integrations.clone();
+            if !integrations.watch_clipboard {
+                // 有効に戻した時に、その時点のクリップボードを文字起こししないよう忘れる
+                watcher = ClipboardWatcher::default();
+                thread::sleep(CLIPBOARD_WATCH_DISABLED_POLL);
+                continue;
+            }
+            if let Ok(text) = clipboard::get_text() {
+                if let Some(path) = watcher.observe(&text) {
+                    info!("Audio file path copied to the clipboard: {}", path);
+                    if tx.send(WindowMessage::TranscribeCopiedFile(path)).is_err() {
+                        break;
+                    }
+                }
+            }
+            thread::sleep(Duration::from_millis(integrations.watch_clipboard_interval_ms.max(MIN_CLIPBOARD_WATCH_INTERVAL_MS)));
+        }
+    });
+}
+
+/// Change detection of the clipboard watcher
+#[derive(Default)]
+struct ClipboardWatcher {
+    /// Clipboard text of the previous read; None before the first one
+    last_text: Option<String>,
+}
+
+impl ClipboardWatcher {
+    /// The audio file to transcribe when `text` was newly copied
+    ///
+    /// Nothing is returned for the text found by the first read, or while the clipboard
+    /// stays the same, so one copy transcribes the file once.
+    fn observe(&mut self, text: &str) -> Option<String> {
+        let text = text.trim();
+        if self.last_text.as_deref() == Some(text) {
+            return None;
+        }
+        let first_read = self.last_text.replace(text.to_string()).is_none();
+        if first_read {
+            return None;
+        }
+        audio_file_path(&copied_path(text)).ok()
+    }
+}
+
+/// Check that `text` names an existing file with a supported audio extension
+fn audio_file_path(text: &str) -> std::result::Result<String, String> {
+    let path = std::path::Path::new(text);
+    if !path.is_file() {
+        return Err(crate::i18n::text().audio_file_not_found(text));
+    }
+    if !audio::has_supported_audio_extension(path) {
+        return Err(crate::i18n::text().not_an_audio_file(&audio::SUPPORTED_AUDIO_EXTENSIONS.join(", "), text));
+    }
+    
+    Ok(text.to_string())
+}
+
+/// Queue the audio file `path` for transcription, or show why it can't be transcribed
+///
+/// `source` names where the path came from in the log.
+fn transcribe_audio_file(
+    ui_state: &mut UiState,
+    state: &mut ThreadSafeState,
+    path: std::result::Result<String, String>,
+    source: &str,
+) {
+    match path {
+        Ok(path) => {
+            info!("Transcribing audio file from {}: {}", source, path);
+            ui_state.warning_label.set_visible(false);
+            state.status = AppStatus::Transcribing;
+            update_ui_status(ui_state, AppStatus::Transcribing);
+            enqueue_transcription(ui_state, state, Recording::File(path), false);
+        },
+        Err(message) => {
+            warn!("{}", message);
+            ui_state.warning_label.set_markup(&format!("<b>⚠ {}</b>", glib::markup_escape_text(&message)));
+            ui_state.warning_label.set_visible(true);
+        }
+    }
+}
+
+/// Queue a recording for transcription and start it if a worker is free
+///
+/// `own_recording` enables the peak/RMS check and the archive/delete step afterwards,
+/// which only apply to our own WAV recordings.
+fn enqueue_transcription(ui_state: &mut UiState, state: &ThreadSafeState, recording: Recording, own_recording: bool) {
+    let language = take_language_override(&ui_state.language_combo);
+    let device = if own_recording { ui_state.device_combo.active_text().map(|text| text.to_string()) } else { None };
+    let verbatim = ui_state.verbatim_toggle.is_active();
+    let tag = selected_tag(&ui_state.tag_combo);
+    let markers = if own_recording { std::mem::take(&mut ui_state.markers) } else { Markers::default() };
+    ui_state.queue.push(TranscriptionJob { recording, language, own_recording, device, verbatim, tag, markers });
+    
+    // 処理中のインジケーターを更新するタイマー
+    if PROCESSING_STATUS_TIMER_ID.lock().map(|id| id.is_none()).unwrap_or(false) {
+        setup_processing_status_timer(ui_state);
+    }
+    
+    dispatch_transcriptions(ui_state, state);
+    update_queue_label(ui_state);
+}
+
+/// Start queued jobs in FIFO order, up to `api.max_concurrent` at a time
+fn dispatch_transcriptions(ui_state: &mut UiState, state: &ThreadSafeState) {
+    while let Some(job) = ui_state.queue.next_job(state.config.api.max_concurrent) {
+        spawn_transcription(ui_state, state, job);
+    }
+}
+
+/// Show how many recordings are being transcribed or waiting, and whether another recording is queued
+fn update_queue_label(ui_state: &UiState) {
+    let t = crate::i18n::text();
+    let queue = &ui_state.queue;
+    let mut parts = Vec::new();
+    if queue.is_busy() {
+        parts.push(t.queue_status(queue.active, queue.pending.len()));
+    }
+    if ui_state.pending_recording.get() {
+        parts.push(t.recording_queued.to_string());
+    }
+    ui_state.queue_label.set_text(&parts.join(" · "));
+    ui_state.queue_label.set_visible(!parts.is_empty());
+    ui_state.abort_button.set_visible(queue.is_busy());
+}
+
+/// Remember a start request that arrived during a recording
+///
+/// `update_ui_status` starts it once the recording is stopped.
+fn queue_next_recording(ui_state: &UiState) {
+    if !ui_state.pending_recording.replace(true) {
+        info!("Recording in progress, queueing the next recording");
+    }
+    update_queue_label(ui_state);
+}
+
+/// Transcribe an audio file in a background thread and report the result to the main thread
+fn spawn_transcription(ui_state: &UiState, state: &ThreadSafeState, job: TranscriptionJob) {
+    let TranscriptionJob { recording, language, own_recording, device, verbatim, tag, markers } = job;
+    let generation = ui_state.queue.generation;
+    let cancel = ui_state.queue.cancel.clone();
+    let tx_clone = ui_state.tx_main.clone();
+    let api = state.api.clone();
+    let clipboard_config = state.config.clipboard.clone();
+    let warn_on_bad_audio = state.config.recording.warn_on_bad_audio;
+    let skip_bad_audio = state.config.recording.skip_bad_audio;
+    let notification_enabled = state.config.ui.notification_enabled;
+    let preview_chars = state.config.ui.preview_chars;
+    let recording_config = state.config.recording.clone();
+    let temp_dir = state.config.temp_dir.clone();
+    let log_jsonl = own_recording && state.config.logging.jsonl;
+    let integrations_config = state.config.integrations.clone();
+    let report_config = state.config.clone();
+    
+    // トランスクリプション処理用スレッド
+    std::thread::spawn(move || {
+        // 録音はアーカイブで移動・削除されるので、長さは先に読んでおく
+        let duration = if log_jsonl {
+            audio::recording_duration(&recording)
+                .map_err(|e| warn!("Failed to read recording duration: {}", e))
+                .ok()
+        } else {
+            None
+        };
+        
+        // アップロード前に録音の音量をチェック
+        if own_recording && warn_on_bad_audio {
+            match audio::analyze_recording(&recording) {
+                Ok(analysis) => {
+                    if let Some(warning) = analysis.warning() {
+                        warn!("{}", warning);
+                        let _ = tx_clone.send(WindowMessage::ShowWarning(warning));
+                        
+                        if skip_bad_audio {
+                            info!("Skipping transcription of bad recording");
+                            let _ = tx_clone.send(WindowMessage::TranscriptionFinished(generation, None, tag));
+                            return;
+                        }
+                    }
+                },
+                Err(e) => warn!("Failed to analyze recording: {}", e),
+            }
+        }
+        
+        info!("Starting transcription in background thread");
+        // パニックしても結果を返し、キューを止めない
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            transcribe_recording(&api, &clipboard_config, &recording, language.as_deref(), verbatim, &markers, &cancel)
+        }))
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("Transcription worker panicked")));
+        
+        // 処理完了後、結果をメインスレッドに送信（ステータスやキューはメインスレッドで更新）
+        let mut paste = false;
+        let text = match result {
+            Ok(None) => {
+                info!("Transcription aborted");
+                if let (true, Some(path)) = (own_recording, recording.path()) {
+                    info!("Recording kept at {} after the aborted transcription", path);
+                }
+                return;
+            },
+            Ok(Some((ProcessedTranscript { text: transcript, notices, language, backend }, copied))) => {
+                info!("Transcription complete, sending result to main thread");
+                if let Some(language) = &language {
+                    info!("Detected language: {}", language);
+                }
+                let _ = tx_clone.send(WindowMessage::DetectedLanguage(language));
+                // 長い文字起こしを分割・省略して整形した場合などは画面に表示する
+                for notice in notices {
+                    warn!("{}", notice);
+                    let _ = tx_clone.send(WindowMessage::ShowWarning(notice));
+                }
+                if notification_enabled && !transcript.is_empty() {
+                    send_notification(crate::i18n::text().notify_transcription_done, &truncate_preview(&transcript, preview_chars));
+                }
+                // メモファイルへの追記に失敗しても文字起こしは続ける
+                if !transcript.is_empty() {
+                    if let Err(e) = integrations::append_to_output_file(&integrations_config, &transcript) {
+                        warn!("Failed to append transcript to the output file: {:#}", e);
+                    }
+                }
+                if log_jsonl {
+                    let entry = TranscriptionLogEntry {
+                        timestamp: chrono::Local::now().to_rfc3339(),
+                        duration_secs: duration.map(|duration| duration.as_secs_f64()),
+                        device,
+                        chars: transcript.chars().count(),
+                        backend,
+                        tag: tag.clone(),
+                    };
+                    // 書き込みに失敗しても文字起こしは続ける
+                    if let Err(e) = append_transcription_log(&temp_dir, &entry) {
+                        warn!("Failed to append to {}: {}", TRANSCRIPTION_LOG_FILE, e);
+                    }
+                }
+                if own_recording {
+                    if let Err(e) = audio::archive_recording(&recording, &recording_config, &temp_dir) {
+                        warn!("Failed to archive or delete recording {}: {}", recording, e);
+                    }
+                }
+                paste = copied && !transcript.trim().is_empty();
+                Ok(transcript)
+            },
+            Err(e) => {
+                error!("Transcription error: {}", e);
+                report_error(&report_config, "transcription", &e);
+                if let (true, Some(path)) = (own_recording, recording.path()) {
+                    info!("Recording kept at {} after the failed transcription", path);
+                }
+                if matches!(e.downcast_ref::<TranscriptionError>(), Some(TranscriptionError::Unauthorized { .. })) {
+                    let _ = tx_clone.send(WindowMessage::ApiKeyRejected);
+                }
+                Err(transcription_error_message(&e))
+            }
+        };
+        let _ = tx_clone.send(WindowMessage::TranscriptionFinished(generation, Some(text), tag));
+        // 貼り付けは文字起こし欄を更新した後に行う
+        if paste {
+            let _ = tx_clone.send(WindowMessage::PasteTranscript(generation));
+        }
+    });
+}
+
+/// Serve `/healthz` for `integrations.health_port`; failing to listen only logs a warning
+#[cfg(feature = "health")]
+fn start_health_server(port: u16, state: &Arc<Mutex<ThreadSafeState>>) {
+    let state = state.clone();
+    if let Err(e) = health::spawn_health_server(port, move || format!("{:?}", lock_or_recover(&state).status)) {
+        warn!("ヘルスチェックを開始できませんでした: {:#}", e);
+    }
+}
+
+#[cfg(not(feature = "health"))]
+fn start_health_server(port: u16, _state: &Arc<Mutex<ThreadSafeState>>) {
+    warn!("integrations.health_port = {} は health 機能付きでビルドした場合のみ有効です", port);
+}
+
+/// Serve the `integrations.http_control` endpoints; a missing token or failing to listen only logs a warning
+///
+/// Actions go through the `WindowMessage` channel like the record button, so a toggle
+/// stops a running recording and otherwise starts one.
+#[cfg(feature = "http-control")]
+fn start_http_control(config: &crate::config::IntegrationsConfig, state: &Arc<Mutex<ThreadSafeState>>, tx: Sender<WindowMessage>) {
+    let Some(token) = config.http_control_token.clone().filter(|token| !token.trim().is_empty()) else {
+        warn!("integrations.http_control_token が設定されていないため、HTTP 操作は無効です");
+        return;
+    };
+    let action_state = state.clone();
+    let on_action = move |action| {
+        let message = match action {
+            ControlAction::Start => WindowMessage::StartRecording,
+            ControlAction::Stop => WindowMessage::StopRecording,
+            ControlAction::Toggle => match lock_or_recover(&action_state).status {
+                AppStatus::Idle | AppStatus::Transcribing => WindowMessage::StartRecording,
+                AppStatus::Armed | AppStatus::Recording => WindowMessage::StopRecording,
+            },
+        };
+        let _ = tx.send(message);
+    };
+    let state = state.clone();
+    let transcript = move || lock_or_recover(&state).transcript.clone();
+    if let Err(e) = http_control::spawn_control_server(&config.http_control_address, config.http_control_port, token, on_action, transcript) {
+        warn!("HTTP 操作を開始できませんでした: {:#}", e);
+    }
+}
+
+#[cfg(not(feature = "http-control"))]
+fn start_http_control(_config: &crate::config::IntegrationsConfig, _state: &Arc<Mutex<ThreadSafeState>>, _tx: Sender<WindowMessage>) {
+    warn!("integrations.http_control は http-control 機能付きでビルドした場合のみ有効です");
+}
+
+/// Append `error` to `logging.error_report_file`; failing to write it only logs a warning
+fn report_error(config: &Config, context: &str, error: &anyhow::Error) {
+    if let Err(e) = error_report::append_error_report(config, context, error) {
+        warn!("Failed to write the error report: {:#}", e);
+    }
+}
+
+/// Show `text` as the warning in the API key banner
+fn set_api_key_banner_text(label: &Label, text: &str) {
+    label.set_markup(&format!("<b>⚠ {}</b>", glib::markup_escape_text(text)));
+}
+
+/// Error text shown in the transcript area, with guidance for errors the user can fix
+fn transcription_error_message(error: &anyhow::Error) -> String {
+    let t = crate::i18n::text();
+    match error.downcast_ref::<TranscriptionError>() {
+        Some(TranscriptionError::NoApiKey) => t.error_no_api_key.to_string(),
+        Some(TranscriptionError::Unauthorized { status, .. }) => t.error_unauthorized(*status),
+        Some(TranscriptionError::RateLimited(_)) => t.error_rate_limited.to_string(),
+        Some(TranscriptionError::Network(e)) => t.error_network(&e.to_string()),
+        Some(TranscriptionError::Offline { host, .. }) => t.error_offline(host),
+        _ => t.error_other(&error.to_string()),
+    }
+}
+
+/// Add a completed transcript to the recent list and refresh the tray's "Recent" submenu
+fn remember_recent_transcript(ui_state: &mut UiState, transcript: &str, tag: Option<String>) {
+    if transcript.trim().is_empty() {
+        return;
+    }
+    ui_state.recent_transcripts.retain(|entry| entry.text != transcript);
+    ui_state.recent_transcripts.push_front(HistoryEntry { text: transcript.to_string(), tag });
+    ui_state.recent_transcripts.truncate(TRANSCRIPT_LIST_LIMIT);
+    render_history(ui_state);
+    
+    #[cfg(feature = "tray")]
+    if let Ok(sender) = TRAY_SENDER.lock() {
+        if let Some(sender) = sender.as_ref() {
+            let recent = ui_state.recent_transcripts.iter()
+                .take(RECENT_TRANSCRIPTS_LIMIT)
+                .map(|entry| entry.text.clone())
+                .collect();
+            let _ = sender.send(tray::TrayMessage::UpdateRecent(recent));
+        }
+    }
+}
+
+/// Text tag highlighting search matches in the history panel
+const HISTORY_MATCH_TAG: &str = "history-match";
+
+/// Char ranges of case-insensitive matches of `query` in `text`
+///
+/// Works on chars rather than bytes, so multibyte text can't be split mid-character.
+/// Ranges are in chars, as GTK text iters expect.
+fn find_matches(text: &str, query: &str) -> Vec<(usize, usize)> {
+    let needle: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    // 小文字化で文字数が変わる文字もあるので、元の文字位置を覚えておく
+    let folded: Vec<(char, usize)> = text.chars()
+        .enumerate()
+        .flat_map(|(index, c)| c.to_lowercase().map(move |lower| (lower, index)))
+        .collect();
+    
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start + needle.len() <= folded.len() {
+        let window = &folded[start..start + needle.len()];
+        if window.iter().map(|(c, _)| *c).eq(needle.iter().copied()) {
+            matches.push((window[0].1, window[needle.len() - 1].1 + 1));
+            start += needle.len();
+        } else {
+            start += 1;
+        }
+    }
+    matches
+}
+
+/// Show the transcripts matching the search text, newest first, with the matches highlighted
+fn render_history(ui_state: &UiState) {
+    let buffer = &ui_state.history_buffer;
+    let query = ui_state.history_search.text().to_string();
+    buffer.set_text("");
+    
+    if ui_state.recent_transcripts.is_empty() {
+        buffer.set_text(crate::i18n::text().history_empty);
+        return;
+    }
+    
+    let mut shown = 0;
+    for entry in &ui_state.recent_transcripts {
+        // タグも表示して検索の対象にする
+        let transcript = entry.display_text();
+        let matches = find_matches(&transcript, &query);
+        if !query.is_empty() && matches.is_empty() {
+            continue;
+        }
+        let mut end = buffer.end_iter();
+        if shown > 0 {
+            buffer.insert(&mut end, "\n\n");
+        }
+        let entry_start = end.offset();
+        buffer.insert(&mut end, &transcript);
+        for (from, to) in matches {
+            let from = buffer.iter_at_offset(entry_start + from as i32);
+            let to = buffer.iter_at_offset(entry_start + to as i32);
+            buffer.apply_tag_by_name(HISTORY_MATCH_TAG, &from, &to);
+        }
+        shown += 1;
+    }
+    if shown == 0 {
+        buffer.set_text(&crate::i18n::text().history_no_match(&query));
+    }
+}
+
+/// Replace the transcript, keeping the previous text (including manual edits) for undo
+fn set_transcript(ui_state: &mut UiState, text: String) {
+    let current = buffer_text(&ui_state.transcript_buffer);
+    if current != text {
+        ui_state.history.record(current);
+    }
+    let max_chars = lock_or_recover(&ui_state.state).config.ui.max_transcript_chars;
+    let dropped = text.len() - newest_transcript_text(&text, max_chars).len();
+    let text = if dropped > 0 {
+        info!("Transcript is over ui.max_transcript_chars ({}), dropping its oldest {} bytes", max_chars, dropped);
+        text[dropped..].to_string()
+    } else {
+        text
+    };
+    if let Ok(mut state) = ui_state.state.lock() {
+        state.transcript = text.clone();
+    }
+    update_transcript_text(ui_state, &text);
+}
+
+/// The newest part of `text` that fits in `max_chars` characters (0 for no limit)
+///
+/// The kept part starts at a line where there is one, so the first line isn't cut off mid-sentence.
+fn newest_transcript_text(text: &str, max_chars: usize) -> &str {
+    let total = text.chars().count();
+    if max_chars == 0 || total <= max_chars {
+        return text;
+    }
+    let cut = text.char_indices().nth(total - max_chars).map_or(text.len(), |(index, _)| index);
+    let kept = &text[cut..];
+    if text[..cut].ends_with('\n') {
+        return kept.trim_start();
+    }
+    // 行の途中から始まらないよう、次の改行までは捨てる
+    match kept.find('\n') {
+        Some(newline) if !kept[newline..].trim().is_empty() => kept[newline..].trim_start(),
+        _ => kept,
+    }
+}
+
+/// Copy the current transcript to the clipboard, returning whether anything was copied
+fn copy_transcript(state: &ThreadSafeState) -> bool {
+    if state.transcript.is_empty() {
+        info!("Transcript is empty, nothing to copy");
+        return false;
+    }
+    match clipboard::copy_transcript(&state.transcript, &state.config.clipboard) {
+        Ok(_) => {
+            info!("Transcript copied to clipboard");
+            true
+        },
+        Err(e) => {
+            error!("Failed to copy to clipboard: {}", e);
+            false
+        }
+    }
+}
+
+/// Copy the text selected in `buffer` to the clipboard; false when nothing is selected
+fn copy_selection(buffer: &TextBuffer, clipboard_config: &ClipboardConfig) -> bool {
+    let Some((start, end)) = buffer.selection_bounds() else {
+        return false;
+    };
+    let selection = buffer.text(&start, &end, false).map(|text| text.to_string()).unwrap_or_default();
+    if selection.is_empty() {
+        return false;
+    }
+    match clipboard::set_text(&selection, clipboard_config.verify, clipboard_config.retries) {
+        Ok(_) => {
+            info!("Copied {} selected characters to clipboard", selection.chars().count());
+            true
+        },
+        Err(e) => {
+            error!("Failed to copy the selection: {}", e);
+            false
+        }
+    }
+}
+
+/// Open the config file with `xdg-open`, which picks the user's default editor
+fn open_config_file(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Err(anyhow::anyhow!("file not found"));
+    }
+    std::process::Command::new("xdg-open")
+        .arg(path)
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run xdg-open: {}", e))?;
+    info!("Opened config file: {}", path.display());
+    Ok(())
+}
+
+/// Load the config file again and apply it to the following recordings and transcriptions
+///
+/// The API client is rebuilt and the keyboard shortcuts are registered again. The window
+/// layout and the UI language are read once at startup and still need a restart.
+/// Transcriptions already running keep the settings they started with.
+fn reload_config(ui_state: &mut UiState) {
+    let path = ui_state.config_path.to_string_lossy().into_owned();
+    let markup = match crate::config::load_config(Some(path)) {
+        Ok(mut config) => {
+            let temp_dir_warning = crate::config::ensure_writable_temp_dir(&mut config);
+            // デバイス一覧とモニターは起動時のホストのままなので、録音もそれに揃える
+            let startup_host = lock_or_recover(&ui_state.state).config.audio.host.clone();
+            if config.audio.host != startup_host {
+                warn!("audio.host changes take effect after a restart; keeping {:?}", startup_host);
+                config.audio.host = startup_host;
+            }
+            ui_state.autoscroll = config.ui.autoscroll;
+            set_api_key_banner_text(&ui_state.api_key_label, crate::i18n::text().api_key_missing);
+            ui_state.api_key_banner.set_visible(crate::config::missing_api_key(&config));
+            ui_state.context_entry.set_text(&config.text_processing.context);
+            ui_state.style_combo.set_active_id(Some(config.text_processing.style.id()));
+            if !ui_state.output_combo.set_active_id(Some(config.audio.output_device.as_deref().unwrap_or("default"))) {
+                ui_state.output_combo.set_active_id(Some("default"));
+            }
+            ui_state.waveform.set_visible(config.ui.show_waveform);
+            set_recording_overlay(ui_state, &config.ui);
+            MAX_DURATION_SECS.store(config.recording.max_duration_secs, Ordering::SeqCst);
+            // 分単位に丸めた表示で秒単位の設定を上書きしない
+            ui_state.max_duration_spin.block_signal(&ui_state.max_duration_changed);
+            ui_state.max_duration_spin.set_value(duration_minutes(config.recording.max_duration_secs));
+            ui_state.max_duration_spin.unblock_signal(&ui_state.max_duration_changed);
+            populate_tags(&ui_state.tag_combo, &config.ui.tags);
+            ui_state.tag_combo.set_visible(!config.ui.tags.is_empty());
+            populate_api_profiles(&ui_state.profile_combo, &config.api);
+            ui_state.profile_combo.set_visible(!config.api.profiles.is_empty());
+            load_font_css(&ui_state.font_css, &config.ui);
+            let changed = match ui_state.state.lock() {
+                Ok(mut state) => {
+                    let changed = crate::config::changed_settings(&state.config, &config);
+                    state.api = Arc::new(TranscriptionAPI::new(config.clone()));
+                    state.config = config.clone();
+                    changed
+                },
+                Err(_) => Vec::new(),
+            };
+            if changed.is_empty() {
+                info!("Config reloaded from {} (no settings changed)", ui_state.config_path.display());
+            } else {
+                info!("Config reloaded from {}; changed settings: {}", ui_state.config_path.display(), changed.join(", "));
+            }
+            
+            if changed.iter().any(|key| key.starts_with("shortcuts.")) {
+                for handler in ui_state.shortcut_handlers.drain(..) {
+                    ui_state.window.disconnect(handler);
+                }
+                ui_state.shortcut_handlers = setup_keyboard_shortcuts(&ui_state.window, &config, ui_state.state.clone(), ui_state.tx_main.clone());
+                ui_state.shortcut_label.set_markup(&crate::i18n::text().shortcut_help(&config.shortcuts));
+                info!("Keyboard shortcuts re-registered");
+            }
+            
+            let mut message = crate::i18n::text().config_reloaded(&changed);
+            if let Some(warning) = temp_dir_warning {
+                message = format!("{}\n⚠ {}", message, warning);
+            }
+            format!("<b>{}</b>", glib::markup_escape_text(&message))
+        },
+        Err(e) => {
+            // 読み込めない場合は今の設定をそのまま使う
+            error!("Failed to reload config: {:#}", e);
+            let message = crate::i18n::text().config_reload_failed(&format!("{:#}", e));
+            format!("<b>⚠ {}</b>", glib::markup_escape_text(&message))
+        }
+    };
+    ui_state.warning_label.set_markup(&markup);
+    ui_state.warning_label.set_visible(true);
+}
+
+/// Show a desktop notification using notify-send
+fn send_notification(summary: &str, body: &str) {
+    if let Err(e) = std::process::Command::new("notify-send")
+        .arg("--app-name=Wispr")
+        .arg(summary)
+        .arg(body)
+        .status()
+    {
+        error!("Failed to send notification: {}", e);
+    }
+}
+
+/// Add simplified keyboard shortcuts
+///
+/// Returns the signal handlers so the shortcuts can be replaced when the config is reloaded.
+fn setup_keyboard_shortcuts(window: &Window, config: &Config, state: Arc<Mutex<ThreadSafeState>>, tx: Sender<WindowMessage>) -> Vec<glib::SignalHandlerId> {
+    let mut handlers = Vec::new();
+    let mode = config.shortcuts.mode;
+    let debounce = Duration::from_millis(config.shortcuts.debounce_ms);
+    let min_hold = Duration::from_millis(config.shortcuts.min_hold_ms);
+    let debouncer = Rc::new(RefCell::new(ShortcutDebouncer::new(debounce)));
+    
+    // 空のショートカットは無効（ハンドラーを登録しない）
+    if config.shortcuts.toggle_recording.is_empty() {
+        info!("Recording shortcut disabled");
+    } else {
+        // For recording - handle key press event
+        let tx_clone = tx.clone();
+        let key = parse_configured_shortcut(&config.shortcuts.toggle_recording);
+        let debouncer_clone = debouncer.clone();
+        handlers.push(window.connect_key_press_event(move |_, event| {
+            if !is_shortcut_key(event, &key) {
+                return glib::Propagation::Proceed;
+            }
+            if debouncer_clone.borrow_mut().press(Instant::now()) {
+                match mode {
+                    ShortcutMode::Hold => {
+                        info!("Shortcut key pressed - starting recording");
+                        let _ = tx_clone.send(WindowMessage::StartRecording);
+                    },
+                    ShortcutMode::Toggle => {
+                        // 押すたびに録音開始と停止を切り替える
+                        let status = lock_or_recover(&state).status;
+                        match status {
+                            AppStatus::Idle | AppStatus::Transcribing => {
+                                info!("Shortcut key pressed - starting recording (toggle mode)");
+                                let _ = tx_clone.send(WindowMessage::StartRecording);
+                            },
+                            AppStatus::Armed | AppStatus::Recording => {
+                                info!("Shortcut key pressed - stopping recording and transcribing (toggle mode)");
+                                let _ = tx_clone.send(WindowMessage::StopRecording);
+                            },
+                        }
+                    }
+                }
+            }
+            glib::Propagation::Stop
+        }));
+        
+        // For recording - handle key release event
+        let tx_clone = tx.clone();
+        let key = parse_configured_shortcut(&config.shortcuts.toggle_recording);
+        handlers.push(window.connect_key_release_event(move |_, event| {
+            if !is_shortcut_key(event, &key) {
+                return glib::Propagation::Proceed;
+            }
+            let Some(release_id) = debouncer.borrow_mut().release(Instant::now()) else {
+                return glib::Propagation::Stop;
+            };
+            // トグルモードではキーを離しても何もしない
+            if mode == ShortcutMode::Hold {
+                // 押し直されなければ待ち時間の後に停止する
+                let tx_clone = tx_clone.clone();
+                let debouncer = debouncer.clone();
+                glib::timeout_add_local_once(debounce, move || {
+                    match debouncer.borrow_mut().settle_hold(release_id, min_hold) {
+                        Some(HoldRelease::Held) => {
+                            info!("Shortcut key released - stopping recording and transcribing");
+                            let _ = tx_clone.send(WindowMessage::StopRecording);
+                        },
+                        Some(HoldRelease::Tap) => {
+                            // 短いタップは誤操作とみなして文字起こししない
+                            info!("Shortcut key tapped for less than {:?} - discarding the recording", min_hold);
+                            let _ = tx_clone.send(WindowMessage::CancelRecording);
+                        },
+                        None => {},
+                    }
+                });
+            }
+            glib::Propagation::Stop
+        }));
+    }
+    
+    // For transcribing the audio file path on the clipboard
+    connect_shortcut(window, &mut handlers, &config.shortcuts.transcribe_clipboard, &tx, || WindowMessage::TranscribeClipboard);
+    
+    // For opening the config file in the editor
+    connect_shortcut(window, &mut handlers, &config.shortcuts.open_config, &tx, || WindowMessage::OpenConfig);
+    
+    // For marking a point of the recording
+    connect_shortcut(window, &mut handlers, &config.shortcuts.drop_marker, &tx, || WindowMessage::DropMarker);
+    
+    // For undo/redo of transcript replacements
+    let tx_clone = tx.clone();
+    let undo = parse_configured_shortcut("Control+z");
+    let redo = parse_configured_shortcut("Control+y");
+    handlers.push(window.connect_key_press_event(move |_, event| {
+        if is_shortcut_key(event, &undo) {
+            let _ = tx_clone.send(WindowMessage::UndoTranscript);
+            return glib::Propagation::Stop;
+        }
+        if is_shortcut_key(event, &redo) {
+            let _ = tx_clone.send(WindowMessage::RedoTranscript);
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    }));
+    
+    // For clearing transcript
+    connect_shortcut(window, &mut handlers, &config.shortcuts.clear_transcript, &tx, || WindowMessage::UpdateTranscript(String::new()));
+    
+    // For copying to clipboard
+    connect_shortcut(window, &mut handlers, &config.shortcuts.copy_to_clipboard, &tx, || WindowMessage::CopyTranscript);
+    
+    info!("Keyboard shortcuts configured");
+    handlers
+}
+
+/// Register a shortcut that sends `message()` when pressed; empty shortcuts are disabled
+fn connect_shortcut(
+    window: &Window,
+    handlers: &mut Vec<glib::SignalHandlerId>,
+    shortcut: &str,
+    tx: &Sender<WindowMessage>,
+    message: fn() -> WindowMessage,
+) {
+    if shortcut.is_empty() {
+        return;
+    }
+    let tx = tx.clone();
+    let key = parse_configured_shortcut(shortcut);
+    handlers.push(window.connect_key_press_event(move |_, event| {
+        if is_shortcut_key(event, &key) {
+            let _ = tx.send(message());
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    }));
+}
+
+/// Parse a shortcut that `config::load_config` has already validated
+///
+/// A shortcut that still fails to parse never matches instead of panicking.
+fn parse_configured_shortcut(shortcut: &str) -> Option<ParsedShortcut> {
+    match shortcut::parse_shortcut(shortcut) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            warn!("Ignoring shortcut {:?}: {}", shortcut, e);
+            None
+        }
+    }
+}
+
+/// Check if a key event matches a parsed shortcut
+fn is_shortcut_key(event: &gdk::EventKey, shortcut: &Option<ParsedShortcut>) -> bool {
+    shortcut
+        .as_ref()
+        .is_some_and(|shortcut| shortcut::matches(shortcut, event.keyval(), event.state()))
+}
+
+/// Let the tray show `status` too, so its icon and menu follow the window
+#[cfg(feature = "tray")]
+fn forward_status_to_tray(status: AppStatus) {
+    if let Some(sender) = lock_or_recover(&TRAY_SENDER).as_ref() {
+        let _ = sender.send(tray::TrayMessage::UpdateStatus(status));
+    }
+}
+
+#[cfg(not(feature = "tray"))]
+fn forward_status_to_tray(_status: AppStatus) {}
+
+/// Update the UI status (button and label)
+///
+/// Repeating the status already shown does nothing, so duplicate updates neither
+/// relabel the button nor restart the recording timer.
+fn update_ui_status(ui_state: &UiState, status: AppStatus) {
+    if ui_state.shown_status.replace(Some(status)) == Some(status) {
+        debug!("UI already shows {:?}", status);
+        return;
+    }
+    forward_status_to_tray(status);
+    // 録音が終わったら待っていた次の録音を始める
+    if matches!(status, AppStatus::Idle | AppStatus::Transcribing) && ui_state.pending_recording.replace(false) {
+        info!("Starting the queued recording");
+        update_queue_label(ui_state);
+        let _ = ui_state.tx_main.send(WindowMessage::StartRecording);
+    }
+    update_recording_overlay(ui_state);
+    let t = crate::i18n::text();
+    // 既存のタイマーがあれば削除
+    if let Ok(mut timer_id) = BUTTON_UPDATE_TIMER_ID.lock() {
+        if let Some(id) = timer_id.take() {
+            // source_removeではなく、SourceIdのメソッドを使用
+            id.remove();
+        }
+    }
 
     match status {
         AppStatus::Idle => {
-            ui_state.record_button.set_label("● 録音");
+            ui_state.record_button.set_label(t.record);
             ui_state.record_button.set_sensitive(true);
             // タイマーをリセット
             if let Ok(mut start_time) = RECORDING_START_TIME.lock() {
@@ -860,9 +3348,18 @@ fn update_ui_status(ui_state: &UiState, status: AppStatus) {
             }
             ui_state.timer_label.set_text("00:00");
         },
+        AppStatus::Armed => {
+            ui_state.record_button.set_label(t.waiting_for_voice);
+            ui_state.record_button.set_sensitive(true);
+            // 音声を検出するまではタイマーを動かさない
+            if let Ok(mut start_time) = RECORDING_START_TIME.lock() {
+                *start_time = None;
+            }
+            ui_state.timer_label.set_text(t.record_hint_armed);
+        },
         AppStatus::Recording => {
             // 録音ボタンラベルを簡素化 - 時間表示を削除
-            ui_state.record_button.set_label("■ 停止");
+            ui_state.record_button.set_label(t.stop);
             ui_state.record_button.set_sensitive(true);
             // タイマー開始時間を設定
             if let Ok(mut start_time) = RECORDING_START_TIME.lock() {
@@ -913,112 +3410,923 @@ fn update_ui_status(ui_state: &UiState, status: AppStatus) {
             }
         },
         AppStatus::Transcribing => {
-            ui_state.record_button.set_label("処理中...");
-            ui_state.record_button.set_sensitive(false);
+            // 文字起こしはバックグラウンドで進むので、次の録音は開始できる
+            ui_state.record_button.set_label(t.record);
+            ui_state.record_button.set_sensitive(true);
             // タイマーをリセット
             if let Ok(mut start_time) = RECORDING_START_TIME.lock() {
                 *start_time = None;
             }
-            ui_state.timer_label.set_text("処理中...");
+            ui_state.timer_label.set_text(&format!("{}...", t.processing));
+        }
+    }
+}
+
+/// Update the transcript text in the UI
+fn update_transcript_text(ui_state: &UiState, text: &str) {
+    // 改行を保持して表示
+    let buffer = &ui_state.transcript_buffer;
+    buffer.set_text(text);
+    
+    // 末尾までスクロールして新しい内容を表示
+    // （scroll_to_iter はレイアウト前だと届かないため、レイアウト後に処理されるマークを使う）
+    if ui_state.autoscroll {
+        let end = buffer.end_iter();
+        let mark = buffer.mark(TRANSCRIPT_END_MARK)
+            .or_else(|| buffer.create_mark(Some(TRANSCRIPT_END_MARK), &end, false));
+        if let Some(mark) = mark {
+            buffer.move_mark(&mark, &end);
+            ui_state.transcript_view.scroll_to_mark(&mark, 0.0, false, 0.0, 1.0);
+        }
+    }
+}
+
+/// Mark kept at the end of the transcript for auto-scrolling
+const TRANSCRIPT_END_MARK: &str = "transcript_end";
+
+/// Get the full text of a buffer
+fn buffer_text(buffer: &TextBuffer) -> String {
+    buffer.text(&buffer.start_iter(), &buffer.end_iter(), false)
+        .map(|text| text.to_string())
+        .unwrap_or_default()
+}
+
+/// Put the default and supported formats of `device` into the device combo's tooltip
+///
+/// Opening the device can take a while, so it is queried in a background thread that
+/// sends `DeviceCapabilities` with the tooltip.
+fn query_device_capabilities(host_name: &str, device: Option<String>, tx: Sender<WindowMessage>) {
+    let host_name = host_name.to_string();
+    std::thread::spawn(move || {
+        let t = crate::i18n::text();
+        let tooltip = match audio::DeviceCapabilities::query(&host_name, device.as_deref()) {
+            Ok(capabilities) => {
+                let summary = capabilities.summary(t.device_default_format, t.device_supported_formats);
+                if summary.is_empty() { t.device.1.to_string() } else { format!("{}\n\n{}", t.device.1, summary) }
+            },
+            Err(e) => {
+                debug!("Could not query the capabilities of the selected device: {:#}", e);
+                t.device.1.to_string()
+            }
+        };
+        let _ = tx.send(WindowMessage::DeviceCapabilities(device, tooltip));
+    });
+}
+
+/// Input device chosen in the device combo, or None for the default device
+fn selected_device(combo: &ComboBoxText) -> Option<String> {
+    combo.active_text()
+        .map(|text| {
+            info!("Using selected audio device: {}", text);
+            if text.contains("(Default)") {
+                None // Use default device
+            } else {
+                Some(text.to_string())
+            }
+        })
+        .unwrap_or(None)
+}
+
+/// Length of the microphone test recording
+const MIC_TEST_SECS: u64 = 3;
+
+/// Record `MIC_TEST_SECS` seconds, then play the clip back and report its levels
+///
+/// Sends `MicTestFinished` with the result once playback is done. `output` is the sink
+/// to play back on (the default output when `None`).
+fn start_mic_test(config: &Config, device: Option<String>, output: Option<String>, tx: Sender<WindowMessage>) -> Result<()> {
+    // 通常の録音と同じ設定で、テスト用のファイルに録音する
+    let mut test_config = config.clone();
+    test_config.recording.filename_template = "mic_test.wav".to_string();
+    test_config.recording.disable_silence_detection = true;
+    test_config.recording.trim_silence = false;
+    // 再生するのでファイルに録音する
+    test_config.recording.in_memory = false;
+    
+    let mut recorder = AudioRecorder::new(test_config);
+    recorder.start_with_device(device)?;
+    info!("Microphone test started");
+    
+    glib::timeout_add_local_once(Duration::from_secs(MIC_TEST_SECS), move || {
+        let path = match recorder.stop() {
+            Ok(Some(Recording::File(path))) => path,
+            Ok(_) => {
+                let _ = tx.send(WindowMessage::MicTestFinished(crate::i18n::text().mic_test_no_recording.to_string()));
+                return;
+            },
+            Err(e) => {
+                error!("Failed to stop microphone test: {}", e);
+                let _ = tx.send(WindowMessage::MicTestFinished(crate::i18n::text().mic_test_no_recording.to_string()));
+                return;
+            }
+        };
+        
+        // 解析と再生はUIを止めないよう別スレッドで行う
+        std::thread::spawn(move || {
+            let result = match audio::analyze_wav(&path) {
+                Ok(analysis) => {
+                    if let Err(e) = audio::play_wav(&path, output.as_deref()) {
+                        warn!("Failed to play back microphone test: {}", e);
+                    }
+                    match analysis.warning() {
+                        Some(warning) => warning,
+                        None => crate::i18n::text().mic_test_peak(analysis.peak),
+                    }
+                },
+                Err(e) => {
+                    error!("Failed to analyze microphone test: {}", e);
+                    crate::i18n::text().mic_test_analysis_failed.to_string()
+                }
+            };
+            info!("Microphone test finished: {}", result);
+            let _ = std::fs::remove_file(&path);
+            let _ = tx.send(WindowMessage::MicTestFinished(result));
+        });
+    });
+    Ok(())
+}
+
+/// Populate the device combo box with available audio devices
+///
+/// Lists the input devices of the `audio.host` cpal host, plus the monitor sources.
+fn populate_audio_devices(combo: &ComboBoxText, host_name: &str) {
+    fill_input_devices(combo, &list_audio_devices(host_name));
+}
+
+/// Names of the audio devices, read where blocking is fine (see `list_audio_devices`)
+#[derive(Debug, Clone)]
+pub struct AudioDeviceList {
+    default_input: Option<String>,
+    inputs: Vec<String>,
+    /// PulseAudio/PipeWire monitor sources, without `LOOPBACK_PREFIX`
+    loopback_sources: Vec<String>,
+    output_sinks: Vec<String>,
+}
+
+/// Enumerate the input devices, monitor sources and output sinks
+///
+/// Goes through cpal and runs `pactl`, so it can block for a while.
+fn list_audio_devices(host_name: &str) -> AudioDeviceList {
+    let host = audio::audio_host(host_name);
+    AudioDeviceList {
+        default_input: host.default_input_device().and_then(|device| device.name().ok()),
+        inputs: host.input_devices()
+            .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+            .unwrap_or_default(),
+        loopback_sources: audio::loopback_sources(),
+        output_sinks: audio::output_sinks(),
+    }
+}
+
+/// Fill the device combo box with `devices`, the default device first and selected
+fn fill_input_devices(combo: &ComboBoxText, devices: &AudioDeviceList) {
+    if let Some(name) = &devices.default_input {
+        combo.append(Some("default"), &format!("{} (Default)", name));
+        combo.set_active_id(Some("default"));
+    }
+    
+    for (idx, name) in devices.inputs.iter().enumerate() {
+        let id = format!("device_{}", idx);
+        combo.append(Some(&id), name);
+    }
+    
+    // PulseAudio/PipeWire のモニターソース（システム音声）を追加
+    for (idx, source) in devices.loopback_sources.iter().enumerate() {
+        let id = format!("monitor_{}", idx);
+        combo.append(Some(&id), &format!("{}{}", audio::LOOPBACK_PREFIX, source));
+    }
+}
+
+/// Show the freshly listed `devices`, keeping the selected ones when still present
+///
+/// The changed handlers are blocked while the combos are refilled, so the idle monitor and
+/// any recording keep their open streams. Only a selection that really changed, because its
+/// device went away, is passed on, the way picking it by hand would.
+fn refresh_audio_devices(ui_state: &UiState, devices: &AudioDeviceList) {
+    let (device_combo, output_combo) = (&ui_state.device_combo, &ui_state.output_combo);
+    let selected_input = device_combo.active_text();
+    let previous_device = selected_device(device_combo);
+    let selected_output = output_combo.active_id();
+    
+    device_combo.block_signal(&ui_state.device_changed);
+    // ID は並び順から振られるので、表示名で選択し直す
+    device_combo.remove_all();
+    fill_input_devices(device_combo, devices);
+    // 既定のデバイスは fill_input_devices が選択済み
+    if let Some(text) = selected_input.filter(|text| !text.contains("(Default)")) {
+        if !select_by_text(device_combo, &text) {
+            warn!("Audio device {} is no longer available; using the default device", text);
+        }
+    }
+    device_combo.unblock_signal(&ui_state.device_changed);
+    let device = selected_device(device_combo);
+    if device != previous_device {
+        query_device_capabilities(&lock_or_recover(&ui_state.state).config.audio.host, device.clone(), ui_state.tx_main.clone());
+        set_monitor_device(device);
+    }
+    
+    output_combo.block_signal(&ui_state.output_changed);
+    output_combo.remove_all();
+    fill_output_devices(output_combo, &devices.output_sinks, None);
+    if let Some(id) = &selected_output {
+        if !output_combo.set_active_id(Some(id)) {
+            warn!("Output device {} is no longer available; using the default output", id);
+        }
+    }
+    output_combo.unblock_signal(&ui_state.output_changed);
+    if output_combo.active_id() != selected_output {
+        let _ = ui_state.tx_main.send(WindowMessage::SetOutputDevice(None));
+    }
+    info!("Audio devices refreshed");
+}
+
+/// Select the combo entry labeled `text`; false when there is none
+fn select_by_text(combo: &ComboBoxText, text: &str) -> bool {
+    let Some(model) = combo.model() else {
+        return false;
+    };
+    let mut found = None;
+    model.foreach(|model, _, iter| {
+        let label = model.value(iter, 0).get::<String>().ok();
+        if label.as_deref() == Some(text) {
+            found = Some(*iter);
+            return true;
+        }
+        false
+    });
+    match found {
+        Some(iter) => {
+            combo.set_active_iter(Some(&iter));
+            true
+        },
+        None => false,
+    }
+}
+
+/// Fill the output combo with the sound server sinks, selecting `configured` when listed
+fn populate_output_devices(combo: &ComboBoxText, configured: Option<&str>) {
+    fill_output_devices(combo, &audio::output_sinks(), configured);
+}
+
+/// Fill the output combo with `sinks`, selecting `configured` when listed and the default otherwise
+fn fill_output_devices(combo: &ComboBoxText, sinks: &[String], configured: Option<&str>) {
+    combo.append(Some("default"), crate::i18n::text().output_default);
+    combo.set_active_id(Some("default"));
+    for sink in sinks {
+        combo.append(Some(sink), sink);
+    }
+    if let Some(sink) = configured {
+        if !combo.set_active_id(Some(sink)) {
+            warn!("audio.output_device {} was not found; using the default output", sink);
+        }
+    }
+}
+
+/// Stop idle monitoring once nothing was recorded for `ui.idle_timeout_secs`
+///
+/// Only goes dormant while idle with monitoring on, so a user who switched monitoring
+/// off keeps it off after waking up.
+fn check_idle_timeout(ui_state: &mut UiState) {
+    let timeout = match ui_state.state.lock() {
+        Ok(state) if state.status == AppStatus::Idle => state.config.ui.idle_timeout_secs,
+        _ => return,
+    };
+    // 休止中に手動でモニターをオンに戻した場合は起きたものとする
+    if ui_state.dormant && IDLE_MONITORING.load(Ordering::SeqCst) {
+        wake_from_idle(ui_state);
+    }
+    if timeout == 0 || ui_state.dormant || ui_state.queue.is_busy() || ui_state.countdown.is_some() {
+        return;
+    }
+    if !IDLE_MONITORING.load(Ordering::SeqCst) || ui_state.last_activity.elapsed() < Duration::from_secs(timeout) {
+        return;
+    }
+    
+    info!("No recording for {} seconds, releasing the microphone until the next recording", timeout);
+    ui_state.dormant = true;
+    // チェックボックスの toggled ハンドラが IDLE_MONITORING を更新し、モニターがマイクを閉じる
+    ui_state.monitor_check.set_active(false);
+    IDLE_MONITORING.store(false, Ordering::SeqCst);
+    ui_state.window.set_title(crate::i18n::text().dormant_title);
+}
+
+/// Leave the dormant state of `ui.idle_timeout_secs` and restart the idle countdown
+fn wake_from_idle(ui_state: &mut UiState) {
+    ui_state.last_activity = Instant::now();
+    if ui_state.dormant {
+        info!("Waking up from the idle timeout");
+        ui_state.dormant = false;
+        ui_state.window.set_title("Wispr");
+        resume_idle_monitoring(ui_state);
+    }
+}
+
+/// Turn idle monitoring back on after the transcriptions finished (`ui.resume_monitoring_after_record`)
+///
+/// The monitor thread keeps running while monitoring is off, so it reopens the input stream
+/// on its next poll.
+fn resume_idle_monitoring(ui_state: &UiState) {
+    if !IDLE_MONITORING.load(Ordering::SeqCst) {
+        info!("Resuming idle audio monitoring after the recording");
+        // チェックボックスの toggled ハンドラが IDLE_MONITORING を更新する
+        ui_state.monitor_check.set_active(true);
+        IDLE_MONITORING.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Monitor audio input levels until the application exits
+///
+/// The idle input stream is only held while IDLE_MONITORING is set, so the microphone
+/// is released when the user turns idle monitoring off.
+fn monitor_audio_input(vad_threshold: Option<f32>, level_scale: LevelScale, preroll_secs: f32, host_name: &str) {
+    while AUDIO_MONITORING.load(Ordering::SeqCst) {
+        if IDLE_MONITORING.load(Ordering::SeqCst) {
+            if !monitor_audio_input_once(vad_threshold, level_scale, preroll_secs, host_name) {
+                // デバイスが無い場合などは少し待ってから再試行
+                std::thread::sleep(std::time::Duration::from_secs(2));
+            }
+            if let Ok(mut level) = AUDIO_LEVEL.lock() {
+                *level = 0.0;
+            }
+        } else {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    }
+}
+
+/// How long the monitoring stream may go without data before it is reopened
+const MONITOR_STALL_MS: u64 = 3000;
+
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+thread_local! {
+    // Keeps the system bus connection for the logind subscription alive
+    static LOGIND_BUS: std::cell::RefCell<Option<gio::DBusConnection>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Reopen the monitoring stream when logind reports a resume from suspend
+///
+/// Best effort: without a system bus the stall check in `monitor_audio_input_once` still recovers.
+fn watch_resume_from_suspend() {
+    let connection = match gio::bus_get_sync(gio::BusType::System, None::<&gio::Cancellable>) {
+        Ok(connection) => connection,
+        Err(e) => {
+            warn!("Could not connect to the system bus, suspend/resume will not be watched: {}", e);
+            return;
+        }
+    };
+    
+    connection.signal_subscribe(
+        Some("org.freedesktop.login1"),
+        Some("org.freedesktop.login1.Manager"),
+        Some("PrepareForSleep"),
+        Some("/org/freedesktop/login1"),
+        None,
+        gio::DBusSignalFlags::NONE,
+        |_, _, _, _, _, parameters| {
+            // PrepareForSleep(false) は復帰時に送られる
+            if let Some((false,)) = parameters.get::<(bool,)>() {
+                info!("Resumed from suspend, restarting audio monitoring");
+                MONITOR_RESTART.store(true, Ordering::SeqCst);
+            }
+        },
+    );
+    LOGIND_BUS.with(|bus| *bus.borrow_mut() = Some(connection));
+}
+
+/// Small borderless window showing that a recording is running (`ui.recording_overlay`)
+///
+/// Popup windows aren't managed by the window manager, so it stays above other windows
+/// without ever taking the focus from the application being dictated into.
+fn build_recording_overlay() -> Window {
+    let overlay = Window::new(WindowType::Popup);
+    overlay.set_accept_focus(false);
+    overlay.set_focus_on_map(false);
+    overlay.set_keep_above(true);
+    overlay.set_skip_taskbar_hint(true);
+    overlay.set_skip_pager_hint(true);
+    overlay.set_type_hint(gdk::WindowTypeHint::Notification);
+    let label = Label::new(None);
+    label.set_markup("<span foreground=\"#e53935\" size=\"x-large\">●</span> <b>REC</b>");
+    label.set_margin(6);
+    overlay.add(&label);
+    label.show();
+    overlay
+}
+
+/// Show the recording indicator while recording with the main window hidden, and hide it otherwise
+fn update_recording_overlay(ui_state: &UiState) {
+    if let Some(overlay) = &ui_state.recording_overlay {
+        if ui_state.shown_status.get() == Some(AppStatus::Recording) && !ui_state.window.is_visible() {
+            overlay.show();
+            place_recording_overlay(overlay, ui_state.overlay_corner);
+        } else {
+            overlay.hide();
+        }
+    }
+}
+
+/// Create or drop the recording indicator after `ui.recording_overlay` changed
+fn set_recording_overlay(ui_state: &mut UiState, ui: &UiConfig) {
+    ui_state.overlay_corner = ui.overlay_corner;
+    match (&ui_state.recording_overlay, ui.recording_overlay) {
+        (None, true) => ui_state.recording_overlay = Some(build_recording_overlay()),
+        (Some(overlay), false) => {
+            overlay.close();
+            ui_state.recording_overlay = None;
+        },
+        _ => {},
+    }
+}
+
+/// Distance in pixels between the recording indicator and the screen edges
+const OVERLAY_MARGIN: i32 = 16;
+
+/// Top-left position of a `size` window in `corner` of a work area given as (x, y, width, height)
+fn overlay_position(area: (i32, i32, i32, i32), size: (i32, i32), corner: OverlayCorner) -> (i32, i32) {
+    let (x, y, width, height) = area;
+    let left = x + OVERLAY_MARGIN;
+    let right = x + (width - size.0 - OVERLAY_MARGIN).max(0);
+    let top = y + OVERLAY_MARGIN;
+    let bottom = y + (height - size.1 - OVERLAY_MARGIN).max(0);
+    match corner {
+        OverlayCorner::TopLeft => (left, top),
+        OverlayCorner::TopRight => (right, top),
+        OverlayCorner::BottomLeft => (left, bottom),
+        OverlayCorner::BottomRight => (right, bottom),
+    }
+}
+
+/// Move the recording indicator into `corner` of the monitor under the pointer
+fn place_recording_overlay(overlay: &Window, corner: OverlayCorner) {
+    let Some(display) = gdk::Display::default() else {
+        return;
+    };
+    let monitor = display.default_seat()
+        .and_then(|seat| seat.pointer())
+        .and_then(|pointer| {
+            let (_, x, y) = pointer.position();
+            display.monitor_at_point(x, y)
+        })
+        .or_else(|| display.primary_monitor());
+    let Some(monitor) = monitor else {
+        return;
+    };
+    // パネルに重ならないよう作業領域を使う
+    let area = monitor.workarea();
+    let (x, y) = overlay_position((area.x(), area.y(), area.width(), area.height()), overlay.size(), corner);
+    overlay.move_(x, y);
+}
+
+/// Center the window on the monitor under the pointer, or on `ui.monitor_index` when set
+///
+/// Falls back to GTK's own centering (primary monitor) when no monitor can be determined.
+/// Wayland compositors ignore client-side positioning, so this only has an effect on X11.
+fn center_on_monitor(window: &Window, monitor_index: Option<i32>) {
+    let Some(display) = gdk::Display::default() else {
+        return;
+    };
+    let monitor = match monitor_index {
+        Some(index) => {
+            let monitor = display.monitor(index);
+            if monitor.is_none() {
+                warn!("ui.monitor_index {} のモニターが見つかりません（モニター数: {}）", index, display.n_monitors());
+            }
+            monitor
+        },
+        None => display.default_seat()
+            .and_then(|seat| seat.pointer())
+            .and_then(|pointer| {
+                let (_, x, y) = pointer.position();
+                display.monitor_at_point(x, y)
+            }),
+    };
+    let Some(monitor) = monitor else {
+        return;
+    };
+    
+    let area = monitor.geometry();
+    let (width, height) = window.default_size();
+    window.set_position(gtk::WindowPosition::None);
+    window.move_(
+        area.x() + (area.width() - width).max(0) / 2,
+        area.y() + (area.height() - height).max(0) / 2,
+    );
+}
+
+/// Style class flashing the record button red after a failed transcription
+const ERROR_FLASH_CLASS: &str = "error-flash";
+
+const ERROR_FLASH_CSS: &str = "button.error-flash { background-image: none; background-color: #c01c28; color: #ffffff; }";
+
+/// How long the record button stays red after a failed transcription
+const ERROR_FLASH_DURATION: Duration = Duration::from_millis(1500);
+
+/// Events with a sound when `recording.play_sounds` is set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SoundCue {
+    Start,
+    Stop,
+    Done,
+    Error,
+}
+
+impl SoundCue {
+    /// The WAV file configured for the cue under `[sounds]`
+    fn file(self, sounds: &SoundsConfig) -> Option<&std::path::Path> {
+        match self {
+            SoundCue::Start => sounds.start.as_deref(),
+            SoundCue::Stop => sounds.stop.as_deref(),
+            SoundCue::Done => sounds.done.as_deref(),
+            SoundCue::Error => sounds.error.as_deref(),
+        }
+    }
+    
+    /// Display bell beeps of the built-in cue
+    fn builtin_beeps(self) -> u32 {
+        match self {
+            SoundCue::Start | SoundCue::Stop => 1,
+            SoundCue::Done => 0,
+            SoundCue::Error => 2,
         }
     }
 }
 
-/// Update the transcript text in the UI
-fn update_transcript_text(buffer: &TextBuffer, text: &str) {
-    // 改行を保持して表示
-    buffer.set_text(text);
-    
-    // テキストビューにスクロールして表示を更新
-    buffer.emit_by_name::<()>("changed", &[]);
+/// Gap between the beeps of a built-in cue
+const CUE_BEEP_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Play the sound of `cue` if `recording.play_sounds` is set
+///
+/// The file under `[sounds]` plays on its own thread on `audio.output_device`, and is skipped
+/// while that sink is unavailable; without one, or when it doesn't exist, the display bell
+/// rings the built-in cue.
+fn play_cue(config: &Config, cue: SoundCue) {
+    if !config.recording.play_sounds {
+        return;
+    }
+    if let Some(path) = cue.file(&config.sounds).map(crate::config::expand_home) {
+        if path.is_file() {
+            let sink = config.audio.output_device.clone();
+            thread::spawn(move || {
+                let available = if sink.is_some() { audio::output_sinks() } else { Vec::new() };
+                match audio::play_cue_wav(&path.to_string_lossy(), sink.as_deref(), &available) {
+                    Ok(true) => {},
+                    Ok(false) => warn!("Output device {:?} is not available; skipping the {:?} sound", sink, cue),
+                    Err(e) => warn!("Failed to play the {:?} sound: {:#}", cue, e),
+                }
+            });
+            return;
+        }
+        warn!("Sound file for {:?} not found, using the built-in one: {}", cue, path.display());
+    }
+    let Some(display) = gdk::Display::default() else {
+        return;
+    };
+    for beep in 0..cue.builtin_beeps() {
+        let display = display.clone();
+        glib::timeout_add_local_once(CUE_BEEP_INTERVAL * beep, move || display.beep());
+    }
 }
 
-/// Populate the device combo box with available audio devices
-fn populate_audio_devices(combo: &ComboBoxText) {
-    let host = cpal::default_host();
+/// Make a failed transcription stand out from a successful one
+///
+/// The error cue plays (two beeps by default, unlike the single countdown beeps), and with
+/// `ui.notification_enabled` the record button flashes red and the failure is also sent as
+/// a desktop notification, since the error text alone looks like any other transcript.
+fn signal_transcription_error(ui_state: &UiState, message: &str, config: &Config) {
+    play_cue(config, SoundCue::Error);
+    if config.ui.notification_enabled {
+        let context = ui_state.record_button.style_context();
+        context.add_class(ERROR_FLASH_CLASS);
+        glib::timeout_add_local_once(ERROR_FLASH_DURATION, move || context.remove_class(ERROR_FLASH_CLASS));
+        send_notification(crate::i18n::text().notify_transcription_failed, message);
+    }
+}
+
+/// Earlier transcripts kept in tabs next to the live one
+const MAX_TRANSCRIPT_TABS: u32 = 10;
+
+/// Keep `text` in a read-only tab after the live transcript, newest first
+///
+/// The oldest tab is closed beyond `MAX_TRANSCRIPT_TABS`, and the live tab stays selected.
+fn archive_transcript_tab(ui_state: &UiState, text: &str) {
+    let view = TextView::new();
+    view.set_editable(false);
+    view.set_wrap_mode(gtk::WrapMode::Word);
+    view.style_context().add_provider(&ui_state.font_css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    if let Some(buffer) = view.buffer() {
+        buffer.set_text(text);
+    }
+    let scrolled = ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled.set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
+    scrolled.add(&view);
+    scrolled.show_all();
     
-    // Get default device first
-    if let Some(default_device) = host.default_input_device() {
-        if let Ok(name) = default_device.name() {
-            combo.append(Some("default"), &format!("{} (Default)", name));
-            combo.set_active_id(Some("default"));
-        }
+    let tabs = &ui_state.transcript_tabs;
+    let label = Label::new(Some(&chrono::Local::now().format("%H:%M:%S").to_string()));
+    tabs.insert_page(&scrolled, Some(&label), Some(1));
+    while tabs.n_pages() > MAX_TRANSCRIPT_TABS + 1 {
+        tabs.remove_page(Some(tabs.n_pages() - 1));
     }
+    tabs.set_show_tabs(true);
+    tabs.set_current_page(Some(0));
+}
+
+/// Count down `secs` seconds in the timer label, then send `CountdownFinished`
+///
+/// With `beep`, the display bell rings on every step.
+fn start_countdown(ui_state: &mut UiState, secs: u64, beep: bool) {
+    info!("Starting a {} second countdown before recording", secs);
+    let cancelled = Rc::new(Cell::new(false));
+    ui_state.countdown = Some(cancelled.clone());
+    // タイマー表示を書き換えるので、中止時は状態表示をやり直す
+    ui_state.shown_status.set(None);
     
-    // Add all other input devices
-    if let Ok(devices) = host.input_devices() {
-        for (idx, device) in devices.enumerate() {
-            if let Ok(name) = device.name() {
-                let id = format!("device_{}", idx);
-                combo.append(Some(&id), &name);
+    let show_step = {
+        let timer_label = ui_state.timer_label.clone();
+        move |remaining: u64| {
+            timer_label.set_text(&format!("{}…", remaining));
+            if beep {
+                if let Some(display) = gdk::Display::default() {
+                    display.beep();
+                }
             }
         }
+    };
+    show_step(secs);
+    
+    let tx = ui_state.tx_main.clone();
+    let mut remaining = secs;
+    glib::timeout_add_local(Duration::from_secs(1), move || {
+        if cancelled.get() {
+            return ControlFlow::Break;
+        }
+        remaining -= 1;
+        if remaining == 0 {
+            let _ = tx.send(WindowMessage::CountdownFinished);
+            return ControlFlow::Break;
+        }
+        show_step(remaining);
+        ControlFlow::Continue
+    });
+}
+
+/// Stop a running countdown without starting the recording
+fn cancel_countdown(ui_state: &mut UiState, status: AppStatus) {
+    if let Some(cancelled) = ui_state.countdown.take() {
+        cancelled.set(true);
+        info!("Countdown cancelled");
+        update_ui_status(ui_state, status);
+    }
+}
+
+/// CSS for the transcript and dictionary font; empty when neither setting is given
+fn font_css(ui: &UiConfig) -> String {
+    let mut rules = Vec::new();
+    if let Some(family) = ui.font_family.as_deref().map(str::trim).filter(|family| !family.is_empty()) {
+        rules.push(format!("font-family: \"{}\";", family.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    match ui.font_size {
+        Some(size) if size.is_finite() && size > 0.0 => rules.push(format!("font-size: {}pt;", size)),
+        Some(size) => warn!("Ignoring ui.font_size = {} (must be a positive number)", size),
+        None => {},
+    }
+    if rules.is_empty() {
+        String::new()
+    } else {
+        format!("textview {{ {} }}", rules.join(" "))
+    }
+}
+
+/// Apply `ui.font_family` and `ui.font_size` to the views using `provider`
+fn load_font_css(provider: &gtk::CssProvider, ui: &UiConfig) {
+    if let Err(e) = provider.load_from_data(font_css(ui).as_bytes()) {
+        warn!("Failed to apply the transcript font: {}", e);
+    }
+}
+
+/// Level samples shown by the waveform: 5 seconds at the 100 ms meter interval
+const WAVEFORM_SAMPLES: usize = 50;
+
+/// Drawing area showing `history` as bars mirrored around the middle, newest on the right
+///
+/// The meter timer appends to `history` and queues a redraw, so drawing only reads the
+/// samples already collected and never waits on the audio threads.
+fn build_waveform(history: Rc<RefCell<VecDeque<f64>>>) -> gtk::DrawingArea {
+    let area = gtk::DrawingArea::new();
+    area.set_size_request(-1, 40);
+    area.connect_draw(move |widget, cr| {
+        let width = widget.allocated_width() as f64;
+        let height = widget.allocated_height() as f64;
+        let color = widget.style_context().color(gtk::StateFlags::NORMAL);
+        cr.set_source_rgba(color.red(), color.green(), color.blue(), color.alpha());
+        
+        let history = history.borrow();
+        let bar_width = width / WAVEFORM_SAMPLES as f64;
+        let offset = WAVEFORM_SAMPLES - history.len();
+        for (i, level) in history.iter().enumerate() {
+            // 無音でも位置が分かるよう最低 1px の線を描く
+            let bar_height = (level * height).max(1.0);
+            cr.rectangle(
+                (offset + i) as f64 * bar_width,
+                (height - bar_height) / 2.0,
+                (bar_width - 1.0).max(1.0),
+                bar_height,
+            );
+        }
+        if let Err(e) = cr.fill() {
+            debug!("Failed to draw the waveform: {}", e);
+        }
+        glib::Propagation::Proceed
+    });
+    area
+}
+
+/// Set the name and description screen readers announce for a widget, and a matching tooltip
+fn describe_widget(widget: &impl IsA<gtk::Widget>, name: &str, description: &str) {
+    widget.set_tooltip_text(Some(description));
+    if let Some(accessible) = widget.accessible() {
+        accessible.set_name(name);
+        accessible.set_description(description);
+    }
+}
+
+/// RMS of a buffer of input samples, normalized to -1.0..1.0
+fn input_rms<T>(data: &[T]) -> f32
+where
+    T: cpal::Sample,
+    f32: cpal::FromSample<T>,
+{
+    let sum: f32 = data.iter()
+        .map(|&sample| {
+            let sample: f32 = sample.to_sample();
+            sample * sample
+        })
+        .sum();
+    (sum / data.len().max(1) as f32).sqrt()
+}
+
+/// Monitor `device` (None for the default input) from now on, reopening the stream if it changed
+fn set_monitor_device(device: Option<String>) {
+    let mut monitored = lock_or_recover(&MONITOR_DEVICE);
+    if *monitored != device {
+        *monitored = device;
+        MONITOR_RESTART.store(true, Ordering::SeqCst);
     }
 }
 
-/// Start monitoring audio input levels in a separate thread
-fn monitor_audio_input() {
+/// Input devices to try for monitoring, the one recordings use first, and that device's name
+///
+/// Monitor sources are recorded through the sound server, so while one is selected the
+/// default input only shows the level and no device gets the pre-roll.
+fn monitor_devices(host_name: &str) -> (Vec<cpal::Device>, Option<String>) {
+    let host = audio::audio_host(host_name);
+    let recording_device = match lock_or_recover(&MONITOR_DEVICE).clone() {
+        Some(name) if name.starts_with(audio::LOOPBACK_PREFIX) => None,
+        Some(name) => Some(name),
+        None => host.default_input_device().and_then(|device| device.name().ok()),
+    };
+    let mut devices: Vec<cpal::Device> = host.input_devices().map(|devices| devices.collect()).unwrap_or_default();
+    devices.sort_by_key(|device| device.name().ok() != recording_device);
+    (devices, recording_device)
+}
+
+/// Hold a monitoring stream open until monitoring is stopped or disabled
+///
+/// The device selected for recording is opened first, and only its input is kept as
+/// pre-roll; another device is only used for the level when that one can't be opened.
+/// With `vad_threshold`, sustained speech sets `VAD_TRIGGERED` to start a recording.
+/// Returns false when no monitoring stream could be set up.
+fn monitor_audio_input_once(vad_threshold: Option<f32>, level_scale: LevelScale, preroll_secs: f32, host_name: &str) -> bool {
     // We need to create a temporary input stream to monitor audio levels
-    if let Ok(devices) = cpal::default_host().input_devices() {
-        for device in devices {
-            if let Ok(config) = device.default_input_config() {
-                info!("Setting up audio monitoring");
-                
-                // Try to build a stream for monitoring
-                let stream_result = match config.sample_format() {
-                    cpal::SampleFormat::F32 => {
-                        let audio_level = AUDIO_LEVEL.clone();
-                        device.build_input_stream(
-                            &config.into(),
-                            move |data: &[f32], _: &_| {
-                                if AUDIO_MONITORING.load(Ordering::SeqCst) {
-                                    // Calculate RMS of the audio samples
-                                    let sum: f32 = data.iter()
-                                        .map(|&sample| sample * sample)
-                                        .sum();
-                                    let rms = (sum / data.len() as f32).sqrt();
-                                    
-                                    // Update shared audio level (scale RMS to 0.0-1.0 range)
-                                    // Use non-linear scaling to make the meter more useful
-                                    let level = (rms * 5.0).min(1.0) as f64;
-                                    if let Ok(mut level_guard) = audio_level.lock() {
-                                        *level_guard = level;
-                                    }
-                                }
-                            },
-                            |err| error!("Error in audio monitoring: {}", err),
-                            None,
-                        )
-                    },
-                    _ => {
-                        error!("Unsupported sample format for audio monitoring");
-                        Err(cpal::BuildStreamError::DeviceNotAvailable)
-                    }
-                };
-                
-                // Start the stream if successful
-                if let Ok(stream) = stream_result {
-                    if let Err(e) = stream.play() {
-                        error!("Could not play stream for audio monitoring: {}", e);
-                        continue;
+    let (devices, recording_device) = monitor_devices(host_name);
+    for device in devices {
+        if let Ok(config) = device.default_input_config() {
+            info!("Setting up audio monitoring");
+            
+            // Try to build a stream for monitoring
+            let audio_level = AUDIO_LEVEL.clone();
+            let frames_per_ms = config.sample_rate().0 as f32 * config.channels() as f32 / 1000.0;
+            let mut vad = vad_threshold.map(VoiceActivityDetector::new);
+            let mut on_level = move |rms: f32, samples: usize| {
+                LAST_MONITOR_DATA.store(unix_millis(), Ordering::SeqCst);
+                if AUDIO_MONITORING.load(Ordering::SeqCst) && IDLE_MONITORING.load(Ordering::SeqCst) {
+                    // Update shared audio level (scale RMS to 0.0-1.0 range)
+                    if let Ok(mut level_guard) = audio_level.lock() {
+                        *level_guard = level_scale.smoothed_level(*level_guard, rms);
                     }
                     
-                    // より短い間隔でフラグをチェックして、すぐに反応できるようにする
-                    while AUDIO_MONITORING.load(Ordering::SeqCst) {
-                        std::thread::sleep(std::time::Duration::from_millis(10));
+                    if let Some(vad) = vad.as_mut() {
+                        if vad.update(rms, samples as f32 / frames_per_ms) {
+                            info!("Voice detected by monitoring stream");
+                            VAD_TRIGGERED.store(true, Ordering::SeqCst);
+                        }
                     }
+                }
+            };
+            let err_fn = |err| {
+                error!("Error in audio monitoring: {}", err);
+                MONITOR_RESTART.store(true, Ordering::SeqCst);
+            };
+            let stream_config: cpal::StreamConfig = config.clone().into();
+            // 録音しないデバイスの入力を録音の先頭に付け足さない
+            let records_here = recording_device.is_some() && device.name().ok() == recording_device;
+            if let Ok(mut preroll) = PREROLL.lock() {
+                let secs = if records_here { preroll_secs } else { 0.0 };
+                preroll.configure(secs, stream_config.sample_rate.0, stream_config.channels);
+            }
+            if !records_here && preroll_secs > 0.0 {
+                warn!("Monitoring {:?} instead of the recording device, so recordings start without pre-roll", device.name().ok());
+            }
+            
+            let stream_result = match config.sample_format() {
+                cpal::SampleFormat::F32 => device.build_input_stream(
+                    &stream_config,
+                    move |data: &[f32], _: &_| {
+                        on_level(input_rms(data), data.len());
+                        push_preroll(data);
+                    },
+                    err_fn,
+                    None,
+                ),
+                cpal::SampleFormat::I16 => device.build_input_stream(
+                    &stream_config,
+                    move |data: &[i16], _: &_| {
+                        on_level(input_rms(data), data.len());
+                        push_preroll(data);
+                    },
+                    err_fn,
+                    None,
+                ),
+                cpal::SampleFormat::U16 => device.build_input_stream(
+                    &stream_config,
+                    move |data: &[u16], _: &_| {
+                        on_level(input_rms(data), data.len());
+                        push_preroll(data);
+                    },
+                    err_fn,
+                    None,
+                ),
+                _ => {
+                    error!("Unsupported sample format for audio monitoring");
+                    Err(cpal::BuildStreamError::DeviceNotAvailable)
+                }
+            };
+            
+            // Start the stream if successful
+            if let Ok(stream) = stream_result {
+                if let Err(e) = stream.play() {
+                    error!("Could not play stream for audio monitoring: {}", e);
+                    continue;
+                }
+                
+                // より短い間隔でフラグをチェックして、すぐに反応できるようにする
+                MONITOR_RESTART.store(false, Ordering::SeqCst);
+                LAST_MONITOR_DATA.store(unix_millis(), Ordering::SeqCst);
+                while AUDIO_MONITORING.load(Ordering::SeqCst) && IDLE_MONITORING.load(Ordering::SeqCst) {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
                     
-                    // ストリームを明示的に停止して解放
-                    drop(stream);
-                    info!("Audio monitoring stopped and resources released");
-                    
-                    return; // Exit after setting up monitoring with the first working device
+                    // ストリームのエラーや復帰通知があれば開き直す
+                    if MONITOR_RESTART.swap(false, Ordering::SeqCst) {
+                        warn!("Reopening audio monitoring stream");
+                        break;
+                    }
+                    // データが届かなくなったストリーム（サスペンド後など）も開き直す
+                    let stalled_ms = unix_millis().saturating_sub(LAST_MONITOR_DATA.load(Ordering::SeqCst));
+                    if stalled_ms > MONITOR_STALL_MS {
+                        warn!("Audio monitoring stream produced no data for {} ms, reopening", stalled_ms);
+                        break;
+                    }
                 }
+                
+                // ストリームを明示的に停止して解放
+                drop(stream);
+                info!("Audio monitoring stopped and resources released");
+                
+                return true; // Exit after setting up monitoring with the first working device
             }
         }
     }
     
     error!("Failed to set up audio monitoring");
+    false
+}
+
+/// Keep monitor input for the pre-roll of the next recording
+fn push_preroll<T>(data: &[T])
+where
+    T: cpal::Sample,
+    i16: cpal::FromSample<T>,
+{
+    // 音声コールバックを待たせないよう、ロック中なら今回の分は諦める
+    if let Ok(mut preroll) = PREROLL.try_lock() {
+        preroll.push(data);
+    }
 }
 
 /// 辞書内容を表示用テキストビューに更新する
@@ -1057,6 +4365,365 @@ fn update_dictionary_view(buffer: &TextBuffer, config: &Config) {
     buffer.set_text(&content);
 }
 
+/// 文字起こしする音声ファイルを選択する（キャンセル時は None）
+///
+/// 対応形式以外も「すべてのファイル」から選べるが、文字起こしの前に警告を表示して止める。
+fn choose_audio_file(window: &Window) -> Option<String> {
+    let t = crate::i18n::text();
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some(t.open_audio_file_title),
+        Some(window),
+        gtk::FileChooserAction::Open,
+        &[(t.dialog_cancel, gtk::ResponseType::Cancel), (t.dialog_transcribe, gtk::ResponseType::Accept)],
+    );
+    let audio_filter = gtk::FileFilter::new();
+    audio_filter.set_name(Some(&t.audio_files_filter(&audio::SUPPORTED_AUDIO_EXTENSIONS.join(", "))));
+    for extension in audio::SUPPORTED_AUDIO_EXTENSIONS {
+        audio_filter.add_pattern(&format!("*.{}", extension.to_lowercase()));
+        audio_filter.add_pattern(&format!("*.{}", extension.to_uppercase()));
+    }
+    dialog.add_filter(audio_filter);
+    let all_filter = gtk::FileFilter::new();
+    all_filter.set_name(Some(t.all_files_filter));
+    all_filter.add_pattern("*");
+    dialog.add_filter(all_filter);
+
+    let response = dialog.run();
+    let path = dialog.filename();
+    dialog.close();
+
+    if response != gtk::ResponseType::Accept {
+        return None;
+    }
+    path.map(|path| path.to_string_lossy().into_owned())
+}
+
+/// CSVファイルを選択して辞書に取り込む
+fn import_dictionary_csv(window: &Window, config: &Config) {
+    let t = crate::i18n::text();
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some(t.import_dictionary_title),
+        Some(window),
+        gtk::FileChooserAction::Open,
+        &[(t.dialog_cancel, gtk::ResponseType::Cancel), (t.dialog_import, gtk::ResponseType::Accept)],
+    );
+    let csv_filter = gtk::FileFilter::new();
+    csv_filter.set_name(Some("CSV"));
+    csv_filter.add_pattern("*.csv");
+    dialog.add_filter(csv_filter);
+
+    // 既存の単語と衝突した場合の扱いを選択
+    let overwrite_check = gtk::CheckButton::with_label(t.overwrite_existing_words);
+    dialog.set_extra_widget(&overwrite_check);
+
+    let response = dialog.run();
+    let path = dialog.filename();
+    let overwrite = overwrite_check.is_active();
+    dialog.close();
+
+    if response != gtk::ResponseType::Accept {
+        return;
+    }
+    let Some(path) = path else { return };
+
+    let dict_path = config.temp_dir.join("user_dictionary.json");
+    let mut dictionary = UserDictionary::load(&dict_path);
+    let message = match dictionary.import_csv(&path, overwrite) {
+        Ok(summary) => match dictionary.save(&dict_path) {
+            Ok(_) => t.dictionary_imported(summary.imported, summary.skipped, summary.invalid),
+            Err(e) => {
+                error!("Failed to save dictionary: {}", e);
+                t.dictionary_save_failed(&e.to_string())
+            }
+        },
+        Err(e) => {
+            error!("Failed to import dictionary CSV: {}", e);
+            t.dictionary_import_failed(&e.to_string())
+        }
+    };
+
+    show_message_dialog(window, &message);
+}
+
+/// 辞書をCSVファイルとして書き出す
+fn export_dictionary_csv(window: &Window, config: &Config) {
+    let t = crate::i18n::text();
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some(t.export_dictionary_title),
+        Some(window),
+        gtk::FileChooserAction::Save,
+        &[(t.dialog_cancel, gtk::ResponseType::Cancel), (t.dialog_save, gtk::ResponseType::Accept)],
+    );
+    dialog.set_do_overwrite_confirmation(true);
+    dialog.set_current_name("user_dictionary.csv");
+
+    let response = dialog.run();
+    let path = dialog.filename();
+    dialog.close();
+
+    if response != gtk::ResponseType::Accept {
+        return;
+    }
+    let Some(path) = path else { return };
+
+    let dictionary = UserDictionary::load(&config.temp_dir.join("user_dictionary.json"));
+    let message = match dictionary.export_csv(&path) {
+        Ok(count) => t.dictionary_exported(count),
+        Err(e) => {
+            error!("Failed to export dictionary CSV: {}", e);
+            t.dictionary_export_failed(&e.to_string())
+        }
+    };
+
+    show_message_dialog(window, &message);
+}
+
+/// 学習した単語の頻度を消去する（登録単語は残す）
+fn reset_dictionary_frequencies(window: &Window, config: &Config) {
+    let t = crate::i18n::text();
+    let dict_path = config.temp_dir.join("user_dictionary.json");
+    let mut dictionary = UserDictionary::load(&dict_path);
+    let count = dictionary.clear_frequent_terms();
+    let message = match dictionary.save(&dict_path) {
+        Ok(_) => {
+            info!("Cleared {} learned word frequencies", count);
+            t.dictionary_frequencies_cleared(count)
+        },
+        Err(e) => {
+            error!("Failed to save dictionary: {}", e);
+            t.dictionary_save_failed(&e.to_string())
+        }
+    };
+
+    show_message_dialog(window, &message);
+}
+
+/// 確認のうえ、登録単語をすべて削除する
+fn clear_dictionary_words(window: &Window, config: &Config) {
+    let t = crate::i18n::text();
+    let dict_path = config.temp_dir.join("user_dictionary.json");
+    let mut dictionary = UserDictionary::load(&dict_path);
+    let count = dictionary.originals().len();
+    if count == 0 {
+        show_message_dialog(window, t.dictionary_empty);
+        return;
+    }
+
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Warning,
+        gtk::ButtonsType::None,
+        &t.confirm_clear_dictionary(count),
+    );
+    dialog.add_button(t.dialog_cancel, gtk::ResponseType::Cancel);
+    dialog.add_button(t.dialog_delete, gtk::ResponseType::Accept);
+    dialog.set_default_response(gtk::ResponseType::Cancel);
+    let response = dialog.run();
+    dialog.close();
+    if response != gtk::ResponseType::Accept {
+        return;
+    }
+
+    dictionary.clear_words();
+    let message = match dictionary.save(&dict_path) {
+        Ok(_) => {
+            info!("Cleared {} dictionary words", count);
+            t.dictionary_words_cleared(count)
+        },
+        Err(e) => {
+            error!("Failed to save dictionary: {}", e);
+            t.dictionary_save_failed(&e.to_string())
+        }
+    };
+
+    show_message_dialog(window, &message);
+}
+
+/// 自動バックアップを選んで辞書を復元する
+fn restore_dictionary_backup(window: &Window, config: &Config) {
+    let t = crate::i18n::text();
+    let dict_path = config.temp_dir.join("user_dictionary.json");
+    let backups = list_dictionary_backups(&dict_path);
+    if backups.is_empty() {
+        show_message_dialog(window, t.dictionary_no_backups);
+        return;
+    }
+
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::None,
+        t.choose_dictionary_backup,
+    );
+    // 新しい順に並べ、最新を選択しておく
+    let backup_combo = ComboBoxText::new();
+    for backup in &backups {
+        let name = backup.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        backup_combo.append_text(&name);
+    }
+    backup_combo.set_active(Some(0));
+    dialog.content_area().pack_start(&backup_combo, false, false, 5);
+    backup_combo.show();
+    dialog.add_button(t.dialog_cancel, gtk::ResponseType::Cancel);
+    dialog.add_button(t.dialog_restore, gtk::ResponseType::Accept);
+    dialog.set_default_response(gtk::ResponseType::Cancel);
+    let response = dialog.run();
+    let selected = backup_combo.active().and_then(|index| backups.get(index as usize).cloned());
+    dialog.close();
+    if response != gtk::ResponseType::Accept {
+        return;
+    }
+    let Some(backup) = selected else { return };
+
+    let message = match UserDictionary::restore_backup(&dict_path, &backup) {
+        Ok(dictionary) => t.dictionary_backup_restored(dictionary.originals().len()),
+        Err(e) => {
+            error!("Failed to restore dictionary backup: {:#}", e);
+            t.dictionary_restore_failed(&format!("{:#}", e))
+        }
+    };
+
+    show_message_dialog(window, &message);
+}
+
+/// 文字起こしログ（logging.jsonl）から今日と今週の使用状況を集計して表示する（開くたびに読み直す）
+fn show_usage_stats(window: &Window, config: &Config) {
+    let message = match stats::usage_summary(&config.temp_dir.join(TRANSCRIPTION_LOG_FILE)) {
+        Ok(summary) => {
+            let t = crate::i18n::text();
+            let line = |label, stats: &stats::UsageStats| t.usage_line(label, stats.recordings, stats.audio_secs, stats.chars, stats.cost_usd);
+            let mut message = format!(
+                "{}\n{}\n\n{}",
+                line(t.usage_today, &summary.today),
+                line(t.usage_week, &summary.week),
+                t.usage_cost_note(stats::OPENAI_USD_PER_MINUTE)
+            );
+            if !config.logging.jsonl {
+                message.push_str("\n\n");
+                message.push_str(t.usage_not_logged);
+            }
+            message
+        },
+        Err(e) => {
+            error!("Failed to read usage statistics: {:#}", e);
+            crate::i18n::text().usage_stats_failed(&format!("{:#}", e))
+        }
+    };
+    show_message_dialog(window, &message);
+}
+
+/// 録音中・処理中に閉じようとした場合に確認し、終了してよければ true を返す
+fn confirm_quit(window: &Window, status: AppStatus) -> bool {
+    const CANCEL: gtk::ResponseType = gtk::ResponseType::Cancel;
+    const SAVE: gtk::ResponseType = gtk::ResponseType::Accept;
+    const DISCARD: gtk::ResponseType = gtk::ResponseType::Reject;
+    
+    let t = crate::i18n::text();
+    let message = match status {
+        // 音声待ちの間はまだ何も録音されていない
+        AppStatus::Idle | AppStatus::Armed => return true,
+        AppStatus::Recording => t.quit_while_recording,
+        AppStatus::Transcribing => t.quit_while_transcribing,
+    };
+    
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Warning,
+        gtk::ButtonsType::None,
+        message,
+    );
+    dialog.add_button(t.dialog_cancel, CANCEL);
+    if status == AppStatus::Recording {
+        dialog.add_button(t.quit_save_recording, SAVE);
+        dialog.add_button(t.quit_discard_recording, DISCARD);
+    } else {
+        dialog.add_button(t.quit_button, DISCARD);
+    }
+    dialog.set_default_response(CANCEL);
+    let response = dialog.run();
+    dialog.close();
+    
+    if response != SAVE && response != DISCARD {
+        return false;
+    }
+    
+    if status == AppStatus::Recording {
+        // 終了前に録音を止めてWAVを確定させる
+        let recording = unsafe {
+            match (*std::ptr::addr_of_mut!(GLOBAL_RECORDER)).take() {
+                Some(mut recorder) => recorder.stop().unwrap_or_else(|e| {
+                    error!("Failed to stop recording: {}", e);
+                    None
+                }),
+                None => None,
+            }
+        };
+        if let Some(recording) = recording {
+            if response == SAVE {
+                // メモリ上の録音も保存を選んだ場合はファイルに書き出す
+                match recording.persist() {
+                    Ok(path) => info!("Recording saved to {}", path),
+                    Err(e) => error!("Failed to save recording: {}", e),
+                }
+            } else {
+                recording.discard();
+            }
+        }
+    }
+    true
+}
+
+/// 中断から復元した録音を一覧にして、文字起こしするか確認する
+///
+/// ダイアログは待たずに表示し、「文字起こし」が選ばれたら `TranscribeRecoveredRecordings` を送る
+fn confirm_recovered_recordings(window: &Window, paths: Vec<PathBuf>, tx: Sender<WindowMessage>) {
+    let strings = crate::i18n::text();
+    let names: Vec<String> = paths.iter().map(|path| format!("• {}", path.display())).collect();
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::None,
+        &strings.recovered_recordings(paths.len(), &names.join("\n")),
+    );
+    let (later, transcribe) = strings.recovered_recordings_buttons;
+    dialog.add_button(later, gtk::ResponseType::Cancel);
+    dialog.add_button(transcribe, gtk::ResponseType::Accept);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        if response == gtk::ResponseType::Accept {
+            let _ = tx.send(WindowMessage::TranscribeRecoveredRecordings(paths.clone()));
+        }
+    });
+    dialog.show_all();
+}
+
+/// 結果を知らせるメッセージダイアログを表示する
+fn show_message_dialog(window: &Window, message: &str) {
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Info,
+        gtk::ButtonsType::Ok,
+        message,
+    );
+    dialog.run();
+    dialog.close();
+}
+
+/// 処理中タイマーを停止
+fn stop_processing_timer() {
+    if let Ok(mut timer_id) = PROCESSING_STATUS_TIMER_ID.lock() {
+        if let Some(id) = timer_id.take() {
+            id.remove();
+        }
+    }
+}
+
 /// トランスクリプション処理中のステータス表示を更新するタイマーをセットアップ
 fn setup_processing_status_timer(ui_state: &UiState) {
     // 既存のタイマーがあれば削除
@@ -1072,18 +4739,23 @@ fn setup_processing_status_timer(ui_state: &UiState) {
     }
     
     // 処理中を示すアニメーションを表示
-    let record_button_clone = ui_state.record_button.clone();
     let timer_label_clone = ui_state.timer_label.clone();
+    let state_arc = ui_state.state.clone();
     
     let timer_id = glib::timeout_add_local(std::time::Duration::from_millis(300), move || {
+        // 次の録音中はタイマーラベルを録音時間の表示に使う
+        let transcribing = state_arc.lock().map(|state| state.status == AppStatus::Transcribing).unwrap_or(false);
+        if !transcribing {
+            return ControlFlow::Continue;
+        }
+        
         if let Ok(mut dots) = PROCESSING_DOTS.lock() {
             *dots = (*dots + 1) % 4;
             let dots_str = ".".repeat(*dots);
             let padding = " ".repeat(3 - *dots);
             
-            // ボタンとタイマーラベルのテキストを更新
-            record_button_clone.set_label(&format!("処理中{}{}", dots_str, padding));
-            timer_label_clone.set_text(&format!("処理中{}{}", dots_str, padding));
+            // タイマーラベルのテキストを更新
+            timer_label_clone.set_text(&format!("{}{}{}", crate::i18n::text().processing, dots_str, padding));
         }
         
         ControlFlow::Continue
@@ -1093,4 +4765,323 @@ fn setup_processing_status_timer(ui_state: &UiState) {
     if let Ok(mut timer_id_guard) = PROCESSING_STATUS_TIMER_ID.lock() {
         *timer_id_guard = Some(timer_id);
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn test_state() -> ThreadSafeState {
+        let config = crate::config::default_config();
+        ThreadSafeState {
+            status: AppStatus::Idle,
+            config: config.clone(),
+            transcript: String::new(),
+            api: Arc::new(TranscriptionAPI::new(config)),
+        }
+    }
+    
+    #[test]
+    fn copied_file_uris_are_decoded() {
+        assert_eq!(copied_path(" /tmp/memo.wav\n"), "/tmp/memo.wav");
+        assert_eq!(copied_path("file:///tmp/my%20memo%E3%81%82.wav"), "/tmp/my memoあ.wav");
+    }
+    
+    #[test]
+    fn clipboard_watcher_picks_up_each_newly_copied_audio_file_once() {
+        let dir = std::env::temp_dir().join(format!("wispr_clipboard_watch_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let audio = dir.join("memo.wav");
+        std::fs::write(&audio, b"").unwrap();
+        let audio = audio.to_string_lossy().into_owned();
+        
+        // 起動時にあったパスは文字起こししない
+        let mut watcher = ClipboardWatcher::default();
+        assert_eq!(watcher.observe(&audio), None);
+        assert_eq!(watcher.observe("hello"), None);
+        assert_eq!(watcher.observe(&format!("file://{}\n", audio)).as_deref(), Some(audio.as_str()));
+        assert_eq!(watcher.observe(&format!("file://{}", audio)), None);
+        assert_eq!(watcher.observe(&dir.join("missing.wav").to_string_lossy()), None);
+        assert_eq!(watcher.observe(&audio).as_deref(), Some(audio.as_str()));
+        
+        // ファイルマネージャーのURIはエスケープを戻してから探す
+        let spaced = dir.join("my memo.wav");
+        std::fs::write(&spaced, b"").unwrap();
+        let spaced = spaced.to_string_lossy().into_owned();
+        let uri = format!("file://{}", spaced.replace(' ', "%20"));
+        assert_eq!(watcher.observe(&uri).as_deref(), Some(spaced.as_str()));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn rapid_start_calls_start_only_one_recording() {
+        let mut state = test_state();
+        
+        assert!(state.start().is_ok());
+        assert!(state.start().is_err());
+        assert_eq!(state.status, AppStatus::Recording);
+    }
+    
+    #[test]
+    fn a_second_recorder_is_refused_and_released() {
+        struct Recorder(Rc<Cell<usize>>);
+        impl Drop for Recorder {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+        
+        let released = Rc::new(Cell::new(0));
+        let mut slot = None;
+        assert!(store_recorder(&mut slot, Recorder(released.clone())).is_ok());
+        let refused = store_recorder(&mut slot, Recorder(released.clone()));
+        assert!(refused.is_err());
+        drop(refused);
+        // 2つ目だけが解放され、最初の録音は残っている
+        assert_eq!(released.get(), 1);
+        assert!(slot.is_some());
+        
+        drop(slot.take());
+        assert_eq!(released.get(), 2);
+    }
+    
+    #[test]
+    fn start_is_allowed_while_transcribing() {
+        // 文字起こしはキューで処理されるので、次の録音を始められる
+        let mut state = test_state();
+        state.status = AppStatus::Transcribing;
+        
+        assert!(state.start().is_ok());
+        assert_eq!(state.status, AppStatus::Recording);
+    }
+    
+    fn job(path: &str) -> TranscriptionJob {
+        TranscriptionJob {
+            recording: Recording::File(path.to_string()),
+            language: None,
+            own_recording: true,
+            device: None,
+            verbatim: false,
+            tag: None,
+            markers: Markers::default(),
+        }
+    }
+    
+    #[test]
+    fn queue_runs_jobs_in_order_up_to_the_limit() {
+        let mut queue = TranscriptionQueue::default();
+        queue.push(job("a.wav"));
+        queue.push(job("b.wav"));
+        queue.push(job("c.wav"));
+        
+        assert_eq!(queue.next_job(2).unwrap().recording.path(), Some("a.wav"));
+        assert_eq!(queue.next_job(2).unwrap().recording.path(), Some("b.wav"));
+        assert!(queue.next_job(2).is_none());
+        
+        queue.finish();
+        assert_eq!(queue.next_job(2).unwrap().recording.path(), Some("c.wav"));
+        queue.finish();
+        queue.finish();
+        assert!(!queue.is_busy());
+    }
+    
+    #[test]
+    fn abort_drops_pending_jobs_and_cancels_workers() {
+        let mut queue = TranscriptionQueue::default();
+        queue.push(job("a.wav"));
+        queue.push(job("b.wav"));
+        queue.next_job(1);
+        let cancel = queue.cancel.clone();
+        let generation = queue.generation;
+        
+        let dropped = queue.abort();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].recording.path(), Some("b.wav"));
+        assert!(cancel.load(Ordering::SeqCst));
+        assert_ne!(queue.generation, generation);
+        assert!(!queue.is_busy());
+        // 次の文字起こしは新しいフラグで動く
+        assert!(!queue.cancel.load(Ordering::SeqCst));
+    }
+    
+    #[test]
+    fn queue_appends_results_within_a_batch() {
+        let mut queue = TranscriptionQueue::default();
+        queue.push(job("a.wav"));
+        queue.push(job("b.wav"));
+        
+        queue.next_job(1);
+        queue.finish();
+        let text = queue.merge_result("前回の結果", "一つ目".to_string(), false);
+        assert_eq!(text, "一つ目");
+        
+        queue.next_job(1);
+        queue.finish();
+        let text = queue.merge_result(&text, "二つ目".to_string(), false);
+        assert_eq!(text, "一つ目\n\n二つ目");
+        
+        // キューが空になったら次の結果は置き換える
+        assert!(queue.starts_batch());
+        assert_eq!(queue.merge_result(&text, "三つ目".to_string(), false), "三つ目");
+    }
+    
+    #[test]
+    fn append_mode_keeps_the_previous_transcript() {
+        let mut queue = TranscriptionQueue::default();
+        queue.push(job("a.wav"));
+        queue.next_job(1);
+        queue.finish();
+        assert_eq!(queue.merge_result("前回の結果", "一つ目".to_string(), true), "前回の結果\n\n一つ目");
+        assert_eq!(queue.merge_result("  ", "二つ目".to_string(), true), "二つ目");
+    }
+    
+    #[test]
+    fn debouncer_ignores_release_and_repress_glitch() {
+        let mut debouncer = ShortcutDebouncer::new(Duration::from_millis(50));
+        let start = Instant::now();
+        
+        assert!(debouncer.press(start));
+        // 押しっぱなしのキーリピート
+        assert!(!debouncer.press(start + Duration::from_millis(500)));
+        // 離してすぐ押し直す（グリッチ）
+        let glitch = debouncer.release(start + Duration::from_millis(1000)).unwrap();
+        assert!(!debouncer.press(start + Duration::from_millis(1005)));
+        assert!(!debouncer.settle(glitch));
+        
+        // 本当に離した場合は待ち時間の後に停止する
+        let release = debouncer.release(start + Duration::from_millis(2000)).unwrap();
+        assert!(debouncer.settle(release));
+        assert!(debouncer.press(start + Duration::from_millis(3000)));
+    }
+    
+    #[test]
+    fn debouncer_rapid_press_release_press_starts_one_session() {
+        let mut debouncer = ShortcutDebouncer::new(Duration::from_millis(50));
+        let start = Instant::now();
+        let mut sessions = 0;
+        
+        if debouncer.press(start) {
+            sessions += 1;
+        }
+        let release = debouncer.release(start + Duration::from_millis(10)).unwrap();
+        if debouncer.press(start + Duration::from_millis(20)) {
+            sessions += 1;
+        }
+        
+        assert_eq!(sessions, 1);
+        assert!(!debouncer.settle(release));
+    }
+    
+    #[test]
+    fn recordings_under_the_minimum_duration_are_too_short() {
+        assert!(is_too_short(Some(Duration::from_millis(200)), 0.3));
+        assert!(!is_too_short(Some(Duration::from_millis(300)), 0.3));
+        assert!(!is_too_short(Some(Duration::from_millis(10)), 0.0));
+        assert!(!is_too_short(None, 0.3));
+    }
+    
+    #[test]
+    fn transcription_log_appends_one_json_line_per_entry() {
+        let dir = std::env::temp_dir().join(format!("wispr_jsonl_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        for chars in [12, 34] {
+            let entry = TranscriptionLogEntry {
+                timestamp: "2024-01-01T12:00:00+09:00".to_string(),
+                duration_secs: Some(1.5),
+                device: Some("USB Mic".to_string()),
+                chars,
+                backend: TranscriptionBackend::Openai,
+                tag: (chars == 34).then(|| "work".to_string()),
+            };
+            append_transcription_log(&dir, &entry).unwrap();
+        }
+        
+        let log = std::fs::read_to_string(dir.join(TRANSCRIPTION_LOG_FILE)).unwrap();
+        let lines: Vec<serde_json::Value> = log.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["chars"], 34);
+        assert_eq!(lines[0]["backend"], "openai");
+        assert_eq!(lines[0]["device"], "USB Mic");
+        assert!(lines[0]["tag"].is_null());
+        assert_eq!(lines[1]["tag"], "work");
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn short_tap_discards_instead_of_transcribing() {
+        let min_hold = Duration::from_millis(150);
+        let mut debouncer = ShortcutDebouncer::new(Duration::from_millis(50));
+        let start = Instant::now();
+        
+        // 誤タップでは StopRecording（文字起こし）を送らない
+        assert!(debouncer.press(start));
+        let tap = debouncer.release(start + Duration::from_millis(40)).unwrap();
+        assert_eq!(debouncer.settle_hold(tap, min_hold), Some(HoldRelease::Tap));
+        
+        assert!(debouncer.press(start + Duration::from_millis(1000)));
+        let hold = debouncer.release(start + Duration::from_millis(3000)).unwrap();
+        assert_eq!(debouncer.settle_hold(hold, min_hold), Some(HoldRelease::Held));
+        
+        // 0 はタップでも文字起こしする
+        assert!(debouncer.press(start + Duration::from_millis(4000)));
+        let tap = debouncer.release(start + Duration::from_millis(4010)).unwrap();
+        assert_eq!(debouncer.settle_hold(tap, Duration::ZERO), Some(HoldRelease::Held));
+    }
+    
+    #[test]
+    fn font_css_includes_only_the_given_settings() {
+        let mut ui = crate::config::default_config().ui;
+        assert_eq!(font_css(&ui), "");
+        ui.font_size = Some(14.0);
+        assert_eq!(font_css(&ui), "textview { font-size: 14pt; }");
+        ui.font_family = Some("Noto \"Sans\"".to_string());
+        ui.font_size = Some(-1.0);
+        assert_eq!(font_css(&ui), "textview { font-family: \"Noto \\\"Sans\\\"\"; }");
+    }
+    
+    #[test]
+    fn history_search_is_case_insensitive_and_multibyte_safe() {
+        assert_eq!(find_matches("Hello RUST and rust", "Rust"), vec![(6, 10), (15, 19)]);
+        // 日本語は文字単位の位置を返す
+        assert_eq!(find_matches("今日はラストについて話す", "ラスト"), vec![(3, 6)]);
+        assert_eq!(find_matches("ＡＢＣ全角", "ａｂｃ"), vec![(0, 3)]);
+        // 小文字化で2文字になる「İ」の後ろでも位置がずれない
+        assert_eq!(find_matches("İx Straße", "straße"), vec![(3, 9)]);
+        assert!(find_matches("テスト", "").is_empty());
+        assert!(find_matches("テ", "テスト").is_empty());
+    }
+    
+    #[test]
+    fn max_duration_is_shown_in_whole_minutes() {
+        assert_eq!(duration_minutes(1800), 30.0);
+        assert_eq!(duration_minutes(90), 2.0);
+        assert_eq!(duration_minutes(0), 1.0);
+        assert_eq!(duration_minutes(u64::MAX), MAX_DURATION_MINUTES);
+    }
+    
+    #[test]
+    fn overlay_is_placed_inside_the_chosen_corner() {
+        let area = (1920, 24, 1920, 1056);
+        assert_eq!(overlay_position(area, (80, 30), OverlayCorner::TopLeft), (1936, 40));
+        assert_eq!(overlay_position(area, (80, 30), OverlayCorner::TopRight), (3744, 40));
+        assert_eq!(overlay_position(area, (80, 30), OverlayCorner::BottomLeft), (1936, 1034));
+        assert_eq!(overlay_position(area, (80, 30), OverlayCorner::BottomRight), (3744, 1034));
+    }
+    
+    #[test]
+    fn long_transcripts_keep_their_newest_lines() {
+        let text = "一つ目の段落です。\n\n二つ目の段落です。\n\n三つ目";
+        assert_eq!(newest_transcript_text(text, 0), text);
+        assert_eq!(newest_transcript_text(text, 100), text);
+        assert_eq!(newest_transcript_text(text, 16), "二つ目の段落です。\n\n三つ目");
+        assert_eq!(newest_transcript_text(text, 14), "二つ目の段落です。\n\n三つ目");
+        assert_eq!(newest_transcript_text(text, 13), "三つ目");
+        // 改行がなければ文字数で切る
+        assert_eq!(newest_transcript_text("abcdefgh", 3), "fgh");
+    }
+}